@@ -0,0 +1,184 @@
+// benches/auction.rs
+
+//! 竞价热路径基准测试：BidRequest 解析、延迟解析 getter、以及使用内存 BidFetcher 的完整竞价流程
+//!
+//! 运行：cargo bench --bench auction
+//!
+//! 基线（release，单核 x86_64 Linux，warm-up 1s / measurement 3s）：
+//!
+//! | 基准                          | 耗时      |
+//! |-------------------------------|-----------|
+//! | parse_bid_request             | ~5.4 µs   |
+//! | detail_getters (cold)         | ~3.9 µs   |
+//! | clone_warm_request            | ~4.7 µs   |
+//! | process_bid_request (3 DSP)   | ~40.6 µs  |
+//!
+//! 后续性能相关的改动（客户端复用、getter 去除 JSON 往返、请求只序列化一次）以此为对照
+//!
+//! clone_warm_request：已解析的 detail 缓存改为 Arc 共享前为 ~10.3 µs（克隆时深拷贝全部解析结果）
+//!
+//! detail_getters：getter 经 JSON 文本中转（to_string + from_str）时为 ~9.1 µs。中转文本是解析时唯一的
+//! 临时分配，曾考虑用请求级 arena（bumpalo）集中分配并在请求结束时统一释放，但直接从 OwnedValue
+//! 反序列化即可完全去掉这部分分配，且解析结果不变
+
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::Instant;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use serde_json::json;
+
+use rust_adx::bidding::dsp_client::{BidFetcher, DspCallOutcome, DspCallResult};
+use rust_adx::bidding::engine::process_bid_request_with;
+use rust_adx::bidding::events::EventBus;
+use rust_adx::config::config_manager::ConfigManager;
+use rust_adx::logging::runtime_logger::RuntimeLogger;
+use rust_adx::model::context::Context;
+use rust_adx::model::dsp::{Demand, DemandManager};
+use rust_adx::openrtb::request::BidRequest;
+use rust_adx::openrtb::response::BidResponse;
+
+/// 具有代表性的请求：banner + video 两个展示位，携带 site / device.geo / user / regs / source
+const BID_REQUEST: &str = r#"{
+    "id": "bench-request",
+    "imp": [
+        {"id": "1", "bidfloor": 0.5, "bidfloorcur": "USD", "secure": 1,
+         "banner": {"w": 300, "h": 250, "api": [3, 5]},
+         "metric": [{"type": "viewability", "value": 0.8}]},
+        {"id": "2", "bidfloor": 2.0, "bidfloorcur": "USD",
+         "video": {"mimes": ["video/mp4"], "minduration": 5, "maxduration": 30, "protocols": [2, 3], "w": 640, "h": 480, "api": [7]},
+         "pmp": {"private_auction": 0, "deals": [{"id": "deal-1", "bidfloor": 3.0, "at": 1}]}}
+    ],
+    "site": {"id": "site-1", "name": "Example", "domain": "example.com", "page": "https://example.com/article", "cat": ["IAB1", "IAB12"]},
+    "device": {"ua": "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X)", "ip": "203.0.113.7",
+               "geo": {"lat": 31.23, "lon": 121.47, "country": "CHN", "region": "CN-SH", "type": 2}},
+    "user": {"id": "user-1"},
+    "source": {"fd": 1, "tid": "tid-1"},
+    "regs": {"coppa": 0, "gdpr": 0},
+    "at": 2,
+    "tmax": 300,
+    "cur": ["USD"],
+    "bcat": ["IAB25"]
+}"#;
+
+/// 返回预置出价的 BidFetcher，不发起网络请求
+struct CannedFetcher {
+    results: Vec<DspCallResult>,
+}
+
+impl BidFetcher for CannedFetcher {
+    async fn fetch_bids(&self, _request: &Arc<BidRequest>) -> Vec<DspCallResult> {
+        self.results.clone()
+    }
+}
+
+fn canned_result(dsp_id: u64, prices: [f64; 2]) -> DspCallResult {
+    let bid_response: BidResponse = serde_json::from_value(json!({
+        "id": "bench-request",
+        "seatbid": [{
+            "seat": format!("seat-{}", dsp_id),
+            "bid": [
+                {"id": format!("{}-1", dsp_id), "impid": "1", "price": prices[0], "crid": format!("crid-{}-1", dsp_id),
+                 "adomain": ["advertiser.com"], "cat": ["IAB3"],
+                 "adm": "<html><body>ad {AUCTION_PRICE}</body></html>"},
+                {"id": format!("{}-2", dsp_id), "impid": "2", "price": prices[1], "crid": format!("crid-{}-2", dsp_id),
+                 "adm": "<VAST version=\"3.0\"><Ad><InLine><Impression><![CDATA[http://dsp/imp?p={AUCTION_PRICE}]]></Impression></InLine></Ad></VAST>"}
+            ]
+        }],
+        "cur": "USD"
+    })).expect("valid canned bid response");
+    DspCallResult {
+        dsp_id,
+        dsp_url: format!("http://dsp-{}.local/bid", dsp_id),
+        price: prices[0].max(prices[1]),
+        bid_response,
+        outcome: DspCallOutcome::Success,
+        elapsed_ms: 0,
+        retries: 0,
+    }
+}
+
+fn bench_parse(c: &mut Criterion) {
+    c.bench_function("parse_bid_request", |b| {
+        b.iter(|| serde_json::from_str::<BidRequest>(black_box(BID_REQUEST)).unwrap())
+    });
+}
+
+/// 访问竞价流程中用到的全部 getter
+fn touch_details(bid_request: &BidRequest) {
+    for imp in bid_request.get_imp_details() {
+        black_box(imp.get_banner_detail());
+        black_box(imp.get_video_detail());
+        black_box(imp.get_pmp_detail());
+    }
+    black_box(bid_request.get_site_detail());
+    black_box(bid_request.get_device_detail());
+    black_box(bid_request.get_geo());
+    black_box(bid_request.get_user_detail());
+    black_box(bid_request.get_source_detail());
+    black_box(bid_request.get_regs_detail());
+}
+
+fn bench_getters(c: &mut Criterion) {
+    // 每次迭代使用新解析的请求，测量首次调用（未命中缓存）的开销
+    c.bench_function("detail_getters", |b| {
+        b.iter_batched(
+            || serde_json::from_str::<BidRequest>(BID_REQUEST).unwrap(),
+            |bid_request| {
+                touch_details(&bid_request);
+                bid_request
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_clone(c: &mut Criterion) {
+    // 已访问过 getter 的请求克隆后再次访问 getter（handler 构造 Context、DSP 请求调整 tmax 时的路径）
+    let warm = serde_json::from_str::<BidRequest>(BID_REQUEST).unwrap();
+    touch_details(&warm);
+    c.bench_function("clone_warm_request", |b| {
+        b.iter(|| {
+            let cloned = black_box(&warm).clone();
+            touch_details(&cloned);
+            cloned
+        })
+    });
+}
+
+fn bench_process_bid_request(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let log_dir = std::env::temp_dir().join("rust-adx-bench");
+    let runtime_logger = runtime.block_on(async {
+        RuntimeLogger::new(log_dir.to_str().unwrap(), "bench", 10_000, 1000, 1000)
+    });
+    let events = EventBus::new(1024);
+
+    let mut demand_manager = DemandManager::new();
+    for id in 1..=3 {
+        demand_manager.add_demand(Demand::new(id, &format!("bench{}_dsp", id), &format!("http://dsp-{}.local/bid", id), true, Some(200)));
+    }
+    let config = ConfigManager::new(demand_manager);
+    let fetcher = CannedFetcher {
+        results: vec![canned_result(1, [1.2, 4.5]), canned_result(2, [2.4, 3.1]), canned_result(3, [0.9, 5.0])],
+    };
+    let context = Context {
+        bid_request: serde_json::from_str(BID_REQUEST).unwrap(),
+        ssp: serde_json::from_value(json!({"id": 1, "uuid": "ssp-uuid-001", "name": "Bench SSP", "qps": 1000})).unwrap(),
+        ssp_placement: serde_json::from_value(json!({
+            "ssp_id": 1, "ssp_uuid": "ssp-uuid-001", "placement_id": "placement-001",
+            "ad_type": 2, "update_time": 0, "status": 1
+        })).unwrap(),
+        dsp_requests: vec![],
+        start_time: Instant::now(),
+    };
+
+    c.bench_function("process_bid_request", |b| {
+        b.iter(|| {
+            runtime.block_on(process_bid_request_with(&context, &config, &runtime_logger, &events, &fetcher))
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse, bench_getters, bench_clone, bench_process_bid_request);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+// fuzz/fuzz_targets/bid_request.rs
+
+//! 将任意字节解析为 BidRequest，并调用全部延迟解析 getter，要求任何输入都不 panic
+//!
+//! 运行：cargo +nightly fuzz run bid_request fuzz/corpus/bid_request
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_adx::api::validation::{apply_default_cur, validate_bid_request};
+use rust_adx::openrtb::request::BidRequest;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut bid_request) = serde_json::from_slice::<BidRequest>(data) else {
+        return;
+    };
+    let _ = validate_bid_request(&bid_request);
+    apply_default_cur(&mut bid_request);
+
+    for imp in bid_request.get_imp_details() {
+        let _ = imp.get_banner_detail();
+        let _ = imp.get_video_detail();
+        let _ = imp.get_audio_detail();
+        let _ = imp.get_native_detail();
+        let _ = imp.get_pmp_detail();
+        let _ = imp.supported_apis();
+    }
+    let _ = bid_request.get_site_detail();
+    let _ = bid_request.get_app_detail();
+    let _ = bid_request.get_device_detail();
+    let _ = bid_request.get_geo();
+    let _ = bid_request.get_user_detail();
+    let _ = bid_request.get_source_detail();
+    let _ = bid_request.get_regs_detail();
+
+    bid_request.strip_precise_geo();
+    let _ = bid_request.get_geo();
+    let _ = serde_json::to_vec(&bid_request);
+});
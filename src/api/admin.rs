@@ -0,0 +1,209 @@
+// src/api/admin.rs
+
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use crate::api::extract::ApiJson;
+use crate::api::models::ErrorResponse;
+use crate::bidding::outcome::AuctionOutcome;
+use crate::logging::runtime_logger::LogChannelStats;
+use crate::model::placements::AdType;
+use crate::AppState;
+
+/// 管理接口路由，只允许可信来源访问（见 require_trusted_source）
+pub fn router(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/admin/blocked_crids", get(list_blocked_crids).post(block_crid))
+        .route("/admin/blocked_crids/{crid}", delete(unblock_crid))
+        .route("/admin/log_channel", get(log_channel_stats))
+        .route("/admin/reload-demands", post(reload_demands))
+        .route("/admin/ad-types", get(list_ad_types).post(set_ad_type))
+        .route("/admin/maintenance", get(get_maintenance).post(set_maintenance))
+        .route("/admin/recent-auctions", get(recent_auctions))
+        .route_layer(middleware::from_fn_with_state(state, require_trusted_source))
+}
+
+/// 管理接口与竞价接口共用端口，来源 IP 不在 admin_trusted_ips 内（未配置时不是本机回环地址）的请求返回 403
+pub async fn require_trusted_source(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = peer.ip().to_canonical();
+    let trusted_ips = &state.config.admin_trusted_ips;
+    let trusted = if trusted_ips.is_empty() {
+        ip.is_loopback()
+    } else {
+        trusted_ips.iter().any(|net| net.contains(&ip))
+    };
+    if trusted {
+        return next.run(request).await;
+    }
+    let log_entry = json!({ "adx_log": "admin_untrusted_source", "path": request.uri().path(), "peer_ip": ip.to_string() });
+    state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    (StatusCode::FORBIDDEN, Json(ErrorResponse {
+        error: "untrusted_source".to_string(),
+        detail: format!("source address {} is not allowed to access admin endpoints", ip),
+    })).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct BlockCridRequest {
+    pub crid: String,
+}
+
+#[derive(Serialize)]
+pub struct BlockedCridsResponse {
+    pub blocked_crids: Vec<String>,
+}
+
+/// 查看当前屏蔽的创意 ID
+pub async fn list_blocked_crids(State(state): State<Arc<AppState>>) -> Json<BlockedCridsResponse> {
+    Json(BlockedCridsResponse { blocked_crids: state.config.get_blocked_crids() })
+}
+
+/// 屏蔽创意 ID，下一次竞价起携带该 crid 的出价会以 blocked_crid 被拒绝
+pub async fn block_crid(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<BlockCridRequest>,
+) -> Result<Json<BlockedCridsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let crid = request.crid.trim();
+    if crid.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
+            error: "invalid_crid".to_string(),
+            detail: "crid must not be empty".to_string(),
+        })));
+    }
+    if state.config.block_crid(crid) {
+        let log_entry = json!({ "adx_log": "crid_blocked", "crid": crid });
+        state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    }
+    Ok(Json(BlockedCridsResponse { blocked_crids: state.config.get_blocked_crids() }))
+}
+
+/// 解除创意 ID 屏蔽
+pub async fn unblock_crid(
+    State(state): State<Arc<AppState>>,
+    Path(crid): Path<String>,
+) -> Result<Json<BlockedCridsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    if !state.config.unblock_crid(&crid) {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse {
+            error: "crid_not_blocked".to_string(),
+            detail: format!("crid {} is not blocked", crid),
+        })));
+    }
+    let log_entry = json!({ "adx_log": "crid_unblocked", "crid": crid });
+    state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    Ok(Json(BlockedCridsResponse { blocked_crids: state.config.get_blocked_crids() }))
+}
+
+/// 运行日志通道积压、丢弃模式与丢弃计数
+pub async fn log_channel_stats(State(state): State<Arc<AppState>>) -> Json<LogChannelStats> {
+    Json(state.runtime_logger.channel_stats())
+}
+
+#[derive(Serialize)]
+pub struct ReloadDemandsResponse {
+    pub before: usize,
+    pub after: usize,
+    pub active: usize,
+}
+
+/// 通过配置适配器重新读取 DSP 列表并整体替换，下一次竞价起生效
+pub async fn reload_demands(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ReloadDemandsResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let demands = match state.config_adapter.get_demands() {
+        Some(demands) if !demands.is_empty() => demands,
+        _ => {
+            return Err((StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse {
+                error: "demands_unavailable".to_string(),
+                detail: "config adapter returned no demands, keeping the current list".to_string(),
+            })));
+        }
+    };
+    let (before, after) = state.config.replace_demands(demands);
+    let active = state.config.active_demands().len();
+    let log_entry = json!({ "adx_log": "demands_reloaded", "before": before, "after": after, "active": active });
+    state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    Ok(Json(ReloadDemandsResponse { before, after, active }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+}
+
+/// 查看是否处于维护（摘流）模式
+pub async fn get_maintenance(State(state): State<Arc<AppState>>) -> Json<MaintenanceMode> {
+    Json(MaintenanceMode { enabled: state.maintenance.load(Ordering::Relaxed) })
+}
+
+/// 开启或关闭维护模式：开启后 /readyz 返回 503 使负载均衡摘除节点，新的竞价请求返回无竞价
+pub async fn set_maintenance(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<MaintenanceMode>,
+) -> Json<MaintenanceMode> {
+    let previous = state.maintenance.swap(request.enabled, Ordering::Relaxed);
+    if previous != request.enabled {
+        let log_entry = json!({ "adx_log": "maintenance_mode", "enabled": request.enabled });
+        state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    }
+    Json(MaintenanceMode { enabled: request.enabled })
+}
+
+#[derive(Deserialize)]
+pub struct SetAdTypeRequest {
+    pub ad_type: AdType,
+    pub enabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct EnabledAdTypesResponse {
+    pub enabled_ad_types: Vec<AdType>,
+}
+
+/// 查看当前启用的广告类型
+pub async fn list_ad_types(State(state): State<Arc<AppState>>) -> Json<EnabledAdTypesResponse> {
+    Json(EnabledAdTypesResponse { enabled_ad_types: state.config.get_enabled_ad_types() })
+}
+
+/// 启用或关闭广告类型（如故障期间临时关闭视频），下一次竞价起生效
+pub async fn set_ad_type(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<SetAdTypeRequest>,
+) -> Json<EnabledAdTypesResponse> {
+    if state.config.set_ad_type_enabled(request.ad_type, request.enabled) {
+        let log_entry = json!({ "adx_log": "ad_type_toggled", "ad_type": request.ad_type, "enabled": request.enabled });
+        state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    }
+    Json(EnabledAdTypesResponse { enabled_ad_types: state.config.get_enabled_ad_types() })
+}
+
+#[derive(Serialize)]
+pub struct RecentAuctionsResponse {
+    pub enabled: bool,
+    pub capacity: usize,
+    /// 最近的竞价结果，最新的在前
+    pub auctions: Vec<AuctionOutcome>,
+}
+
+/// 最近若干次竞价的结果（请求 id、胜出出价、耗时、拒绝原因），未开启时 auctions 为空
+pub async fn recent_auctions(State(state): State<Arc<AppState>>) -> Json<RecentAuctionsResponse> {
+    let response = match &state.config.recent_auctions {
+        Some(recent) => RecentAuctionsResponse { enabled: true, capacity: recent.capacity(), auctions: recent.snapshot() },
+        None => RecentAuctionsResponse { enabled: false, capacity: 0, auctions: Vec::new() },
+    };
+    Json(response)
+}
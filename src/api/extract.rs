@@ -0,0 +1,126 @@
+// src/api/extract.rs
+
+use std::sync::Arc;
+use axum::{
+    body::Bytes,
+    extract::{rejection::{JsonRejection, QueryRejection}, FromRequest, FromRequestParts, Query, Request},
+    http::{header::CONTENT_TYPE, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use crate::api::models::ErrorResponse;
+use crate::AppState;
+
+/// 默认允许的竞价请求 Content-Type
+pub const DEFAULT_CONTENT_TYPES: [&str; 1] = ["application/json"];
+
+/// 校验配置的 Content-Type 白名单：竞价请求体一律按 JSON 解析，不支持 protobuf
+pub fn validate_content_types(content_types: &[String]) -> Result<(), String> {
+    if content_types.is_empty() {
+        return Err("allowed content types must not be empty".to_string());
+    }
+    match content_types.iter().find(|t| t.to_ascii_lowercase().contains("protobuf")) {
+        Some(t) => Err(format!("content type {} is not supported: protobuf bid requests are not decoded", t)),
+        None => Ok(()),
+    }
+}
+
+/// 请求体 / 查询参数解析失败时返回的结构化 JSON 错误
+pub struct ApiRejection {
+    pub status: StatusCode,
+    pub error: ErrorResponse,
+}
+
+impl IntoResponse for ApiRejection {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.error)).into_response()
+    }
+}
+
+impl From<JsonRejection> for ApiRejection {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = match &rejection {
+            JsonRejection::JsonSyntaxError(_) => "invalid_json",
+            JsonRejection::JsonDataError(_) => "invalid_body",
+            JsonRejection::MissingJsonContentType(_) => "unsupported_content_type",
+            _ => "unreadable_body",
+        };
+        Self {
+            status: rejection.status(),
+            error: ErrorResponse { error: code.to_string(), detail: rejection.body_text() },
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiRejection {
+    fn from(rejection: QueryRejection) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: ErrorResponse { error: "invalid_query".to_string(), detail: rejection.body_text() },
+        }
+    }
+}
+
+/// 与 axum::Json 相同，但解析失败时返回 JSON 错误而不是纯文本
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await?;
+        Ok(ApiJson(value))
+    }
+}
+
+/// 竞价请求体：先按配置的 Content-Type 白名单校验（不在白名单内返回 415），再按 JSON 解析，
+/// 解析失败时返回与 ApiJson 相同的 JSON 错误
+pub struct OpenRtbBody<T>(pub T);
+
+impl<T: DeserializeOwned> FromRequest<Arc<AppState>> for OpenRtbBody<T> {
+    type Rejection = ApiRejection;
+
+    async fn from_request(req: Request, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let content_type = req.headers().get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        let allowed = &state.config.allowed_content_types;
+        if !allowed.iter().any(|t| t.eq_ignore_ascii_case(mime)) {
+            return Err(ApiRejection {
+                status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                error: ErrorResponse {
+                    error: "unsupported_content_type".to_string(),
+                    detail: format!("Content-Type {:?} is not allowed, expected one of: {}", content_type, allowed.join(", ")),
+                },
+            });
+        }
+        let body = Bytes::from_request(req, state).await.map_err(|rejection| ApiRejection {
+            status: rejection.status(),
+            error: ErrorResponse { error: "unreadable_body".to_string(), detail: rejection.body_text() },
+        })?;
+        let Json(value) = Json::<T>::from_bytes(&body)?;
+        Ok(OpenRtbBody(value))
+    }
+}
+
+/// 与 axum::extract::Query 相同，但解析失败（如缺少 ssp_uuid）时返回 JSON 错误
+pub struct ApiQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state).await?;
+        Ok(ApiQuery(value))
+    }
+}
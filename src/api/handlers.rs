@@ -1,13 +1,22 @@
 // src/api/handlers.rs
 
-use axum::{extract::{State, Query}, http::StatusCode, Json};
+use axum::{extract::{ConnectInfo, State}, http::{HeaderValue, StatusCode}, response::{IntoResponse, Response}, Json};
+use futures::future::join_all;
 use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::api::extract::{ApiQuery, OpenRtbBody};
+use crate::api::idempotency::Lookup;
+use crate::api::models::{BatchRequestItem, BatchResponseItem, ErrorResponse};
+use crate::api::validation::{apply_default_cur, enforce_min_tmax, validate_bid_request};
 use crate::bidding::engine::process_bid_request;
 use crate::openrtb::request::BidRequest;
 use crate::openrtb::response::BidResponse;
 use crate::model::context::Context;
-use crate::model::ssp::Ssp;
+use crate::model::ssp::{NoBidStatus, ResponseTransform};
 use crate::AppState;
 
 #[derive(Deserialize)]
@@ -15,25 +24,251 @@ pub struct SspQuery {
     pub ssp_uuid: String,
 }
 
+/// 单次竞价的处理结果
+#[derive(Clone)]
+pub enum AuctionReply {
+    /// 有胜出出价
+    Bid(BidResponse),
+    /// 无竞价，按 SSP 配置返回 204（无 body）或 200（带 nbr 的 body）
+    NoBid(NoBidStatus, BidResponse),
+    /// 请求被拒绝
+    Error(StatusCode, ErrorResponse),
+}
+
+impl AuctionReply {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuctionReply::Bid(_) => StatusCode::OK,
+            AuctionReply::NoBid(NoBidStatus::NoContent, _) => StatusCode::NO_CONTENT,
+            AuctionReply::NoBid(NoBidStatus::Ok, _) => StatusCode::OK,
+            AuctionReply::Error(status, _) => *status,
+        }
+    }
+}
+
+impl AuctionReply {
+    /// 按 SSP 的响应调整配置处理返回的 BidResponse
+    fn transformed(mut self, transform: &ResponseTransform) -> Self {
+        if let AuctionReply::Bid(response) | AuctionReply::NoBid(_, response) = &mut self {
+            transform.apply(response);
+        }
+        self
+    }
+}
+
+impl IntoResponse for AuctionReply {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        match self {
+            AuctionReply::NoBid(NoBidStatus::NoContent, _) => status.into_response(),
+            AuctionReply::Bid(response) | AuctionReply::NoBid(_, response) => (status, Json(response)).into_response(),
+            AuctionReply::Error(_, error) => (status, Json(error)).into_response(),
+        }
+    }
+}
+
+impl From<AuctionReply> for BatchResponseItem {
+    fn from(reply: AuctionReply) -> Self {
+        let status = reply.status().as_u16();
+        match reply {
+            AuctionReply::Bid(response) | AuctionReply::NoBid(_, response) => {
+                BatchResponseItem { status, response: Some(response), error: None }
+            }
+            AuctionReply::Error(_, error) => BatchResponseItem { status, response: None, error: Some(error) },
+        }
+    }
+}
+
 pub async fn handle_openrtb_request(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<SspQuery>,
-    Json(bid_request): Json<BidRequest>,
-) -> (StatusCode, Json<BidResponse>) {
-    // 通过查询参数获取 ssp_uuid
-    let ssp_uuid = query.ssp_uuid;
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    ApiQuery(query): ApiQuery<SspQuery>,
+    OpenRtbBody(bid_request): OpenRtbBody<BidRequest>,
+) -> Response {
+    let start_time = Instant::now();
+    let request_id = bid_request.id.clone();
+    let reply = run_auction(&state, peer.ip(), &query.ssp_uuid, bid_request, start_time).await;
+    let serialization_start = Instant::now();
+    let response = reply.into_response();
+    // 序列化耗时超出 SSP 预留的时间时记录日志，用于调整 serialization_reserve_ms
+    let reserve_ms = state.ssp_info.iter()
+        .find(|ssp| ssp.uuid == query.ssp_uuid)
+        .and_then(|ssp| ssp.serialization_reserve_ms);
+    if let Some(reserve_ms) = reserve_ms {
+        let serialization = serialization_start.elapsed();
+        if serialization > Duration::from_millis(reserve_ms) {
+            let log_entry = json!({
+                "request_id": request_id,
+                "adx_log": "serialization_reserve_exceeded",
+                "ssp_uuid": query.ssp_uuid,
+                "serialization_us": serialization.as_micros(),
+                "serialization_reserve_ms": reserve_ms,
+            });
+            state.runtime_logger.log("WARN", &log_entry.to_string()).await;
+        }
+    }
+    with_latency_header(response, start_time)
+}
+
+/// ADX 处理耗时响应头（毫秒）
+pub const LATENCY_HEADER: &str = "x-adx-latency-ms";
+
+/// 在响应中附带 ADX 处理耗时，竞价成功与无竞价均返回
+fn with_latency_header(mut response: Response, start_time: Instant) -> Response {
+    let latency_ms = start_time.elapsed().as_millis();
+    if let Ok(value) = HeaderValue::from_str(&latency_ms.to_string()) {
+        response.headers_mut().insert(LATENCY_HEADER, value);
+    }
+    response
+}
+
+/// 批量竞价：请求体为 [{ "ssp_uuid": "...", "bid_request": { ... } }, ...]，
+/// 各请求并发竞价，单个请求解析或处理失败不影响其它请求，结果按请求顺序返回
+pub async fn handle_openrtb_batch(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    OpenRtbBody(items): OpenRtbBody<Vec<Value>>,
+) -> Response {
+    let start_time = Instant::now();
+    let state = &state;
+    let replies = join_all(items.into_iter().map(|item| async move {
+        match serde_json::from_value::<BatchRequestItem>(item) {
+            Ok(item) => run_auction(state, peer.ip(), &item.ssp_uuid, item.bid_request, Instant::now()).await,
+            Err(e) => AuctionReply::Error(StatusCode::BAD_REQUEST, ErrorResponse {
+                error: "invalid_batch_item".to_string(),
+                detail: e.to_string(),
+            }),
+        }
+    })).await;
+    let items: Vec<BatchResponseItem> = replies.into_iter().map(BatchResponseItem::from).collect();
+    with_latency_header(Json(items).into_response(), start_time)
+}
+
+/// 处理单个 SSP 的竞价请求：校验、查找 SSP 配置、构造 Context 并调用竞价引擎
+async fn run_auction(state: &AppState, peer_ip: IpAddr, ssp_uuid: &str, mut bid_request: BidRequest, start_time: Instant) -> AuctionReply {
+    // 降级模式下关键配置缺失，直接拒绝
+    if state.degraded {
+        return AuctionReply::Error(StatusCode::SERVICE_UNAVAILABLE, ErrorResponse {
+            error: "config_unavailable".to_string(),
+            detail: "adx is running in degraded mode because critical config is missing".to_string(),
+        });
+    }
+
+    // 维护模式下不再竞价，直接返回无竞价
+    if state.maintenance.load(Ordering::Relaxed) {
+        return match state.ssp_info.iter().find(|s| s.uuid == ssp_uuid) {
+            Some(ssp) => no_bid_response(&bid_request.id, ssp.nobid_status).transformed(&ssp.response_transform),
+            None => no_bid_response(&bid_request.id, NoBidStatus::default()),
+        };
+    }
+
+    let validation = validate_bid_request(&bid_request)
+        .and_then(|_| enforce_min_tmax(&mut bid_request, state.config.min_tmax_ms, state.config.min_tmax_action));
+    if let Err(e) = validation {
+        state.runtime_logger.log("WARN", &format!(
+            r#"{{ "request_id": "{}", "adx_log": "invalid_request", "reason": "{}" }}"#,
+            bid_request.id,
+            e.code()
+        )).await;
+        return AuctionReply::Error(e.status(), e.to_response());
+    }
+    apply_default_cur(&mut bid_request);
+    // COPPA 流量及适用 GPP 美国隐私 section 的流量不向下游传递精确地理位置
+    if bid_request.get_regs_detail().is_some_and(|regs| regs.requires_coarse_geo()) {
+        bid_request.strip_precise_geo();
+    }
+
+    // 并发竞价数达到上限时立即拒绝，避免排队超过 tmax
+    let Ok(_auction_slot) = state.auction_slots.try_acquire() else {
+        state.runtime_logger.log("WARN", &format!(
+            r#"{{ "request_id": "{}", "adx_log": "load_shed" }}"#,
+            bid_request.id
+        )).await;
+        return AuctionReply::Error(StatusCode::SERVICE_UNAVAILABLE, ErrorResponse {
+            error: "overloaded".to_string(),
+            detail: "too many concurrent auctions".to_string(),
+        });
+    };
 
     // 在全局 SSP 信息列表中查找匹配的 SSP
-    let ssp = state.ssp_info.iter()
-        .find(|s| s.uuid == ssp_uuid)
-        .cloned()
-        .expect("No matching SSP found");
+    let Some(ssp) = state.ssp_info.iter().find(|s| s.uuid == ssp_uuid).cloned() else {
+        return AuctionReply::Error(StatusCode::BAD_REQUEST, ErrorResponse {
+            error: "unknown_ssp".to_string(),
+            detail: format!("no ssp found for ssp_uuid {}", ssp_uuid),
+        });
+    };
+
+    // 来源 IP 不在白名单内时拒绝
+    if !ssp.is_trusted_source(peer_ip, &state.config.trusted_ips) {
+        state.runtime_logger.log("WARN", &format!(
+            r#"{{ "request_id": "{}", "adx_log": "untrusted_source", "ssp_uuid": "{}", "peer_ip": "{}" }}"#,
+            bid_request.id,
+            ssp.uuid,
+            peer_ip
+        )).await;
+        return AuctionReply::Error(StatusCode::FORBIDDEN, ErrorResponse {
+            error: "untrusted_source".to_string(),
+            detail: format!("source address {} is not allowed for ssp_uuid {}", peer_ip, ssp_uuid),
+        });
+    }
+
+    // 超出 SSP 的 qps 与突发额度时拒绝
+    if let Some(rate_limiter) = &state.rate_limiter {
+        if !rate_limiter.try_acquire(&ssp.uuid, ssp.qps) {
+            state.runtime_logger.log("WARN", &format!(
+                r#"{{ "request_id": "{}", "adx_log": "qps_exceeded", "ssp_uuid": "{}", "qps": {} }}"#,
+                bid_request.id,
+                ssp.uuid,
+                ssp.qps
+            )).await;
+            return AuctionReply::Error(StatusCode::TOO_MANY_REQUESTS, ErrorResponse {
+                error: "qps_exceeded".to_string(),
+                detail: format!("ssp_uuid {} exceeded its qps limit of {}", ssp_uuid, ssp.qps),
+            });
+        }
+    }
 
     // 在 ConfigManager 中查找 SSP 广告位
-    let ssp_placement = state.config.get_ssp_placements()
+    let Some(ssp_placement) = state.config.get_ssp_placements()
         .into_iter()
-        .find(|sp| sp.ssp_uuid == ssp.uuid)
-        .expect("No matching SSP placement found");
+        .find(|sp| sp.ssp_uuid == ssp.uuid) else {
+        return AuctionReply::Error(StatusCode::BAD_REQUEST, ErrorResponse {
+            error: "unknown_ssp_placement".to_string(),
+            detail: format!("no placement configured for ssp_uuid {}", ssp_uuid),
+        });
+    };
+
+    // 开启幂等缓存时，TTL 内重复的请求 id 直接返回之前的竞价结果，与进行中的竞价重复时等待其结果
+    let reservation = match &state.idempotency_cache {
+        Some(cache) => match cache.get_or_reserve(&ssp.uuid, &bid_request.id).await {
+            Lookup::Replay(reply) => {
+                state.runtime_logger.log("INFO", &format!(
+                    r#"{{ "request_id": "{}", "adx_log": "idempotent_replay", "ssp_uuid": "{}" }}"#,
+                    bid_request.id,
+                    ssp.uuid
+                )).await;
+                return reply;
+            }
+            Lookup::Reserved(reservation) => Some(reservation),
+        },
+        None => None,
+    };
+
+    // 检测 TTL 内重复出现的请求 id，默认只记录，开启后直接返回无竞价
+    if state.recent_request_ids.check_and_record(&bid_request.id) {
+        let rejected = state.config.reject_duplicate_requests;
+        state.runtime_logger.log("WARN", &format!(
+            r#"{{ "request_id": "{}", "adx_log": "duplicate_request", "rejected": {} }}"#,
+            bid_request.id,
+            rejected
+        )).await;
+        if rejected {
+            return no_bid_response(&bid_request.id, ssp.nobid_status).transformed(&ssp.response_transform);
+        }
+    }
+
+    let nobid_status = ssp.nobid_status;
+    let response_transform = ssp.response_transform.clone();
 
     // 构造 Context（贯穿整个调用链），由 API Handler 构造
     let context = Context {
@@ -41,36 +276,56 @@ pub async fn handle_openrtb_request(
         ssp,
         ssp_placement,
         dsp_requests: vec![], // 后续可构造 DSP 请求信息
-        start_time: std::time::Instant::now(),
+        start_time,
     };
 
-    let bid_response = process_bid_request(&context, &state.config, &state.runtime_logger).await;
+    // 开启测试流量分流时，测试流量的竞价日志写入独立的测试日志
+    let runtime_logger = match &state.test_logger {
+        Some(test_logger) if context.is_test_traffic() => test_logger,
+        _ => &state.runtime_logger,
+    };
+    let bid_response = process_bid_request(&context, &state.config, runtime_logger, &state.event_bus).await;
 
-    match bid_response {
+    let reply = match bid_response {
         Some(response) if !response.seatbid.is_empty() => {
-            state.runtime_logger.log("INFO", &format!(
+            runtime_logger.log("INFO", &format!(
                 r#"{{ "request_id": "{}", "adx_log": "adx_inquiry_success", "winning_price": {} }}"#,
                 response.id,
                 response.seatbid[0].bid[0].price
             )).await;
-            (StatusCode::OK, Json(response))
+            AuctionReply::Bid(response)
         }
-        _ => {
-            state.runtime_logger.log("ERROR", &format!(
-                r#"{{ "request_id": "{}", "adx_log": "adx_inquiry_failed" }}"#,
-                bid_request.id
+        bid_response => {
+            // 唯一 DSP 返回的 nbr 原样转给 SSP
+            let dsp_nbr = bid_response.and_then(|response| response.nbr);
+            runtime_logger.log("ERROR", &format!(
+                r#"{{ "request_id": "{}", "adx_log": "adx_inquiry_failed", "dsp_nbr": {} }}"#,
+                bid_request.id,
+                dsp_nbr.map_or("null".to_string(), |nbr| nbr.to_string())
             )).await;
-            (
-                StatusCode::NO_CONTENT,
-                Json(BidResponse {
-                    id: bid_request.id.clone(),
-                    seatbid: vec![],
-                    bidid: None,
-                    cur: Some("USD".to_string()),
-                    customdata: None,
-                    nbr: Some(3),
-                }),
-            )
+            let mut reply = no_bid_response(&bid_request.id, nobid_status);
+            if let (Some(nbr), AuctionReply::NoBid(_, response)) = (dsp_nbr, &mut reply) {
+                response.nbr = Some(nbr);
+            }
+            reply
         }
+    };
+    let reply = reply.transformed(&response_transform);
+    if let Some(reservation) = reservation {
+        reservation.complete(&reply);
     }
+    reply
+}
+
+/// 构造无竞价响应
+fn no_bid_response(request_id: &str, status: NoBidStatus) -> AuctionReply {
+    AuctionReply::NoBid(status, BidResponse {
+        id: request_id.to_string(),
+        seatbid: vec![],
+        bidid: None,
+        cur: Some("USD".to_string()),
+        customdata: None,
+        nbr: Some(3),
+        ext: None,
+    })
 }
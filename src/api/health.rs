@@ -0,0 +1,34 @@
+// src/api/health.rs
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use crate::bidding::stats::{DspStatsSnapshot, HealthStatus};
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    /// 滚动窗口内所有 DSP 的整体成功率，样本不足时为 null
+    pub success_rate: Option<f64>,
+    /// 是否处于维护（摘流）模式
+    pub maintenance: bool,
+    pub dsps: Vec<DspStatsSnapshot>,
+}
+
+/// 就绪检查：根据滚动窗口内的 DSP 成功率给出 healthy / degraded / unhealthy，
+/// unhealthy、关键配置缺失（降级启动）或处于维护模式时返回 503
+pub async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let dsps = state.config.dsp_stats.snapshot();
+    let (mut status, success_rate) = state.config.health_thresholds.evaluate(&dsps);
+    let maintenance = state.maintenance.load(Ordering::Relaxed);
+    if state.degraded || maintenance {
+        status = HealthStatus::Unhealthy;
+    }
+    let code = match status {
+        HealthStatus::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        HealthStatus::Healthy | HealthStatus::Degraded => StatusCode::OK,
+    };
+    (code, Json(ReadinessResponse { status, success_rate, maintenance, dsps }))
+}
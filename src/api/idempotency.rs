@@ -0,0 +1,204 @@
+// src/api/idempotency.rs
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+
+use crate::api::handlers::AuctionReply;
+
+/// 缓存条目数上限，超出后淘汰最早的条目
+const MAX_ENTRIES: usize = 100_000;
+
+type CacheKey = (String, String);
+
+/// 按 (ssp_uuid, BidRequest.id) 缓存竞价结果（TTL 内有效）：SSP 超时重试同一请求时直接返回
+/// 之前的结果而不是重新竞价，避免同一请求产生两次胜出通知。
+/// 竞价进行中的请求 id 会被预占，同时到达的重复请求等待该竞价完成后返回同一结果
+pub struct IdempotencyCache {
+    ttl: Duration,
+    inner: Mutex<CacheInner>,
+}
+
+struct CacheInner {
+    entries: HashMap<CacheKey, Entry>,
+    /// 已完成条目按写入顺序排列的 (时间, key)，用于按 TTL 顺序淘汰
+    order: VecDeque<(Instant, CacheKey)>,
+}
+
+enum Entry {
+    /// 竞价进行中，完成后通过 watch 通道广播结果
+    Pending(watch::Receiver<Option<AuctionReply>>),
+    Done(Instant, AuctionReply),
+}
+
+/// get_or_reserve 的结果
+pub enum Lookup<'a> {
+    /// TTL 内已有（或刚完成的）竞价结果
+    Replay(AuctionReply),
+    /// 本请求预占了该请求 id，竞价完成后须调用 complete；未调用即被丢弃时释放预占
+    Reserved(Reservation<'a>),
+}
+
+/// 对一个请求 id 的预占
+pub struct Reservation<'a> {
+    cache: &'a IdempotencyCache,
+    key: CacheKey,
+    sender: watch::Sender<Option<AuctionReply>>,
+    completed: bool,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            inner: Mutex::new(CacheInner { entries: HashMap::new(), order: VecDeque::new() }),
+        }
+    }
+
+    /// 查找 TTL 内缓存的竞价结果；同一请求 id 正在竞价时等待其完成，否则预占该请求 id
+    pub async fn get_or_reserve(&self, ssp_uuid: &str, request_id: &str) -> Lookup<'_> {
+        let key = (ssp_uuid.to_string(), request_id.to_string());
+        loop {
+            let mut pending = {
+                let now = Instant::now();
+                let mut inner = self.inner.lock().unwrap();
+                match inner.entries.get(&key) {
+                    Some(Entry::Done(stored_at, reply)) if now.duration_since(*stored_at) < self.ttl => {
+                        return Lookup::Replay(reply.clone());
+                    }
+                    Some(Entry::Pending(receiver)) => receiver.clone(),
+                    _ => {
+                        let (sender, receiver) = watch::channel(None);
+                        inner.entries.insert(key.clone(), Entry::Pending(receiver));
+                        return Lookup::Reserved(Reservation { cache: self, key, sender, completed: false });
+                    }
+                }
+            };
+            // 进行中的竞价放弃（结果不缓存或预占被丢弃）时重新查找，由其中一个重复请求重新竞价
+            let reply = pending.wait_for(Option::is_some).await.ok().and_then(|reply| reply.clone());
+            if let Some(reply) = reply {
+                return Lookup::Replay(reply);
+            }
+        }
+    }
+
+    /// 缓存竞价结果，只缓存竞价成功与无竞价，请求被拒绝的结果不缓存
+    fn store(&self, key: &CacheKey, reply: &AuctionReply) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        if matches!(reply, AuctionReply::Error(..)) {
+            inner.entries.remove(key);
+            return;
+        }
+        while let Some((stored_at, _)) = inner.order.front() {
+            if now.duration_since(*stored_at) < self.ttl && inner.order.len() < MAX_ENTRIES {
+                break;
+            }
+            let (stored_at, key) = inner.order.pop_front().unwrap();
+            if inner.entries.get(&key).is_some_and(|entry| matches!(entry, Entry::Done(at, _) if *at == stored_at)) {
+                inner.entries.remove(&key);
+            }
+        }
+        inner.entries.insert(key.clone(), Entry::Done(now, reply.clone()));
+        inner.order.push_back((now, key.clone()));
+    }
+
+    /// 释放未完成的预占
+    fn release(&self, key: &CacheKey) {
+        let mut inner = self.inner.lock().unwrap();
+        if matches!(inner.entries.get(key), Some(Entry::Pending(_))) {
+            inner.entries.remove(key);
+        }
+    }
+}
+
+impl Reservation<'_> {
+    /// 记录竞价结果并唤醒等待中的重复请求；请求被拒绝的结果不缓存，等待者会重新竞价
+    pub fn complete(mut self, reply: &AuctionReply) {
+        self.completed = true;
+        self.cache.store(&self.key, reply);
+        if !matches!(reply, AuctionReply::Error(..)) {
+            self.sender.send_replace(Some(reply.clone()));
+        }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.cache.release(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use crate::api::models::ErrorResponse;
+
+    fn bid_reply(price: f64) -> AuctionReply {
+        AuctionReply::Bid(serde_json::from_value(json!({
+            "id": "r1",
+            "seatbid": [{"bid": [{"id": "b1", "impid": "1", "price": price}]}],
+        })).unwrap())
+    }
+
+    fn replayed_price(lookup: Lookup<'_>) -> Option<f64> {
+        match lookup {
+            Lookup::Replay(AuctionReply::Bid(response)) => Some(response.seatbid[0].bid[0].price),
+            _ => None,
+        }
+    }
+
+    fn reserve(lookup: Lookup<'_>) -> Reservation<'_> {
+        match lookup {
+            Lookup::Reserved(reservation) => reservation,
+            Lookup::Replay(_) => panic!("expected a reservation"),
+        }
+    }
+
+    #[tokio::test]
+    async fn repeated_id_within_ttl_replays_the_previous_reply() {
+        let cache = IdempotencyCache::new(Duration::from_secs(10));
+        reserve(cache.get_or_reserve("ssp", "r1").await).complete(&bid_reply(1.5));
+        assert_eq!(replayed_price(cache.get_or_reserve("ssp", "r1").await), Some(1.5));
+        // 不同 SSP 的同一请求 id 互不影响
+        assert!(matches!(cache.get_or_reserve("other", "r1").await, Lookup::Reserved(_)));
+    }
+
+    #[tokio::test]
+    async fn repeated_id_beyond_ttl_runs_a_new_auction() {
+        let cache = IdempotencyCache::new(Duration::from_millis(20));
+        reserve(cache.get_or_reserve("ssp", "r1").await).complete(&bid_reply(1.5));
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(matches!(cache.get_or_reserve("ssp", "r1").await, Lookup::Reserved(_)));
+    }
+
+    #[tokio::test]
+    async fn concurrent_duplicate_waits_for_the_in_flight_auction() {
+        let cache = IdempotencyCache::new(Duration::from_secs(10));
+        let reservation = reserve(cache.get_or_reserve("ssp", "r1").await);
+        let duplicate = cache.get_or_reserve("ssp", "r1");
+        tokio::pin!(duplicate);
+        assert!(tokio::time::timeout(Duration::from_millis(20), &mut duplicate).await.is_err());
+        reservation.complete(&bid_reply(2.0));
+        assert_eq!(replayed_price(duplicate.await), Some(2.0));
+    }
+
+    #[tokio::test]
+    async fn abandoned_or_rejected_auctions_are_not_replayed() {
+        let cache = IdempotencyCache::new(Duration::from_secs(10));
+        drop(reserve(cache.get_or_reserve("ssp", "r1").await));
+        let rejected = AuctionReply::Error(StatusCode::SERVICE_UNAVAILABLE, ErrorResponse {
+            error: "overloaded".to_string(),
+            detail: String::new(),
+        });
+        reserve(cache.get_or_reserve("ssp", "r1").await).complete(&rejected);
+        assert!(matches!(cache.get_or_reserve("ssp", "r1").await, Lookup::Reserved(_)));
+    }
+}
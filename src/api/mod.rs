@@ -1,3 +1,10 @@
 // src/api/mod.rs
 
-pub mod handlers;
\ No newline at end of file
+pub mod admin;
+pub mod extract;
+pub mod handlers;
+pub mod health;
+pub mod idempotency;
+pub mod models;
+pub mod stats;
+pub mod validation;
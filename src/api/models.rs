@@ -0,0 +1,31 @@
+// src/api/models.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::openrtb::request::BidRequest;
+use crate::openrtb::response::BidResponse;
+
+/// API 错误响应体
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub detail: String,
+}
+
+/// 批量竞价请求中的单个请求
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchRequestItem {
+    pub ssp_uuid: String,
+    pub bid_request: BidRequest,
+}
+
+/// 批量竞价响应中的单个结果，与请求按顺序一一对应
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchResponseItem {
+    /// 与单请求接口一致的 HTTP 状态码（200 / 204 / 4xx）
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<BidResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
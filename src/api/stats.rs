@@ -0,0 +1,27 @@
+// src/api/stats.rs
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use crate::bidding::stats::DspStatsSnapshot;
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct StatsResponse {
+    /// 滚动窗口内各 DSP 的调用统计（成功率、超时率、耗时 p95 与建议超时）
+    pub dsps: Vec<DspStatsSnapshot>,
+    /// 各 DSP 按无竞价原因（nbr）累计的次数，自启动起
+    pub no_bid_reasons: BTreeMap<u64, BTreeMap<&'static str, u64>>,
+    /// 各 DSP 按超时类型（connect_timeout / read_timeout）累计的次数，自启动起
+    pub timeouts: BTreeMap<u64, BTreeMap<&'static str, u64>>,
+}
+
+/// DSP 调用统计（含滚动窗口超时率与建议超时）、无竞价原因与超时类型分布
+pub async fn stats(State(state): State<Arc<AppState>>) -> Json<StatsResponse> {
+    Json(StatsResponse {
+        dsps: state.config.dsp_stats.snapshot(),
+        no_bid_reasons: state.config.dsp_stats.no_bid_reasons(),
+        timeouts: state.config.dsp_stats.timeouts(),
+    })
+}
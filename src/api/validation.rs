@@ -0,0 +1,208 @@
+// src/api/validation.rs
+
+use axum::http::StatusCode;
+use serde_json::Value;
+
+use crate::api::models::ErrorResponse;
+use crate::bidding::currency::{is_iso4217, DEFAULT_CURRENCY};
+use crate::config::config_manager::MinTmaxAction;
+use crate::openrtb::request::{BidRequest, ImpDetail, PmpDetail};
+
+/// BidRequest 校验失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// imp 缺失、为空、不是数组，或其中元素缺少 id
+    InvalidImp(String),
+    /// cur 中存在非 ISO 4217 的货币代码
+    InvalidCurrency(String),
+    /// tmax 低于配置的最小值
+    TmaxTooLow(String),
+}
+
+impl ValidationError {
+    /// 返回给 SSP 的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::InvalidImp(_) => "invalid_imp",
+            ValidationError::InvalidCurrency(_) => "invalid_currency",
+            ValidationError::TmaxTooLow(_) => "tmax_too_low",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        StatusCode::BAD_REQUEST
+    }
+
+    pub fn to_response(&self) -> ErrorResponse {
+        let detail = match self {
+            ValidationError::InvalidImp(detail)
+            | ValidationError::InvalidCurrency(detail)
+            | ValidationError::TmaxTooLow(detail) => detail.clone(),
+        };
+        ErrorResponse { error: self.code().to_string(), detail }
+    }
+}
+
+/// 在进入竞价流程之前校验 BidRequest
+pub fn validate_bid_request(bid_request: &BidRequest) -> Result<(), ValidationError> {
+    validate_imp(bid_request)?;
+    validate_cur(bid_request)
+}
+
+/// cur 与各 imp 的 bidfloorcur 存在时都必须是 ISO 4217 货币代码
+fn validate_cur(bid_request: &BidRequest) -> Result<(), ValidationError> {
+    for (i, cur) in bid_request.cur.iter().flatten().enumerate() {
+        if !is_iso4217(cur) {
+            return Err(ValidationError::InvalidCurrency(format!("cur[{}] {:?} is not an ISO 4217 currency code", i, cur)));
+        }
+    }
+    for (i, imp) in bid_request.get_imp_details().iter().enumerate() {
+        if let Some(cur) = imp.bidfloorcur.as_deref().filter(|cur| !is_iso4217(cur)) {
+            return Err(ValidationError::InvalidCurrency(format!("imp[{}].bidfloorcur {:?} is not an ISO 4217 currency code", i, cur)));
+        }
+    }
+    Ok(())
+}
+
+/// tmax 低于最小值时按配置拒绝，或提升到最小值；未携带 tmax 的请求不受影响
+pub fn enforce_min_tmax(bid_request: &mut BidRequest, min_tmax_ms: Option<u64>, action: MinTmaxAction) -> Result<(), ValidationError> {
+    let (Some(tmax), Some(min_tmax_ms)) = (bid_request.tmax, min_tmax_ms) else {
+        return Ok(());
+    };
+    if tmax >= min_tmax_ms {
+        return Ok(());
+    }
+    match action {
+        MinTmaxAction::Reject => Err(ValidationError::TmaxTooLow(format!(
+            "tmax {} ms is below the minimum {} ms", tmax, min_tmax_ms
+        ))),
+        MinTmaxAction::Clamp => {
+            bid_request.tmax = Some(min_tmax_ms);
+            Ok(())
+        }
+    }
+}
+
+/// 请求未指定 cur（或为空）时显式补齐为 ["USD"]，下游统一按 cur 处理
+pub fn apply_default_cur(bid_request: &mut BidRequest) {
+    if bid_request.cur.as_ref().is_none_or(|cur| cur.is_empty()) {
+        bid_request.cur = Some(vec![DEFAULT_CURRENCY.to_string()]);
+    }
+}
+
+/// imp 必须是非空数组，且每个元素都是带字符串 id、字段类型正确（含 pmp）的对象
+fn validate_imp(bid_request: &BidRequest) -> Result<(), ValidationError> {
+    let imp = serde_json::to_value(&*bid_request.imp)
+        .map_err(|e| ValidationError::InvalidImp(format!("imp is not valid json: {}", e)))?;
+    let items = match imp {
+        Value::Array(items) => items,
+        Value::Null => return Err(ValidationError::InvalidImp("imp is required".to_string())),
+        _ => return Err(ValidationError::InvalidImp("imp must be an array".to_string())),
+    };
+    if items.is_empty() {
+        return Err(ValidationError::InvalidImp("imp must not be empty".to_string()));
+    }
+    for (i, item) in items.iter().enumerate() {
+        if !item.get("id").is_some_and(Value::is_string) {
+            return Err(ValidationError::InvalidImp(format!("imp[{}] must be an object with a string id", i)));
+        }
+        if let Err(e) = serde_json::from_value::<ImpDetail>(item.clone()) {
+            return Err(ValidationError::InvalidImp(format!("imp[{}] is malformed: {}", i, e)));
+        }
+        // pmp 延迟解析，格式不符（如 deal 底价不是数字）时 getter 只会返回 None，这里提前拒绝
+        if let Some(pmp) = item.get("pmp").filter(|pmp| !pmp.is_null()) {
+            if let Err(e) = serde_json::from_value::<PmpDetail>(pmp.clone()) {
+                return Err(ValidationError::InvalidImp(format!("imp[{}].pmp is malformed: {}", i, e)));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(body: Value) -> BidRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn imp_must_be_a_non_empty_array() {
+        let invalid_imp = |imp: Value| validate_bid_request(&request(json!({"id": "r1", "imp": imp}))).unwrap_err();
+        assert_eq!(invalid_imp(json!({})), ValidationError::InvalidImp("imp must be an array".to_string()));
+        assert_eq!(invalid_imp(json!([])), ValidationError::InvalidImp("imp must not be empty".to_string()));
+        assert_eq!(invalid_imp(json!([{"banner": {}}])).code(), "invalid_imp");
+        assert_eq!(validate_bid_request(&request(json!({"id": "r1"}))), Err(ValidationError::InvalidImp("imp is required".to_string())));
+        assert_eq!(validate_bid_request(&request(json!({"id": "r1", "imp": [{"id": "1", "banner": {"w": 300}}]}))), Ok(()));
+    }
+
+    #[test]
+    fn each_imp_bidfloorcur_must_be_iso4217() {
+        let imps = |second_cur: &str| request(json!({"id": "r1", "imp": [
+            {"id": "1", "bidfloor": 1.0, "bidfloorcur": "USD"},
+            {"id": "2", "bidfloor": 7.0, "bidfloorcur": second_cur},
+        ]}));
+        assert_eq!(validate_bid_request(&imps("CNY")), Ok(()));
+        assert_eq!(validate_bid_request(&imps("yuan")).unwrap_err().code(), "invalid_currency");
+    }
+
+    #[test]
+    fn string_and_float_floors_are_parsed() {
+        let bid_request = request(json!({"id": "r1", "imp": [
+            {"id": "1", "bidfloor": 1.25},
+            {"id": "2", "bidfloor": "0.75", "pmp": {"deals": [{"id": "d1", "bidfloor": "2.5"}, {"id": "d2", "bidfloor": 3}]}},
+        ]}));
+        assert_eq!(validate_bid_request(&bid_request), Ok(()));
+        let imps = bid_request.get_imp_details();
+        assert_eq!((imps[0].bidfloor, imps[1].bidfloor), (Some(1.25), Some(0.75)));
+        let deals = imps[1].get_pmp_detail().unwrap().deals.as_ref().unwrap();
+        assert_eq!((deals[0].bidfloor, deals[1].bidfloor), (Some(2.5), Some(3.0)));
+    }
+
+    #[test]
+    fn non_numeric_floors_are_rejected() {
+        let invalid_imp = |imp: Value| validate_bid_request(&request(json!({"id": "r1", "imp": [imp]}))).unwrap_err().code();
+        assert_eq!(invalid_imp(json!({"id": "1", "bidfloor": "abc"})), "invalid_imp");
+        assert_eq!(invalid_imp(json!({"id": "1", "pmp": {"deals": [{"id": "d1", "bidfloor": "free"}]}})), "invalid_imp");
+    }
+
+    #[test]
+    fn lowercase_currency_codes_are_accepted() {
+        let bid_request = request(json!({
+            "id": "r1",
+            "imp": [{"id": "1", "bidfloorcur": "eur"}],
+            "cur": ["usd", "Cny"],
+        }));
+        assert_eq!(validate_bid_request(&bid_request), Ok(()));
+    }
+
+    #[test]
+    fn unknown_currency_codes_are_rejected() {
+        let bid_request = request(json!({"id": "r1", "imp": [{"id": "1"}], "cur": ["usdx"]}));
+        assert!(matches!(validate_bid_request(&bid_request), Err(ValidationError::InvalidCurrency(_))));
+    }
+
+    #[test]
+    fn tmax_below_the_minimum_is_rejected_or_clamped() {
+        let mut low = request(json!({"id": "r1", "imp": [{"id": "1"}], "tmax": 5}));
+        let rejected = enforce_min_tmax(&mut low, Some(50), MinTmaxAction::Reject).unwrap_err();
+        assert_eq!(rejected.code(), "tmax_too_low");
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(low.tmax, Some(5));
+        assert_eq!(enforce_min_tmax(&mut low, Some(50), MinTmaxAction::Clamp), Ok(()));
+        assert_eq!(low.tmax, Some(50));
+    }
+
+    #[test]
+    fn tmax_at_or_above_the_minimum_is_left_alone() {
+        for (tmax, expected) in [(json!(50), Some(50)), (json!(300), Some(300)), (Value::Null, None)] {
+            let mut bid_request = request(json!({"id": "r1", "imp": [{"id": "1"}], "tmax": tmax}));
+            assert_eq!(enforce_min_tmax(&mut bid_request, Some(50), MinTmaxAction::Reject), Ok(()));
+            assert_eq!(bid_request.tmax, expected);
+        }
+        let mut unconfigured = request(json!({"id": "r1", "imp": [{"id": "1"}], "tmax": 5}));
+        assert_eq!(enforce_min_tmax(&mut unconfigured, None, MinTmaxAction::Reject), Ok(()));
+    }
+}
@@ -0,0 +1,56 @@
+// src/bidding/adapter.rs
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use bytes::Bytes;
+use crate::openrtb::request::BidRequest;
+use crate::openrtb::response::BidResponse;
+
+/// DSP 请求 / 响应格式适配器：不使用标准 OpenRTB JSON 的 DSP 通过适配器完成编解码
+pub trait DspAdapter: Debug + Send + Sync {
+    /// 适配器名称，用于日志
+    fn name(&self) -> &'static str;
+
+    /// 询价请求的 Content-Type
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    /// 将竞价请求编码为发送给 DSP 的请求体
+    fn to_request(&self, request: &BidRequest) -> Bytes;
+
+    /// 将 DSP 的响应体解码为 BidResponse，无法解析时返回错误描述
+    fn parse_response(&self, body: Bytes) -> Result<BidResponse, String>;
+
+    /// 是否为标准 OpenRTB JSON 格式，是则可与其他 DSP 共享同一份序列化结果
+    fn is_openrtb_json(&self) -> bool {
+        false
+    }
+}
+
+/// 默认适配器：标准 OpenRTB JSON
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenRtbJsonAdapter;
+
+impl DspAdapter for OpenRtbJsonAdapter {
+    fn name(&self) -> &'static str {
+        "openrtb_json"
+    }
+
+    fn to_request(&self, request: &BidRequest) -> Bytes {
+        serde_json::to_vec(request).map(Bytes::from).unwrap_or_default()
+    }
+
+    fn parse_response(&self, body: Bytes) -> Result<BidResponse, String> {
+        serde_json::from_slice(&body).map_err(|e| e.to_string())
+    }
+
+    fn is_openrtb_json(&self) -> bool {
+        true
+    }
+}
+
+/// 未单独配置适配器的 DSP 使用的默认适配器
+pub fn default_adapter() -> Arc<dyn DspAdapter> {
+    Arc::new(OpenRtbJsonAdapter)
+}
@@ -0,0 +1,153 @@
+// src/bidding/capture.rs
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde_json::{json, Value};
+
+use crate::logging::runtime_logger::RuntimeLogger;
+
+/// 脱敏后的占位值
+const REDACTED: &str = "[REDACTED]";
+
+/// 默认脱敏字段（点分路径，数组会对每个元素生效）
+pub const DEFAULT_REDACT_FIELDS: [&str; 6] = [
+    "device.ip",
+    "device.ipv6",
+    "device.ifa",
+    "device.geo",
+    "user.id",
+    "user.buyeruid",
+];
+
+/// DSP 请求/响应报文采样抓取，用于排查 DSP 对接问题：
+/// 每 sample_rate 次 DSP 调用抓取一次，脱敏后写入独立的调试日志
+pub struct BodyCapture {
+    sample_rate: u64,
+    counter: AtomicU64,
+    redact_fields: Vec<String>,
+    logger: Arc<RuntimeLogger>,
+}
+
+impl fmt::Debug for BodyCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyCapture")
+            .field("sample_rate", &self.sample_rate)
+            .field("redact_fields", &self.redact_fields)
+            .finish()
+    }
+}
+
+impl BodyCapture {
+    /// sample_rate 为 N 表示 1/N 采样，0 表示关闭（返回 None）
+    pub fn new(sample_rate: u64, redact_fields: Vec<String>, logger: Arc<RuntimeLogger>) -> Option<Self> {
+        (sample_rate > 0).then(|| Self {
+            sample_rate,
+            counter: AtomicU64::new(0),
+            redact_fields,
+            logger,
+        })
+    }
+
+    /// 本次 DSP 调用是否需要抓取
+    pub fn should_sample(&self) -> bool {
+        self.counter.fetch_add(1, Ordering::Relaxed).is_multiple_of(self.sample_rate)
+    }
+
+    /// 按配置的字段路径脱敏
+    pub fn redact(&self, value: &mut Value) {
+        for field in &self.redact_fields {
+            let path: Vec<&str> = field.split('.').collect();
+            redact_path(value, &path);
+        }
+    }
+
+    /// 记录一次 DSP 调用的请求与原始响应报文；响应为 JSON 时同样脱敏
+    pub async fn record(&self, dsp_id: u64, dsp_url: &str, mut request: Value, status: Option<u16>, response_body: &[u8]) {
+        self.redact(&mut request);
+        let response = match serde_json::from_slice::<Value>(response_body) {
+            Ok(mut response) => {
+                self.redact(&mut response);
+                response
+            }
+            Err(_) => Value::String(String::from_utf8_lossy(response_body).into_owned()),
+        };
+        let log_entry = json!({
+            "adx_log": "dsp_body_capture",
+            "dsp_id": dsp_id,
+            "url": dsp_url,
+            "request": request,
+            "status": status,
+            "response": response,
+        });
+        self.logger.log("DEBUG", &log_entry.to_string()).await;
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                redact_path(item, path);
+            }
+        }
+        Value::Object(map) => {
+            if let Some(child) = map.get_mut(*first) {
+                if rest.is_empty() {
+                    *child = Value::String(REDACTED.to_string());
+                } else {
+                    redact_path(child, rest);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(sample_rate: u64, redact_fields: &[&str]) -> Option<BodyCapture> {
+        let log_dir = std::env::temp_dir().join("rust-adx-tests");
+        let logger = RuntimeLogger::new(log_dir.to_str().unwrap(), "test", 10_000, 1000, 1000);
+        BodyCapture::new(sample_rate, redact_fields.iter().map(|f| f.to_string()).collect(), logger)
+    }
+
+    #[tokio::test]
+    async fn capture_triggers_at_the_configured_rate() {
+        assert!(capture(0, &[]).is_none());
+        let every_fourth = capture(4, &[]).unwrap();
+        let sampled = (0..100).filter(|_| every_fourth.should_sample()).count();
+        assert_eq!(sampled, 25);
+        let every_call = capture(1, &[]).unwrap();
+        assert!((0..10).all(|_| every_call.should_sample()));
+    }
+
+    #[tokio::test]
+    async fn configured_fields_are_redacted() {
+        let privacy = capture(1, &DEFAULT_REDACT_FIELDS).unwrap();
+        let mut request = json!({
+            "id": "r1",
+            "device": {"ip": "1.2.3.4", "ua": "Mozilla", "geo": {"lat": 31.2}},
+            "user": {"id": "u1"},
+            "imp": [{"id": "1"}],
+        });
+        privacy.redact(&mut request);
+        assert_eq!(request, json!({
+            "id": "r1",
+            "device": {"ip": REDACTED, "ua": "Mozilla", "geo": REDACTED},
+            "user": {"id": REDACTED},
+            "imp": [{"id": "1"}],
+        }));
+
+        // 数组中的每个元素都会脱敏
+        let adm = capture(1, &["seatbid.bid.adm"]).unwrap();
+        let mut response = json!({"seatbid": [{"bid": [{"id": "b1", "adm": "a"}, {"id": "b2", "adm": "b"}]}]});
+        adm.redact(&mut response);
+        assert_eq!(response, json!({"seatbid": [{"bid": [{"id": "b1", "adm": REDACTED}, {"id": "b2", "adm": REDACTED}]}]}));
+    }
+}
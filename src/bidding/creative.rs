@@ -0,0 +1,452 @@
+// src/bidding/creative.rs
+
+use std::collections::HashSet;
+
+use crate::bidding::notice::AUCTION_LOSS_MACRO;
+use crate::openrtb::request::{BidRequest, ImpDetail};
+use crate::openrtb::response::Bid;
+
+/// 成交价宏
+pub const AUCTION_PRICE_MACRO: &str = "{AUCTION_PRICE}";
+
+/// 价格替换值的最大长度（f64 的十进制表示不会超过该长度）
+const MAX_PRICE_MACRO_LEN: usize = 24;
+
+/// 创意宏替换结果超过长度限制
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroExpansionTooLarge {
+    pub limit: usize,
+}
+
+/// 单次扫描替换宏：替换值写入结果后不会被再次扫描，因此替换值或替换后拼接出的
+/// 片段中即使出现宏也不会被二次展开；结果长度超过 max_len 时立即返回错误
+pub fn substitute_macros(template: &str, macros: &[(&str, &str)], max_len: usize) -> Result<String, MacroExpansionTooLarge> {
+    let mut result = String::with_capacity(template.len().min(max_len));
+    let mut rest = template;
+    while let Some(pos) = rest.find('{') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        match macros.iter().find(|(name, _)| rest.starts_with(name)) {
+            Some((name, value)) => {
+                result.push_str(value);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+        if result.len() > max_len {
+            return Err(MacroExpansionTooLarge { limit: max_len });
+        }
+    }
+    result.push_str(rest);
+    if result.len() > max_len {
+        return Err(MacroExpansionTooLarge { limit: max_len });
+    }
+    Ok(result)
+}
+
+/// 估算 adm 替换 {AUCTION_PRICE} 后的最大长度
+pub fn expanded_adm_len_upper_bound(adm: &str) -> usize {
+    let occurrences = adm.matches(AUCTION_PRICE_MACRO).count();
+    adm.len() + occurrences * MAX_PRICE_MACRO_LEN.saturating_sub(AUCTION_PRICE_MACRO.len())
+}
+
+/// 由 ADX 负责替换的宏，响应中仍出现时说明漏替换
+pub const ADX_MACROS: [&str; 2] = [AUCTION_PRICE_MACRO, AUCTION_LOSS_MACRO];
+
+/// 返回文本中未被替换的 ADX 宏
+pub fn unsubstituted_macros(text: &str) -> Vec<&'static str> {
+    ADX_MACROS.into_iter().filter(|name| text.contains(name)).collect()
+}
+
+/// 返回创意（adm 与 crid）中命中的敏感词
+pub fn sensitive_keyword_hits(bid: &Bid, keywords: &[String]) -> Vec<String> {
+    let content = format!(
+        "{} {}",
+        bid.adm.as_deref().unwrap_or(""),
+        bid.crid.as_deref().unwrap_or("")
+    );
+    keywords.iter()
+        .filter(|word| content.contains(word.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// 会强制跳出到系统/外部浏览器打开的链接协议
+const EXTERNAL_BROWSER_SCHEMES: [&str; 5] = [
+    "intent://",
+    "googlechrome://",
+    "x-safari-https://",
+    "x-safari-http://",
+    "firefox://",
+];
+
+/// 校验创意的点击打开方式是否与 imp.clickbrowser 兼容（仅能检测到的情况）：
+/// clickbrowser = 0 要求在内嵌浏览器（webview）中打开，创意中硬编码外部浏览器协议视为不兼容
+pub fn respects_clickbrowser(imp: &ImpDetail, bid: &Bid) -> bool {
+    match (imp.clickbrowser, bid.adm.as_deref()) {
+        (Some(0), Some(adm)) => {
+            let adm = adm.to_lowercase();
+            !EXTERNAL_BROWSER_SCHEMES.iter().any(|scheme| adm.contains(scheme))
+        }
+        _ => true,
+    }
+}
+
+/// 读取 DSP 在 bid.ext.api 中声明的创意所需 API 框架，支持单个整数或整数数组
+pub fn required_apis(bid: &Bid) -> Vec<i32> {
+    match bid.ext.as_ref().and_then(|ext| ext.get("api")) {
+        Some(serde_json::Value::Number(n)) => n.as_i64().map(|v| vec![v as i32]).unwrap_or_default(),
+        Some(serde_json::Value::Array(arr)) => arr.iter().filter_map(|v| v.as_i64()).map(|v| v as i32).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 校验创意所需的 API 框架是否都被展示位支持：
+/// DSP 未声明或展示位未声明 api 时不做限制
+pub fn respects_api_frameworks(imp: &ImpDetail, bid: &Bid) -> Result<(), Vec<i32>> {
+    let Some(supported) = imp.supported_apis() else {
+        return Ok(());
+    };
+    let unsupported: Vec<i32> = required_apis(bid)
+        .into_iter()
+        .filter(|api| !supported.contains(api))
+        .collect();
+    if unsupported.is_empty() { Ok(()) } else { Err(unsupported) }
+}
+
+/// 校验创意有效期与展示位的展示延迟是否兼容：创意有效期（bid.exp）短于展示位
+/// 可能的展示延迟（imp.exp）时，展示发生时创意可能已经失效；任一方未声明时不做限制
+pub fn respects_exp(imp: &ImpDetail, bid: &Bid) -> bool {
+    match (imp.exp, bid.exp) {
+        (Some(imp_exp), Some(bid_exp)) => bid_exp >= imp_exp,
+        _ => true,
+    }
+}
+
+/// 校验 banner 创意尺寸：只对纯 banner 展示位且出价声明了 w / h 的出价生效
+pub fn respects_banner_size(imp: &ImpDetail, bid: &Bid) -> bool {
+    if imp.video.is_some() || imp.audio.is_some() || imp.native.is_some() {
+        return true;
+    }
+    match (imp.get_banner_detail(), bid.w.zip(bid.h)) {
+        (Some(banner), Some((w, h))) => banner.accepts_size(w, h),
+        _ => true,
+    }
+}
+
+/// 常见的插屏（全屏）创意尺寸，竖屏与横屏
+pub const INTERSTITIAL_SIZES: [(i32, i32); 4] = [(320, 480), (480, 320), (768, 1024), (1024, 768)];
+
+/// 校验插屏创意尺寸：插屏 banner 展示位未声明尺寸时，出价声明的 w / h 须为全屏尺寸（INTERSTITIAL_SIZES）；
+/// 展示位声明了尺寸时由 respects_banner_size 校验
+pub fn respects_interstitial(imp: &ImpDetail, bid: &Bid) -> bool {
+    if !imp.is_interstitial() || imp.video.is_some() || imp.audio.is_some() || imp.native.is_some() {
+        return true;
+    }
+    match (imp.get_banner_detail(), bid.w.zip(bid.h)) {
+        (Some(banner), Some(size)) if !banner.declares_size() => INTERSTITIAL_SIZES.contains(&size),
+        _ => true,
+    }
+}
+
+/// 展示位声明了伴随广告位（video.companionad）且要求伴随广告时，VAST 创意必须包含 Companion；
+/// 非 VAST 创意不做判断
+pub fn respects_companions(imp: &ImpDetail, bid: &Bid, required: bool) -> bool {
+    if !required {
+        return true;
+    }
+    let expects_companion = imp.get_video_detail()
+        .and_then(|video| video.companionad.as_ref())
+        .is_some_and(|companions| !companions.is_empty());
+    match bid.adm.as_deref() {
+        Some(adm) if expects_companion && adm.contains("<VAST") => vast_has_companions(adm),
+        _ => true,
+    }
+}
+
+/// VAST 中是否包含至少一个 CompanionAds/Companion 节点
+pub fn vast_has_companions(vast: &str) -> bool {
+    vast.find("<CompanionAds")
+        .is_some_and(|start| vast[start..].contains("<Companion ") || vast[start..].contains("<Companion>"))
+}
+
+/// 广告主域名白名单校验：白名单为空时不限制；否则 adomain 必须非空且全部在白名单内（不区分大小写），
+/// 不通过时返回不在白名单内的域名
+pub fn respects_adomain_allowlist(bid: &Bid, allowlist: &HashSet<String>) -> Result<(), Vec<String>> {
+    if allowlist.is_empty() {
+        return Ok(());
+    }
+    let adomains = bid.adomain.as_deref().unwrap_or_default();
+    if adomains.is_empty() {
+        return Err(Vec::new());
+    }
+    let disallowed: Vec<String> = adomains.iter()
+        .filter(|domain| !allowlist.contains(&domain.to_ascii_lowercase()))
+        .cloned()
+        .collect();
+    if disallowed.is_empty() { Ok(()) } else { Err(disallowed) }
+}
+
+/// 返回出价中缺失（未设置、为 null 或为空字符串 / 空数组）的必填字段，字段名与 OpenRTB Bid 一致
+pub fn missing_required_fields(bid: &Bid, required: &[String]) -> Vec<String> {
+    if required.is_empty() {
+        return Vec::new();
+    }
+    let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(bid) else {
+        return required.to_vec();
+    };
+    required.iter()
+        .filter(|field| match fields.get(field.as_str()) {
+            None | Some(serde_json::Value::Null) => true,
+            Some(serde_json::Value::String(s)) => s.is_empty(),
+            Some(serde_json::Value::Array(items)) => items.is_empty(),
+            Some(_) => false,
+        })
+        .cloned()
+        .collect()
+}
+
+/// 缺省的类目分类体系：IAB Content Category Taxonomy 1.0
+pub const DEFAULT_CATTAX: i32 = 1;
+
+/// 类目屏蔽校验结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum CategoryCheck {
+    /// 未命中屏蔽类目
+    Allowed,
+    /// 命中的屏蔽类目（出价类目）
+    Blocked(Vec<String>),
+    /// 请求与出价的分类体系版本不同，无法比较
+    TaxonomyMismatch { request_cattax: i32, bid_cattax: i32 },
+}
+
+/// 按分类体系检查出价类目是否命中请求的 bcat：仅在版本一致时比较；
+/// IAB 1.0 下屏蔽一级类目（如 IAB25）同时屏蔽其子类目（如 IAB25-3）
+pub fn check_blocked_categories(bid_request: &BidRequest, bid: &Bid) -> CategoryCheck {
+    let (Some(bcat), Some(cat)) = (bid_request.bcat.as_ref(), bid.cat.as_ref()) else {
+        return CategoryCheck::Allowed;
+    };
+    if bcat.is_empty() || cat.is_empty() {
+        return CategoryCheck::Allowed;
+    }
+    let request_cattax = bid_request.cattax.unwrap_or(DEFAULT_CATTAX);
+    let bid_cattax = bid.cattax.unwrap_or(DEFAULT_CATTAX);
+    if request_cattax != bid_cattax {
+        return CategoryCheck::TaxonomyMismatch { request_cattax, bid_cattax };
+    }
+    let is_blocked = |category: &str| bcat.iter().any(|blocked| {
+        category == blocked
+            || (request_cattax == DEFAULT_CATTAX
+                && category.strip_prefix(blocked.as_str()).is_some_and(|rest| rest.starts_with('-')))
+    });
+    let hits: Vec<String> = cat.iter().filter(|c| is_blocked(c)).cloned().collect();
+    if hits.is_empty() { CategoryCheck::Allowed } else { CategoryCheck::Blocked(hits) }
+}
+
+/// 判断展示位是否要求 HTTPS，优先级：
+/// 1. imp.secure 显式指定时以其为准；
+/// 2. 否则根据请求上下文推断：site.page 或 app.storeurl 为 https 时视为安全；
+/// 3. 都无法判断时默认不要求 HTTPS。
+pub fn is_secure_impression(imp: &ImpDetail, bid_request: &BidRequest) -> bool {
+    if let Some(secure) = imp.secure {
+        return secure == 1;
+    }
+    let is_https = |url: &Option<String>| url.as_deref().is_some_and(|u| u.starts_with("https://"));
+    bid_request.get_site_detail().is_some_and(|site| is_https(&site.page))
+        || bid_request.get_app_detail().is_some_and(|app| is_https(&app.storeurl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn imp(value: Value) -> ImpDetail {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// 单个展示位 "1" 上的出价，fields 补充或覆盖出价字段
+    fn bid(fields: Value) -> Bid {
+        let mut value = json!({"id": "b1", "impid": "1", "price": 1.0});
+        if let (Some(value), Value::Object(fields)) = (value.as_object_mut(), fields) {
+            value.extend(fields);
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// 单展示位请求，fields 补充或覆盖请求字段
+    fn request(fields: Value) -> BidRequest {
+        let mut value = json!({"id": "r1", "imp": [{"id": "1"}]});
+        if let (Some(value), Value::Object(fields)) = (value.as_object_mut(), fields) {
+            value.extend(fields);
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn clickbrowser_zero_rejects_external_browser_links() {
+        let webview_imp = imp(json!({"id": "1", "clickbrowser": 0}));
+        let external = bid(json!({"adm": "<a href=\"intent://shop#Intent;end\">buy</a>"}));
+        let inline = bid(json!({"adm": "<a href=\"https://shop.example.com\">buy</a>"}));
+        assert!(!respects_clickbrowser(&webview_imp, &external));
+        assert!(respects_clickbrowser(&webview_imp, &inline));
+        // clickbrowser = 1 或未声明时不做限制
+        assert!(respects_clickbrowser(&imp(json!({"id": "1", "clickbrowser": 1})), &external));
+        assert!(respects_clickbrowser(&imp(json!({"id": "1"})), &external));
+    }
+
+    #[test]
+    fn creatives_requiring_an_unsupported_api_are_rejected() {
+        let mraid_imp = imp(json!({"id": "1", "banner": {"w": 300, "h": 250, "api": [3, 5]}}));
+        assert_eq!(respects_api_frameworks(&mraid_imp, &bid(json!({"ext": {"api": [5]}}))), Ok(()));
+        assert_eq!(respects_api_frameworks(&mraid_imp, &bid(json!({"ext": {"api": [5, 7]}}))), Err(vec![7]));
+        assert_eq!(respects_api_frameworks(&mraid_imp, &bid(json!({"ext": {"api": 2}}))), Err(vec![2]));
+        // DSP 未声明或展示位未声明 api 时不做限制
+        assert_eq!(respects_api_frameworks(&mraid_imp, &bid(json!({}))), Ok(()));
+        let plain_imp = imp(json!({"id": "1", "banner": {"w": 300, "h": 250}}));
+        assert_eq!(respects_api_frameworks(&plain_imp, &bid(json!({"ext": {"api": [7]}}))), Ok(()));
+    }
+
+    #[test]
+    fn ranged_banner_accepts_sizes_within_the_bounds() {
+        let ranged_imp = imp(json!({"id": "1", "banner": {"wmin": 300, "wmax": 400, "hmin": 250, "hmax": 300}}));
+        let sized = |w: i32, h: i32| bid(json!({"w": w, "h": h}));
+        assert!(respects_banner_size(&ranged_imp, &sized(300, 250)));
+        assert!(respects_banner_size(&ranged_imp, &sized(360, 280)));
+        assert!(respects_banner_size(&ranged_imp, &sized(400, 300)));
+        assert!(!respects_banner_size(&ranged_imp, &sized(728, 90)));
+        assert!(!respects_banner_size(&ranged_imp, &sized(320, 50)));
+        // 范围之外的尺寸仍可按 w / h 或 format 精确匹配
+        let mixed_imp = imp(json!({"id": "1", "banner": {"w": 728, "h": 90, "format": [{"w": 320, "h": 50}], "wmin": 300, "wmax": 400, "hmin": 250, "hmax": 300}}));
+        assert!(respects_banner_size(&mixed_imp, &sized(728, 90)));
+        assert!(respects_banner_size(&mixed_imp, &sized(320, 50)));
+        assert!(!respects_banner_size(&mixed_imp, &sized(160, 600)));
+        // 出价未声明尺寸时不做限制
+        assert!(respects_banner_size(&ranged_imp, &bid(json!({}))));
+    }
+
+    #[test]
+    fn unsized_interstitial_requires_a_full_screen_creative() {
+        let interstitial_imp = imp(json!({"id": "1", "instl": 1, "banner": {}}));
+        assert!(respects_interstitial(&interstitial_imp, &bid(json!({"w": 320, "h": 480}))));
+        assert!(respects_interstitial(&interstitial_imp, &bid(json!({"w": 1024, "h": 768}))));
+        assert!(!respects_interstitial(&interstitial_imp, &bid(json!({"w": 300, "h": 250}))));
+        // 声明了尺寸的插屏展示位、非插屏展示位不做全屏尺寸限制
+        assert!(respects_interstitial(&imp(json!({"id": "1", "instl": 1, "banner": {"w": 300, "h": 250}})), &bid(json!({"w": 300, "h": 250}))));
+        assert!(respects_interstitial(&imp(json!({"id": "1", "banner": {}})), &bid(json!({"w": 300, "h": 250}))));
+    }
+
+    #[test]
+    fn required_companions_must_be_present_in_the_vast() {
+        let companion_imp = imp(json!({"id": "1", "video": {
+            "mimes": ["video/mp4"], "companiontype": [1, 2],
+            "companionad": [{"id": "c1", "w": 300, "h": 250}],
+        }}));
+        let with_companion = bid(json!({"adm": "<VAST version=\"3.0\"><Ad><InLine><Creatives><Creative><CompanionAds><Companion width=\"300\" height=\"250\"></Companion></CompanionAds></Creative></Creatives></InLine></Ad></VAST>"}));
+        let without_companion = bid(json!({"adm": "<VAST version=\"3.0\"><Ad><InLine><Creatives><Creative><Linear></Linear></Creative></Creatives></InLine></Ad></VAST>"}));
+        assert!(respects_companions(&companion_imp, &with_companion, true));
+        assert!(!respects_companions(&companion_imp, &without_companion, true));
+        // 未开启校验、展示位未声明伴随广告位、或非 VAST 创意时不做限制
+        assert!(respects_companions(&companion_imp, &without_companion, false));
+        assert!(respects_companions(&imp(json!({"id": "1", "video": {"mimes": ["video/mp4"]}})), &without_companion, true));
+        assert!(respects_companions(&companion_imp, &bid(json!({"adm": "<html></html>"})), true));
+    }
+
+    #[test]
+    fn adomain_allowlist_requires_every_domain_to_be_approved() {
+        let allowlist: HashSet<String> = ["brand.com".to_string(), "shop.example.com".to_string()].into();
+        assert_eq!(respects_adomain_allowlist(&bid(json!({"adomain": ["brand.com"]})), &allowlist), Ok(()));
+        assert_eq!(respects_adomain_allowlist(&bid(json!({"adomain": ["Brand.COM", "shop.example.com"]})), &allowlist), Ok(()));
+        assert_eq!(respects_adomain_allowlist(&bid(json!({"adomain": ["brand.com", "other.com"]})), &allowlist), Err(vec!["other.com".to_string()]));
+        // 白名单非空时，未声明 adomain 的出价同样被拒绝
+        assert_eq!(respects_adomain_allowlist(&bid(json!({})), &allowlist), Err(Vec::new()));
+        assert_eq!(respects_adomain_allowlist(&bid(json!({"adomain": ["other.com"]})), &HashSet::new()), Ok(()));
+    }
+
+    #[test]
+    fn empty_or_absent_required_fields_are_reported_missing() {
+        let required = vec!["adomain".to_string(), "crid".to_string(), "cat".to_string()];
+        assert_eq!(missing_required_fields(&bid(json!({"adomain": ["brand.com"], "crid": "c1", "cat": ["IAB1"]})), &required), Vec::<String>::new());
+        assert_eq!(missing_required_fields(&bid(json!({"adomain": [], "crid": "", "cat": ["IAB1"]})), &required), vec!["adomain", "crid"]);
+        assert_eq!(missing_required_fields(&bid(json!({})), &required), required);
+        assert!(missing_required_fields(&bid(json!({})), &[]).is_empty());
+    }
+
+    #[test]
+    fn creative_must_stay_valid_through_the_impression_delay() {
+        let delayed_imp = imp(json!({"id": "1", "exp": 600}));
+        assert!(respects_exp(&delayed_imp, &bid(json!({"exp": 600}))));
+        assert!(respects_exp(&delayed_imp, &bid(json!({"exp": 3600}))));
+        assert!(!respects_exp(&delayed_imp, &bid(json!({"exp": 300}))));
+        // 任一方未声明时不做限制
+        assert!(respects_exp(&delayed_imp, &bid(json!({}))));
+        assert!(respects_exp(&imp(json!({"id": "1"})), &bid(json!({"exp": 300}))));
+    }
+
+    #[test]
+    fn nested_macros_are_expanded_in_a_single_pass() {
+        let macros = [(AUCTION_PRICE_MACRO, "{AUCTION_PRICE}"), (AUCTION_LOSS_MACRO, "1")];
+        // 替换值中的宏不会被再次展开
+        assert_eq!(substitute_macros("p={AUCTION_PRICE}", &macros, 1024).unwrap(), "p={AUCTION_PRICE}");
+        // 替换后拼接出的宏也不会被展开
+        let price = [(AUCTION_PRICE_MACRO, "1.5")];
+        assert_eq!(substitute_macros("{{AUCTION_PRICE}}", &price, 1024).unwrap(), "{1.5}");
+        assert_eq!(substitute_macros("{AUCTION_{AUCTION_PRICE}PRICE}", &price, 1024).unwrap(), "{AUCTION_1.5PRICE}");
+    }
+
+    #[test]
+    fn expansion_past_the_limit_is_rejected() {
+        let macros = [(AUCTION_PRICE_MACRO, "123456789.123456789")];
+        let template = AUCTION_PRICE_MACRO.repeat(10);
+        assert_eq!(substitute_macros(&template, &macros, 100), Err(MacroExpansionTooLarge { limit: 100 }));
+        assert_eq!(substitute_macros(&template, &macros, 190).unwrap().len(), 190);
+        assert!(expanded_adm_len_upper_bound(&template) >= 190);
+    }
+
+    #[test]
+    fn explicit_secure_wins_then_inferred_then_insecure() {
+        let https_site = request(json!({"site": {"id": "s1", "page": "https://news.example.com"}}));
+        let http_site = request(json!({"site": {"id": "s1", "page": "http://news.example.com"}}));
+        let https_app = request(json!({"app": {"id": "a1", "storeurl": "https://apps.example.com/app"}}));
+        let no_context = request(json!({}));
+
+        assert!(!is_secure_impression(&imp(json!({"id": "1", "secure": 0})), &https_site));
+        assert!(is_secure_impression(&imp(json!({"id": "1", "secure": 1})), &http_site));
+        assert!(is_secure_impression(&imp(json!({"id": "1"})), &https_site));
+        assert!(is_secure_impression(&imp(json!({"id": "1"})), &https_app));
+        assert!(!is_secure_impression(&imp(json!({"id": "1"})), &http_site));
+        assert!(!is_secure_impression(&imp(json!({"id": "1"})), &no_context));
+    }
+
+    #[test]
+    fn categories_are_compared_only_within_the_same_taxonomy() {
+        let iab1 = request(json!({"bcat": ["IAB25"]}));
+        let iab3 = request(json!({"bcat": ["IAB25"], "cattax": 3}));
+        let bid_in = |cattax: Option<i32>| bid(json!({"cat": ["IAB25-3"], "cattax": cattax}));
+
+        // 缺省均为 IAB 1.0，屏蔽一级类目同时屏蔽其子类目
+        assert_eq!(check_blocked_categories(&iab1, &bid_in(None)), CategoryCheck::Blocked(vec!["IAB25-3".to_string()]));
+        assert_eq!(check_blocked_categories(&iab1, &bid_in(Some(1))), CategoryCheck::Blocked(vec!["IAB25-3".to_string()]));
+        // 版本一致但非 IAB 1.0 时只做精确匹配
+        assert_eq!(check_blocked_categories(&iab3, &bid_in(Some(3))), CategoryCheck::Allowed);
+        assert_eq!(
+            check_blocked_categories(&iab3, &bid(json!({"cat": ["IAB25"], "cattax": 3}))),
+            CategoryCheck::Blocked(vec!["IAB25".to_string()]),
+        );
+        // 版本不一致时无法比较
+        assert_eq!(
+            check_blocked_categories(&iab1, &bid_in(Some(3))),
+            CategoryCheck::TaxonomyMismatch { request_cattax: 1, bid_cattax: 3 },
+        );
+        assert_eq!(check_blocked_categories(&iab1, &bid(json!({"cat": ["IAB1"]}))), CategoryCheck::Allowed);
+    }
+
+    #[test]
+    fn unsubstituted_macros_lists_remaining_adx_macros() {
+        assert_eq!(unsubstituted_macros("<img src=\"http://t/?p={AUCTION_PRICE}&l={AUCTION_LOSS}\">"), vec![AUCTION_PRICE_MACRO, AUCTION_LOSS_MACRO]);
+        assert!(unsubstituted_macros("<img src=\"http://t/?p=1.5\">").is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+// src/bidding/currency.rs
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::time::{self, Duration};
+
+use crate::logging::runtime_logger::RuntimeLogger;
+
+/// 系统默认货币（OpenRTB 约定 cur 缺省为 USD）
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// ISO 4217 现行货币代码
+const ISO_4217_CODES: [&str; 155] = [
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD",
+    "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN",
+    "BZD", "CAD", "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF",
+    "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS",
+    "GIP", "GMD", "GNF", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR",
+    "IQD", "IRR", "ISK", "JMD", "JOD", "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW",
+    "KWD", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "LYD", "MAD", "MDL", "MGA",
+    "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD",
+    "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN",
+    "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD",
+    "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB", "TJS", "TMT",
+    "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS", "VES",
+    "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// 判断是否为合法的 ISO 4217 货币代码（不区分大小写，与汇率换算一致）
+pub fn is_iso4217(code: &str) -> bool {
+    ISO_4217_CODES.contains(&code.to_ascii_uppercase().as_str())
+}
+
+/// 汇率表：记录 1 USD 可兑换的各货币数量
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FxTable {
+    pub rates: HashMap<String, f64>,
+}
+
+impl Default for FxTable {
+    fn default() -> Self {
+        let mut rates = HashMap::new();
+        rates.insert(DEFAULT_CURRENCY.to_string(), 1.0);
+        Self { rates }
+    }
+}
+
+impl FxTable {
+    /// 由 {货币: 汇率} 构造汇率表，汇率必须为正数，缺少 USD 时自动补上
+    pub fn from_rates(rates: HashMap<String, f64>) -> Result<Self, String> {
+        let mut table = FxTable { rates: HashMap::new() };
+        for (currency, rate) in rates {
+            if !rate.is_finite() || rate <= 0.0 {
+                return Err(format!("invalid fx rate {} for {}", rate, currency));
+            }
+            table.set_rate(&currency, rate);
+        }
+        table.rates.entry(DEFAULT_CURRENCY.to_string()).or_insert(1.0);
+        Ok(table)
+    }
+
+    pub fn set_rate(&mut self, currency: &str, rate: f64) {
+        self.rates.insert(currency.to_uppercase(), rate);
+    }
+
+    /// 将 amount 从 from 货币换算为 to 货币，任一货币无汇率时返回 None
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        if from.eq_ignore_ascii_case(to) {
+            return Some(amount);
+        }
+        let from_rate = self.rates.get(&from.to_uppercase())?;
+        let to_rate = self.rates.get(&to.to_uppercase())?;
+        Some(amount / from_rate * to_rate)
+    }
+}
+
+/// 汇率数据源，内容为 {"CNY": 7.2, "EUR": 0.92, ...}（1 USD 可兑换的数量）
+#[derive(Debug, Clone)]
+pub enum FxSource {
+    File(String),
+    Http(String),
+}
+
+impl FxSource {
+    /// 以 http:// 或 https:// 开头的视为 HTTP 数据源，否则视为本地文件路径
+    pub fn parse(source: &str) -> Self {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            FxSource::Http(source.to_string())
+        } else {
+            FxSource::File(source.to_string())
+        }
+    }
+
+    pub async fn load(&self) -> Result<FxTable, String> {
+        let content = match self {
+            FxSource::File(path) => tokio::fs::read_to_string(path).await
+                .map_err(|e| format!("failed to read fx file {}: {}", path, e))?,
+            FxSource::Http(url) => reqwest::get(url).await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(|e| format!("failed to fetch fx rates from {}: {}", url, e))?
+                .text().await
+                .map_err(|e| format!("failed to read fx rates from {}: {}", url, e))?,
+        };
+        let rates: HashMap<String, f64> = serde_json::from_str(&content)
+            .map_err(|e| format!("invalid fx rates json: {}", e))?;
+        FxTable::from_rates(rates)
+    }
+}
+
+/// 启动后台任务，按 interval 周期性地从数据源刷新汇率表；
+/// 刷新失败时记录日志并保留上一次成功加载的汇率表
+pub fn spawn_fx_refresher(
+    source: FxSource,
+    interval: Duration,
+    table: Arc<RwLock<FxTable>>,
+    runtime_logger: Arc<RuntimeLogger>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match source.load().await {
+                Ok(fresh) => {
+                    let count = fresh.rates.len();
+                    *table.write().unwrap() = fresh;
+                    runtime_logger.log("INFO", &format!("FX rates refreshed from {:?}, {} currencies", source, count)).await;
+                }
+                Err(e) => {
+                    runtime_logger.log("ERROR", &format!("FX rates refresh failed, keeping last good table: {}", e)).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso4217_is_case_insensitive() {
+        assert!(is_iso4217("USD"));
+        assert!(is_iso4217("usd"));
+        assert!(is_iso4217("Cny"));
+        assert!(!is_iso4217("XXX"));
+        assert!(!is_iso4217("US"));
+    }
+
+    /// 轮询等待后台刷新生效
+    async fn wait_for_rate(table: &RwLock<FxTable>, currency: &str, rate: f64) -> bool {
+        for _ in 0..100 {
+            if table.read().unwrap().convert(1.0, "USD", currency) == Some(rate) {
+                return true;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn refresher_picks_up_new_rates_and_keeps_the_last_good_table() {
+        let dir = std::env::temp_dir().join("rust-adx-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("fx-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"CNY": 7.2}"#).unwrap();
+
+        let table = Arc::new(RwLock::new(FxTable::default()));
+        let runtime_logger = RuntimeLogger::new(dir.to_str().unwrap(), "test", 10_000, 1000, 1000);
+        spawn_fx_refresher(FxSource::parse(path.to_str().unwrap()), Duration::from_millis(20), table.clone(), runtime_logger);
+        assert!(wait_for_rate(&table, "CNY", 7.2).await);
+
+        std::fs::write(&path, r#"{"CNY": 7.3}"#).unwrap();
+        assert!(wait_for_rate(&table, "CNY", 7.3).await);
+
+        std::fs::write(&path, "not json").unwrap();
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(table.read().unwrap().convert(1.0, "USD", "CNY"), Some(7.3));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,69 +1,386 @@
 // src/bidding/dsp_client.rs
 
 use std::cmp::Ordering;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
 use std::time::Instant;
 use reqwest::Client;
+use bytes::Bytes;
+use serde_json::Value;
 use tokio::time::{timeout, Duration};
-use futures::future::join_all;
-use crate::openrtb::request::BidRequest;
+use tower::{Layer, Service};
+use futures::future::{join_all, BoxFuture};
+use crate::openrtb::request::{BidRequest, ImpDetail};
 use crate::openrtb::response::BidResponse;
-use crate::model::dsp::Demand;
+use crate::bidding::adapter::{default_adapter, DspAdapter};
+use crate::bidding::capture::BodyCapture;
+use crate::bidding::host_limit::HostLimiter;
+use crate::bidding::retry::{RetryBudget, TimeoutJitter};
+use crate::model::dsp::{Demand, PriceUnit};
+use crate::model::placements::MEDIA_TYPES;
+
+/// 单次 DSP 调用的结果状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DspCallOutcome {
+    Success,
+    JsonParseError,
+    InvalidResponse,
+    /// 超时未能建立连接
+    ConnectTimeout,
+    /// 已建立连接，但在超时前未收到完整响应
+    ReadTimeout,
+    /// DSP 主机进行中的询价数已达上限，未发起询价
+    HostBusy,
+    /// 请求体超过该 DSP 的大小上限，未发起询价
+    PayloadTooLarge,
+}
+
+impl DspCallOutcome {
+    /// 日志中使用的状态描述
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DspCallOutcome::Success => "success",
+            DspCallOutcome::JsonParseError => "json_parse_error",
+            DspCallOutcome::InvalidResponse => "invalid_response",
+            DspCallOutcome::ConnectTimeout => "connect_timeout",
+            DspCallOutcome::ReadTimeout => "read_timeout",
+            DspCallOutcome::HostBusy => "host_busy",
+            DspCallOutcome::PayloadTooLarge => "payload_too_large",
+        }
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, DspCallOutcome::ConnectTimeout | DspCallOutcome::ReadTimeout)
+    }
+
+    /// ADX 侧跳过、未实际向 DSP 发起询价，不计入 DSP 的成功率统计
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, DspCallOutcome::HostBusy | DspCallOutcome::PayloadTooLarge)
+    }
+
+    pub fn is_success(&self) -> bool {
+        *self == DspCallOutcome::Success
+    }
+}
+
+/// 单个 DSP 的询价结果
+#[derive(Debug, Clone)]
+pub struct DspCallResult {
+    pub dsp_id: u64,
+    pub dsp_url: String,
+    /// 该 DSP 响应中的最高出价（失败时为 0）
+    pub price: f64,
+    pub bid_response: BidResponse,
+    pub outcome: DspCallOutcome,
+    /// 请求耗时（毫秒，含重试）
+    pub elapsed_ms: u128,
+    /// 实际发生的重试次数
+    pub retries: u32,
+}
+
+/// 获取各 DSP 出价的抽象：线上使用 HTTP 的 DspClient，基准测试等场景可替换为内存实现
+pub trait BidFetcher: Send + Sync {
+    /// 并发获取 DSP 竞价响应，结果按最高出价降序排列
+    fn fetch_bids(&self, request: &Arc<BidRequest>) -> impl Future<Output = Vec<DspCallResult>> + Send;
+}
+
+impl BidFetcher for DspClient {
+    fn fetch_bids(&self, request: &Arc<BidRequest>) -> impl Future<Output = Vec<DspCallResult>> + Send {
+        DspClient::fetch_bids(self, request)
+    }
+}
+
+impl DspCallResult {
+    /// 构造一个失败的调用结果（空 BidResponse，出价为 0）
+    pub fn failed(dsp_id: u64, dsp_url: String, outcome: DspCallOutcome, elapsed_ms: u128) -> Self {
+        Self {
+            dsp_id,
+            dsp_url,
+            price: 0.0,
+            bid_response: BidResponse { id: "".to_string(), seatbid: vec![], bidid: None, cur: None, customdata: None, nbr: None, ext: None },
+            outcome,
+            elapsed_ms,
+            retries: 0,
+        }
+    }
+}
+
+/// 转发给 DSP 的 tmax 下限（毫秒）
+pub const MIN_FORWARDED_TMAX_MS: u64 = 50;
+
+/// 扣除已耗时与 ADX 自身预留时间后剩余的时间预算（毫秒），预算耗尽时为 0
+pub fn remaining_budget_ms(tmax: u64, elapsed: Duration, reserve_ms: u64) -> u64 {
+    let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    tmax.saturating_sub(elapsed_ms).saturating_sub(reserve_ms)
+}
+
+/// 根据已耗时与 ADX 自身预留时间计算转发给 DSP 的 tmax，不低于 MIN_FORWARDED_TMAX_MS。
+/// 下限只作用于请求中的 tmax 字段，本地的 DSP 超时按 remaining_budget_ms 计算
+pub fn forwarded_tmax(tmax: u64, elapsed: Duration, reserve_ms: u64) -> u64 {
+    remaining_budget_ms(tmax, elapsed, reserve_ms).max(MIN_FORWARDED_TMAX_MS)
+}
+
+/// 请求未携带 tmax 时使用的默认 tmax（毫秒）
+pub const DEFAULT_TMAX_MS: u64 = 250;
+
+/// 可配置的默认 tmax 范围（毫秒）
+pub const DEFAULT_TMAX_RANGE_MS: std::ops::RangeInclusive<u64> = 10..=10_000;
+
+/// 校验配置的默认 tmax 是否在合理范围内
+pub fn validate_default_tmax(default_tmax_ms: u64) -> Result<(), String> {
+    if DEFAULT_TMAX_RANGE_MS.contains(&default_tmax_ms) {
+        Ok(())
+    } else {
+        Err(format!(
+            "default tmax {} ms is outside {}..={} ms",
+            default_tmax_ms, DEFAULT_TMAX_RANGE_MS.start(), DEFAULT_TMAX_RANGE_MS.end()
+        ))
+    }
+}
+
+/// 与 DSP 建立连接的默认超时（毫秒），应小于 DSP 超时，以便区分连接超时与读取超时
+pub const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 100;
+
+/// 向 DSP 询价时默认携带的 User-Agent
+pub const DEFAULT_USER_AGENT: &str = concat!("rust-adx/", env!("CARGO_PKG_VERSION"));
+
+/// 单个 DSP 的生效超时：取 DSP 配置的超时与请求剩余时间预算中较小者，
+/// DSP 超时再大也不会超出请求的时间预算
+pub fn effective_timeout_ms(demand_timeout: Option<u64>, remaining_budget_ms: u64) -> u64 {
+    demand_timeout.map_or(remaining_budget_ms, |timeout| timeout.min(remaining_budget_ms))
+}
 
 pub struct DspClient {
     client: Client,
     demands: Vec<Demand>,
+    retry_budget: Arc<RetryBudget>,
+    body_capture: Option<Arc<BodyCapture>>,
+    /// 竞价开始时间与 ADX 预留时间，用于缩减转发给 DSP 的 tmax
+    deadline: Option<(Instant, u64)>,
+    /// 各 DSP 支持的媒体类型，未配置的 DSP 收到完整的 imp
+    media_types: HashMap<u64, Vec<&'static str>>,
+    /// 各 DSP 的请求 / 响应格式适配器，未配置的 DSP 使用 default_adapter
+    adapters: HashMap<u64, Arc<dyn DspAdapter>>,
+    /// 询价请求的 User-Agent，便于 DSP 识别（部分 DSP 按 UA 设置白名单）
+    user_agent: Arc<str>,
+    /// 请求未携带 tmax 时按该值计算时间预算
+    default_tmax_ms: u64,
+    /// SSP 配置的整场竞价时长上限，与 tmax 取较小者
+    max_auction_ms: Option<u64>,
+    /// 每个 DSP 主机同时进行中的询价数上限，None 表示不限制
+    host_limiter: Option<Arc<HostLimiter>>,
+    /// DSP 超时与重试等待的随机抖动，None 表示不加抖动
+    timeout_jitter: Option<Arc<TimeoutJitter>>,
 }
 
 impl DspClient {
     pub fn new(demands: Vec<Demand>) -> Self {
         Self {
-            client: Client::new(),
+            client: default_client(),
             demands,
+            retry_budget: Arc::new(RetryBudget::new(0)),
+            body_capture: None,
+            deadline: None,
+            media_types: HashMap::new(),
+            adapters: HashMap::new(),
+            user_agent: Arc::from(DEFAULT_USER_AGENT),
+            default_tmax_ms: DEFAULT_TMAX_MS,
+            max_auction_ms: None,
+            host_limiter: None,
+            timeout_jitter: None,
         }
     }
 
-    /// 并发获取 DSP 竞价响应
-    /// 返回元组：(dsp_id, dsp_url, 最高出价, BidResponse, 状态描述, 请求耗时_ms)
-    pub async fn fetch_bids(&self, request: &Arc<BidRequest>) -> Vec<(u64, String, f64, BidResponse, String, u128)> {
+    /// 设置请求未携带 tmax 时使用的默认 tmax（毫秒）
+    pub fn with_default_tmax(mut self, default_tmax_ms: u64) -> Self {
+        self.default_tmax_ms = default_tmax_ms;
+        self
+    }
+
+    /// 设置整场竞价时长上限（毫秒），请求 tmax 更宽松时按该值计算时间预算
+    pub fn with_max_auction_ms(mut self, max_auction_ms: Option<u64>) -> Self {
+        self.max_auction_ms = max_auction_ms;
+        self
+    }
+
+    /// 使用共享的 HTTP Client，连接池在所有竞价请求间复用（DspClient 按请求创建，不应每次重建 Client）
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// 限制每个 DSP 主机同时进行中的询价数，超出时该 DSP 记为 host_busy，不发起询价
+    pub fn with_host_limiter(mut self, host_limiter: Option<Arc<HostLimiter>>) -> Self {
+        self.host_limiter = host_limiter;
+        self
+    }
+
+    /// 为每个 DSP 的超时与重试前的等待加入随机抖动，避免多个 DSP 同时超时、同时重试
+    pub fn with_timeout_jitter(mut self, timeout_jitter: Option<Arc<TimeoutJitter>>) -> Self {
+        self.timeout_jitter = timeout_jitter;
+        self
+    }
+
+    /// 设置询价请求的 User-Agent
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Arc::from(user_agent);
+        self
+    }
+
+    /// 设置各 DSP 支持的媒体类型，转发前会从 imp 中移除 DSP 不支持的媒体对象
+    pub fn with_media_types(mut self, media_types: HashMap<u64, Vec<&'static str>>) -> Self {
+        self.media_types = media_types;
+        self
+    }
+
+    /// 设置各 DSP 的请求 / 响应格式适配器，询价时按适配器编码请求、解码响应
+    pub fn with_adapters(mut self, adapters: HashMap<u64, Arc<dyn DspAdapter>>) -> Self {
+        self.adapters = adapters;
+        self
+    }
+
+    /// 按竞价开始时间与 ADX 预留时间（毫秒）缩减转发给 DSP 的 tmax
+    pub fn with_deadline(mut self, start_time: Instant, reserve_ms: u64) -> Self {
+        self.deadline = Some((start_time, reserve_ms));
+        self
+    }
+
+    /// 开启 DSP 请求/响应报文采样抓取
+    pub fn with_body_capture(mut self, body_capture: Option<Arc<BodyCapture>>) -> Self {
+        self.body_capture = body_capture;
+        self
+    }
+
+    /// 设置本次请求所有 DSP 共享的重试预算
+    pub fn with_retry_budget(mut self, retry_budget: Arc<RetryBudget>) -> Self {
+        self.retry_budget = retry_budget;
+        self
+    }
+
+    /// 是否至少有一个启用的 DSP 在按媒体类型与插屏裁剪后仍有可投放的展示位，即本次请求会向其询价
+    pub fn has_eligible_demand(&self, request: &BidRequest) -> bool {
+        self.demands.iter()
+            .filter(|demand| demand.status)
+            .any(|demand| {
+                let media_types = self.media_types.get(&demand.id).map(Vec::as_slice);
+                serves_any_imp(request, media_types, demand.accepts_interstitial)
+            })
+    }
+
+    /// 并发获取 DSP 竞价响应，结果按最高出价降序排列
+    pub async fn fetch_bids(&self, request: &Arc<BidRequest>) -> Vec<DspCallResult> {
+        // DSP 只能使用剩余的时间预算，tmax（未携带时为默认 tmax，且不超过 SSP 的竞价时长上限）
+        // 需扣除已耗时与 ADX 自身的预留时间
+        let tmax = request.tmax.unwrap_or(self.default_tmax_ms);
+        let tmax = self.max_auction_ms.map_or(tmax, |max_auction_ms| tmax.min(max_auction_ms));
+        let elapsed = self.deadline.map(|(start_time, reserve_ms)| (start_time.elapsed(), reserve_ms));
+        let (remaining_budget, remaining_tmax) = match elapsed {
+            Some((elapsed, reserve_ms)) => (remaining_budget_ms(tmax, elapsed, reserve_ms), forwarded_tmax(tmax, elapsed, reserve_ms)),
+            None => (tmax, tmax),
+        };
+        // 只改写请求自带的 tmax，未携带 tmax 的请求原样转发
+        let request = match request.tmax {
+            Some(tmax) if tmax != remaining_tmax => {
+                let mut adjusted = (**request).clone();
+                adjusted.tmax = Some(remaining_tmax);
+                &Arc::new(adjusted)
+            }
+            _ => request,
+        };
+        // 请求只序列化一次，所有未做裁剪的 DSP 复用同一份字节
+        let shared_payload = serde_json::to_vec(&**request).map(Bytes::from).unwrap_or_default();
         let tasks: Vec<_> = self.demands.iter()
             .filter(|demand| demand.status)
-            .map(|demand| {
+            .filter_map(|demand| {
                 let dsp_id = demand.id;
+                let media_types = self.media_types.get(&dsp_id).map(Vec::as_slice);
+                let adapter = self.adapters.get(&dsp_id).cloned().unwrap_or_else(default_adapter);
+                // 裁剪后没有可投放展示位的 DSP 不再询价
+                let req = payload_for_demand(&shared_payload, request, &demand.strip_fields, media_types, demand.accepts_interstitial, adapter.as_ref())?;
                 let client = self.client.clone();
-                let req = Arc::clone(request);
                 let dsp_url = demand.url.clone();
-                let timeout_duration = Duration::from_millis(demand.timeout.unwrap_or(request.tmax.unwrap_or(250)));
-                tokio::spawn(async move {
+                let max_retries = demand.max_retries.unwrap_or(0);
+                let retry_budget = Arc::clone(&self.retry_budget);
+                let body_capture = self.body_capture.clone();
+                let user_agent = Arc::clone(&self.user_agent);
+                let price_unit = demand.price_unit;
+                let max_request_bytes = demand.max_request_bytes;
+                let host_limiter = self.host_limiter.clone();
+                // 本地超时按未加下限的剩余预算计算，转发的 tmax 被提升到下限时也不会超出竞价时间
+                let timeout_duration = Duration::from_millis(effective_timeout_ms(demand.timeout, remaining_budget));
+                // 抖动只缩短超时，不会超出时间预算
+                let timeout_duration = match self.timeout_jitter.as_deref() {
+                    Some(jitter) => timeout_duration.saturating_sub(jitter.sample().min(timeout_duration / 2)),
+                    None => timeout_duration,
+                };
+                let timeout_jitter = self.timeout_jitter.clone();
+                Some(tokio::spawn(async move {
+                    if max_request_bytes.is_some_and(|max| req.len() > max) {
+                        return Some(DspCallResult::failed(dsp_id, dsp_url, DspCallOutcome::PayloadTooLarge, 0));
+                    }
                     let start = Instant::now();
-                    let response = timeout(timeout_duration, client.post(&dsp_url)
-                        .header("Content-Type", "application/json")
-                        .json(&*req)
-                        .send()).await;
-                    let elapsed = start.elapsed().as_millis();
-                    match response {
-                        Ok(Ok(resp)) => {
-                            match resp.json::<BidResponse>().await {
-                                Ok(bid_response) => {
-                                    let price = bid_response.seatbid.iter()
-                                        .flat_map(|seatbid| seatbid.bid.iter().map(|bid| bid.price))
-                                        .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
-                                        .unwrap_or(0.0);
-                                    Some((dsp_id, dsp_url, price, bid_response, "success".to_string(), elapsed))
-                                },
-                                Err(_) => Some((dsp_id, dsp_url, 0.0,
-                                                BidResponse { id: "".to_string(), seatbid: vec![], bidid: None, cur: None, customdata: None, nbr: None },
-                                                "json_parse_error".to_string(), elapsed))
+                    let mut retries = 0;
+                    // 重试共用同一个超时窗口，剩余时间耗尽后不再重试
+                    loop {
+                        let remaining = timeout_duration.saturating_sub(start.elapsed());
+                        let capture = body_capture.as_deref().filter(|capture| capture.should_sample());
+                        // 许可在本次询价结束前一直持有
+                        let permit = match host_limiter.as_deref().map(|limiter| limiter.try_acquire(&dsp_url)) {
+                            Some(None) => {
+                                let mut result = DspCallResult::failed(dsp_id, dsp_url, DspCallOutcome::HostBusy, start.elapsed().as_millis());
+                                result.retries = retries;
+                                return Some(result);
+                            }
+                            permit => permit.flatten(),
+                        };
+                        let outcome = call_dsp(&client, &dsp_url, &user_agent, adapter.as_ref(), &req, remaining, capture.map(|c| (c, dsp_id))).await;
+                        drop(permit);
+                        let retryable = matches!(outcome, Err(DspCallOutcome::InvalidResponse | DspCallOutcome::ConnectTimeout | DspCallOutcome::ReadTimeout));
+                        if retryable
+                            && retries < max_retries
+                            && start.elapsed() < timeout_duration
+                            && retry_budget.try_acquire()
+                        {
+                            retries += 1;
+                            // 重试前随机等待，最多占用剩余时间的一半
+                            if let Some(jitter) = timeout_jitter.as_deref() {
+                                let remaining = timeout_duration.saturating_sub(start.elapsed());
+                                tokio::time::sleep(jitter.sample().min(remaining / 2)).await;
+                            }
+                            continue;
+                        }
+                        let elapsed = start.elapsed().as_millis();
+                        let mut result = match outcome {
+                            Ok(mut bid_response) => {
+                                // 非 CPM 单位的出价统一换算为 CPM，之后的比较与定价均按 CPM 进行
+                                if price_unit != PriceUnit::Cpm {
+                                    for bid in bid_response.seatbid.iter_mut().flat_map(|seatbid| seatbid.bid.iter_mut()) {
+                                        bid.price = price_unit.to_cpm(bid.price);
+                                    }
+                                }
+                                let price = bid_response.seatbid.iter()
+                                    .flat_map(|seatbid| seatbid.bid.iter().map(|bid| bid.price))
+                                    .max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+                                    .unwrap_or(0.0);
+                                DspCallResult {
+                                    dsp_id,
+                                    dsp_url,
+                                    price,
+                                    bid_response,
+                                    outcome: DspCallOutcome::Success,
+                                    elapsed_ms: elapsed,
+                                    retries: 0,
+                                }
                             }
-                        },
-                        Ok(Err(_)) => Some((dsp_id, dsp_url, 0.0,
-                                            BidResponse { id: "".to_string(), seatbid: vec![], bidid: None, cur: None, customdata: None, nbr: None },
-                                            "invalid_response".to_string(), elapsed)),
-                        Err(_) => Some((dsp_id, dsp_url, 0.0,
-                                        BidResponse { id: "".to_string(), seatbid: vec![], bidid: None, cur: None, customdata: None, nbr: None },
-                                        "timeout".to_string(), elapsed)),
+                            Err(failure) => DspCallResult::failed(dsp_id, dsp_url, failure, elapsed),
+                        };
+                        result.retries = retries;
+                        return Some(result);
                     }
-                })
+                }))
             }).collect();
 
         let mut results = join_all(tasks)
@@ -71,7 +388,354 @@ impl DspClient {
             .into_iter()
             .filter_map(|res| res.ok().flatten())
             .collect::<Vec<_>>();
-        results.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+        results.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap_or(Ordering::Equal));
         results
     }
 }
+
+/// 默认配置的 HTTP Client，进程内只构建一次
+fn default_client() -> Client {
+    static DEFAULT_CLIENT: OnceLock<Client> = OnceLock::new();
+    DEFAULT_CLIENT.get_or_init(|| build_client(Duration::from_millis(DEFAULT_CONNECT_TIMEOUT_MS), None)).clone()
+}
+
+/// 构建向 DSP 询价的 HTTP Client；构建开销较大（加载根证书等），应在启动时构建一次并在请求间共享
+pub fn build_client(connect_timeout: Duration, pool_max_idle_per_host: Option<usize>) -> Client {
+    // 声明 Accept-Encoding 并在 JSON 解析前透明解压 gzip / br / deflate 压缩的 DSP 响应
+    let mut builder = Client::builder()
+        .gzip(true)
+        .brotli(true)
+        .deflate(true)
+        .connect_timeout(connect_timeout)
+        .connector_layer(ConnectTrackingLayer);
+    if let Some(pool_max_idle_per_host) = pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+    builder.build().unwrap_or_else(|_| Client::new())
+}
+
+/// 构造发送给单个 DSP 的请求体：未配置字段裁剪的 DSP 共享同一份序列化结果，
+/// 只有需要裁剪的 DSP 才单独序列化。media_types 不为空时从每个 imp 中移除 DSP 不支持的
+/// 媒体对象，并丢弃不再含有任何媒体对象的 imp；不接收插屏的 DSP 同时丢弃插屏 imp（instl = 1）；
+/// 没有 imp 剩余时返回 None。使用非 OpenRTB JSON 适配器的 DSP 由适配器对裁剪后的请求编码
+fn payload_for_demand(
+    shared: &Bytes,
+    request: &BidRequest,
+    strip_fields: &[String],
+    media_types: Option<&[&str]>,
+    accepts_interstitial: bool,
+    adapter: &dyn DspAdapter,
+) -> Option<Bytes> {
+    let media_types = media_types.filter(|types| !types.is_empty());
+    let strip_interstitial = !accepts_interstitial && request.get_imp_details().iter().any(ImpDetail::is_interstitial);
+    if strip_fields.is_empty() && media_types.is_none() && !strip_interstitial {
+        if adapter.is_openrtb_json() {
+            return Some(shared.clone());
+        }
+        return Some(adapter.to_request(request));
+    }
+    let mut value = serde_json::to_value(request).unwrap_or_default();
+    for field in strip_fields {
+        let path: Vec<&str> = field.split('.').collect();
+        remove_path(&mut value, &path);
+    }
+    if let Some(imps) = value.get_mut("imp").and_then(Value::as_array_mut) {
+        if strip_interstitial {
+            imps.retain(|imp| imp.get("instl").and_then(Value::as_i64) != Some(1));
+        }
+        if let Some(media_types) = media_types {
+            imps.retain_mut(|imp| trim_imp_media(imp, media_types));
+        }
+        if imps.is_empty() {
+            return None;
+        }
+    }
+    if !adapter.is_openrtb_json() {
+        return serde_json::from_value::<BidRequest>(value).ok().map(|trimmed| adapter.to_request(&trimmed));
+    }
+    serde_json::to_vec(&value).map(Bytes::from).ok()
+}
+
+/// 请求中是否有 DSP 可以投放的展示位，规则与 payload_for_demand 的裁剪一致
+fn serves_any_imp(request: &BidRequest, media_types: Option<&[&str]>, accepts_interstitial: bool) -> bool {
+    let media_types = media_types.filter(|types| !types.is_empty());
+    request.get_imp_details().iter()
+        .filter(|imp| accepts_interstitial || !imp.is_interstitial())
+        .any(|imp| {
+            let Some(media_types) = media_types else {
+                return true;
+            };
+            let present = [("banner", imp.banner.is_some()), ("video", imp.video.is_some()), ("audio", imp.audio.is_some()), ("native", imp.native.is_some())];
+            let had_media = present.iter().any(|(_, is_present)| *is_present);
+            !had_media || present.iter().any(|(media, is_present)| *is_present && media_types.contains(media))
+        })
+}
+
+/// 移除 imp 中 DSP 不支持的媒体对象，返回 imp 是否仍有可投放的媒体对象
+/// （原本就没有任何媒体对象的 imp 原样保留）
+fn trim_imp_media(imp: &mut Value, media_types: &[&str]) -> bool {
+    let Some(imp) = imp.as_object_mut() else {
+        return true;
+    };
+    let had_media = MEDIA_TYPES.iter().any(|m| imp.contains_key(*m));
+    for media in MEDIA_TYPES {
+        if !media_types.contains(&media) {
+            imp.remove(media);
+        }
+    }
+    !had_media || MEDIA_TYPES.iter().any(|m| imp.contains_key(*m))
+}
+
+/// 删除点分路径指向的字段，路径经过数组时对每个元素生效
+fn remove_path(value: &mut Value, path: &[&str]) {
+    let Some((first, rest)) = path.split_first() else {
+        return;
+    };
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                remove_path(item, path);
+            }
+        }
+        Value::Object(map) => {
+            if rest.is_empty() {
+                map.remove(*first);
+            } else if let Some(child) = map.get_mut(*first) {
+                remove_path(child, rest);
+            }
+        }
+        _ => {}
+    }
+}
+
+tokio::task_local! {
+    /// 当前询价是否正在建立连接，由 ConnectTracking 在新建连接前后更新（复用连接池中的连接时保持 false）
+    static CONNECTING: Arc<AtomicBool>;
+}
+
+/// 为 Client 的 connector 套上 ConnectTracking，记录询价所处的连接建立阶段
+#[derive(Clone)]
+struct ConnectTrackingLayer;
+
+impl<S> Layer<S> for ConnectTrackingLayer {
+    type Service = ConnectTracking<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTracking { inner }
+    }
+}
+
+#[derive(Clone)]
+struct ConnectTracking<S> {
+    inner: S,
+}
+
+impl<S, R> Service<R> for ConnectTracking<S>
+where
+    S: Service<R>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        // connector 在询价任务内被调用；连接在后台建立（如连接池先返回了空闲连接）时不在 CONNECTING 作用域内，不做记录
+        let connecting = CONNECTING.try_with(Arc::clone).ok();
+        if let Some(connecting) = &connecting {
+            connecting.store(true, AtomicOrdering::Relaxed);
+        }
+        let connect = self.inner.call(request);
+        Box::pin(async move {
+            let result = connect.await;
+            if let Some(connecting) = connecting {
+                connecting.store(false, AtomicOrdering::Relaxed);
+            }
+            result
+        })
+    }
+}
+
+/// 向 DSP 发起一次询价，响应由 adapter 解码，capture 不为空时抓取本次的请求与原始响应报文。
+/// 超时窗口内仍未建立连接（Client 的 connect_timeout 或本次询价的超时先到）记为连接超时；
+/// 连接建立后，超时窗口内未读完响应记为读取超时
+async fn call_dsp(
+    client: &Client,
+    dsp_url: &str,
+    user_agent: &str,
+    adapter: &dyn DspAdapter,
+    req: &Bytes,
+    timeout_duration: Duration,
+    capture: Option<(&BodyCapture, u64)>,
+) -> Result<BidResponse, DspCallOutcome> {
+    let start = Instant::now();
+    let connecting = Arc::new(AtomicBool::new(false));
+    let send = client.post(dsp_url)
+        .header("Content-Type", adapter.content_type())
+        .header("User-Agent", user_agent)
+        .body(req.clone())
+        .send();
+    let response = timeout(timeout_duration, CONNECTING.scope(Arc::clone(&connecting), send)).await;
+    let resp = match response {
+        Ok(Ok(resp)) => resp,
+        Ok(Err(e)) if e.is_connect() && e.is_timeout() => return Err(DspCallOutcome::ConnectTimeout),
+        Ok(Err(e)) if e.is_timeout() => return Err(DspCallOutcome::ReadTimeout),
+        Ok(Err(_)) => return Err(DspCallOutcome::InvalidResponse),
+        Err(_) if connecting.load(AtomicOrdering::Relaxed) => return Err(DspCallOutcome::ConnectTimeout),
+        Err(_) => return Err(DspCallOutcome::ReadTimeout),
+    };
+    let status = resp.status().as_u16();
+    let body = match timeout(timeout_duration.saturating_sub(start.elapsed()), resp.bytes()).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(_)) => return Err(DspCallOutcome::InvalidResponse),
+        Err(_) => return Err(DspCallOutcome::ReadTimeout),
+    };
+    if let Some((capture, dsp_id)) = capture {
+        let request = serde_json::from_slice(req).unwrap_or_default();
+        capture.record(dsp_id, dsp_url, request, Some(status), &body).await;
+    }
+    adapter.parse_response(body).map_err(|_| DspCallOutcome::JsonParseError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dsp_timeout_larger_than_tmax_is_clamped() {
+        assert_eq!(effective_timeout_ms(Some(1000), 200), 200);
+        assert_eq!(effective_timeout_ms(Some(80), 200), 80);
+        assert_eq!(effective_timeout_ms(None, 200), 200);
+    }
+
+    #[test]
+    fn forwarded_tmax_floor_does_not_extend_the_budget() {
+        let elapsed = Duration::from_millis(90);
+        assert_eq!(remaining_budget_ms(100, elapsed, 20), 0);
+        assert_eq!(forwarded_tmax(100, elapsed, 20), MIN_FORWARDED_TMAX_MS);
+        assert_eq!(remaining_budget_ms(300, elapsed, 20), 190);
+        assert_eq!(forwarded_tmax(300, elapsed, 20), 190);
+    }
+
+    #[test]
+    fn default_tmax_must_be_within_range() {
+        assert!(validate_default_tmax(DEFAULT_TMAX_MS).is_ok());
+        assert!(validate_default_tmax(10).is_ok() && validate_default_tmax(10_000).is_ok());
+        assert!(validate_default_tmax(0).is_err());
+        assert!(validate_default_tmax(60_000).is_err());
+    }
+
+    fn bid_request() -> Arc<BidRequest> {
+        Arc::new(serde_json::from_value(json!({"id": "r1", "imp": [{"id": "1"}], "tmax": 1000})).unwrap())
+    }
+
+    /// 连接队列已满、新连接无法完成握手的 DSP
+    async fn unconnectable_dsp() -> (String, Vec<tokio::net::TcpStream>) {
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = socket.local_addr().unwrap();
+        let listener = socket.listen(0).unwrap();
+        let mut backlog = Vec::new();
+        for _ in 0..16 {
+            match timeout(Duration::from_millis(100), tokio::net::TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => backlog.push(stream),
+                _ => break,
+            }
+        }
+        std::mem::forget(listener);
+        (format!("http://{}/bid", addr), backlog)
+    }
+
+    /// 接受连接但从不响应的 DSP
+    async fn unresponsive_dsp() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bid", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn stalled_connect_is_a_connect_timeout() {
+        let (url, _backlog) = unconnectable_dsp().await;
+        // Client 的连接超时长于本次询价的超时，由询价超时先触发
+        let client = DspClient::new(vec![Demand::new(1, "unreachable_dsp", &url, true, Some(100))])
+            .with_client(build_client(Duration::from_secs(5), None));
+        let results = client.fetch_bids(&bid_request()).await;
+        assert_eq!(results[0].outcome, DspCallOutcome::ConnectTimeout);
+    }
+
+    #[tokio::test]
+    async fn slow_response_is_a_read_timeout() {
+        let url = unresponsive_dsp().await;
+        let client = DspClient::new(vec![Demand::new(1, "slow_dsp", &url, true, Some(100))])
+            .with_client(build_client(Duration::from_secs(5), None));
+        let results = client.fetch_bids(&bid_request()).await;
+        assert_eq!(results[0].outcome, DspCallOutcome::ReadTimeout);
+    }
+
+    #[tokio::test]
+    async fn local_timeout_uses_the_unfloored_budget() {
+        let url = unresponsive_dsp().await;
+        let request: BidRequest = serde_json::from_value(json!({"id": "r1", "imp": [{"id": "1"}], "tmax": 100})).unwrap();
+        // 已耗时超过 tmax：转发的 tmax 被提升到下限，但本地不应再等待 DSP 的 1000ms 超时或下限的 50ms
+        let client = DspClient::new(vec![Demand::new(1, "slow_dsp", &url, true, Some(1000))])
+            .with_deadline(Instant::now() - Duration::from_millis(200), 10);
+        let results = client.fetch_bids(&Arc::new(request)).await;
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0].outcome, DspCallOutcome::ReadTimeout | DspCallOutcome::ConnectTimeout));
+        assert!(results[0].elapsed_ms < u128::from(MIN_FORWARDED_TMAX_MS), "elapsed {} ms", results[0].elapsed_ms);
+    }
+
+    #[tokio::test]
+    async fn request_without_tmax_uses_the_configured_default() {
+        let url = unresponsive_dsp().await;
+        let request: Arc<BidRequest> = Arc::new(serde_json::from_value(json!({"id": "r1", "imp": [{"id": "1"}]})).unwrap());
+        // DSP 未配置超时，时间预算完全来自默认 tmax
+        let client = DspClient::new(vec![Demand::new(1, "slow_dsp", &url, true, None)])
+            .with_client(build_client(Duration::from_secs(5), None))
+            .with_default_tmax(60);
+        let results = client.fetch_bids(&request).await;
+        assert_eq!(results[0].outcome, DspCallOutcome::ReadTimeout);
+        assert!((60..DEFAULT_TMAX_MS as u128).contains(&results[0].elapsed_ms), "elapsed {} ms", results[0].elapsed_ms);
+    }
+
+    #[tokio::test]
+    async fn ssp_auction_cap_tighter_than_tmax_bounds_the_budget() {
+        let url = unresponsive_dsp().await;
+        // 请求 tmax 为 1000ms，SSP 的竞价时长上限 60ms 生效
+        let client = DspClient::new(vec![Demand::new(1, "slow_dsp", &url, true, None)])
+            .with_client(build_client(Duration::from_secs(5), None))
+            .with_max_auction_ms(Some(60));
+        let results = client.fetch_bids(&bid_request()).await;
+        assert_eq!(results[0].outcome, DspCallOutcome::ReadTimeout);
+        assert!((60..500).contains(&results[0].elapsed_ms), "elapsed {} ms", results[0].elapsed_ms);
+    }
+
+    #[tokio::test]
+    async fn jittered_timeouts_stay_within_the_jitter_band() {
+        let url = unresponsive_dsp().await;
+        let demands = (1..=4).map(|dsp_id| Demand::new(dsp_id, "slow_dsp", &url, true, Some(200))).collect();
+        // 抖动只缩短超时：各 DSP 的超时落在 200ms 减去 0 ~ 80ms 的区间内
+        let client = DspClient::new(demands)
+            .with_client(build_client(Duration::from_secs(5), None))
+            .with_timeout_jitter(TimeoutJitter::new(80, Some(1)).map(Arc::new));
+        let results = client.fetch_bids(&bid_request()).await;
+        assert_eq!(results.len(), 4);
+        for result in &results {
+            assert_eq!(result.outcome, DspCallOutcome::ReadTimeout);
+            assert!((120..400).contains(&result.elapsed_ms), "elapsed {} ms", result.elapsed_ms);
+        }
+    }
+}
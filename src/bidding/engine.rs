@@ -1,194 +1,239 @@
 // src/bidding/engine.rs
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::time::Duration;
-use serde_json::{json, Value};
-use tracing::info;
-use std::time::Instant;
+use serde_json::json;
 
-use crate::bidding::dsp_client::DspClient;
-use crate::config::config_manager::ConfigManager;
+use crate::bidding::dsp_client::{BidFetcher, DspClient};
+use crate::bidding::events::EventBus;
+use crate::bidding::outcome::AuctionOutcome;
+use crate::bidding::pipeline::{AuctionContext, AuctionPipeline, CandidateBid};
+use crate::bidding::retry::RetryBudget;
+use crate::config::config_manager::{ConfigManager, LogVerbosity};
 use crate::logging::runtime_logger::RuntimeLogger;
-use crate::openrtb::response::{Bid, BidResponse, SeatBid};
+use crate::openrtb::response::{BidResponse, NoBidReason, SeatBid};
 use crate::model::context::Context;
-
-/// 辅助函数，根据 DSP 下发的 adm 内容生成 ADX 注入的 SSP tracking 部分（保留 {AUCTION_PRICE} 占位符）
-fn generate_ssp_tracking(adm: &str) -> String {
-    if adm.contains("<html") {
-        "<img src=\"http://tk.rust-adx.com/impression?price={AUCTION_PRICE}\" style=\"display:none;\" />".to_string()
-    } else if adm.contains("<VAST") {
-        "<Impression><![CDATA[http://tk.rust-adx.com/impression?price={AUCTION_PRICE}]]></Impression>".to_string()
-    } else if adm.trim_start().starts_with("{") {
-        "".to_string() // native 类型不额外注入
-    } else {
-        "".to_string()
-    }
-}
+use crate::model::ssp::SeatBidGrouping;
 
 /// 处理竞价请求，参数为 Context，贯穿整个调用链的信息
 pub async fn process_bid_request(
     context: &Context,
     config: &ConfigManager,
     runtime_logger: &Arc<RuntimeLogger>,
+    events: &EventBus,
 ) -> Option<BidResponse> {
     let bid_request = &context.bid_request;
-    let dsp_client = DspClient::new(config.active_demands());
-    let mut dsp_details = Vec::new();
-    let bid_responses = dsp_client.fetch_bids(&Arc::new(bid_request.clone())).await;
-    let mut valid_responses = Vec::new();
-    let mut failed_dsp_logs = Vec::new();
-
-    for (dsp_id, dsp_url, price, bid_response, status, elapsed) in bid_responses {
-        let detail = json!({
-            "dsp_id": dsp_id,
-            "url": dsp_url,
-            "bid_price": price,
-            "result": status,
-            "inquiry_time_ms": elapsed,
-            "failure_reason": if status == "success" { Value::Null } else { json!(status) }
+    let mut active_demands = config.active_demands();
+    // 没有任何启用的 DSP（全部禁用或熔断），直接返回无竞价，不再发起 DSP 询价，
+    // 并以 no_active_dsp 与 nbr=1（技术错误）与真实的 DSP 询价失败区分开
+    if active_demands.is_empty() {
+        let log_entry = json!({
+            "request_id": bid_request.id,
+            "adx_log": "adx_inquiry_failed",
+            "reason": "no_active_dsp",
         });
-        dsp_details.push(detail);
+        runtime_logger.log("WARN", &log_entry.to_string()).await;
+        return Some(BidResponse {
+            id: bid_request.id.clone(),
+            seatbid: vec![],
+            bidid: None,
+            cur: None,
+            customdata: None,
+            nbr: Some(NoBidReason::TechnicalError.code()),
+            ext: None,
+        });
+    }
 
-        if let Some(nbr) = bid_response.nbr {
-            failed_dsp_logs.push(json!({
-                "dsp_id": dsp_id,
-                "url": dsp_url,
-                "nbr": nbr,
-                "result": status,
-                "inquiry_time_ms": elapsed,
-            }).to_string());
-            continue;
+    // 测试 DSP 只接收测试流量；开启分流时测试流量也只发给测试 DSP
+    let test_traffic = config.route_test_traffic && context.is_test_traffic();
+    active_demands.retain(|demand| demand.test == test_traffic);
+    // 影子 DSP 只接收抽样的请求
+    if let Some(shadow) = config.shadow_dsp {
+        if !shadow.should_sample() {
+            active_demands.retain(|demand| demand.id != shadow.dsp_id);
         }
-        if bid_response.seatbid.is_empty() {
-            failed_dsp_logs.push(json!({
-                "dsp_id": dsp_id,
-                "url": dsp_url,
-                "reason": "no_seatbid",
-                "result": status,
-                "inquiry_time_ms": elapsed,
-            }).to_string());
-            continue;
+    }
+
+    let retry_budget = Arc::new(RetryBudget::new(config.retry_budget));
+    // 按 DSP 广告位的 custom_ad_type 确定各 DSP 支持的媒体类型，用于转发前裁剪 imp
+    let mut media_types: HashMap<u64, Vec<&'static str>> = HashMap::new();
+    for placement in config.get_dsp_placements().iter().filter(|p| p.status == 1) {
+        let types = media_types.entry(placement.dsp_id).or_default();
+        for media in placement.supported_media() {
+            if !types.contains(&media) {
+                types.push(media);
+            }
         }
-        valid_responses.push((bid_response, price));
     }
+    let dsp_client = DspClient::new(active_demands)
+        .with_media_types(media_types)
+        .with_adapters(config.dsp_adapters.clone())
+        .with_retry_budget(retry_budget.clone())
+        .with_body_capture(config.body_capture.clone())
+        .with_deadline(context.start_time, config.tmax_reserve_ms + context.ssp.serialization_reserve_ms.unwrap_or(0))
+        .with_default_tmax(config.default_tmax_ms)
+        .with_max_auction_ms(context.ssp.max_auction_ms)
+        .with_user_agent(&config.dsp_user_agent)
+        .with_client(config.dsp_http_client.clone())
+        .with_host_limiter(config.host_limiter.clone())
+        .with_timeout_jitter(config.timeout_jitter.clone());
 
-    if !failed_dsp_logs.is_empty() {
+    // 有启用的 DSP，但经过测试流量、影子抽样、媒体类型与插屏过滤后没有一个会收到本次请求，
+    // 同样不发起询价，并以 no_eligible_dsp 与“全部禁用”及 DSP 询价失败区分开
+    if !dsp_client.has_eligible_demand(bid_request) {
         let log_entry = json!({
             "request_id": bid_request.id,
-            "adx_log": "dsp_inquiry_failed",
-            "details": failed_dsp_logs,
+            "adx_log": "adx_inquiry_failed",
+            "reason": "no_eligible_dsp",
+        });
+        runtime_logger.log("WARN", &log_entry.to_string()).await;
+        return config.no_eligible_dsp_nbr.map(|nbr| BidResponse {
+            id: bid_request.id.clone(),
+            seatbid: vec![],
+            bidid: None,
+            cur: None,
+            customdata: None,
+            nbr: Some(nbr),
+            ext: None,
         });
-        runtime_logger.log("ERROR", &log_entry.to_string()).await;
     }
-
-    let adx_result;
-    let winning_bid_opt;
-
-    if valid_responses.is_empty() {
-        adx_result = "failed";
+    let response = process_bid_request_with(context, config, runtime_logger, events, &dsp_client).await;
+    if retry_budget.is_exhausted() {
         let log_entry = json!({
             "request_id": bid_request.id,
-            "adx_log": "adx_inquiry_failed",
-            "reason": "all_dsp_failed",
+            "adx_log": "retry_budget_exhausted",
+            "retry_budget": retry_budget.limit(),
+            "retries_used": retry_budget.used(),
         });
-        runtime_logger.log("ERROR", &log_entry.to_string()).await;
-        winning_bid_opt = None;
-    } else {
-        valid_responses.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        let mut checked_bids = Vec::new();
-        for (winning_response, _) in valid_responses {
-            for seatbid in winning_response.seatbid {
-                for bid in seatbid.bid {
-                    if contains_sensitive_content(&bid) {
-                        let log_entry = json!({
-                            "request_id": bid_request.id,
-                            "adx_log": "bid_rejected",
-                            "bid_id": bid.id,
-                            "reason": "contains_sensitive_content",
-                        });
-                        runtime_logger.log("WARN", &log_entry.to_string()).await;
-                        continue;
-                    }
-                    checked_bids.push(bid.clone());
-                }
-            }
-        }
-        if checked_bids.is_empty() {
-            adx_result = "failed";
-            let log_entry = json!({
-                "request_id": bid_request.id,
-                "adx_log": "adx_inquiry_failed",
-                "reason": "all_bids_filtered",
-            });
-            runtime_logger.log("ERROR", &log_entry.to_string()).await;
-            winning_bid_opt = None;
-        } else {
-            checked_bids.sort_by(|a, b| b.price.partial_cmp(&a.price).unwrap());
-            let mut winning_bid = checked_bids.first().unwrap().clone();
-            adx_result = "success";
-            let original_price = winning_bid.price;
-            let final_price = original_price * 0.8; // 扣除20%利润后的价格
-
-            // 先替换 DSP 下发的 offer 中的 {AUCTION_PRICE} 占位符为 final_price，
-            // 然后生成 ADX 注入的 SSP tracking（其中 tracking URL 保留 {AUCTION_PRICE} 占位符），并追加
-            if let Some(original_adm) = winning_bid.adm.as_ref() {
-                let dsp_adm_processed = original_adm.replace("{AUCTION_PRICE}", &final_price.to_string());
-                let ssp_tracking = generate_ssp_tracking(original_adm);
-                let final_adm = format!("{}{}", dsp_adm_processed, ssp_tracking);
-                winning_bid.adm = Some(final_adm);
-            }
-            let price_info = json!({
-                "original_price": original_price,
-                "final_price": final_price
-            });
-            dsp_details.push(price_info);
-            winning_bid_opt = Some(winning_bid);
-        }
+        runtime_logger.log("WARN", &log_entry.to_string()).await;
     }
+    response
+}
+
+/// 通过指定的 BidFetcher 询价并完成竞价：依次执行竞价流水线的各阶段（配置中关闭的可选阶段除外），
+/// 最后记录调用链日志并构造响应
+pub async fn process_bid_request_with<F: BidFetcher>(
+    context: &Context,
+    config: &ConfigManager,
+    runtime_logger: &Arc<RuntimeLogger>,
+    events: &EventBus,
+    fetcher: &F,
+) -> Option<BidResponse> {
+    let mut auction = AuctionContext::new(context, config, runtime_logger, events);
+    AuctionPipeline::standard(fetcher)
+        .without(&config.disabled_auction_stages)
+        .run(&mut auction)
+        .await;
+    finish_auction(auction).await
+}
+
+/// 记录调用链日志，并由各展示位的胜出出价构造返回给 SSP 的响应
+async fn finish_auction(auction: AuctionContext<'_>) -> Option<BidResponse> {
+    let AuctionContext { context, config, runtime_logger, final_decision, dsp_details, sole_dsp_no_bid, winners, imp_no_bids, response_cur, rejections, .. } = auction;
+    let bid_request = &context.bid_request;
+    let adx_result = if winners.is_empty() { "failed" } else { "success" };
 
-    // 记录整个调用链耗时，并判断是否超过 bid_request.tmax
+    // 记录整个调用链耗时，并判断是否超过 bid_request.tmax（未携带时为默认 tmax，且不超过 SSP 的竞价时长上限）
     let elapsed_total = context.start_time.elapsed();
-    if let Some(tmax) = bid_request.tmax {
-        if elapsed_total > Duration::from_millis(tmax) {
-            runtime_logger.log("WARN", &format!(
-                "Processing time {} ms exceeded tmax {} ms",
-                elapsed_total.as_millis(),
-                tmax
-            )).await;
-        }
+    let tmax = context.auction_tmax(config.default_tmax_ms);
+    if elapsed_total > Duration::from_millis(tmax) {
+        runtime_logger.log("WARN", &format!(
+            "Processing time {} ms exceeded tmax {} ms",
+            elapsed_total.as_millis(),
+            tmax
+        )).await;
     }
 
-    let aggregated_log = json!({
-        "request_id": bid_request.id,
-        "adx_inquiry_result": adx_result,
-        "winning_bid": winning_bid_opt,
-        "dsp_call_details": dsp_details,
-        "elapsed_time_ms": elapsed_total.as_millis(),
-    });
-    runtime_logger.log("INFO", &aggregated_log.to_string()).await;
+    let outcome = AuctionOutcome {
+        request_id: bid_request.id.clone(),
+        adx_inquiry_result: adx_result.to_string(),
+        decision_mode: final_decision,
+        winning_bids: winners.iter().map(|w| w.bid.clone()).collect(),
+        imp_no_bids: imp_no_bids.clone(),
+        dsp_call_details: dsp_details,
+        rejections: rejections.into_rejections(),
+        elapsed_time_ms: elapsed_total.as_millis(),
+    };
+    // 环形缓冲区中保留询价明细与拒绝明细，不保留 adm
+    if let Some(recent_auctions) = &config.recent_auctions {
+        recent_auctions.record(outcome.clone().with_verbosity(LogVerbosity::Standard));
+    }
+    let outcome = outcome.with_verbosity(config.log_verbosity);
+    if let Some(webhook) = &config.auction_webhook {
+        webhook.send(&outcome);
+    }
+    if let Ok(aggregated_log) = serde_json::to_string(&outcome) {
+        runtime_logger.log("INFO", &aggregated_log).await;
+    }
 
-    winning_bid_opt.map(|winning_bid| {
-        BidResponse {
+    // 无竞价时只返回唯一 DSP 给出的 nbr（空 seatbid），其余情况由调用方按默认原因处理
+    if winners.is_empty() {
+        return sole_dsp_no_bid.map(|reason| BidResponse {
             id: bid_request.id.clone(),
-            seatbid: vec![SeatBid {
-                bid: vec![winning_bid],
-                seat: Some("".to_string()),
-                group: Some(0),
-            }],
+            seatbid: vec![],
             bidid: None,
-            cur: Some("USD".to_string()),
+            cur: Some(response_cur),
             customdata: None,
-            nbr: None,
+            nbr: Some(reason.code()),
+            ext: None,
+        });
+    }
+    // 多展示位请求部分填充时，在 ext.imp_nbr 中返回每个未填充展示位的原因
+    let mut ext = if imp_no_bids.is_empty() {
+        None
+    } else {
+        Some(json!({ "imp_nbr": imp_no_bids }))
+    };
+    // SSP 开启时在 ext.adx.winning_dsp 中返回胜出 DSP
+    if context.ssp.expose_winning_dsp {
+        if let Some(top) = winners.iter().max_by(|a, b| a.bid.price.total_cmp(&b.bid.price)) {
+            let winning_dsp = json!({ "id": top.dsp_id, "name": config.demand_name(top.dsp_id) });
+            if let Some(ext) = ext.get_or_insert_with(|| json!({})).as_object_mut() {
+                ext.insert("adx".to_string(), json!({ "winning_dsp": winning_dsp }));
+            }
         }
-    })
+    }
+    let mut response = BidResponse {
+        id: bid_request.id.clone(),
+        seatbid: group_seatbids(winners, context.ssp.seatbid_grouping),
+        bidid: None,
+        cur: Some(response_cur),
+        customdata: None,
+        nbr: None,
+        ext,
+    };
+    // 返回给 SSP 的响应不包含空 SeatBid，全部为空时按无竞价处理
+    response.drop_empty_seatbids().then_some(response)
 }
 
-fn contains_sensitive_content(bid: &Bid) -> bool {
-    let content = format!(
-        "{} {}",
-        bid.adm.as_ref().map(|s| s.as_str()).unwrap_or(""),
-        bid.crid.as_deref().unwrap_or("")
-    );
-    let sensitive_keywords = ["forbidden", "banned", "restricted"];
-    sensitive_keywords.iter().any(|&word| content.contains(word))
+/// 按 SSP 配置的方式将胜出出价组织为 SeatBid，顺序与胜出出价一致
+fn group_seatbids(winners: Vec<CandidateBid>, grouping: SeatBidGrouping) -> Vec<SeatBid> {
+    match grouping {
+        SeatBidGrouping::PerBid => winners.into_iter().map(|winner| SeatBid {
+            bid: vec![winner.bid],
+            seat: winner.seat,
+            group: winner.group,
+        }).collect(),
+        SeatBidGrouping::PerDsp => {
+            let mut seatbids: Vec<((u64, Option<String>), SeatBid)> = Vec::new();
+            for winner in winners {
+                let key = (winner.dsp_id, winner.seat.clone());
+                match seatbids.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, seatbid)) => seatbid.bid.push(winner.bid),
+                    None => seatbids.push((key, SeatBid {
+                        bid: vec![winner.bid],
+                        seat: winner.seat,
+                        group: winner.group,
+                    })),
+                }
+            }
+            seatbids.into_iter().map(|(_, seatbid)| seatbid).collect()
+        }
+        SeatBidGrouping::Single => vec![SeatBid {
+            bid: winners.into_iter().map(|winner| winner.bid).collect(),
+            seat: None,
+            group: None,
+        }],
+    }
 }
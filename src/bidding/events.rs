@@ -0,0 +1,64 @@
+// src/bidding/events.rs
+
+use std::sync::Arc;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::logging::runtime_logger::RuntimeLogger;
+
+/// 竞价过程中产生的事件，由引擎发布，指标、日志、Kafka 等订阅方各自消费
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuctionEvent {
+    /// 收到 DSP 的一条出价
+    BidReceived { request_id: String, dsp_id: u64, bid_id: String, impid: String, price: f64 },
+    /// 出价被过滤
+    BidFiltered { request_id: String, bid_id: String, reason: String },
+    /// 出价胜出
+    BidWon { request_id: String, bid_id: String, impid: String, original_price: f64, final_price: f64 },
+    /// 已触发胜出通知 nurl
+    NurlFired { request_id: String, bid_id: String, url: String },
+}
+
+/// 进程内异步事件总线（基于 broadcast），发布方与订阅方互不感知
+pub struct EventBus {
+    sender: Sender<AuctionEvent>,
+}
+
+impl EventBus {
+    /// - `capacity`: 每个订阅方可积压的事件数，超出后最旧的事件会被丢弃
+    pub fn new(capacity: usize) -> Arc<Self> {
+        let (sender, _) = broadcast::channel(capacity);
+        Arc::new(Self { sender })
+    }
+
+    /// 发布事件，没有订阅方时直接丢弃
+    pub fn publish(&self, event: AuctionEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> Receiver<AuctionEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// 启动一个将竞价事件写入运行日志（DEBUG 级别）的订阅方
+pub fn spawn_log_subscriber(bus: &EventBus, runtime_logger: Arc<RuntimeLogger>) {
+    let mut receiver = bus.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if let Ok(content) = serde_json::to_string(&event) {
+                        runtime_logger.log("DEBUG", &content).await;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    runtime_logger.log("WARN", &format!("Auction event log subscriber lagged, skipped {} events", skipped)).await;
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
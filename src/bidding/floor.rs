@@ -0,0 +1,140 @@
+// src/bidding/floor.rs
+
+use std::collections::HashMap;
+
+use crate::bidding::currency::{FxTable, DEFAULT_CURRENCY};
+use crate::model::placements::SspPlacement;
+use crate::openrtb::request::{BidRequest, ImpDetail};
+
+/// 每单位 CPM 对应的 micros 数：micros = CPM × 1e6
+pub const MICROS_PER_CPM: f64 = 1_000_000.0;
+
+/// CPM 换算为 micros（四舍五入到整数）
+pub fn to_micros(cpm: f64) -> i64 {
+    (cpm * MICROS_PER_CPM).round() as i64
+}
+
+/// micros 换算为 CPM
+pub fn from_micros(micros: i64) -> f64 {
+    micros as f64 / MICROS_PER_CPM
+}
+
+/// 出价是否低于底价：统一换算为整数 micros 后比较，避免浮点误差（如 0.1 + 0.2 与 0.3）
+pub fn is_below_floor(price: f64, floor: f64) -> bool {
+    to_micros(price) < to_micros(floor)
+}
+
+/// 计算展示位的生效底价（以该 imp 的底价货币 floor_cur 计）：imp 自带底价（bidfloor_micros 优先于 bidfloor）优先，
+/// 否则回退到 SSP 广告位配置的默认底价（USD，换算为 floor_cur，无法换算时视为无底价）
+pub fn effective_bidfloor(imp: &ImpDetail, ssp_placement: &SspPlacement, floor_cur: &str, fx_table: &FxTable) -> Option<f64> {
+    imp.bidfloor_micros.map(from_micros)
+        .or(imp.bidfloor)
+        .or_else(|| {
+            ssp_placement.default_bidfloor
+                .and_then(|floor| fx_table.convert(floor, DEFAULT_CURRENCY, floor_cur))
+        })
+}
+
+/// 计算底价货币，按 OpenRTB 约定依次回退：imp.bidfloorcur → 请求 cur 的第一个 → USD
+pub fn floor_currency(imp: &ImpDetail, bid_request: &BidRequest) -> String {
+    imp.bidfloorcur.clone()
+        .or_else(|| bid_request.cur.as_ref().and_then(|cur| cur.first().cloned()))
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string())
+}
+
+/// 按 site.cat / app.cat 计算内容类目底价（USD），多个类目命中时取最高；
+/// 配置一级类目（如 IAB7）时同样作用于其子类目（如 IAB7-1）
+pub fn category_floor(bid_request: &BidRequest, category_floors: &HashMap<String, f64>) -> Option<f64> {
+    if category_floors.is_empty() {
+        return None;
+    }
+    let site_cat = bid_request.get_site_detail().and_then(|site| site.cat.as_ref());
+    let app_cat = bid_request.get_app_detail().and_then(|app| app.cat.as_ref());
+    site_cat.into_iter()
+        .chain(app_cat)
+        .flatten()
+        .filter_map(|cat| {
+            category_floors.get(cat).or_else(|| {
+                let (parent, _) = cat.split_once('-')?;
+                category_floors.get(parent)
+            })
+        })
+        .copied()
+        .max_by(f64::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn imp(value: serde_json::Value) -> ImpDetail {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn placement(default_bidfloor: Option<f64>) -> SspPlacement {
+        serde_json::from_value(json!({
+            "ssp_id": 1, "ssp_uuid": "ssp-1", "placement_id": "placement-1",
+            "ad_type": 2, "update_time": 0, "status": 1, "default_bidfloor": default_bidfloor
+        })).unwrap()
+    }
+
+    #[test]
+    fn imp_without_floor_inherits_the_placement_floor() {
+        let fx_table = FxTable::default();
+        assert_eq!(effective_bidfloor(&imp(json!({"id": "1"})), &placement(Some(0.8)), "USD", &fx_table), Some(0.8));
+        assert_eq!(effective_bidfloor(&imp(json!({"id": "1"})), &placement(None), "USD", &fx_table), None);
+    }
+
+    #[test]
+    fn imp_floor_takes_precedence_over_the_placement_floor() {
+        let fx_table = FxTable::default();
+        let floor = effective_bidfloor(&imp(json!({"id": "1", "bidfloor": 2.0})), &placement(Some(0.8)), "USD", &fx_table);
+        assert_eq!(floor, Some(2.0));
+    }
+
+    #[test]
+    fn floor_currency_defaults_from_bidfloorcur_then_request_cur_then_usd() {
+        let bid_request = |value: serde_json::Value| -> BidRequest { serde_json::from_value(value).unwrap() };
+        let with_cur = bid_request(json!({"id": "r1", "imp": [], "cur": ["EUR", "USD"]}));
+        let without_cur = bid_request(json!({"id": "r1", "imp": []}));
+        assert_eq!(floor_currency(&imp(json!({"id": "1", "bidfloorcur": "CNY"})), &with_cur), "CNY");
+        assert_eq!(floor_currency(&imp(json!({"id": "1"})), &with_cur), "EUR");
+        assert_eq!(floor_currency(&imp(json!({"id": "1"})), &without_cur), "USD");
+    }
+
+    #[test]
+    fn placement_floor_is_converted_to_the_floor_currency() {
+        let fx_table = FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap();
+        let floor = effective_bidfloor(&imp(json!({"id": "1"})), &placement(Some(1.0)), "CNY", &fx_table);
+        assert_eq!(floor, Some(7.0));
+    }
+
+    #[test]
+    fn micro_floor_is_compared_against_float_bids_without_precision_loss() {
+        let fx_table = FxTable::default();
+        let micro_imp = imp(json!({"id": "1", "bidfloor": 9.0, "bidfloor_micros": 1_100_000}));
+        let floor = effective_bidfloor(&micro_imp, &placement(None), "USD", &fx_table).unwrap();
+        assert_eq!(floor, 1.1);
+        assert_eq!(to_micros(floor), 1_100_000);
+        // 0.15 + 0.95 = 1.0999999999999999，按浮点比较会低于底价，按 micros 比较与底价相等
+        assert!(0.15 + 0.95 < floor);
+        assert!(!is_below_floor(0.15 + 0.95, floor));
+        assert!(!is_below_floor(1.1, floor));
+        assert!(is_below_floor(1.099999, floor));
+        assert_eq!(from_micros(to_micros(2.5)), 2.5);
+    }
+
+    #[test]
+    fn category_floor_matches_site_and_app_categories_and_their_parents() {
+        let bid_request = |value: serde_json::Value| -> BidRequest { serde_json::from_value(value).unwrap() };
+        let floors = HashMap::from([("IAB7".to_string(), 2.0), ("IAB3-1".to_string(), 1.5)]);
+        let site = bid_request(json!({"id": "r1", "imp": [], "site": {"id": "s1", "cat": ["IAB7-1", "IAB3-1"]}}));
+        let app = bid_request(json!({"id": "r1", "imp": [], "app": {"id": "a1", "cat": ["IAB3-1"]}}));
+        let unmatched = bid_request(json!({"id": "r1", "imp": [], "site": {"id": "s1", "cat": ["IAB1"]}}));
+        assert_eq!(category_floor(&site, &floors), Some(2.0));
+        assert_eq!(category_floor(&app, &floors), Some(1.5));
+        assert_eq!(category_floor(&unmatched, &floors), None);
+        assert_eq!(category_floor(&site, &HashMap::new()), None);
+    }
+}
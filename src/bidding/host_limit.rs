@@ -0,0 +1,78 @@
+// src/bidding/host_limit.rs
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 限制每个 DSP 主机（host:port）同时进行中的询价数，避免单个 DSP 主机占满连接。
+/// 在所有竞价请求间共享
+#[derive(Debug)]
+pub struct HostLimiter {
+    max_in_flight: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    /// max_in_flight 为 0 时不限制，返回 None
+    pub fn new(max_in_flight: usize) -> Option<Self> {
+        (max_in_flight > 0).then(|| Self { max_in_flight, hosts: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    /// 尝试为一次询价占用该 URL 所在主机的名额，名额用尽时返回 None；
+    /// 返回的许可在询价结束（drop）时归还
+    pub fn try_acquire(&self, url: &str) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(hosts.entry(host_key(url)).or_insert_with(|| Arc::new(Semaphore::new(self.max_in_flight))))
+        };
+        semaphore.try_acquire_owned().ok()
+    }
+
+    /// 该 URL 所在主机当前进行中的询价数
+    pub fn in_flight(&self, url: &str) -> usize {
+        let hosts = self.hosts.lock().unwrap_or_else(|e| e.into_inner());
+        hosts.get(&host_key(url))
+            .map_or(0, |semaphore| self.max_in_flight - semaphore.available_permits())
+    }
+}
+
+/// URL 的 host:port（端口缺省时取协议默认端口），无法解析时按原始 URL 计数
+fn host_key(url: &str) -> String {
+    reqwest::Url::parse(url).ok()
+        .and_then(|parsed| {
+            let host = parsed.host_str()?;
+            Some(format!("{}:{}", host, parsed.port_or_known_default().unwrap_or(0)))
+        })
+        .unwrap_or_else(|| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_flight_calls_per_host_are_capped_and_released_on_drop() {
+        let limiter = HostLimiter::new(2).unwrap();
+        let first = limiter.try_acquire("http://dsp.local/bid");
+        let second = limiter.try_acquire("http://dsp.local:80/other");
+        assert!(first.is_some() && second.is_some());
+        assert!(limiter.try_acquire("http://dsp.local/bid").is_none());
+        assert_eq!(limiter.in_flight("http://dsp.local/bid"), 2);
+        // 其他主机（含同一主机的其他端口）单独计数
+        assert!(limiter.try_acquire("http://dsp.local:8080/bid").is_some());
+        assert!(limiter.try_acquire("http://other.local/bid").is_some());
+
+        drop(first);
+        assert_eq!(limiter.in_flight("http://dsp.local/bid"), 1);
+        assert!(limiter.try_acquire("http://dsp.local/bid").is_some());
+    }
+
+    #[test]
+    fn zero_disables_the_limit() {
+        assert!(HostLimiter::new(0).is_none());
+    }
+}
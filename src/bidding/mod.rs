@@ -1,2 +1,23 @@
 pub mod engine;
 pub mod dsp_client;
+pub mod floor;
+pub mod currency;
+pub mod events;
+pub mod pricing;
+pub mod request_ids;
+pub mod creative;
+pub mod outcome;
+pub mod retry;
+pub mod capture;
+pub mod stats;
+pub mod vast;
+pub mod notice;
+pub mod rate_limit;
+pub mod pipeline;
+pub mod stages;
+pub mod sanitize;
+pub mod recent_auctions;
+pub mod ranking;
+pub mod adapter;
+pub mod host_limit;
+pub mod webhook;
@@ -0,0 +1,240 @@
+// src/bidding/notice.rs
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+
+use crate::bidding::creative::substitute_macros;
+use crate::bidding::events::{AuctionEvent, EventBus};
+use crate::logging::runtime_logger::RuntimeLogger;
+
+/// lurl 中的败出原因宏
+pub const AUCTION_LOSS_MACRO: &str = "{AUCTION_LOSS}";
+/// OpenRTB 败出原因：被更高出价击败
+pub const LOSS_LOST_TO_HIGHER_BID: i32 = 102;
+
+/// 通知类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeKind {
+    /// 胜出通知（nurl）
+    Win,
+    /// 败出通知（lurl）
+    Loss,
+}
+
+/// 一条待发送的胜出 / 败出通知，URL 中的宏已替换完成
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Notice {
+    pub kind: NoticeKind,
+    pub request_id: String,
+    pub bid_id: String,
+    pub dsp_id: u64,
+    pub url: String,
+}
+
+/// 通知重试队列配置
+#[derive(Clone, Debug)]
+pub struct NoticeQueueConfig {
+    /// 同时处于发送 / 重试中的通知上限，超出后新通知写入落盘文件（未配置时丢弃）
+    pub capacity: usize,
+    /// 从入队开始计算的重试窗口，超出后放弃
+    pub retry_window: Duration,
+    /// 首次重试前的等待时间，之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 重试等待时间上限
+    pub max_backoff: Duration,
+    /// 单次 HTTP 请求超时
+    pub request_timeout: Duration,
+    /// 未能送达的通知以 JSON 行追加到该文件，启动时重新入队；None 表示不落盘
+    pub spool_path: Option<PathBuf>,
+}
+
+impl Default for NoticeQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            retry_window: Duration::from_secs(300),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(2),
+            spool_path: None,
+        }
+    }
+}
+
+/// 进程内的胜出 / 败出通知重试队列：发送失败的通知按指数退避在重试窗口内重试，
+/// 队列已满或重试窗口耗尽的通知可落盘，下次启动时重新入队
+pub struct NoticeQueue {
+    client: reqwest::Client,
+    config: NoticeQueueConfig,
+    slots: Arc<Semaphore>,
+    events: Arc<EventBus>,
+    runtime_logger: Arc<RuntimeLogger>,
+    delivered: AtomicU64,
+    expired: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl NoticeQueue {
+    pub fn new(config: NoticeQueueConfig, events: Arc<EventBus>, runtime_logger: Arc<RuntimeLogger>) -> Arc<Self> {
+        Arc::new(Self {
+            client: reqwest::Client::builder()
+                .timeout(config.request_timeout)
+                .build()
+                .expect("Failed to build notice http client"),
+            slots: Arc::new(Semaphore::new(config.capacity)),
+            config,
+            events,
+            runtime_logger,
+            delivered: AtomicU64::new(0),
+            expired: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// 通知入队并在后台发送，不阻塞竞价流程
+    pub async fn enqueue(self: &Arc<Self>, notice: Notice) {
+        let Ok(slot) = self.slots.clone().try_acquire_owned() else {
+            self.give_up(&notice, "notice_queue_full").await;
+            return;
+        };
+        let queue = self.clone();
+        tokio::spawn(async move {
+            queue.deliver(notice).await;
+            drop(slot);
+        });
+    }
+
+    /// 在重试窗口内按指数退避发送，直到 DSP 返回 2xx
+    async fn deliver(&self, notice: Notice) {
+        let deadline = Instant::now() + self.config.retry_window;
+        let mut backoff = self.config.initial_backoff;
+        loop {
+            let error = match self.client.get(&notice.url).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    self.delivered.fetch_add(1, Ordering::Relaxed);
+                    if notice.kind == NoticeKind::Win {
+                        self.events.publish(AuctionEvent::NurlFired {
+                            request_id: notice.request_id.clone(),
+                            bid_id: notice.bid_id.clone(),
+                            url: notice.url.clone(),
+                        });
+                    }
+                    return;
+                }
+                Ok(resp) => format!("status {}", resp.status()),
+                Err(e) => e.to_string(),
+            };
+            if Instant::now() + backoff > deadline {
+                self.expired.fetch_add(1, Ordering::Relaxed);
+                let log_entry = json!({
+                    "request_id": notice.request_id,
+                    "adx_log": "notice_expired",
+                    "kind": notice.kind,
+                    "dsp_id": notice.dsp_id,
+                    "bid_id": notice.bid_id,
+                    "error": error,
+                });
+                self.runtime_logger.log("WARN", &log_entry.to_string()).await;
+                self.spool(&notice).await;
+                return;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+
+    /// 无法入队的通知：配置了落盘文件时写入，否则丢弃
+    async fn give_up(&self, notice: &Notice, reason: &str) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        let log_entry = json!({
+            "request_id": notice.request_id,
+            "adx_log": reason,
+            "kind": notice.kind,
+            "dsp_id": notice.dsp_id,
+            "bid_id": notice.bid_id,
+            "spooled": self.config.spool_path.is_some(),
+        });
+        self.runtime_logger.log("WARN", &log_entry.to_string()).await;
+        self.spool(notice).await;
+    }
+
+    async fn spool(&self, notice: &Notice) {
+        let Some(path) = &self.config.spool_path else {
+            return;
+        };
+        let Ok(mut line) = serde_json::to_string(notice) else {
+            return;
+        };
+        line.push('\n');
+        let result = async {
+            let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+            file.write_all(line.as_bytes()).await
+        }.await;
+        if let Err(e) = result {
+            self.runtime_logger.log("ERROR", &format!("Failed to spool notice to {}: {}", path.display(), e)).await;
+        }
+    }
+
+    /// 读取落盘文件中的通知并重新入队，读取后清空文件；返回重新入队的条数
+    pub async fn replay_spool(self: &Arc<Self>) -> usize {
+        let Some(path) = self.config.spool_path.clone() else {
+            return 0;
+        };
+        let Ok(content) = tokio::fs::read_to_string(&path).await else {
+            return 0;
+        };
+        if let Err(e) = tokio::fs::write(&path, b"").await {
+            self.runtime_logger.log("ERROR", &format!("Failed to truncate notice spool {}: {}", path.display(), e)).await;
+            return 0;
+        }
+        let notices: Vec<Notice> = content.lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let count = notices.len();
+        for notice in notices {
+            self.enqueue(notice).await;
+        }
+        count
+    }
+
+    /// 当前正在发送或等待重试的通知数
+    pub fn pending(&self) -> usize {
+        self.config.capacity - self.slots.available_permits()
+    }
+
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    pub fn expired(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Debug for NoticeQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NoticeQueue")
+            .field("config", &self.config)
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+/// 通知 URL 宏替换后的最大字节数
+pub const MAX_NOTICE_URL_BYTES: usize = 8 * 1024;
+
+/// 替换通知 URL 中的宏，超长时返回 None
+pub fn render_notice_url(template: &str, macros: &[(&str, &str)]) -> Option<String> {
+    substitute_macros(template, macros, MAX_NOTICE_URL_BYTES).ok()
+}
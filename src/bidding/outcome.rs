@@ -0,0 +1,85 @@
+// src/bidding/outcome.rs
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::config_manager::LogVerbosity;
+use crate::openrtb::request::BidRequest;
+use crate::openrtb::response::Bid;
+
+/// 由谁做最终的展示决策（source.fd）：
+/// - Exchange（fd = 0 或缺省）：ADX 的竞价结果即最终结果，创意兼容性校验（clickbrowser、API 框架）不通过的出价直接拒绝；
+/// - Upstream（fd = 1）：上游（如 header bidding 封装）还会再做决策，兼容性校验不通过的出价只记录 bid_flagged 日志并继续参与竞价，
+///   由上游自行取舍。底价、货币、敏感内容等硬性规则两种模式下一致。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalDecision {
+    #[default]
+    Exchange,
+    Upstream,
+}
+
+impl FinalDecision {
+    pub fn of(bid_request: &BidRequest) -> Self {
+        match bid_request.get_source_detail().and_then(|source| source.fd) {
+            Some(1) => FinalDecision::Upstream,
+            _ => FinalDecision::Exchange,
+        }
+    }
+
+    /// 是否严格执行创意兼容性校验
+    pub fn is_strict(&self) -> bool {
+        *self == FinalDecision::Exchange
+    }
+}
+
+/// 单条出价被拒绝的原因
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BidRejection {
+    pub bid_id: String,
+    pub dsp_id: u64,
+    pub reason: String,
+}
+
+/// 多展示位请求中未填充展示位的原因
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImpNoBid {
+    pub impid: String,
+    /// no_bids（没有 DSP 对该展示位出价）/ all_bids_filtered（出价全部被过滤）/ response_currency_unconvertible
+    pub reason: String,
+}
+
+/// 一次竞价的完整结果，即聚合日志（调用链日志）的内容
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuctionOutcome {
+    pub request_id: String,
+    /// success / failed
+    pub adx_inquiry_result: String,
+    /// 最终展示决策方（source.fd）
+    pub decision_mode: FinalDecision,
+    /// 每个展示位的胜出出价
+    pub winning_bids: Vec<Bid>,
+    /// 未填充的展示位及原因
+    pub imp_no_bids: Vec<ImpNoBid>,
+    pub dsp_call_details: Vec<Value>,
+    /// 每条被拒绝的出价及原因
+    pub rejections: Vec<BidRejection>,
+    pub elapsed_time_ms: u128,
+}
+
+impl AuctionOutcome {
+    /// 按日志详细程度裁剪：standard 去掉胜出出价的 adm，minimal 另外去掉各 DSP 询价明细与拒绝明细
+    pub fn with_verbosity(mut self, verbosity: LogVerbosity) -> Self {
+        if verbosity == LogVerbosity::Full {
+            return self;
+        }
+        for bid in &mut self.winning_bids {
+            bid.adm = None;
+        }
+        if verbosity == LogVerbosity::Minimal {
+            self.dsp_call_details.clear();
+            self.rejections.clear();
+        }
+        self
+    }
+}
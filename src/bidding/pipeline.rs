@@ -0,0 +1,258 @@
+// src/bidding/pipeline.rs
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+use crate::bidding::currency::{FxTable, DEFAULT_CURRENCY};
+use crate::bidding::dsp_client::{BidFetcher, DspCallResult};
+use crate::bidding::events::{AuctionEvent, EventBus};
+use crate::bidding::floor::{category_floor, effective_bidfloor, floor_currency};
+use crate::bidding::outcome::{BidRejection, FinalDecision, ImpNoBid};
+use crate::bidding::stages::{
+    FanOut, FilterBlocklists, FilterEligibility, FilterSensitive, InjectTracking, LimitResponseBids, Notify, Price, SanitizeCreatives, ValidateResponses,
+};
+use crate::config::config_manager::ConfigManager;
+use crate::logging::runtime_logger::RuntimeLogger;
+use crate::model::context::Context;
+use crate::openrtb::response::{Bid, NoBidReason};
+
+/// 可通过配置关闭的阶段；fan_out、validate、price 为必需阶段
+pub const OPTIONAL_STAGES: [&str; 7] = [
+    "filter_blocklists",
+    "filter_sensitive",
+    "sanitize_creatives",
+    "filter_eligibility",
+    "inject_tracking",
+    "limit_response_bids",
+    "notify",
+];
+
+/// 校验配置中要关闭的阶段名称
+pub fn validate_disabled_stages(names: &HashSet<String>) -> Result<(), String> {
+    match names.iter().find(|name| !OPTIONAL_STAGES.contains(&name.as_str())) {
+        Some(name) => Err(format!("unknown or required auction stage: {}", name)),
+        None => Ok(()),
+    }
+}
+
+/// 阶段执行后的流程控制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageFlow {
+    /// 继续执行下一个阶段
+    Continue,
+    /// 结束竞价（如没有可用的 DSP 响应或出价），跳过后续阶段
+    Halt,
+}
+
+/// 竞价流水线中的一个阶段，各阶段按顺序读写同一个 AuctionContext
+pub trait AuctionStage: Send + Sync {
+    /// 阶段名称，用于按配置关闭阶段
+    fn name(&self) -> &'static str;
+
+    /// 必需阶段不能通过配置关闭
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow>;
+}
+
+/// 按顺序执行的竞价阶段
+#[derive(Default)]
+pub struct AuctionPipeline<'p> {
+    stages: Vec<Box<dyn AuctionStage + 'p>>,
+}
+
+impl<'p> AuctionPipeline<'p> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 默认流水线：询价 → 校验 DSP 响应 → 黑白名单过滤 → 敏感内容过滤 → 创意净化 → 资格校验 → 定价 → 注入 tracking → 限制出价数 → 通知
+    pub fn standard<F: BidFetcher>(fetcher: &'p F) -> Self {
+        Self::new()
+            .with_stage(FanOut { fetcher })
+            .with_stage(ValidateResponses)
+            .with_stage(FilterBlocklists)
+            .with_stage(FilterSensitive)
+            .with_stage(SanitizeCreatives)
+            .with_stage(FilterEligibility)
+            .with_stage(Price)
+            .with_stage(InjectTracking)
+            .with_stage(LimitResponseBids)
+            .with_stage(Notify)
+    }
+
+    pub fn with_stage(mut self, stage: impl AuctionStage + 'p) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// 移除配置中关闭的可选阶段
+    pub fn without(mut self, disabled: &HashSet<String>) -> Self {
+        self.stages.retain(|stage| stage.required() || !disabled.contains(stage.name()));
+        self
+    }
+
+    pub fn stage_names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// 依次执行各阶段，某个阶段返回 Halt 时停止
+    pub async fn run(&self, auction: &mut AuctionContext<'_>) {
+        for stage in &self.stages {
+            if stage.run(auction).await == StageFlow::Halt {
+                break;
+            }
+        }
+    }
+}
+
+/// 一次竞价在各阶段之间共享的状态
+pub struct AuctionContext<'a> {
+    pub context: &'a Context,
+    pub config: &'a ConfigManager,
+    pub runtime_logger: &'a Arc<RuntimeLogger>,
+    pub events: &'a EventBus,
+    pub final_decision: FinalDecision,
+    pub fx_table: FxTable,
+    /// 每个展示位的生效底价及其货币（imp.bidfloor 优先，其次为 SSP 广告位默认底价），
+    /// 命中内容类目底价时取两者较大值。货币按 imp 各自的 bidfloorcur 确定，多 imp 请求中可以各不相同
+    pub imp_floors: HashMap<String, (f64, String)>,
+    /// 各 DSP 的询价结果
+    pub dsp_results: Vec<DspCallResult>,
+    /// DSP 询价明细与胜出出价的定价明细，写入调用链日志
+    pub dsp_details: Vec<Value>,
+    /// 只询价了一个 DSP 且其返回 nbr 时的无竞价原因，无竞价时原样转给 SSP
+    pub sole_dsp_no_bid: Option<NoBidReason>,
+    /// 收到过出价的展示位，用于区分未填充展示位的原因
+    pub received_impids: HashSet<String>,
+    /// 通过各过滤阶段的候选出价
+    pub candidates: Vec<CandidateBid>,
+    /// 每个展示位独立竞价的胜出出价（按请求中 imp 的顺序），seat / group 原样返回给 SSP
+    pub winners: Vec<CandidateBid>,
+    /// 未填充展示位的原因
+    pub imp_no_bids: Vec<ImpNoBid>,
+    /// 响应的结算货币：SSP 固定货币优先，否则为最高出价的货币
+    pub response_cur: String,
+    pub rejections: RejectionRecorder<'a>,
+}
+
+impl<'a> AuctionContext<'a> {
+    pub fn new(context: &'a Context, config: &'a ConfigManager, runtime_logger: &'a Arc<RuntimeLogger>, events: &'a EventBus) -> Self {
+        let bid_request = &context.bid_request;
+        let fx_table = config.get_fx_table();
+        let category_floor = category_floor(bid_request, &config.category_floors);
+        let imp_floors = bid_request.get_imp_details().iter()
+            .filter_map(|imp| {
+                // 底价货币逐个 imp 确定，多 imp 请求中各 imp 的 bidfloorcur 可以不同
+                let cur = floor_currency(imp, bid_request);
+                let category_floor = category_floor
+                    .and_then(|floor| fx_table.convert(floor, DEFAULT_CURRENCY, &cur));
+                let floor = match (effective_bidfloor(imp, &context.ssp_placement, &cur, &fx_table), category_floor) {
+                    (Some(imp_floor), Some(category_floor)) => Some(imp_floor.max(category_floor)),
+                    (imp_floor, category_floor) => imp_floor.or(category_floor),
+                };
+                floor.map(|floor| (imp.id.clone(), (floor, cur)))
+            })
+            .collect();
+        Self {
+            context,
+            config,
+            runtime_logger,
+            events,
+            final_decision: FinalDecision::of(bid_request),
+            fx_table,
+            imp_floors,
+            dsp_results: Vec::new(),
+            dsp_details: Vec::new(),
+            sole_dsp_no_bid: None,
+            received_impids: HashSet::new(),
+            candidates: Vec::new(),
+            winners: Vec::new(),
+            imp_no_bids: Vec::new(),
+            response_cur: DEFAULT_CURRENCY.to_string(),
+            rejections: RejectionRecorder::new(&bid_request.id, runtime_logger, events),
+        }
+    }
+}
+
+/// 通过过滤的候选出价，保留其来源 DSP 及货币信息
+#[derive(Debug, Clone)]
+pub struct CandidateBid {
+    pub bid: Bid,
+    pub dsp_id: u64,
+    pub cur: String,
+    /// 出价换算为比价货币（RANKING_CURRENCY）后的价格，汇率表缺少出价货币时为 None
+    pub rank_price: Option<f64>,
+    /// 出价所在 SeatBid 的席位与分组信息
+    pub seat: Option<String>,
+    pub group: Option<i32>,
+    /// 胜出后扣除利润的成交价（DSP 出价货币与价格单位），用于 nurl 中的 {AUCTION_PRICE}
+    pub final_price: Option<f64>,
+}
+
+/// 收集一次竞价中被拒绝的出价：记录结构化拒绝原因、写日志并发布 BidFiltered 事件
+pub struct RejectionRecorder<'a> {
+    request_id: &'a str,
+    runtime_logger: &'a RuntimeLogger,
+    events: &'a EventBus,
+    rejections: Vec<BidRejection>,
+}
+
+impl<'a> RejectionRecorder<'a> {
+    pub fn new(request_id: &'a str, runtime_logger: &'a RuntimeLogger, events: &'a EventBus) -> Self {
+        Self { request_id, runtime_logger, events, rejections: Vec::new() }
+    }
+
+    /// 仅记录拒绝原因并发布事件，不写 bid_rejected 日志
+    pub fn record(&mut self, dsp_id: u64, bid: &Bid, reason: &str) {
+        self.rejections.push(BidRejection {
+            bid_id: bid.id.clone(),
+            dsp_id,
+            reason: reason.to_string(),
+        });
+        self.events.publish(AuctionEvent::BidFiltered {
+            request_id: self.request_id.to_string(),
+            bid_id: bid.id.clone(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// 拒绝出价并写 bid_rejected 日志，extra 中的字段会合并进日志
+    pub async fn reject(&mut self, dsp_id: u64, bid: &Bid, reason: &str, extra: Value) {
+        self.record(dsp_id, bid, reason);
+        let mut log_entry = json!({
+            "request_id": self.request_id,
+            "adx_log": "bid_rejected",
+            "bid_id": bid.id,
+            "dsp_id": dsp_id,
+            "reason": reason,
+        });
+        if let (Some(entry), Value::Object(extra)) = (log_entry.as_object_mut(), extra) {
+            entry.extend(extra);
+        }
+        self.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    }
+
+    /// 出价未通过校验但仍保留（如由上游做最终决策），写 bid_flagged 日志
+    pub async fn flag(&self, dsp_id: u64, bid: &Bid, reason: &str, extra: Value) {
+        let mut log_entry = json!({
+            "request_id": self.request_id,
+            "adx_log": "bid_flagged",
+            "bid_id": bid.id,
+            "dsp_id": dsp_id,
+            "reason": reason,
+        });
+        if let (Some(entry), Value::Object(extra)) = (log_entry.as_object_mut(), extra) {
+            entry.extend(extra);
+        }
+        self.runtime_logger.log("WARN", &log_entry.to_string()).await;
+    }
+
+    pub fn into_rejections(self) -> Vec<BidRejection> {
+        self.rejections
+    }
+}
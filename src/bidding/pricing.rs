@@ -0,0 +1,421 @@
+// src/bidding/pricing.rs
+
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::model::placements::DspPlacement;
+
+/// 竞价类型，对应 BidRequest.at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuctionType {
+    /// at = 1，一价：按胜出出价成交
+    First,
+    /// at = 2，二价：按次高价（或底价）加一个最小加价成交
+    Second,
+    /// at = 3，固定价格：按 deal 约定的价格成交
+    Fixed,
+}
+
+impl AuctionType {
+    /// 根据 BidRequest.at 选择竞价类型，缺省或未知值按一价处理
+    pub fn from_at(at: Option<i32>) -> Self {
+        match at {
+            Some(2) => AuctionType::Second,
+            Some(3) => AuctionType::Fixed,
+            _ => AuctionType::First,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuctionType::First => "first_price",
+            AuctionType::Second => "second_price",
+            AuctionType::Fixed => "fixed_price",
+        }
+    }
+}
+
+/// 二价竞价时在次高价之上的最小加价
+pub const SECOND_PRICE_INCREMENT: f64 = 0.01;
+
+/// 二价成交价默认向上取整的出价粒度
+pub const DEFAULT_PRICE_GRANULARITY: f64 = 0.01;
+
+/// 判断取整倍数时容忍的浮点误差，避免 1.23 / 0.01 = 123.00000000000001 被多进一档
+const GRANULARITY_EPSILON: f64 = 1e-9;
+
+/// 将价格向上取整到 granularity 的整数倍，granularity 不大于 0 时原样返回。
+/// 取整结果因浮点误差仍小于原价时再进一档，保证结果不小于 price
+pub fn round_up_to_granularity(price: f64, granularity: f64) -> f64 {
+    if granularity <= 0.0 || !price.is_finite() {
+        return price;
+    }
+    let steps = (price / granularity - GRANULARITY_EPSILON).ceil();
+    let rounded = steps * granularity;
+    if rounded < price {
+        (steps + 1.0) * granularity
+    } else {
+        rounded
+    }
+}
+
+/// 根据竞价类型计算成交价（扣除利润前），所有价格须为同一货币
+///
+/// - `winning_price`: 胜出出价
+/// - `runner_up_price`: 同一展示位上的次高出价
+/// - `floor`: 展示位底价
+/// - `deal_price`: 胜出出价所属 deal 的约定价格
+/// - `granularity`: 二价成交价向上取整的出价粒度，0 表示不取整
+///
+/// 胜出出价不低于次高价与底价时，二价成交价始终满足 max(次高价, 底价) ≤ 成交价 ≤ 胜出出价：
+/// 次高价经汇率换算后的浮点误差通过向上取整消除，取整后超出胜出出价时按胜出出价成交
+pub fn clearing_price(
+    auction_type: AuctionType,
+    winning_price: f64,
+    runner_up_price: Option<f64>,
+    floor: Option<f64>,
+    deal_price: Option<f64>,
+    granularity: f64,
+) -> f64 {
+    match auction_type {
+        AuctionType::First => winning_price,
+        AuctionType::Second => {
+            // 次高价与底价取较大者，加上最小加价并向上取整，但不会超过胜出出价本身
+            match runner_up_price.into_iter().chain(floor).reduce(f64::max) {
+                Some(base) => round_up_to_granularity(base + SECOND_PRICE_INCREMENT, granularity).min(winning_price),
+                None => winning_price,
+            }
+        }
+        // 找不到对应的 deal 时退化为一价
+        AuctionType::Fixed => deal_price.unwrap_or(winning_price),
+    }
+}
+
+/// 一价竞价下的 bid shading：在扣除利润前将成交价向下调整，减少 DSP 的超额支付
+pub trait BidShader: Send + Sync {
+    /// 返回调整后的成交价，结果须介于 [max(次高价, 底价), price] 之间
+    fn shade(&self, price: f64, runner_up_price: Option<f64>, floor: Option<f64>) -> f64;
+}
+
+/// 默认不做 shading
+pub struct NoShading;
+
+impl BidShader for NoShading {
+    fn shade(&self, price: f64, _runner_up_price: Option<f64>, _floor: Option<f64>) -> f64 {
+        price
+    }
+}
+
+/// 线性 shading：按固定比例下调成交价，但不低于次高价与底价
+pub struct LinearShade {
+    /// 下调比例（0 ~ 1），例如 0.1 表示下调 10%
+    pub factor: f64,
+}
+
+impl BidShader for LinearShade {
+    fn shade(&self, price: f64, runner_up_price: Option<f64>, floor: Option<f64>) -> f64 {
+        let lower_bound = runner_up_price.into_iter().chain(floor).fold(0.0, f64::max);
+        (price * (1.0 - self.factor.clamp(0.0, 1.0))).max(lower_bound).min(price)
+    }
+}
+
+/// 默认利润率（扣除 20% 利润）
+pub const DEFAULT_PROFIT_RATE: f64 = 0.2;
+
+/// 参与定价的出价，价格已换算为胜出出价的货币
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricedBid {
+    pub dsp_id: u64,
+    pub price: f64,
+}
+
+/// 定价所需的上下文信息
+pub struct PricingContext<'a> {
+    pub auction_type: AuctionType,
+    /// 展示位底价
+    pub floor: Option<f64>,
+    /// 胜出出价所属 deal 的约定价格
+    pub deal_price: Option<f64>,
+    /// DSP 广告位配置，PerDspRate 从中读取各 DSP 的利润率
+    pub dsp_placements: &'a [DspPlacement],
+    /// 一价成交时在扣除利润前应用的 bid shading
+    pub shader: &'a dyn BidShader,
+    /// 请求来源 SSP 的专属利润率，设置时覆盖策略自身的利润率
+    pub ssp_profit_rate: Option<f64>,
+    /// 二价成交价向上取整的出价粒度，0 表示不取整
+    pub granularity: f64,
+}
+
+/// 按 BidRequest.at 计算成交价，一价成交时再应用 bid shading
+fn auction_clear(bids: &[PricedBid], context: &PricingContext) -> f64 {
+    let runner_up_price = bids.get(1).map(|b| b.price);
+    let clear = clearing_price(
+        context.auction_type,
+        bids[0].price,
+        runner_up_price,
+        context.floor,
+        context.deal_price,
+        context.granularity,
+    );
+    match context.auction_type {
+        AuctionType::First => context.shader.shade(clear, runner_up_price, context.floor),
+        _ => clear,
+    }
+}
+
+/// 定价结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClearedPrice {
+    /// 扣除利润前的成交价
+    pub clear_price: f64,
+    /// 实际生效的利润率
+    pub profit_rate: f64,
+    /// 扣除利润后返回给 SSP 的价格
+    pub final_price: f64,
+}
+
+impl ClearedPrice {
+    /// 固定价格成交：按约定价格结算，不扣除利润
+    pub fn fixed(price: f64) -> Self {
+        Self { clear_price: price, profit_rate: 0.0, final_price: price }
+    }
+
+    fn with_margin(clear_price: f64, profit_rate: f64) -> Self {
+        Self { clear_price, profit_rate, final_price: clear_price * (1.0 - profit_rate) }
+    }
+}
+
+/// 定价策略：根据同一展示位的出价计算成交价与扣除利润后的价格
+pub trait PricingStrategy: Send + Sync {
+    /// bids 为同一展示位按价格降序排列的出价，bids[0] 为胜出出价，调用方保证非空
+    fn clear(&self, bids: &[PricedBid], context: &PricingContext) -> ClearedPrice;
+}
+
+/// 按 BidRequest.at 成交，统一扣除固定利润率
+pub struct FlatMargin {
+    pub profit_rate: f64,
+}
+
+impl PricingStrategy for FlatMargin {
+    fn clear(&self, bids: &[PricedBid], context: &PricingContext) -> ClearedPrice {
+        let clear = auction_clear(bids, context);
+        ClearedPrice::with_margin(clear, context.ssp_profit_rate.unwrap_or(self.profit_rate))
+    }
+}
+
+/// 按 BidRequest.at 成交，利润率取胜出 DSP 的 DspPlacement.profit_rate，未配置时使用默认利润率；
+/// SSP 配置了专属利润率时以 SSP 为准
+pub struct PerDspRate {
+    pub default_rate: f64,
+}
+
+impl PricingStrategy for PerDspRate {
+    fn clear(&self, bids: &[PricedBid], context: &PricingContext) -> ClearedPrice {
+        let clear = auction_clear(bids, context);
+        let profit_rate = context.ssp_profit_rate.unwrap_or_else(|| {
+            context.dsp_placements.iter()
+                .find(|p| p.dsp_id == bids[0].dsp_id && p.status == 1)
+                .map(|p| p.profit_rate)
+                .unwrap_or(self.default_rate)
+        });
+        ClearedPrice::with_margin(clear, profit_rate)
+    }
+}
+
+/// 无论 BidRequest.at 为何值，始终按二价成交，再扣除固定利润率
+pub struct SecondPrice {
+    pub profit_rate: f64,
+}
+
+impl PricingStrategy for SecondPrice {
+    fn clear(&self, bids: &[PricedBid], context: &PricingContext) -> ClearedPrice {
+        let clear = clearing_price(
+            AuctionType::Second,
+            bids[0].price,
+            bids.get(1).map(|b| b.price),
+            context.floor,
+            None,
+            context.granularity,
+        );
+        ClearedPrice::with_margin(clear, context.ssp_profit_rate.unwrap_or(self.profit_rate))
+    }
+}
+
+/// 可配置的定价策略类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PricingStrategyKind {
+    #[default]
+    FlatMargin,
+    PerDspRate,
+    SecondPrice,
+}
+
+impl FromStr for PricingStrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat_margin" => Ok(PricingStrategyKind::FlatMargin),
+            "per_dsp_rate" => Ok(PricingStrategyKind::PerDspRate),
+            "second_price" => Ok(PricingStrategyKind::SecondPrice),
+            _ => Err(format!("Invalid pricing strategy: {}", s)),
+        }
+    }
+}
+
+impl PricingStrategyKind {
+    /// 构造对应的定价策略，profit_rate 为固定利润率或 PerDspRate 的默认利润率
+    pub fn build(&self, profit_rate: f64) -> Box<dyn PricingStrategy> {
+        match self {
+            PricingStrategyKind::FlatMargin => Box::new(FlatMargin { profit_rate }),
+            PricingStrategyKind::PerDspRate => Box::new(PerDspRate { default_rate: profit_rate }),
+            PricingStrategyKind::SecondPrice => Box::new(SecondPrice { profit_rate }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn at_selects_the_auction_type() {
+        assert_eq!(AuctionType::from_at(None), AuctionType::First);
+        assert_eq!(AuctionType::from_at(Some(1)), AuctionType::First);
+        assert_eq!(AuctionType::from_at(Some(2)), AuctionType::Second);
+        assert_eq!(AuctionType::from_at(Some(3)), AuctionType::Fixed);
+        assert_eq!(AuctionType::from_at(Some(7)), AuctionType::First);
+    }
+
+    #[test]
+    fn each_auction_type_produces_its_clear() {
+        let clear = |auction_type, deal_price| clearing_price(auction_type, 2.0, Some(1.0), Some(0.5), deal_price, DEFAULT_PRICE_GRANULARITY);
+        assert_eq!(clear(AuctionType::First, None), 2.0);
+        assert_eq!(clear(AuctionType::Second, None), 1.01);
+        assert_eq!(clear(AuctionType::Fixed, Some(1.5)), 1.5);
+        // 找不到 deal 价格的固定价格竞价退化为一价
+        assert_eq!(clear(AuctionType::Fixed, None), 2.0);
+    }
+
+    #[test]
+    fn second_price_falls_back_to_the_floor_and_never_exceeds_the_winning_bid() {
+        assert_eq!(clearing_price(AuctionType::Second, 2.0, None, Some(1.5), None, DEFAULT_PRICE_GRANULARITY), 1.51);
+        assert_eq!(clearing_price(AuctionType::Second, 2.0, None, None, None, DEFAULT_PRICE_GRANULARITY), 2.0);
+        assert_eq!(clearing_price(AuctionType::Second, 1.0, Some(1.0), None, None, DEFAULT_PRICE_GRANULARITY), 1.0);
+    }
+
+    #[test]
+    fn rounding_up_never_lands_below_the_price() {
+        assert_eq!(round_up_to_granularity(1.23, 0.01), 1.23);
+        assert_eq!(round_up_to_granularity(1.231, 0.01), 1.24);
+        assert_eq!(round_up_to_granularity(1.231, 0.0), 1.231);
+        assert_eq!(round_up_to_granularity(1.2, 0.25), 1.25);
+    }
+
+    #[test]
+    fn second_price_clear_stays_at_or_above_the_converted_runner_up() {
+        let fx_table = crate::bidding::currency::FxTable::from_rates(std::collections::HashMap::from([
+            ("CNY".to_string(), 7.0),
+            ("EUR".to_string(), 0.93),
+        ])).unwrap();
+        for cents in 1..=2_000 {
+            for cur in ["CNY", "EUR"] {
+                // 次高价以其他货币出价，换算为胜出出价的货币（USD）后带有浮点误差
+                let second = fx_table.convert(f64::from(cents) * 0.01, cur, "USD").unwrap();
+                for winning in [second, second + 0.005, second + 1.0] {
+                    let clear = clearing_price(AuctionType::Second, winning, Some(second), None, None, DEFAULT_PRICE_GRANULARITY);
+                    assert!(clear >= second, "{} {}: clear {} below second {}", cents, cur, clear, second);
+                    assert!(clear <= winning, "{} {}: clear {} above winning {}", cents, cur, clear, winning);
+                }
+            }
+        }
+    }
+
+    fn dsp_placement(dsp_id: u64, profit_rate: f64) -> DspPlacement {
+        DspPlacement {
+            dsp_id,
+            dsp_uuid: format!("dsp-{}", dsp_id),
+            tag_id: "tag".to_string(),
+            custom_ad_type: "banner".to_string(),
+            profit_rate,
+            auth: String::new(),
+            update_time: 0,
+            status: 1,
+        }
+    }
+
+    fn pricing_context<'a>(auction_type: AuctionType, dsp_placements: &'a [DspPlacement], ssp_profit_rate: Option<f64>) -> PricingContext<'a> {
+        PricingContext {
+            auction_type,
+            floor: None,
+            deal_price: None,
+            dsp_placements,
+            shader: &NoShading,
+            ssp_profit_rate,
+            granularity: DEFAULT_PRICE_GRANULARITY,
+        }
+    }
+
+    fn assert_cleared(cleared: ClearedPrice, clear_price: f64, final_price: f64) {
+        assert!((cleared.clear_price - clear_price).abs() < 1e-9, "{:?}", cleared);
+        assert!((cleared.final_price - final_price).abs() < 1e-9, "{:?}", cleared);
+    }
+
+    #[test]
+    fn each_strategy_clears_the_same_bids_differently() {
+        let bids = [PricedBid { dsp_id: 1, price: 2.0 }, PricedBid { dsp_id: 2, price: 1.0 }];
+        let placements = [dsp_placement(1, 0.1)];
+        let context = pricing_context(AuctionType::First, &placements, None);
+        assert_cleared(PricingStrategyKind::FlatMargin.build(0.2).clear(&bids, &context), 2.0, 1.6);
+        assert_cleared(PricingStrategyKind::PerDspRate.build(0.2).clear(&bids, &context), 2.0, 1.8);
+        assert_cleared(PricingStrategyKind::SecondPrice.build(0.2).clear(&bids, &context), 1.01, 0.808);
+    }
+
+    #[test]
+    fn ssp_profit_rate_overrides_every_strategy_rate() {
+        let bids = [PricedBid { dsp_id: 1, price: 2.0 }, PricedBid { dsp_id: 2, price: 1.0 }];
+        let placements = [dsp_placement(1, 0.1)];
+        let context = pricing_context(AuctionType::First, &placements, Some(0.05));
+        assert_cleared(PricingStrategyKind::FlatMargin.build(0.2).clear(&bids, &context), 2.0, 1.9);
+        assert_cleared(PricingStrategyKind::PerDspRate.build(0.2).clear(&bids, &context), 2.0, 1.9);
+        assert_cleared(PricingStrategyKind::SecondPrice.build(0.2).clear(&bids, &context), 1.01, 0.9595);
+    }
+
+    #[test]
+    fn per_dsp_rate_falls_back_to_the_default_rate() {
+        let bids = [PricedBid { dsp_id: 2, price: 2.0 }];
+        let placements = [dsp_placement(1, 0.1)];
+        let context = pricing_context(AuctionType::First, &placements, None);
+        assert_cleared(PricingStrategyKind::PerDspRate.build(0.25).clear(&bids, &context), 2.0, 1.5);
+    }
+
+    #[test]
+    fn linear_shade_lowers_the_clear_within_bounds() {
+        let shader = LinearShade { factor: 0.1 };
+        assert!((shader.shade(2.0, None, None) - 1.8).abs() < 1e-9);
+        // 不低于次高价与底价，也不高于原成交价
+        assert_eq!(shader.shade(2.0, Some(1.9), None), 1.9);
+        assert_eq!(shader.shade(2.0, Some(1.0), Some(1.95)), 1.95);
+        assert_eq!(LinearShade { factor: -0.5 }.shade(2.0, None, None), 2.0);
+        assert_eq!(NoShading.shade(2.0, Some(1.0), Some(0.5)), 2.0);
+    }
+
+    #[test]
+    fn shading_applies_before_markdown_in_first_price_only() {
+        let bids = [PricedBid { dsp_id: 1, price: 2.0 }, PricedBid { dsp_id: 2, price: 1.0 }];
+        let shader = LinearShade { factor: 0.25 };
+        let first = PricingContext { shader: &shader, ..pricing_context(AuctionType::First, &[], None) };
+        assert_cleared(PricingStrategyKind::FlatMargin.build(0.2).clear(&bids, &first), 1.5, 1.2);
+        let second = PricingContext { shader: &shader, ..pricing_context(AuctionType::Second, &[], None) };
+        assert_cleared(PricingStrategyKind::FlatMargin.build(0.2).clear(&bids, &second), 1.01, 0.808);
+    }
+
+    #[test]
+    fn pricing_strategy_names_parse() {
+        assert_eq!("flat_margin".parse(), Ok(PricingStrategyKind::FlatMargin));
+        assert_eq!("per_dsp_rate".parse(), Ok(PricingStrategyKind::PerDspRate));
+        assert_eq!("second_price".parse(), Ok(PricingStrategyKind::SecondPrice));
+        assert!("vickrey".parse::<PricingStrategyKind>().is_err());
+    }
+}
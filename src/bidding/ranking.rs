@@ -0,0 +1,177 @@
+// src/bidding/ranking.rs
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::bidding::currency::DEFAULT_CURRENCY;
+use crate::bidding::pipeline::CandidateBid;
+
+/// 比价货币：不同货币的出价统一换算为汇率表的基准货币后再比较
+pub const RANKING_CURRENCY: &str = DEFAULT_CURRENCY;
+
+/// 按换算为比价货币后的出价从高到低比较；汇率表缺少出价货币的出价排在可换算的出价之后，彼此之间按原始出价比较
+pub fn compare_price(a: &CandidateBid, b: &CandidateBid) -> Ordering {
+    match (a.rank_price, b.rank_price) {
+        (Some(a_price), Some(b_price)) => b_price.total_cmp(&a_price),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => b.bid.price.total_cmp(&a.bid.price),
+    }
+}
+
+/// 候选出价的排序规则，排在最前的出价优先胜出
+pub trait BidComparator: Send + Sync {
+    /// a 应排在 b 之前时返回 Less
+    fn compare(&self, a: &CandidateBid, b: &CandidateBid) -> Ordering;
+}
+
+/// 默认按出价从高到低排序
+pub struct PriceDescending;
+
+impl BidComparator for PriceDescending {
+    fn compare(&self, a: &CandidateBid, b: &CandidateBid) -> Ordering {
+        compare_price(a, b)
+    }
+}
+
+/// 加权得分：price_weight × 出价 + ctr_weight × 预估点击率（bid.ext.pctr）+ priority_weight × DSP 优先级，
+/// 得分从高到低排序，得分相同时按出价。出价按比价货币计算，无法换算的出价排在可换算的出价之后
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WeightedScore {
+    #[serde(default = "default_price_weight")]
+    pub price_weight: f64,
+    #[serde(default)]
+    pub ctr_weight: f64,
+    #[serde(default)]
+    pub priority_weight: f64,
+    /// 各 DSP 的优先级，未配置的 DSP 为 0
+    #[serde(default)]
+    pub dsp_priorities: HashMap<u64, f64>,
+}
+
+fn default_price_weight() -> f64 {
+    1.0
+}
+
+impl Default for WeightedScore {
+    fn default() -> Self {
+        Self { price_weight: default_price_weight(), ctr_weight: 0.0, priority_weight: 0.0, dsp_priorities: HashMap::new() }
+    }
+}
+
+impl WeightedScore {
+    pub fn score(&self, candidate: &CandidateBid) -> f64 {
+        let pctr = candidate.bid.ext.as_ref()
+            .and_then(|ext| ext.get("pctr"))
+            .and_then(|pctr| pctr.as_f64())
+            .filter(|pctr| pctr.is_finite())
+            .unwrap_or(0.0);
+        let priority = self.dsp_priorities.get(&candidate.dsp_id).copied().unwrap_or(0.0);
+        let price = candidate.rank_price.unwrap_or(candidate.bid.price);
+        self.price_weight * price + self.ctr_weight * pctr + self.priority_weight * priority
+    }
+}
+
+impl BidComparator for WeightedScore {
+    fn compare(&self, a: &CandidateBid, b: &CandidateBid) -> Ordering {
+        b.rank_price.is_some().cmp(&a.rank_price.is_some())
+            .then_with(|| self.score(b).total_cmp(&self.score(a)))
+            .then_with(|| compare_price(a, b))
+    }
+}
+
+/// 可配置的排序规则
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BidComparatorKind {
+    #[default]
+    Price,
+    WeightedScore,
+}
+
+impl FromStr for BidComparatorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "price" => Ok(BidComparatorKind::Price),
+            "weighted_score" => Ok(BidComparatorKind::WeightedScore),
+            _ => Err(format!("Invalid bid comparator: {}", s)),
+        }
+    }
+}
+
+impl BidComparatorKind {
+    pub fn build(&self, weighted_score: &WeightedScore) -> Box<dyn BidComparator> {
+        match self {
+            BidComparatorKind::Price => Box::new(PriceDescending),
+            BidComparatorKind::WeightedScore => Box::new(weighted_score.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bidding::currency::FxTable;
+    use serde_json::json;
+
+    fn candidate(dsp_id: u64, price: f64, cur: &str, fx_table: &FxTable, ext: serde_json::Value) -> CandidateBid {
+        CandidateBid {
+            bid: serde_json::from_value(json!({"id": format!("b{}", dsp_id), "impid": "1", "price": price, "ext": ext})).unwrap(),
+            dsp_id,
+            cur: cur.to_string(),
+            rank_price: fx_table.convert(price, cur, RANKING_CURRENCY),
+            seat: None,
+            group: None,
+            final_price: None,
+        }
+    }
+
+    fn fx_table() -> FxTable {
+        FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap()
+    }
+
+    fn ranked(comparator: &dyn BidComparator, mut candidates: Vec<CandidateBid>) -> Vec<u64> {
+        candidates.sort_by(|a, b| comparator.compare(a, b));
+        candidates.iter().map(|c| c.dsp_id).collect()
+    }
+
+    #[test]
+    fn price_descending_compares_converted_prices() {
+        let fx = fx_table();
+        // 5 CNY ≈ 0.71 USD，低于 1 USD
+        let candidates = vec![candidate(1, 5.0, "CNY", &fx, json!({})), candidate(2, 1.0, "USD", &fx, json!({}))];
+        assert_eq!(ranked(&PriceDescending, candidates), vec![2, 1]);
+    }
+
+    #[test]
+    fn unconvertible_bids_rank_after_convertible_ones() {
+        let fx = fx_table();
+        let candidates = vec![candidate(1, 100.0, "EUR", &fx, json!({})), candidate(2, 1.0, "USD", &fx, json!({}))];
+        assert_eq!(ranked(&PriceDescending, candidates), vec![2, 1]);
+    }
+
+    #[test]
+    fn weighted_score_can_pick_a_lower_priced_bid() {
+        let fx = fx_table();
+        let weighted = WeightedScore { price_weight: 1.0, ctr_weight: 100.0, priority_weight: 0.0, dsp_priorities: HashMap::new() };
+        let candidates = vec![
+            candidate(1, 2.0, "USD", &fx, json!({"pctr": 0.001})),
+            candidate(2, 1.5, "USD", &fx, json!({"pctr": 0.02})),
+        ];
+        assert_eq!(ranked(&PriceDescending, candidates.clone()), vec![1, 2]);
+        assert_eq!(ranked(&weighted, candidates), vec![2, 1]);
+    }
+
+    #[test]
+    fn weighted_score_uses_converted_prices() {
+        let fx = fx_table();
+        let weighted = WeightedScore { price_weight: 1.0, ctr_weight: 0.0, priority_weight: 1.0, dsp_priorities: HashMap::from([(1, 0.5)]) };
+        // DSP 1：0.71 USD + 0.5 优先级 = 1.21，高于 DSP 2 的 1.0
+        let candidates = vec![candidate(2, 1.0, "USD", &fx, json!({})), candidate(1, 5.0, "CNY", &fx, json!({}))];
+        assert_eq!(ranked(&weighted, candidates), vec![1, 2]);
+    }
+}
@@ -0,0 +1,99 @@
+// src/bidding/rate_limit.rs
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// 令牌桶：以 rate 个/秒的速度补充令牌，最多积累 capacity 个
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, capacity: f64, now: Instant) -> Self {
+        Self { rate, capacity, tokens: capacity, last_refill: now }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// 按 SSP 限制请求速率：稳态速率为 Ssp.qps，并允许 qps × burst_seconds 的突发量，
+/// 短时间的流量尖峰不会被拒绝；qps 为 0 的 SSP 不限速
+pub struct SspRateLimiter {
+    burst_seconds: f64,
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl SspRateLimiter {
+    pub fn new(burst_seconds: f64) -> Self {
+        Self { burst_seconds, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// 桶容量：qps × burst_seconds，至少为 1
+    pub fn capacity(&self, qps: u32) -> f64 {
+        (qps as f64 * self.burst_seconds).max(1.0)
+    }
+
+    /// 尝试为一次请求获取令牌，超出速率与突发额度时返回 false
+    pub fn try_acquire(&self, ssp_uuid: &str, qps: u32) -> bool {
+        self.try_acquire_at(ssp_uuid, qps, Instant::now())
+    }
+
+    fn try_acquire_at(&self, ssp_uuid: &str, qps: u32, now: Instant) -> bool {
+        if qps == 0 {
+            return true;
+        }
+        let capacity = self.capacity(qps);
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(ssp_uuid.to_string())
+            .or_insert_with(|| TokenBucket::new(qps as f64, capacity, now));
+        // SSP 的 qps 配置变化后按新配置重建
+        if bucket.rate != qps as f64 || bucket.capacity != capacity {
+            *bucket = TokenBucket::new(qps as f64, capacity, now);
+        }
+        bucket.try_take(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn burst_within_the_allowance_is_accepted() {
+        // 10 qps、2 秒突发：同一时刻最多放行 20 个请求
+        let limiter = SspRateLimiter::new(2.0);
+        let now = Instant::now();
+        assert_eq!(limiter.capacity(10), 20.0);
+        assert!((0..20).all(|_| limiter.try_acquire_at("ssp-1", 10, now)));
+    }
+
+    #[test]
+    fn burst_beyond_the_allowance_is_rejected_until_tokens_refill() {
+        let limiter = SspRateLimiter::new(2.0);
+        let now = Instant::now();
+        let accepted = (0..25).filter(|_| limiter.try_acquire_at("ssp-1", 10, now)).count();
+        assert_eq!(accepted, 20);
+        // 每 100ms 补充一个令牌
+        assert!(!limiter.try_acquire_at("ssp-1", 10, now + Duration::from_millis(50)));
+        assert!(limiter.try_acquire_at("ssp-1", 10, now + Duration::from_millis(150)));
+        // 其他 SSP 有独立的桶，qps 为 0 时不限速
+        assert!(limiter.try_acquire_at("ssp-2", 10, now));
+        assert!((0..100).all(|_| limiter.try_acquire_at("ssp-3", 0, now)));
+    }
+}
@@ -0,0 +1,42 @@
+// src/bidding/recent_auctions.rs
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::bidding::outcome::AuctionOutcome;
+
+/// 最近若干次竞价结果的环形缓冲区，供 /admin/recent-auctions 实时排查；
+/// 写入时只在锁内做一次入队与出队，结果的构造与裁剪都在锁外完成
+#[derive(Debug)]
+pub struct RecentAuctions {
+    capacity: usize,
+    buffer: Mutex<VecDeque<AuctionOutcome>>,
+}
+
+impl RecentAuctions {
+    /// capacity 为 0 时返回 None（关闭）
+    pub fn new(capacity: usize) -> Option<Self> {
+        (capacity > 0).then(|| Self { capacity, buffer: Mutex::new(VecDeque::with_capacity(capacity)) })
+    }
+
+    /// 记录一次竞价结果，缓冲区满时淘汰最旧的一条
+    pub fn record(&self, outcome: AuctionOutcome) {
+        let evicted = {
+            let mut buffer = self.buffer.lock().unwrap();
+            let evicted = if buffer.len() >= self.capacity { buffer.pop_front() } else { None };
+            buffer.push_back(outcome);
+            evicted
+        };
+        // 被淘汰的结果在锁外释放
+        drop(evicted);
+    }
+
+    /// 缓冲区中的竞价结果，最新的在前
+    pub fn snapshot(&self) -> Vec<AuctionOutcome> {
+        self.buffer.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
@@ -0,0 +1,74 @@
+// src/bidding/request_ids.rs
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 记录最近出现过的 BidRequest.id（按 TTL 过期），用于发现 SSP 重放或重复发送的请求
+pub struct RecentRequestIds {
+    ttl: Duration,
+    inner: Mutex<RecentIdsInner>,
+}
+
+struct RecentIdsInner {
+    /// id -> 最近一次出现的时间
+    seen: HashMap<String, Instant>,
+    /// 按出现顺序排列的 (时间, id)，用于按 TTL 顺序淘汰
+    order: VecDeque<(Instant, String)>,
+}
+
+impl RecentRequestIds {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            inner: Mutex::new(RecentIdsInner { seen: HashMap::new(), order: VecDeque::new() }),
+        }
+    }
+
+    /// 记录一次请求 id，若该 id 在 TTL 内已出现过则返回 true
+    pub fn check_and_record(&self, request_id: &str) -> bool {
+        self.check_and_record_at(request_id, Instant::now())
+    }
+
+    fn check_and_record_at(&self, request_id: &str, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        // 淘汰已过期的 id；同一 id 被再次记录过时，以 seen 中最新的时间为准
+        while let Some((seen_at, _)) = inner.order.front() {
+            if now.duration_since(*seen_at) < self.ttl {
+                break;
+            }
+            let (seen_at, id) = inner.order.pop_front().unwrap();
+            if inner.seen.get(&id) == Some(&seen_at) {
+                inner.seen.remove(&id);
+            }
+        }
+        let duplicate = inner.seen.contains_key(request_id);
+        inner.seen.insert(request_id.to_string(), now);
+        inner.order.push_back((now, request_id.to_string()));
+        duplicate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_id_within_ttl_is_a_duplicate() {
+        let ids = RecentRequestIds::new(Duration::from_secs(10));
+        let now = Instant::now();
+        assert!(!ids.check_and_record_at("r1", now));
+        assert!(ids.check_and_record_at("r1", now + Duration::from_secs(5)));
+        assert!(!ids.check_and_record_at("r2", now + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn same_id_beyond_ttl_is_not_a_duplicate() {
+        let ids = RecentRequestIds::new(Duration::from_secs(10));
+        let now = Instant::now();
+        assert!(!ids.check_and_record_at("r1", now));
+        assert!(!ids.check_and_record_at("r1", now + Duration::from_secs(11)));
+        // 再次出现会刷新时间，TTL 从最近一次出现算起
+        assert!(ids.check_and_record_at("r1", now + Duration::from_secs(20)));
+    }
+}
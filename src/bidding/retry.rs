@@ -0,0 +1,116 @@
+// src/bidding/retry.rs
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// 单个竞价请求内所有 DSP 重试共享的重试预算，
+/// 避免个别不稳定的 DSP 通过反复重试占满整个 tmax
+#[derive(Debug)]
+pub struct RetryBudget {
+    limit: u32,
+    remaining: AtomicU32,
+    exhausted: AtomicBool,
+}
+
+impl RetryBudget {
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            remaining: AtomicU32::new(limit),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    /// 尝试消耗一次重试机会，预算耗尽时返回 false 并标记为已耗尽
+    pub fn try_acquire(&self) -> bool {
+        let acquired = self.remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok();
+        if !acquired {
+            self.exhausted.store(true, Ordering::Release);
+        }
+        acquired
+    }
+
+    /// 预算上限
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// 已消耗的重试次数
+    pub fn used(&self) -> u32 {
+        self.limit - self.remaining.load(Ordering::Acquire)
+    }
+
+    /// 是否有重试因预算耗尽而被放弃
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Acquire)
+    }
+}
+
+/// DSP 超时与重试等待的随机抖动：共享同一主机的多个 DSP 同时超时、同时重试时会相互同步，
+/// 加入 0 ~ max 的随机抖动将其错开。可指定随机种子以便复现
+#[derive(Debug)]
+pub struct TimeoutJitter {
+    max: Duration,
+    rng: Mutex<StdRng>,
+}
+
+impl TimeoutJitter {
+    /// max_ms 为 0 时不加抖动，返回 None；seed 为 None 时使用随机种子
+    pub fn new(max_ms: u64, seed: Option<u64>) -> Option<Self> {
+        (max_ms > 0).then(|| Self {
+            max: Duration::from_millis(max_ms),
+            rng: Mutex::new(seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)),
+        })
+    }
+
+    /// 抖动上限
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// 在 0 ~ max 之间随机取一个抖动值
+    pub fn sample(&self) -> Duration {
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        rng.gen_range(Duration::ZERO..=self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_is_exhausted_after_the_limit() {
+        let budget = RetryBudget::new(2);
+        assert!(budget.try_acquire());
+        assert!(budget.try_acquire());
+        assert!(!budget.is_exhausted());
+        assert!(!budget.try_acquire());
+        assert!(budget.is_exhausted());
+        assert_eq!(budget.used(), budget.limit());
+    }
+
+    #[test]
+    fn jitter_samples_spread_within_the_band() {
+        assert!(TimeoutJitter::new(0, Some(7)).is_none());
+        let jitter = TimeoutJitter::new(40, Some(7)).unwrap();
+        let samples: Vec<Duration> = (0..200).map(|_| jitter.sample()).collect();
+        assert!(samples.iter().all(|sample| *sample <= jitter.max()));
+        // 抖动值应分散在整个区间内，而不是集中在同一个值
+        assert!(samples.iter().any(|sample| *sample < Duration::from_millis(10)));
+        assert!(samples.iter().any(|sample| *sample > Duration::from_millis(30)));
+    }
+
+    #[test]
+    fn seeded_jitter_is_reproducible() {
+        let first = TimeoutJitter::new(40, Some(42)).unwrap();
+        let second = TimeoutJitter::new(40, Some(42)).unwrap();
+        let sequence = |jitter: &TimeoutJitter| (0..10).map(|_| jitter.sample()).collect::<Vec<_>>();
+        assert_eq!(sequence(&first), sequence(&second));
+    }
+}
@@ -0,0 +1,249 @@
+// src/bidding/sanitize.rs
+
+/// 允许保留的 HTML 标签，其余标签一律移除
+const ALLOWED_TAGS: &[&str] = &[
+    "html", "head", "body", "meta", "title", "style", "div", "span", "p", "a", "img", "br", "hr",
+    "b", "i", "u", "em", "strong", "small", "center", "font", "table", "thead", "tbody", "tr", "td", "th",
+    "ul", "ol", "li", "h1", "h2", "h3", "h4", "h5", "h6", "picture", "source",
+];
+
+/// 允许保留的属性，事件处理属性（on*）等其余属性一律移除
+const ALLOWED_ATTRS: &[&str] = &[
+    "href", "src", "srcset", "alt", "title", "width", "height", "style", "class", "id", "target", "rel",
+    "border", "align", "valign", "colspan", "rowspan", "color", "size", "face", "bgcolor",
+    "cellpadding", "cellspacing", "name", "content", "charset", "type", "media",
+];
+
+/// 取值为 URL 的属性，需检查协议
+const URL_ATTRS: &[&str] = &["href", "src", "srcset"];
+
+/// 不允许的 URL 协议
+const UNSAFE_SCHEMES: &[&str] = &["javascript:", "vbscript:", "data:text/html"];
+
+/// 连同内容一起移除的标签
+const REMOVE_WITH_CONTENT: &[&str] = &["script", "noscript", "template", "iframe", "object"];
+
+/// HTML 净化结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizedHtml {
+    pub html: String,
+    /// 被移除的标签与属性（如 script、onclick、href:javascript），为空表示创意无需净化
+    pub removed: Vec<String>,
+}
+
+/// 是否为需要净化的 HTML 创意（VAST 与 native JSON 不处理）
+pub fn is_html_creative(adm: &str) -> bool {
+    adm.trim_start().starts_with('<') && !adm.contains("<VAST")
+}
+
+/// 按标签 / 属性白名单净化 HTML 创意：移除白名单外的标签（script 等连同内容）、事件处理属性、
+/// javascript: 等不安全协议的 URL 以及注释
+pub fn sanitize_html(html: &str) -> SanitizedHtml {
+    let mut out = String::with_capacity(html.len());
+    let mut removed = Vec::new();
+    let mut rest = html;
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            removed.push("comment".to_string());
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with("<!") {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            if rest[..end].to_ascii_lowercase().starts_with("<!doctype") {
+                out.push_str(&rest[..end]);
+            } else {
+                removed.push("declaration".to_string());
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let Some((tag, after)) = parse_tag(rest) else {
+            // 不是标签（如文本中的 "<"），转义后输出
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = after;
+        if !ALLOWED_TAGS.contains(&tag.name.as_str()) {
+            if !tag.closing && REMOVE_WITH_CONTENT.contains(&tag.name.as_str()) {
+                rest = skip_past_closing_tag(rest, &tag.name);
+            }
+            removed.push(tag.name);
+            continue;
+        }
+        out.push('<');
+        if tag.closing {
+            out.push('/');
+        }
+        out.push_str(&tag.name);
+        for (name, value) in tag.attrs {
+            if !ALLOWED_ATTRS.contains(&name.as_str()) {
+                removed.push(name);
+                continue;
+            }
+            match value {
+                Some(value) if URL_ATTRS.contains(&name.as_str()) && has_unsafe_scheme(&value) => {
+                    removed.push(format!("{}:{}", name, unsafe_scheme_name(&value)));
+                }
+                Some(value) => {
+                    out.push_str(&format!(" {}=\"{}\"", name, value.replace('"', "&quot;")));
+                }
+                None => {
+                    out.push(' ');
+                    out.push_str(&name);
+                }
+            }
+        }
+        if tag.self_closing {
+            out.push_str(" /");
+        }
+        out.push('>');
+    }
+    out.push_str(rest);
+    SanitizedHtml { html: out, removed }
+}
+
+struct Tag {
+    /// 小写标签名
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    /// (小写属性名, 原始属性值)
+    attrs: Vec<(String, Option<String>)>,
+}
+
+/// 解析以 '<' 开头的标签，返回标签及其后的剩余内容；不是合法标签时返回 None
+fn parse_tag(input: &str) -> Option<(Tag, &str)> {
+    let mut s = &input[1..];
+    let closing = s.starts_with('/');
+    if closing {
+        s = &s[1..];
+    }
+    if !s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let name_end = s.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(s.len());
+    let name = s[..name_end].to_ascii_lowercase();
+    s = &s[name_end..];
+    let mut attrs = Vec::new();
+    let mut self_closing = false;
+    loop {
+        s = s.trim_start();
+        if let Some(after) = s.strip_prefix('>') {
+            return Some((Tag { name, closing, self_closing, attrs }, after));
+        }
+        if let Some(after) = s.strip_prefix('/') {
+            self_closing = true;
+            s = after;
+            continue;
+        }
+        if s.is_empty() {
+            // 未闭合的标签：其后内容全部丢弃
+            return Some((Tag { name, closing, self_closing, attrs }, ""));
+        }
+        let attr_end = s.find(|c: char| c.is_ascii_whitespace() || matches!(c, '=' | '>' | '/')).unwrap_or(s.len());
+        let attr_name = s[..attr_end].to_ascii_lowercase();
+        s = s[attr_end..].trim_start();
+        let value = if let Some(after) = s.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(quote).unwrap_or(body.len());
+                    s = body.get(end + 1..).unwrap_or("");
+                    Some(body[..end].to_string())
+                }
+                _ => {
+                    let end = after.find(|c: char| c.is_ascii_whitespace() || c == '>').unwrap_or(after.len());
+                    s = &after[end..];
+                    Some(after[..end].to_string())
+                }
+            }
+        } else {
+            None
+        };
+        if !attr_name.is_empty() {
+            attrs.push((attr_name, value));
+        }
+    }
+}
+
+/// 跳过直到对应结束标签（含）为止的内容，没有结束标签时丢弃其后全部内容
+fn skip_past_closing_tag<'a>(input: &'a str, name: &str) -> &'a str {
+    let closing = format!("</{}", name);
+    input.to_ascii_lowercase()
+        .find(&closing)
+        .and_then(|start| input[start..].find('>').map(|end| &input[start + end + 1..]))
+        .unwrap_or("")
+}
+
+/// 解码数字字符引用并去除空白与控制字符后的小写 URL，用于识别 "jav&#x09;ascript:" 等变形
+fn normalize_url(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find("&#") {
+        decoded.push_str(&rest[..amp]);
+        let entity = &rest[amp + 2..];
+        let (digits, radix) = match entity.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (entity, 10),
+        };
+        let len = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+        match u32::from_str_radix(&digits[..len], radix).ok().and_then(char::from_u32) {
+            Some(c) => {
+                decoded.push(c);
+                let consumed = entity.len() - digits.len() + len;
+                rest = &entity[consumed..];
+                rest = rest.strip_prefix(';').unwrap_or(rest);
+            }
+            None => {
+                decoded.push_str("&#");
+                rest = entity;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+fn has_unsafe_scheme(value: &str) -> bool {
+    let url = normalize_url(value);
+    UNSAFE_SCHEMES.iter().any(|scheme| url.starts_with(scheme))
+}
+
+fn unsafe_scheme_name(value: &str) -> &'static str {
+    let url = normalize_url(value);
+    UNSAFE_SCHEMES.iter()
+        .find(|scheme| url.starts_with(*scheme))
+        .map_or("unsafe", |scheme| scheme.split(':').next().unwrap_or("unsafe"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_scripts_and_event_handlers_are_removed() {
+        let sanitized = sanitize_html(r#"<div onclick="steal()"><script>alert(document.cookie)</script><a href="javascript:alert(1)">x</a><img src="https://cdn.example.com/a.png"></div>"#);
+        assert!(!sanitized.html.contains("script") && !sanitized.html.contains("alert"), "{}", sanitized.html);
+        assert!(!sanitized.html.contains("onclick"), "{}", sanitized.html);
+        assert!(sanitized.html.contains(r#"src="https://cdn.example.com/a.png""#), "{}", sanitized.html);
+        assert!(sanitized.removed.contains(&"script".to_string()));
+        assert!(sanitized.removed.iter().any(|removed| removed.contains("onclick")));
+    }
+
+    #[test]
+    fn safe_html_passes_through_and_vast_is_not_html() {
+        let html = r#"<html><body><a href="https://brand.example.com" target="_blank"><img src="https://cdn.example.com/a.png" width="300"></a></body></html>"#;
+        assert!(sanitize_html(html).removed.is_empty());
+        assert!(is_html_creative(html));
+        assert!(!is_html_creative(r#"<VAST version="3.0"></VAST>"#));
+        assert!(!is_html_creative(r#"{"native": {}}"#));
+    }
+}
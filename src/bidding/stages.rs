@@ -0,0 +1,1194 @@
+// src/bidding/stages.rs
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use futures::future::{join_all, BoxFuture};
+use serde_json::{json, Value};
+
+use crate::bidding::creative::{
+    check_blocked_categories, expanded_adm_len_upper_bound, is_secure_impression, missing_required_fields, CategoryCheck, respects_adomain_allowlist, respects_api_frameworks, respects_clickbrowser, respects_banner_size, respects_companions, respects_interstitial, respects_exp, sensitive_keyword_hits, substitute_macros, unsubstituted_macros, AUCTION_PRICE_MACRO,
+};
+use crate::bidding::currency::{FxTable, DEFAULT_CURRENCY};
+use crate::bidding::dsp_client::BidFetcher;
+use crate::bidding::events::AuctionEvent;
+use crate::bidding::floor::is_below_floor;
+use crate::bidding::notice::{render_notice_url, Notice, NoticeKind, NoticeQueue, AUCTION_LOSS_MACRO, LOSS_LOST_TO_HIGHER_BID, MAX_NOTICE_URL_BYTES};
+use crate::bidding::outcome::ImpNoBid;
+use crate::bidding::pipeline::{AuctionContext, AuctionStage, CandidateBid, StageFlow};
+use crate::bidding::pricing::{AuctionType, ClearedPrice, PricedBid, PricingContext};
+use crate::bidding::ranking::{compare_price, RANKING_CURRENCY};
+use crate::bidding::vast::validate_wrapper_chain;
+use crate::bidding::sanitize::{is_html_creative, sanitize_html};
+use crate::config::config_manager::{CreativeSanitization, IntraDspDuplicates, ResponseIdPolicy, SensitiveAction, TrackingConfig};
+use crate::openrtb::request::{BidRequest, Deal, ImpDetail};
+use crate::openrtb::response::{Bid, NoBidReason};
+
+/// 向各 DSP 询价
+pub struct FanOut<'f, F> {
+    pub fetcher: &'f F,
+}
+
+impl<F: BidFetcher> AuctionStage for FanOut<'_, F> {
+    fn name(&self) -> &'static str {
+        "fan_out"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let bid_request = Arc::new(auction.context.bid_request.clone());
+            auction.dsp_results = self.fetcher.fetch_bids(&bid_request).await;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 校验 DSP 响应（响应 id、影子 DSP、nbr、空 SeatBid），并将有效响应中 impid 合法的出价展开为候选出价
+pub struct ValidateResponses;
+
+impl AuctionStage for ValidateResponses {
+    fn name(&self) -> &'static str {
+        "validate"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let bid_request = &auction.context.bid_request;
+            let config = auction.config;
+            let runtime_logger = auction.runtime_logger;
+            let mut valid_responses = Vec::new();
+            let mut failed_dsp_logs = Vec::new();
+            // 参与竞价（非影子）的 DSP 数，只有一个时其 nbr 可以无歧义地转给 SSP
+            let bidding_dsps = auction.dsp_results.iter()
+                .filter(|result| !config.is_shadow_dsp(result.dsp_id))
+                .count();
+
+            for mut result in std::mem::take(&mut auction.dsp_results) {
+                if !result.outcome.is_skipped() {
+                    let elapsed_ms = u64::try_from(result.elapsed_ms).unwrap_or(u64::MAX);
+                    config.dsp_stats.record(result.dsp_id, result.outcome.is_success(), result.outcome.is_timeout(), elapsed_ms);
+                }
+                if result.outcome.is_timeout() {
+                    config.dsp_stats.record_timeout(result.dsp_id, result.outcome.as_str());
+                }
+                let status = result.outcome.as_str();
+                let detail = json!({
+                    "dsp_id": result.dsp_id,
+                    "url": result.dsp_url,
+                    "bid_price": result.price,
+                    "result": status,
+                    "inquiry_time_ms": result.elapsed_ms,
+                    "retries": result.retries,
+                    "failure_reason": if result.outcome.is_success() { Value::Null } else { json!(status) }
+                });
+                auction.dsp_details.push(detail);
+
+                // 响应 id 必须与转发的请求 id 一致，否则可能是 DSP 的缺陷或伪造的响应
+                if result.outcome.is_success() && result.bid_response.id != bid_request.id {
+                    let rejected = config.response_id_policy == ResponseIdPolicy::Strict;
+                    let log_entry = json!({
+                        "request_id": bid_request.id,
+                        "adx_log": "response_id_mismatch",
+                        "dsp_id": result.dsp_id,
+                        "response_id": result.bid_response.id,
+                        "rejected": rejected,
+                    });
+                    runtime_logger.log("WARN", &log_entry.to_string()).await;
+                    if rejected {
+                        failed_dsp_logs.push(json!({
+                            "dsp_id": result.dsp_id,
+                            "url": result.dsp_url,
+                            "reason": "response_id_mismatch",
+                            "result": status,
+                            "inquiry_time_ms": result.elapsed_ms,
+                        }).to_string());
+                        continue;
+                    }
+                }
+
+                // 影子 DSP 的响应只记录日志，永不参与竞价
+                if config.is_shadow_dsp(result.dsp_id) {
+                    let bids: Vec<Value> = result.bid_response.seatbid.iter()
+                        .flat_map(|seatbid| seatbid.bid.iter())
+                        .map(|bid| json!({ "bid_id": bid.id, "impid": bid.impid, "price": bid.price, "crid": bid.crid }))
+                        .collect();
+                    let log_entry = json!({
+                        "request_id": bid_request.id,
+                        "adx_log": "shadow_dsp_response",
+                        "dsp_id": result.dsp_id,
+                        "result": status,
+                        "inquiry_time_ms": result.elapsed_ms,
+                        "cur": result.bid_response.cur,
+                        "nbr": result.bid_response.nbr,
+                        "bids": bids,
+                    });
+                    runtime_logger.log("INFO", &log_entry.to_string()).await;
+                    continue;
+                }
+
+                if let Some(nbr) = result.bid_response.nbr {
+                    let reason = NoBidReason::from_code(nbr);
+                    config.dsp_stats.record_no_bid(result.dsp_id, reason);
+                    if bidding_dsps == 1 {
+                        auction.sole_dsp_no_bid = Some(reason);
+                    }
+                    failed_dsp_logs.push(json!({
+                        "dsp_id": result.dsp_id,
+                        "url": result.dsp_url,
+                        "nbr": nbr,
+                        "nbr_reason": reason.as_str(),
+                        "result": status,
+                        "inquiry_time_ms": result.elapsed_ms,
+                    }).to_string());
+                    continue;
+                }
+                // 空 SeatBid 视同未返回 SeatBid
+                if !result.bid_response.drop_empty_seatbids() {
+                    failed_dsp_logs.push(json!({
+                        "dsp_id": result.dsp_id,
+                        "url": result.dsp_url,
+                        "reason": "no_seatbid",
+                        "result": status,
+                        "inquiry_time_ms": result.elapsed_ms,
+                    }).to_string());
+                    continue;
+                }
+                for bid in result.bid_response.seatbid.iter().flat_map(|seatbid| seatbid.bid.iter()) {
+                    auction.received_impids.insert(bid.impid.clone());
+                    auction.events.publish(AuctionEvent::BidReceived {
+                        request_id: bid_request.id.clone(),
+                        dsp_id: result.dsp_id,
+                        bid_id: bid.id.clone(),
+                        impid: bid.impid.clone(),
+                        price: bid.price,
+                    });
+                }
+                valid_responses.push((result.dsp_id, result.bid_response, result.price));
+            }
+
+            if !failed_dsp_logs.is_empty() {
+                let log_entry = json!({
+                    "request_id": bid_request.id,
+                    "adx_log": "dsp_inquiry_failed",
+                    "details": failed_dsp_logs,
+                });
+                runtime_logger.log("ERROR", &log_entry.to_string()).await;
+            }
+
+            if valid_responses.is_empty() {
+                let log_entry = json!({
+                    "request_id": bid_request.id,
+                    "adx_log": "adx_inquiry_failed",
+                    "reason": "all_dsp_failed",
+                });
+                runtime_logger.log("ERROR", &log_entry.to_string()).await;
+                return StageFlow::Halt;
+            }
+
+            valid_responses.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap());
+            let request_impids: HashSet<&str> = bid_request.get_imp_details().iter()
+                .map(|imp| imp.id.as_str())
+                .collect();
+            for (dsp_id, response, _) in valid_responses {
+                let cur = response.cur.clone().unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+                for seatbid in response.seatbid {
+                    for bid in seatbid.bid {
+                        // 出价的 impid 必须对应请求中的展示位，避免向不存在的广告位注入创意
+                        if !request_impids.contains(bid.impid.as_str()) {
+                            auction.rejections.reject(dsp_id, &bid, "unknown_impid", json!({
+                                "impid": bid.impid,
+                            })).await;
+                            continue;
+                        }
+                        let rank_price = auction.fx_table.convert(bid.price, &cur, RANKING_CURRENCY);
+                        auction.candidates.push(CandidateBid {
+                            bid,
+                            dsp_id,
+                            cur: cur.clone(),
+                            rank_price,
+                            seat: seatbid.seat.clone(),
+                            group: seatbid.group,
+                            final_price: None,
+                        });
+                    }
+                }
+            }
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 黑白名单过滤：屏蔽的创意 id、屏蔽的席位（bseat）、广告主域名白名单、屏蔽类目
+pub struct FilterBlocklists;
+
+impl AuctionStage for FilterBlocklists {
+    fn name(&self) -> &'static str {
+        "filter_blocklists"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let context = auction.context;
+            let config = auction.config;
+            // 广告主域名白名单：SSP 自身白名单优先，否则使用全局白名单
+            let ssp_adomain_allowlist: HashSet<String>;
+            let adomain_allowlist = if context.ssp.allowed_adomains.is_empty() {
+                &config.allowed_adomains
+            } else {
+                ssp_adomain_allowlist = context.ssp.allowed_adomains.iter().map(|domain| domain.to_ascii_lowercase()).collect();
+                &ssp_adomain_allowlist
+            };
+            let mut kept = Vec::new();
+            for candidate in std::mem::take(&mut auction.candidates) {
+                let (dsp_id, bid) = (candidate.dsp_id, &candidate.bid);
+                if let Some(crid) = bid.crid.as_deref().filter(|crid| config.is_crid_blocked(crid)) {
+                    let extra = json!({ "crid": crid });
+                    auction.rejections.reject(dsp_id, bid, "blocked_crid", extra).await;
+                    continue;
+                }
+                // bseat 优先于 deal 的 wseat：被屏蔽的席位即使在 deal 允许的席位中也不能成交
+                if context.bid_request.is_seat_blocked(candidate.seat.as_deref()) {
+                    let deal = deal_for(&context.bid_request, bid);
+                    let reason = if deal.is_some() { "seat_blocked_on_deal" } else { "seat_blocked" };
+                    auction.rejections.reject(dsp_id, bid, reason, json!({
+                        "seat": candidate.seat,
+                        "bseat": context.bid_request.bseat,
+                        "dealid": deal.map(|deal| &deal.id),
+                        "wseat": deal.and_then(|deal| deal.wseat.as_ref()),
+                    })).await;
+                    continue;
+                }
+                if let Err(disallowed) = respects_adomain_allowlist(bid, adomain_allowlist) {
+                    auction.rejections.reject(dsp_id, bid, "adomain_not_allowed", json!({
+                        "adomain": bid.adomain,
+                        "disallowed": disallowed,
+                    })).await;
+                    continue;
+                }
+                match check_blocked_categories(&context.bid_request, bid) {
+                    CategoryCheck::Allowed => {}
+                    CategoryCheck::Blocked(categories) => {
+                        auction.rejections.reject(dsp_id, bid, "blocked_category", json!({
+                            "cat": categories,
+                        })).await;
+                        continue;
+                    }
+                    // 分类体系不同无法判断是否命中屏蔽类目，标记后放行
+                    CategoryCheck::TaxonomyMismatch { request_cattax, bid_cattax } => {
+                        auction.rejections.flag(dsp_id, bid, "cattax_mismatch", json!({
+                            "request_cattax": request_cattax,
+                            "bid_cattax": bid_cattax,
+                        })).await;
+                    }
+                }
+                kept.push(candidate);
+            }
+            auction.candidates = kept;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 敏感内容过滤：按配置拒绝或标记命中敏感关键词的出价
+pub struct FilterSensitive;
+
+impl AuctionStage for FilterSensitive {
+    fn name(&self) -> &'static str {
+        "filter_sensitive"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let config = auction.config;
+            let mut kept = Vec::new();
+            for mut candidate in std::mem::take(&mut auction.candidates) {
+                let sensitive_hits = sensitive_keyword_hits(&candidate.bid, &config.sensitive_filter.keywords);
+                if !sensitive_hits.is_empty() {
+                    match config.sensitive_filter.action {
+                        SensitiveAction::Reject => {
+                            auction.rejections.reject(candidate.dsp_id, &candidate.bid, "contains_sensitive_content", json!({
+                                "keywords": sensitive_hits,
+                            })).await;
+                            continue;
+                        }
+                        SensitiveAction::Flag => {
+                            let log_entry = json!({
+                                "request_id": auction.context.bid_request.id,
+                                "adx_log": "bid_flagged",
+                                "bid_id": candidate.bid.id,
+                                "reason": "contains_sensitive_content",
+                                "keywords": sensitive_hits,
+                            });
+                            auction.runtime_logger.log("WARN", &log_entry.to_string()).await;
+                            if auction.context.ssp.expose_adx_ext {
+                                annotate_adx_ext(&mut candidate.bid, json!({ "sensitive_keywords": sensitive_hits }));
+                            }
+                        }
+                    }
+                }
+                kept.push(candidate);
+            }
+            auction.candidates = kept;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// HTML 创意 XSS 净化：按配置移除白名单外的标签与属性，或拒绝该出价
+pub struct SanitizeCreatives;
+
+impl AuctionStage for SanitizeCreatives {
+    fn name(&self) -> &'static str {
+        "sanitize_creatives"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let mode = auction.config.creative_sanitization;
+            if mode == CreativeSanitization::Off {
+                return StageFlow::Continue;
+            }
+            let mut kept = Vec::new();
+            for mut candidate in std::mem::take(&mut auction.candidates) {
+                let Some(adm) = candidate.bid.adm.as_deref().filter(|adm| is_html_creative(adm)) else {
+                    kept.push(candidate);
+                    continue;
+                };
+                let sanitized = sanitize_html(adm);
+                if sanitized.removed.is_empty() {
+                    kept.push(candidate);
+                    continue;
+                }
+                let extra = json!({ "removed": sanitized.removed });
+                if mode == CreativeSanitization::Reject {
+                    auction.rejections.reject(candidate.dsp_id, &candidate.bid, "unsafe_creative", extra).await;
+                    continue;
+                }
+                auction.rejections.flag(candidate.dsp_id, &candidate.bid, "creative_sanitized", extra).await;
+                candidate.bid.adm = Some(sanitized.html);
+                kept.push(candidate);
+            }
+            auction.candidates = kept;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 资格校验：必填字段、DSP 最低出价、底价、创意大小、结算货币、dealid 有效性、deal 席位与创意兼容性，
+/// 折叠同一 DSP 对同一展示位的多个出价与不同 DSP 的重复创意，最后并发解析剩余出价的 VAST wrapper
+pub struct FilterEligibility;
+
+impl AuctionStage for FilterEligibility {
+    fn name(&self) -> &'static str {
+        "filter_eligibility"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let context = auction.context;
+            let config = auction.config;
+            let bid_request = &context.bid_request;
+            // 配置了最低出价的 DSP
+            let dsp_min_prices: HashMap<u64, f64> = config.active_demands().iter()
+                .filter_map(|demand| demand.min_bid_price.map(|min| (demand.id, min)))
+                .collect();
+            let imps: HashMap<&str, &ImpDetail> = bid_request.get_imp_details().iter()
+                .map(|imp| (imp.id.as_str(), imp))
+                .collect();
+            let mut kept = Vec::new();
+            for candidate in std::mem::take(&mut auction.candidates) {
+                let (dsp_id, bid, bid_currency) = (candidate.dsp_id, &candidate.bid, &candidate.cur);
+                let missing = missing_required_fields(bid, &context.ssp.required_bid_fields);
+                if !missing.is_empty() {
+                    auction.rejections.reject(dsp_id, bid, "missing_required_field", json!({ "missing": missing })).await;
+                    continue;
+                }
+                if let Some(&min_price) = dsp_min_prices.get(&dsp_id) {
+                    if bid.price < min_price {
+                        auction.rejections.reject(dsp_id, bid, "below_dsp_min", json!({
+                            "dsp_id": dsp_id,
+                            "price": bid.price,
+                            "min_bid_price": min_price,
+                        })).await;
+                        continue;
+                    }
+                }
+                if let Some((floor, floor_cur)) = auction.imp_floors.get(&bid.impid) {
+                    // 将底价换算为出价货币后再比较，无法换算时无法保证底价，拒绝该出价
+                    let reason = match auction.fx_table.convert(*floor, floor_cur, bid_currency) {
+                        Some(converted_floor) if is_below_floor(bid.price, converted_floor) => Some("below_floor"),
+                        Some(_) => None,
+                        None => Some("floor_currency_unconvertible"),
+                    };
+                    if let Some(reason) = reason {
+                        auction.rejections.reject(dsp_id, bid, reason, json!({
+                            "price": bid.price,
+                            "cur": bid_currency,
+                            "bidfloor": floor,
+                            "bidfloorcur": floor_cur,
+                        })).await;
+                        continue;
+                    }
+                }
+                if let Some(adm) = bid.adm.as_deref() {
+                    let expanded_len = expanded_adm_len_upper_bound(adm);
+                    if expanded_len > config.max_adm_bytes {
+                        auction.rejections.reject(dsp_id, bid, "adm_too_large", json!({
+                            "expanded_len": expanded_len,
+                            "max_adm_bytes": config.max_adm_bytes,
+                        })).await;
+                        continue;
+                    }
+                }
+                if let Some(ssp_currency) = context.ssp.currency.as_deref() {
+                    // SSP 固定了结算货币时，无法换算的出价无法结算
+                    if auction.fx_table.convert(bid.price, bid_currency, ssp_currency).is_none() {
+                        auction.rejections.reject(dsp_id, bid, "response_currency_unconvertible", json!({
+                            "cur": bid_currency,
+                            "ssp_currency": ssp_currency,
+                        })).await;
+                        continue;
+                    }
+                }
+                if let Some(imp) = imps.get(bid.impid.as_str()) {
+                    if config.is_imp_ad_type_disabled(imp) {
+                        auction.rejections.reject(dsp_id, bid, "ad_type_disabled", json!({
+                            "ad_types": imp.ad_types(),
+                        })).await;
+                        continue;
+                    }
+                    let deal = deal_for(bid_request, bid);
+                    // 出价声明的 dealid 必须是该展示位 pmp.deals 中实际提供的 deal
+                    if deal.is_none() {
+                        if let Some(dealid) = bid.dealid.as_deref().filter(|dealid| !dealid.is_empty()) {
+                            auction.rejections.reject(dsp_id, bid, "unknown_dealid", json!({
+                                "dealid": dealid,
+                                "impid": bid.impid,
+                            })).await;
+                            continue;
+                        }
+                    }
+                    if let Some(deal) = deal {
+                        if !deal.allows_seat(candidate.seat.as_deref()) {
+                            auction.rejections.reject(dsp_id, bid, "deal_seat_not_allowed", json!({
+                                "dealid": deal.id,
+                                "seat": candidate.seat,
+                                "wseat": deal.wseat,
+                            })).await;
+                            continue;
+                        }
+                        if !deal.allows_adomains(bid.adomain.as_deref().unwrap_or_default()) {
+                            auction.rejections.reject(dsp_id, bid, "deal_adomain_violation", json!({
+                                "dealid": deal.id,
+                                "adomain": bid.adomain,
+                                "wadomain": deal.wadomain,
+                            })).await;
+                            continue;
+                        }
+                    }
+                    if !respects_exp(imp, bid) {
+                        auction.rejections.reject(dsp_id, bid, "creative_expiry_mismatch", json!({
+                            "imp_exp": imp.exp,
+                            "bid_exp": bid.exp,
+                        })).await;
+                        continue;
+                    }
+                    if !respects_banner_size(imp, bid) {
+                        let banner = imp.get_banner_detail();
+                        auction.rejections.reject(dsp_id, bid, "creative_size_mismatch", json!({
+                            "bid_w": bid.w,
+                            "bid_h": bid.h,
+                            "w": banner.and_then(|b| b.w),
+                            "h": banner.and_then(|b| b.h),
+                            "format": banner.and_then(|b| b.format.clone()),
+                            "wmin": banner.and_then(|b| b.wmin),
+                            "wmax": banner.and_then(|b| b.wmax),
+                            "hmin": banner.and_then(|b| b.hmin),
+                            "hmax": banner.and_then(|b| b.hmax),
+                        })).await;
+                        continue;
+                    }
+                    if !respects_interstitial(imp, bid) {
+                        auction.rejections.reject(dsp_id, bid, "interstitial_size_mismatch", json!({
+                            "bid_w": bid.w,
+                            "bid_h": bid.h,
+                        })).await;
+                        continue;
+                    }
+                    if !respects_companions(imp, bid, config.require_companions) {
+                        auction.rejections.reject(dsp_id, bid, "missing_companion", json!({
+                            "companiontype": imp.get_video_detail().and_then(|video| video.companiontype.clone()),
+                        })).await;
+                        continue;
+                    }
+                    // 兼容性校验：ADX 做最终决策时拒绝，上游做最终决策时仅标记
+                    if !respects_clickbrowser(imp, bid) {
+                        let extra = json!({ "clickbrowser": imp.clickbrowser });
+                        if auction.final_decision.is_strict() {
+                            auction.rejections.reject(dsp_id, bid, "clickbrowser_mismatch", extra).await;
+                            continue;
+                        }
+                        auction.rejections.flag(dsp_id, bid, "clickbrowser_mismatch", extra).await;
+                    }
+                    if let Err(unsupported) = respects_api_frameworks(imp, bid) {
+                        let extra = json!({ "unsupported_api": unsupported });
+                        if auction.final_decision.is_strict() {
+                            auction.rejections.reject(dsp_id, bid, "unsupported_api_framework", extra).await;
+                            continue;
+                        }
+                        auction.rejections.flag(dsp_id, bid, "unsupported_api_framework", extra).await;
+                    }
+                }
+                kept.push(candidate);
+            }
+            if config.intra_dsp_duplicates == IntraDspDuplicates::KeepHighest {
+                let (deduped, collapsed) = collapse_intra_dsp_duplicates(kept);
+                for candidate in collapsed {
+                    let bid = &candidate.bid;
+                    let log_entry = json!({
+                        "request_id": bid_request.id,
+                        "adx_log": "intra_dsp_dedup",
+                        "dsp_id": candidate.dsp_id,
+                        "bid_id": bid.id,
+                        "impid": bid.impid,
+                        "price": bid.price,
+                    });
+                    auction.runtime_logger.log("INFO", &log_entry.to_string()).await;
+                    auction.rejections.record(candidate.dsp_id, bid, "intra_dsp_dedup");
+                }
+                kept = deduped;
+            }
+            if config.dedup_creatives {
+                let (deduped, collapsed) = collapse_duplicate_creatives(kept);
+                for candidate in collapsed {
+                    let bid = &candidate.bid;
+                    let log_entry = json!({
+                        "request_id": bid_request.id,
+                        "adx_log": "duplicate_creative_collapsed",
+                        "bid_id": bid.id,
+                        "impid": bid.impid,
+                        "crid": bid.crid,
+                        "price": bid.price,
+                    });
+                    auction.runtime_logger.log("INFO", &log_entry.to_string()).await;
+                    auction.rejections.record(candidate.dsp_id, bid, "duplicate_creative_collapsed");
+                }
+                kept = deduped;
+            }
+            if config.vast_wrapper.enabled {
+                // wrapper 解析放在其余校验与折叠之后，只解析仍可能胜出的出价；各出价并发解析，
+                // 且不超过竞价剩余的时间预算（扣除 ADX 预留与序列化预留）
+                let reserve_ms = config.tmax_reserve_ms + context.ssp.serialization_reserve_ms.unwrap_or(0);
+                let deadline = context.auction_deadline(config.default_tmax_ms, reserve_ms);
+                let checks = kept.iter().map(|candidate| async move {
+                    match candidate.bid.adm.as_deref().filter(|adm| adm.contains("<VAST")) {
+                        Some(adm) => validate_wrapper_chain(adm, &config.vast_wrapper, deadline).await,
+                        None => Ok(()),
+                    }
+                });
+                let results = join_all(checks).await;
+                let mut resolved = Vec::with_capacity(kept.len());
+                for (candidate, result) in kept.into_iter().zip(results) {
+                    match result {
+                        Ok(()) => resolved.push(candidate),
+                        Err(error) => {
+                            auction.rejections.reject(candidate.dsp_id, &candidate.bid, "vast_wrapper_unresolvable", json!({
+                                "error": error,
+                            })).await;
+                        }
+                    }
+                }
+                kept = resolved;
+            }
+            auction.candidates = kept;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 定价：每个展示位独立竞价，按成交规则与定价策略计算成交价，并替换创意、burl 与 nurl 中的 {AUCTION_PRICE}
+pub struct Price;
+
+impl AuctionStage for Price {
+    fn name(&self) -> &'static str {
+        "price"
+    }
+
+    fn required(&self) -> bool {
+        true
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let context = auction.context;
+            let config = auction.config;
+            let bid_request = &context.bid_request;
+            if auction.candidates.is_empty() {
+                let log_entry = json!({
+                    "request_id": bid_request.id,
+                    "adx_log": "adx_inquiry_failed",
+                    "reason": "all_bids_filtered",
+                });
+                auction.runtime_logger.log("ERROR", &log_entry.to_string()).await;
+                return StageFlow::Halt;
+            }
+            // 按配置的排序规则（默认按出价从高到低）排序，排在最前的出价优先胜出；不同货币的出价换算为同一货币后比较
+            let comparator = config.bid_comparator();
+            auction.candidates.sort_by(|a, b| comparator.compare(a, b));
+            let fx_table = &auction.fx_table;
+            auction.response_cur = response_currency(
+                context.ssp.currency.as_deref(),
+                bid_request.cur.as_deref().unwrap_or_default(),
+                &auction.candidates[0].cur,
+                fx_table,
+            );
+            let response_cur = auction.response_cur.clone();
+            let auction_type = AuctionType::from_at(bid_request.at);
+            let dsp_placements = config.get_dsp_placements();
+            let shader = config.bid_shader();
+            let pricing_strategy = config.pricing_strategy();
+
+            // 每个展示位独立竞价：只在该展示位的候选出价之间比价、定价
+            for imp in bid_request.get_imp_details() {
+                if config.is_imp_ad_type_disabled(imp) {
+                    auction.imp_no_bids.push(ImpNoBid { impid: imp.id.clone(), reason: "ad_type_disabled".to_string() });
+                    continue;
+                }
+                let imp_bids: Vec<&CandidateBid> = auction.candidates.iter()
+                    .filter(|c| c.bid.impid == imp.id)
+                    .collect();
+                if imp_bids.is_empty() {
+                    let reason = if auction.received_impids.contains(&imp.id) { "all_bids_filtered" } else { "no_bids" };
+                    auction.imp_no_bids.push(ImpNoBid { impid: imp.id.clone(), reason: reason.to_string() });
+                    continue;
+                }
+                // 最高出价未通过成交后的校验时，按出价顺序回退到下一个出价，最多回退 fallback_depth 次
+                let max_attempts = imp_bids.len().min(config.fallback_depth + 1);
+                let mut last_failure = None;
+                for rank in 0..max_attempts {
+                    let winner = imp_bids[rank];
+                    if let Some(reason) = last_failure.take() {
+                        let log_entry = json!({
+                            "request_id": bid_request.id,
+                            "adx_log": "fallback_to_next_bid",
+                            "impid": imp.id,
+                            "reason": reason,
+                            "rank": rank,
+                            "bid_id": winner.bid.id,
+                            "dsp_id": winner.dsp_id,
+                        });
+                        auction.runtime_logger.log("WARN", &log_entry.to_string()).await;
+                    }
+                    let mut winning_bid = winner.bid.clone();
+                    let original_price = winning_bid.price;
+                    // 按结算货币返回胜出价格，{AUCTION_PRICE} 仍使用 DSP 出价货币
+                    let Some(settled_price) = fx_table.convert(original_price, &winner.cur, &response_cur) else {
+                        auction.rejections.reject(winner.dsp_id, &winning_bid, "response_currency_unconvertible", json!({
+                            "cur": winner.cur,
+                            "response_cur": response_cur,
+                        })).await;
+                        last_failure = Some("response_currency_unconvertible");
+                        continue;
+                    };
+
+                    // 按 BidRequest.at 选择成交规则，次高价与底价均换算为胜出出价的货币；
+                    // 已被淘汰的更高出价不参与定价
+                    let priced_bids: Vec<PricedBid> = imp_bids[rank..].iter()
+                        .filter_map(|c| {
+                            fx_table.convert(c.bid.price, &c.cur, &winner.cur)
+                                .map(|price| PricedBid { dsp_id: c.dsp_id, price })
+                        })
+                        .collect();
+                    // deal 出价按 deal 自身的 at 成交，deal 未指定时沿用请求级 at
+                    let deal = deal_for(bid_request, &winning_bid);
+                    let auction_type = deal.and_then(|deal| deal.at)
+                        .map_or(auction_type, |at| AuctionType::from_at(Some(at)));
+                    // deal 价格换算为胜出出价的货币
+                    let deal_price = deal.and_then(|deal| {
+                        let deal_cur = deal.bidfloorcur.as_deref().unwrap_or(DEFAULT_CURRENCY);
+                        deal.bidfloor.and_then(|price| fx_table.convert(price, deal_cur, &winner.cur))
+                    });
+                    let cleared = match (auction_type, deal, deal_price) {
+                        // at = 3 的固定价格 deal 按约定价格成交，不做 shading、二价与利润扣除
+                        (AuctionType::Fixed, Some(deal), Some(deal_price)) => {
+                            let log_entry = json!({
+                                "request_id": bid_request.id,
+                                "adx_log": "fixed_deal_clear",
+                                "impid": imp.id,
+                                "dealid": deal.id,
+                                "bid_id": winning_bid.id,
+                                "dsp_id": winner.dsp_id,
+                                "bid_price": original_price,
+                                "deal_price": deal_price,
+                                "cur": winner.cur,
+                            });
+                            auction.runtime_logger.log("INFO", &log_entry.to_string()).await;
+                            ClearedPrice::fixed(deal_price)
+                        }
+                        _ => {
+                            let pricing_context = PricingContext {
+                                auction_type,
+                                floor: auction.imp_floors.get(&imp.id)
+                                    .and_then(|(floor, floor_cur)| fx_table.convert(*floor, floor_cur, &winner.cur)),
+                                deal_price,
+                                dsp_placements: &dsp_placements,
+                                shader: shader.as_ref(),
+                                ssp_profit_rate: context.ssp.profit_rate,
+                                granularity: config.price_granularity,
+                            };
+                            // 由配置的定价策略计算成交价并扣除利润
+                            pricing_strategy.clear(&priced_bids, &pricing_context)
+                        }
+                    };
+                    let clear_price = cleared.clear_price;
+                    let final_price = cleared.final_price;
+                    // 返回给 DSP 的成交价（{AUCTION_PRICE}）按 DSP 的价格单位换算
+                    let price_unit = config.price_unit(winner.dsp_id);
+                    let dsp_final_price = price_unit.cpm_to_unit(final_price);
+
+                    // 替换 DSP 下发的 offer 中的 {AUCTION_PRICE} 占位符为 final_price；
+                    // 宏替换为单次扫描且有长度上限，超限的创意视为未通过校验
+                    if let Some(original_adm) = winning_bid.adm.as_ref() {
+                        let final_price_str = dsp_final_price.to_string();
+                        let Ok(dsp_adm_processed) = substitute_macros(
+                            original_adm,
+                            &[(AUCTION_PRICE_MACRO, final_price_str.as_str())],
+                            config.max_adm_bytes,
+                        ) else {
+                            auction.rejections.reject(winner.dsp_id, &winning_bid, "adm_too_large", json!({
+                                "max_adm_bytes": config.max_adm_bytes,
+                            })).await;
+                            last_failure = Some("adm_too_large");
+                            continue;
+                        };
+                        winning_bid.adm = Some(dsp_adm_processed);
+                    }
+                    // burl 由 SSP 触发，同样替换 {AUCTION_PRICE} 后原样返回
+                    if let Some(original_burl) = winning_bid.burl.as_ref() {
+                        let final_price_str = dsp_final_price.to_string();
+                        let Some(burl) = render_notice_url(original_burl, &[(AUCTION_PRICE_MACRO, final_price_str.as_str())]) else {
+                            auction.rejections.reject(winner.dsp_id, &winning_bid, "burl_too_large", json!({
+                                "max_notice_url_bytes": MAX_NOTICE_URL_BYTES,
+                            })).await;
+                            last_failure = Some("burl_too_large");
+                            continue;
+                        };
+                        winning_bid.burl = Some(burl);
+                    }
+                    // nurl 同样替换 {AUCTION_PRICE}，由 SSP 触发时无需 ADX 再次处理
+                    if let Some(original_nurl) = winning_bid.nurl.as_ref() {
+                        let final_price_str = dsp_final_price.to_string();
+                        let Some(nurl) = render_notice_url(original_nurl, &[(AUCTION_PRICE_MACRO, final_price_str.as_str())]) else {
+                            auction.rejections.reject(winner.dsp_id, &winning_bid, "nurl_too_large", json!({
+                                "max_notice_url_bytes": MAX_NOTICE_URL_BYTES,
+                            })).await;
+                            last_failure = Some("nurl_too_large");
+                            continue;
+                        };
+                        winning_bid.nurl = Some(nurl);
+                    }
+                    // 宏替换后 adm、nurl、burl 中仍有 ADX 宏（如 DSP 使用了未替换的宏）时记录 unsubstituted_macro，
+                    // 按配置不返回该出价并回退到下一个出价；lurl 只在落败时使用，不做检查
+                    let found: serde_json::Map<String, Value> = [
+                        ("adm", winning_bid.adm.as_deref()),
+                        ("nurl", winning_bid.nurl.as_deref()),
+                        ("burl", winning_bid.burl.as_deref()),
+                    ]
+                        .into_iter()
+                        .filter_map(|(field, text)| {
+                            let macros = unsubstituted_macros(text?);
+                            (!macros.is_empty()).then(|| (field.to_string(), json!(macros)))
+                        })
+                        .collect();
+                    if !found.is_empty() {
+                        let suppress = config.suppress_unsubstituted_macros;
+                        let log_entry = json!({
+                            "request_id": bid_request.id,
+                            "adx_log": "unsubstituted_macro",
+                            "dsp_id": winner.dsp_id,
+                            "bid_id": winning_bid.id,
+                            "impid": imp.id,
+                            "macros": found,
+                            "suppressed": suppress,
+                        });
+                        auction.runtime_logger.log("WARN", &log_entry.to_string()).await;
+                        if suppress {
+                            auction.rejections.reject(winner.dsp_id, &winning_bid, "unsubstituted_macro", json!({ "macros": found })).await;
+                            last_failure = Some("unsubstituted_macro");
+                            continue;
+                        }
+                    }
+                    winning_bid.price = settled_price;
+                    if context.ssp.expose_adx_ext {
+                        annotate_adx_ext(&mut winning_bid, json!({
+                            "dsp_id": winner.dsp_id,
+                            "pre_markdown_price": clear_price,
+                            "profit_rate": cleared.profit_rate,
+                        }));
+                    }
+                    // 价格透明：原始出价与扣利润前的成交价按结算货币返回
+                    if context.ssp.expose_clearing_price {
+                        annotate_adx_ext(&mut winning_bid, json!({
+                            "original_price": settled_price,
+                            "clearing_price": fx_table.convert(clear_price, &winner.cur, &response_cur),
+                        }));
+                    }
+                    // 多币种对账：成交价（扣利润前）按请求 cur 中的每种货币换算，无法换算的货币不返回
+                    if context.ssp.expose_currency_prices {
+                        let prices: serde_json::Map<String, Value> = bid_request.cur.iter().flatten()
+                            .filter_map(|cur| {
+                                fx_table.convert(clear_price, &winner.cur, cur).map(|price| (cur.clone(), json!(price)))
+                            })
+                            .collect();
+                        annotate_adx_ext(&mut winning_bid, json!({ "prices": prices }));
+                    }
+                    let price_info = json!({
+                        "impid": imp.id,
+                        "auction_type": auction_type.as_str(),
+                        "rank": rank,
+                        "original_price": original_price,
+                        "clear_price": clear_price,
+                        "profit_rate": cleared.profit_rate,
+                        "final_price": final_price,
+                        "price_unit": price_unit,
+                        "bid_cur": winner.cur,
+                        "settled_price": winning_bid.price,
+                        "settled_cur": response_cur,
+                    });
+                    auction.dsp_details.push(price_info);
+                    auction.events.publish(AuctionEvent::BidWon {
+                        request_id: bid_request.id.clone(),
+                        bid_id: winning_bid.id.clone(),
+                        impid: winning_bid.impid.clone(),
+                        original_price,
+                        final_price,
+                    });
+                    let winner = CandidateBid { bid: winning_bid, final_price: Some(dsp_final_price), ..winner.clone() };
+                    auction.winners.push(winner);
+                    break;
+                }
+                if let Some(reason) = last_failure {
+                    auction.imp_no_bids.push(ImpNoBid { impid: imp.id.clone(), reason: reason.to_string() });
+                }
+            }
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 响应货币：SSP 固定货币优先，否则取请求 cur 中第一个可由排在最前的出价货币换算得到的货币，
+/// 均无法换算时沿用该出价的货币
+fn response_currency(ssp_currency: Option<&str>, request_cur: &[String], top_cur: &str, fx_table: &FxTable) -> String {
+    if let Some(ssp_currency) = ssp_currency {
+        return ssp_currency.to_string();
+    }
+    request_cur.iter()
+        .find(|cur| fx_table.convert(1.0, top_cur, cur).is_some())
+        .cloned()
+        .unwrap_or_else(|| top_cur.to_string())
+}
+
+/// 在胜出创意中追加 ADX 注入的 SSP tracking（tracking URL 保留 {AUCTION_PRICE} 占位符），
+/// SSP 关闭 inject_tracking 时跳过
+pub struct InjectTracking;
+
+impl AuctionStage for InjectTracking {
+    fn name(&self) -> &'static str {
+        "inject_tracking"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let context = auction.context;
+            if !context.ssp.inject_tracking {
+                return StageFlow::Continue;
+            }
+            let bid_request = &context.bid_request;
+            let tracking = context.ssp.tracking.as_ref().unwrap_or(&auction.config.tracking);
+            for winner in &mut auction.winners {
+                let Some(adm) = winner.bid.adm.as_mut() else {
+                    continue;
+                };
+                let secure = bid_request.get_imp_details().iter()
+                    .find(|imp| imp.id == winner.bid.impid)
+                    .is_some_and(|imp| is_secure_impression(imp, bid_request));
+                let ssp_tracking = generate_ssp_tracking(adm, tracking, secure);
+                adm.push_str(&ssp_tracking);
+            }
+            StageFlow::Continue
+        })
+    }
+}
+
+/// SSP 限制响应出价数时按成交价保留最高的出价，其余展示位视为未填充：
+/// 先按席位限制（每个席位最多 max_bids_per_seat 个），再限制整个响应（最多 max_response_bids 个）
+pub struct LimitResponseBids;
+
+impl AuctionStage for LimitResponseBids {
+    fn name(&self) -> &'static str {
+        "limit_response_bids"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let ssp = &auction.context.ssp;
+            if let Some(max_per_seat) = ssp.max_bids_per_seat {
+                let mut seat_counts: HashMap<(u64, Option<String>), usize> = HashMap::new();
+                let truncated = truncate_by_price(&mut auction.winners, |winner| {
+                    let count = seat_counts.entry((winner.dsp_id, winner.seat.clone())).or_default();
+                    *count += 1;
+                    *count <= max_per_seat
+                });
+                for candidate in truncated {
+                    auction.rejections.reject(candidate.dsp_id, &candidate.bid, "max_bids_per_seat", json!({
+                        "seat": candidate.seat,
+                        "max_bids_per_seat": max_per_seat,
+                    })).await;
+                    auction.imp_no_bids.push(ImpNoBid { impid: candidate.bid.impid.clone(), reason: "max_bids_per_seat".to_string() });
+                }
+            }
+            if let Some(max_bids) = ssp.max_response_bids {
+                let mut kept = 0;
+                let truncated = truncate_by_price(&mut auction.winners, |_| {
+                    kept += 1;
+                    kept <= max_bids
+                });
+                for candidate in truncated {
+                    auction.rejections.reject(candidate.dsp_id, &candidate.bid, "max_response_bids", json!({
+                        "max_response_bids": max_bids,
+                    })).await;
+                    auction.imp_no_bids.push(ImpNoBid { impid: candidate.bid.impid.clone(), reason: "max_response_bids".to_string() });
+                }
+            }
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 按成交价从高到低依次询问 keep 是否保留每个胜出出价，保留的出价维持原有顺序，返回被截断的出价
+fn truncate_by_price(winners: &mut Vec<CandidateBid>, mut keep: impl FnMut(&CandidateBid) -> bool) -> Vec<CandidateBid> {
+    let mut by_price: Vec<usize> = (0..winners.len()).collect();
+    by_price.sort_by(|&a, &b| winners[b].bid.price.total_cmp(&winners[a].bid.price));
+    let kept: HashSet<usize> = by_price.into_iter().filter(|&i| keep(&winners[i])).collect();
+    if kept.len() == winners.len() {
+        return Vec::new();
+    }
+    let (kept, truncated): (Vec<_>, Vec<_>) = std::mem::take(winners).into_iter()
+        .enumerate()
+        .partition(|(i, _)| kept.contains(i));
+    *winners = kept.into_iter().map(|(_, winner)| winner).collect();
+    truncated.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// 由 ADX 发送 nurl / lurl 时，胜出出价的 nurl 与落败出价的 lurl 进入通知队列，
+/// 并从响应中移除，避免 SSP 重复触发
+pub struct Notify;
+
+impl AuctionStage for Notify {
+    fn name(&self) -> &'static str {
+        "notify"
+    }
+
+    fn run<'s>(&'s self, auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        Box::pin(async move {
+            let Some(queue) = &auction.config.notice_queue else {
+                return StageFlow::Continue;
+            };
+            let request_id = &auction.context.bid_request.id;
+            for winner in &mut auction.winners {
+                let final_price_str = winner.final_price.unwrap_or(winner.bid.price).to_string();
+                if let Some(url) = winner.bid.nurl.take()
+                    .and_then(|nurl| render_notice_url(&nurl, &[(AUCTION_PRICE_MACRO, final_price_str.as_str())]))
+                {
+                    queue.enqueue(Notice {
+                        kind: NoticeKind::Win,
+                        request_id: request_id.clone(),
+                        bid_id: winner.bid.id.clone(),
+                        dsp_id: winner.dsp_id,
+                        url,
+                    }).await;
+                }
+                winner.bid.lurl = None;
+            }
+            notify_losers(queue, request_id, &auction.candidates, &auction.winners).await;
+            StageFlow::Continue
+        })
+    }
+}
+
+/// 为已有胜出出价的展示位上落败的出价发送 lurl
+async fn notify_losers(queue: &Arc<NoticeQueue>, request_id: &str, candidates: &[CandidateBid], winners: &[CandidateBid]) {
+    let loss = LOSS_LOST_TO_HIGHER_BID.to_string();
+    for candidate in candidates {
+        let is_winner = winners.iter().any(|w| w.dsp_id == candidate.dsp_id && w.bid.id == candidate.bid.id);
+        let imp_filled = winners.iter().any(|w| w.bid.impid == candidate.bid.impid);
+        if is_winner || !imp_filled {
+            continue;
+        }
+        let Some(url) = candidate.bid.lurl.as_deref()
+            .and_then(|lurl| render_notice_url(lurl, &[(AUCTION_LOSS_MACRO, loss.as_str())])) else {
+            continue;
+        };
+        queue.enqueue(Notice {
+            kind: NoticeKind::Loss,
+            request_id: request_id.to_string(),
+            bid_id: candidate.bid.id.clone(),
+            dsp_id: candidate.dsp_id,
+            url,
+        }).await;
+    }
+}
+
+/// 辅助函数，根据 DSP 下发的 adm 内容生成 ADX 注入的 SSP tracking 部分（保留 {AUCTION_PRICE} 等宏），
+/// 安全展示位（secure）会将 tracking URL 升级为 https，避免混合内容
+fn generate_ssp_tracking(adm: &str, tracking: &TrackingConfig, secure: bool) -> String {
+    let to_scheme = |url: &str| match url.strip_prefix("http://") {
+        Some(rest) if secure => format!("https://{}", rest),
+        _ => url.to_string(),
+    };
+    if adm.contains("<html") {
+        format!("<img src=\"{}\" style=\"display:none;\" />", to_scheme(&tracking.html_impression_url))
+    } else if adm.contains("<VAST") {
+        format!("<Impression><![CDATA[{}]]></Impression>", to_scheme(&tracking.vast_impression_url))
+    } else {
+        "".to_string() // native 等其它类型不额外注入
+    }
+}
+
+/// 将 ADX 内部信息合并到 bid.ext.adx 中，保留 DSP 原有的 ext 字段；ext 不是对象时不做处理
+fn annotate_adx_ext(bid: &mut Bid, annotation: Value) {
+    let ext = bid.ext.get_or_insert_with(|| json!({}));
+    if let Some(ext) = ext.as_object_mut() {
+        let adx = ext.entry("adx").or_insert_with(|| json!({}));
+        if let (Some(adx), Value::Object(annotation)) = (adx.as_object_mut(), annotation) {
+            adx.extend(annotation);
+        }
+    }
+}
+
+/// 查找出价所属的 deal（该展示位 pmp.deals 中与 bid.dealid 匹配的 deal）
+fn deal_for<'a>(bid_request: &'a BidRequest, bid: &Bid) -> Option<&'a Deal> {
+    let dealid = bid.dealid.as_ref()?;
+    bid_request.get_imp_details().iter()
+        .find(|imp| imp.id == bid.impid)
+        .and_then(|imp| imp.get_pmp_detail())
+        .and_then(|pmp| pmp.deals.as_ref())
+        .and_then(|deals| deals.iter().find(|deal| &deal.id == dealid))
+}
+
+/// 同一 DSP 对同一展示位的多个出价只保留价格最高的一个，返回 (保留的出价, 被折叠的出价)
+fn collapse_intra_dsp_duplicates(bids: Vec<CandidateBid>) -> (Vec<CandidateBid>, Vec<CandidateBid>) {
+    let mut kept: Vec<CandidateBid> = Vec::new();
+    let mut collapsed = Vec::new();
+    let mut index: HashMap<(u64, String), usize> = HashMap::new();
+    for candidate in bids {
+        let key = (candidate.dsp_id, candidate.bid.impid.clone());
+        match index.get(&key) {
+            Some(&i) if kept[i].bid.price >= candidate.bid.price => collapsed.push(candidate),
+            Some(&i) => collapsed.push(std::mem::replace(&mut kept[i], candidate)),
+            None => {
+                index.insert(key, kept.len());
+                kept.push(candidate);
+            }
+        }
+    }
+    (kept, collapsed)
+}
+
+/// 同一展示位上的相同创意（优先按 crid 判断，无 crid 时按 adm 判断）只保留价格最高的出价，
+/// 不同 DSP 的出价货币可能不同，按换算为比价货币后的价格比较。返回 (保留的出价, 被折叠的出价)
+fn collapse_duplicate_creatives(bids: Vec<CandidateBid>) -> (Vec<CandidateBid>, Vec<CandidateBid>) {
+    let mut kept: Vec<CandidateBid> = Vec::new();
+    let mut collapsed = Vec::new();
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for candidate in bids {
+        let bid = &candidate.bid;
+        let creative_key = match (bid.crid.as_ref(), bid.adm.as_ref()) {
+            (Some(crid), _) => format!("crid:{}", crid),
+            (None, Some(adm)) => format!("adm:{}", adm),
+            (None, None) => {
+                kept.push(candidate);
+                continue;
+            }
+        };
+        let key = (bid.impid.clone(), creative_key);
+        match index.get(&key) {
+            Some(&i) if compare_price(&kept[i], &candidate) != Ordering::Greater => collapsed.push(candidate),
+            Some(&i) => collapsed.push(std::mem::replace(&mut kept[i], candidate)),
+            None => {
+                index.insert(key, kept.len());
+                kept.push(candidate);
+            }
+        }
+    }
+    (kept, collapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(dsp_id: u64, price: f64, cur: &str, crid: &str) -> CandidateBid {
+        let fx_table = FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap();
+        CandidateBid {
+            bid: serde_json::from_value(json!({"id": format!("b{}", dsp_id), "impid": "1", "price": price, "crid": crid})).unwrap(),
+            dsp_id,
+            cur: cur.to_string(),
+            rank_price: fx_table.convert(price, cur, RANKING_CURRENCY),
+            seat: None,
+            group: None,
+            final_price: None,
+        }
+    }
+
+    fn dsp_ids(candidates: &[CandidateBid]) -> Vec<u64> {
+        candidates.iter().map(|c| c.dsp_id).collect()
+    }
+
+    #[test]
+    fn response_currency_prefers_the_pinned_ssp_currency() {
+        let fx_table = FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap();
+        let request_cur = vec!["USD".to_string()];
+        assert_eq!(response_currency(Some("CNY"), &request_cur, "USD", &fx_table), "CNY");
+    }
+
+    #[test]
+    fn response_currency_follows_the_first_convertible_request_cur() {
+        let fx_table = FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap();
+        let request_cur = vec!["EUR".to_string(), "CNY".to_string(), "USD".to_string()];
+        assert_eq!(response_currency(None, &request_cur, "USD", &fx_table), "CNY");
+        // 请求 cur 都无法换算时沿用出价货币
+        assert_eq!(response_currency(None, &["EUR".to_string()], "USD", &fx_table), "USD");
+        assert_eq!(response_currency(None, &[], "USD", &fx_table), "USD");
+    }
+
+    #[test]
+    fn duplicate_creatives_keep_the_highest_price() {
+        let (kept, collapsed) = collapse_duplicate_creatives(vec![
+            candidate(1, 1.0, "USD", "cr-1"),
+            candidate(2, 2.0, "USD", "cr-1"),
+            candidate(3, 0.5, "USD", "cr-2"),
+        ]);
+        assert_eq!(dsp_ids(&kept), vec![2, 3]);
+        assert_eq!(dsp_ids(&collapsed), vec![1]);
+    }
+
+    #[test]
+    fn duplicate_creatives_compare_converted_prices() {
+        // 5 CNY ≈ 0.71 USD，低于 1 USD
+        let (kept, collapsed) = collapse_duplicate_creatives(vec![
+            candidate(1, 1.0, "USD", "cr-1"),
+            candidate(2, 5.0, "CNY", "cr-1"),
+        ]);
+        assert_eq!(dsp_ids(&kept), vec![1]);
+        assert_eq!(dsp_ids(&collapsed), vec![2]);
+    }
+}
@@ -0,0 +1,241 @@
+// src/bidding/stats.rs
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+use crate::openrtb::response::NoBidReason;
+
+/// 单个 DSP 滚动窗口内保留的最大调用记录数
+const MAX_SAMPLES_PER_DSP: usize = 10_000;
+
+/// 窗口内调用数达到该值才给出建议超时，样本过少时 p95 没有参考意义
+const MIN_CALLS_FOR_SUGGESTION: usize = 20;
+
+/// 建议超时的下限（毫秒），与 DSP 超时配置的最小值一致
+const MIN_SUGGESTED_TIMEOUT_MS: u64 = 100;
+
+/// 单个 DSP 的滚动窗口统计
+#[derive(Serialize, Debug, Clone)]
+pub struct DspStatsSnapshot {
+    pub dsp_id: u64,
+    pub calls: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+    /// 窗口内超时（connect_timeout / read_timeout）的调用数与占比
+    pub timeouts: usize,
+    pub timeout_rate: f64,
+    /// 窗口内调用耗时（含重试）的 p95（毫秒）
+    pub latency_p95_ms: u64,
+    /// 按观测到的 p95 建议的 DSP 超时（毫秒），样本不足时为 None
+    pub suggested_timeout_ms: Option<u64>,
+}
+
+/// 滚动窗口内的一次调用记录
+#[derive(Debug, Clone, Copy)]
+struct CallSample {
+    at: Instant,
+    success: bool,
+    timed_out: bool,
+    elapsed_ms: u64,
+}
+
+/// 按 DSP 统计滚动时间窗口内的调用成功率（收到可解析的响应即视为成功，包括无竞价）、超时率与耗时分布
+#[derive(Debug)]
+pub struct DspStats {
+    window: Duration,
+    calls: Mutex<HashMap<u64, VecDeque<CallSample>>>,
+    /// 各 DSP 按无竞价原因（nbr）累计的次数（自启动起）
+    no_bid_reasons: Mutex<HashMap<u64, HashMap<NoBidReason, u64>>>,
+    /// 各 DSP 按超时类型（connect_timeout / read_timeout）累计的次数（自启动起）
+    timeouts: Mutex<HashMap<u64, HashMap<&'static str, u64>>>,
+}
+
+impl Default for DspStats {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+impl DspStats {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            calls: Mutex::new(HashMap::new()),
+            no_bid_reasons: Mutex::new(HashMap::new()),
+            timeouts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一次 DSP 调用结果及其耗时（毫秒，含重试）
+    pub fn record(&self, dsp_id: u64, success: bool, timed_out: bool, elapsed_ms: u64) {
+        let now = Instant::now();
+        let mut calls = self.calls.lock().unwrap();
+        let samples = calls.entry(dsp_id).or_default();
+        samples.push_back(CallSample { at: now, success, timed_out, elapsed_ms });
+        if samples.len() > MAX_SAMPLES_PER_DSP {
+            samples.pop_front();
+        }
+        Self::evict(samples, now, self.window);
+    }
+
+    /// 记录一次 DSP 返回的无竞价原因
+    pub fn record_no_bid(&self, dsp_id: u64, reason: NoBidReason) {
+        let mut no_bid_reasons = self.no_bid_reasons.lock().unwrap();
+        *no_bid_reasons.entry(dsp_id).or_default().entry(reason).or_default() += 1;
+    }
+
+    /// 记录一次 DSP 调用超时，kind 为 connect_timeout / read_timeout
+    pub fn record_timeout(&self, dsp_id: u64, kind: &'static str) {
+        let mut timeouts = self.timeouts.lock().unwrap();
+        *timeouts.entry(dsp_id).or_default().entry(kind).or_default() += 1;
+    }
+
+    /// 各 DSP 按超时类型累计的次数，按 dsp_id、类型排序
+    pub fn timeouts(&self) -> BTreeMap<u64, BTreeMap<&'static str, u64>> {
+        self.timeouts.lock().unwrap().iter()
+            .map(|(&dsp_id, kinds)| (dsp_id, kinds.iter().map(|(&kind, &count)| (kind, count)).collect()))
+            .collect()
+    }
+
+    /// 各 DSP 按无竞价原因累计的次数，按 dsp_id、原因排序
+    pub fn no_bid_reasons(&self) -> BTreeMap<u64, BTreeMap<&'static str, u64>> {
+        self.no_bid_reasons.lock().unwrap().iter()
+            .map(|(&dsp_id, reasons)| {
+                (dsp_id, reasons.iter().map(|(reason, &count)| (reason.as_str(), count)).collect())
+            })
+            .collect()
+    }
+
+    /// 各 DSP 窗口内的统计，按 dsp_id 排序
+    pub fn snapshot(&self) -> Vec<DspStatsSnapshot> {
+        let now = Instant::now();
+        let mut calls = self.calls.lock().unwrap();
+        let mut snapshots: Vec<DspStatsSnapshot> = calls.iter_mut()
+            .filter_map(|(&dsp_id, samples)| {
+                Self::evict(samples, now, self.window);
+                if samples.is_empty() {
+                    return None;
+                }
+                let successes = samples.iter().filter(|sample| sample.success).count();
+                let timeouts = samples.iter().filter(|sample| sample.timed_out).count();
+                let mut latencies: Vec<u64> = samples.iter().map(|sample| sample.elapsed_ms).collect();
+                let latency_p95_ms = percentile(&mut latencies, 0.95);
+                Some(DspStatsSnapshot {
+                    dsp_id,
+                    calls: samples.len(),
+                    successes,
+                    success_rate: successes as f64 / samples.len() as f64,
+                    timeouts,
+                    timeout_rate: timeouts as f64 / samples.len() as f64,
+                    latency_p95_ms,
+                    suggested_timeout_ms: (samples.len() >= MIN_CALLS_FOR_SUGGESTION)
+                        .then(|| latency_p95_ms.max(MIN_SUGGESTED_TIMEOUT_MS)),
+                })
+            })
+            .collect();
+        snapshots.sort_by_key(|s| s.dsp_id);
+        snapshots
+    }
+
+    fn evict(samples: &mut VecDeque<CallSample>, now: Instant, window: Duration) {
+        while samples.front().is_some_and(|sample| now.duration_since(sample.at) > window) {
+            samples.pop_front();
+        }
+    }
+}
+
+/// 最近秩法计算分位数（q 取 0 ~ 1），values 为空时返回 0
+fn percentile(values: &mut [u64], q: f64) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    values.sort_unstable();
+    let rank = (q * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+/// 基于 DSP 成功率的健康状态
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+/// 健康状态阈值：整体成功率低于 degraded_below 为 degraded，低于 unhealthy_below 为 unhealthy；
+/// 窗口内调用数不足 min_calls 时样本不足，视为 healthy
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct HealthThresholds {
+    pub degraded_below: f64,
+    pub unhealthy_below: f64,
+    pub min_calls: usize,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self { degraded_below: 0.9, unhealthy_below: 0.5, min_calls: 20 }
+    }
+}
+
+impl HealthThresholds {
+    /// 根据各 DSP 的统计计算整体成功率与健康状态
+    pub fn evaluate(&self, snapshots: &[DspStatsSnapshot]) -> (HealthStatus, Option<f64>) {
+        let calls: usize = snapshots.iter().map(|s| s.calls).sum();
+        if calls == 0 || calls < self.min_calls {
+            return (HealthStatus::Healthy, None);
+        }
+        let successes: usize = snapshots.iter().map(|s| s.successes).sum();
+        let success_rate = successes as f64 / calls as f64;
+        let status = if success_rate < self.unhealthy_below {
+            HealthStatus::Unhealthy
+        } else if success_rate < self.degraded_below {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+        (status, Some(success_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_rate_and_p95_follow_the_recorded_latencies() {
+        let stats = DspStats::default();
+        // 100 次调用耗时 10 ~ 1000ms，最慢的 10 次为超时
+        for i in 1..=100u64 {
+            stats.record(1, i <= 90, i > 90, i * 10);
+        }
+        let snapshot = &stats.snapshot()[0];
+        assert_eq!((snapshot.calls, snapshot.timeouts), (100, 10));
+        assert_eq!(snapshot.timeout_rate, 0.1);
+        assert_eq!(snapshot.latency_p95_ms, 950);
+        assert_eq!(snapshot.suggested_timeout_ms, Some(950));
+    }
+
+    #[test]
+    fn timeout_suggestion_needs_enough_samples_and_respects_the_minimum() {
+        let stats = DspStats::default();
+        for _ in 0..MIN_CALLS_FOR_SUGGESTION - 1 {
+            stats.record(1, true, false, 30);
+        }
+        assert_eq!(stats.snapshot()[0].suggested_timeout_ms, None);
+        stats.record(1, true, false, 30);
+        let snapshot = &stats.snapshot()[0];
+        assert_eq!(snapshot.latency_p95_ms, 30);
+        assert_eq!(snapshot.suggested_timeout_ms, Some(MIN_SUGGESTED_TIMEOUT_MS));
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        assert_eq!(percentile(&mut [], 0.95), 0);
+        assert_eq!(percentile(&mut [5], 0.95), 5);
+        assert_eq!(percentile(&mut [40, 10, 30, 20], 0.5), 20);
+        assert_eq!(percentile(&mut [40, 10, 30, 20], 0.95), 40);
+    }
+}
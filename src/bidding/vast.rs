@@ -0,0 +1,289 @@
+// src/bidding/vast.rs
+
+use once_cell::sync::Lazy;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Url};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 跟随重定向的最大次数
+const MAX_WRAPPER_REDIRECTS: usize = 5;
+
+/// 拉取 VAST wrapper 指向的 VAST 使用的 HTTP 客户端：VASTAdTagURI 来自 DSP 的 adm，
+/// 只允许访问公网地址（域名解析与重定向同样校验），避免 DSP 借 ADX 探测内网服务
+static WRAPPER_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .gzip(true)
+        .dns_resolver(Arc::new(PublicOnlyResolver))
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_WRAPPER_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if is_internal_host(attempt.url()) {
+                attempt.error("redirect to an internal host")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+        .unwrap_or_else(|_| Client::new())
+});
+
+/// 允许访问内网地址的客户端，仅在显式开启 allow_private_hosts 时使用（本地联调）
+static UNRESTRICTED_WRAPPER_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .gzip(true)
+        .build()
+        .unwrap_or_else(|_| Client::new())
+});
+
+/// 过滤内网地址的域名解析，解析结果全部为内网地址时视为解析失败
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?
+                .filter(|addr| !is_internal_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} resolves only to internal addresses", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 回环、私有、链路本地、未指定等非公网地址
+fn is_internal_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_documentation()
+                // 100.64.0.0/10 运营商级 NAT
+                || (ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ipv4) => is_internal_ip(IpAddr::V4(ipv4)),
+            // fc00::/7 唯一本地地址，fe80::/10 链路本地地址
+            None => ip.is_loopback() || ip.is_unspecified()
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80,
+        },
+    }
+}
+
+/// URL 的主机是否为 localhost 或内网 IP（域名由 PublicOnlyResolver 在解析时校验）
+fn is_internal_host(url: &Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return true;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_internal_ip(ip),
+        Err(_) => {
+            let domain = host.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+    }
+}
+
+/// VAST wrapper 链解析配置，默认关闭（每层都需要一次额外的 HTTP 请求）
+#[derive(Debug, Clone, Copy)]
+pub struct VastWrapperConfig {
+    pub enabled: bool,
+    /// 最多解析的 wrapper 层数
+    pub max_depth: usize,
+    /// 每层拉取（含读取响应体）的超时，同时不超过竞价剩余的时间预算
+    pub timeout: Duration,
+    /// 每层响应体的大小上限（字节）
+    pub max_body_bytes: usize,
+    /// 是否允许访问回环与内网地址，仅用于本地联调
+    pub allow_private_hosts: bool,
+}
+
+impl Default for VastWrapperConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_depth: 1,
+            timeout: Duration::from_millis(100),
+            max_body_bytes: 256 * 1024,
+            allow_private_hosts: false,
+        }
+    }
+}
+
+/// 提取 VAST wrapper 中的 VASTAdTagURI，非 wrapper 时返回 None
+pub fn wrapper_ad_tag_uri(vast: &str) -> Option<String> {
+    if !vast.contains("<Wrapper") {
+        return None;
+    }
+    let start = vast.find("<VASTAdTagURI")?;
+    let rest = &vast[start..];
+    let content_start = rest.find('>')? + 1;
+    let content_end = rest.find("</VASTAdTagURI>")?;
+    let content = rest.get(content_start..content_end)?.trim();
+    let uri = content.strip_prefix("<![CDATA[")
+        .and_then(|c| c.strip_suffix("]]>"))
+        .unwrap_or(content)
+        .trim();
+    (!uri.is_empty()).then(|| uri.to_string())
+}
+
+/// 沿 wrapper 链最多解析 max_depth 层，校验每层指向的 VAST 可访问且为合法 VAST；
+/// 非 wrapper 的创意直接通过，超过层数后剩余的 wrapper 不再跟进。
+/// 每层的超时不超过 deadline（竞价剩余的时间预算），预算耗尽时视为无法解析
+pub async fn validate_wrapper_chain(adm: &str, config: &VastWrapperConfig, deadline: Instant) -> Result<(), String> {
+    let mut current = adm.to_string();
+    for _ in 0..config.max_depth {
+        let Some(uri) = wrapper_ad_tag_uri(&current) else {
+            return Ok(());
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!("auction budget exhausted before fetching {}", uri));
+        }
+        let url = Url::parse(&uri).map_err(|e| format!("invalid VASTAdTagURI {}: {}", uri, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("unsupported scheme in {}", uri));
+        }
+        let client = if config.allow_private_hosts {
+            &*UNRESTRICTED_WRAPPER_CLIENT
+        } else {
+            if is_internal_host(&url) {
+                return Err(format!("refusing to fetch internal host {}", uri));
+            }
+            &*WRAPPER_CLIENT
+        };
+        // 发送请求与读取响应体共用同一个超时
+        current = tokio::time::timeout(config.timeout.min(remaining), fetch_vast(client, url, config.max_body_bytes))
+            .await
+            .map_err(|_| format!("timeout fetching {}", uri))??;
+    }
+    Ok(())
+}
+
+/// 拉取一层 VAST，响应体超过 max_body_bytes 时中止读取
+async fn fetch_vast(client: &Client, url: Url, max_body_bytes: usize) -> Result<String, String> {
+    let uri = url.to_string();
+    let mut response = client.get(url).send()
+        .await
+        .map_err(|e| format!("failed to fetch {}: {}", uri, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{} returned status {}", uri, response.status()));
+    }
+    if response.content_length().is_some_and(|len| len > max_body_bytes as u64) {
+        return Err(format!("{} body exceeds {} bytes", uri, max_body_bytes));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| format!("failed to read {}: {}", uri, e))? {
+        if body.len() + chunk.len() > max_body_bytes {
+            return Err(format!("{} body exceeds {} bytes", uri, max_body_bytes));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = String::from_utf8_lossy(&body).into_owned();
+    if !body.contains("<VAST") {
+        return Err(format!("{} did not return a VAST document", uri));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+
+    const INLINE_VAST: &str = r#"<VAST version="3.0"><Ad><InLine><AdSystem>dsp</AdSystem></InLine></Ad></VAST>"#;
+
+    fn wrapper(uri: &str) -> String {
+        format!(r#"<VAST version="3.0"><Ad><Wrapper><VASTAdTagURI><![CDATA[{}]]></VASTAdTagURI></Wrapper></Ad></VAST>"#, uri)
+    }
+
+    /// 本地 VAST 服务：/inline 返回合法 VAST，/html 返回非 VAST 内容，/large 返回超大 VAST，
+    /// /slow 延迟 500ms 返回，其余路径 404
+    async fn vast_server() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = Router::new()
+            .route("/inline", get(|| async { INLINE_VAST }))
+            .route("/html", get(|| async { "<html></html>" }))
+            .route("/large", get(|| async { format!("<VAST version=\"3.0\">{}</VAST>", " ".repeat(64 * 1024)) }))
+            .route("/slow", get(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                INLINE_VAST
+            }))
+            .fallback(|| async { StatusCode::NOT_FOUND });
+        tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+        base
+    }
+
+    /// 开启解析，并允许访问本地测试服务
+    fn enabled() -> VastWrapperConfig {
+        VastWrapperConfig { enabled: true, allow_private_hosts: true, ..VastWrapperConfig::default() }
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + Duration::from_secs(1)
+    }
+
+    #[test]
+    fn ad_tag_uri_is_read_from_wrappers_only() {
+        assert_eq!(wrapper_ad_tag_uri(&wrapper("http://vast.example.com/a")).as_deref(), Some("http://vast.example.com/a"));
+        assert_eq!(wrapper_ad_tag_uri(INLINE_VAST), None);
+    }
+
+    #[tokio::test]
+    async fn valid_wrapper_resolves_and_broken_wrappers_are_rejected() {
+        let base = vast_server().await;
+        assert_eq!(validate_wrapper_chain(&wrapper(&format!("{}/inline", base)), &enabled(), deadline()).await, Ok(()));
+        assert_eq!(validate_wrapper_chain(INLINE_VAST, &enabled(), deadline()).await, Ok(()));
+        assert!(validate_wrapper_chain(&wrapper(&format!("{}/missing", base)), &enabled(), deadline()).await.is_err());
+        assert!(validate_wrapper_chain(&wrapper(&format!("{}/html", base)), &enabled(), deadline()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_rejected() {
+        let base = vast_server().await;
+        let config = VastWrapperConfig { max_body_bytes: 16 * 1024, ..enabled() };
+        let error = validate_wrapper_chain(&wrapper(&format!("{}/large", base)), &config, deadline()).await.unwrap_err();
+        assert!(error.contains("exceeds"), "{}", error);
+        assert_eq!(validate_wrapper_chain(&wrapper(&format!("{}/large", base)), &enabled(), deadline()).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn fetches_are_bounded_by_the_auction_deadline() {
+        let base = vast_server().await;
+        let config = VastWrapperConfig { timeout: Duration::from_secs(5), ..enabled() };
+        let started = Instant::now();
+        let slow = wrapper(&format!("{}/slow", base));
+        assert!(validate_wrapper_chain(&slow, &config, Instant::now() + Duration::from_millis(50)).await.is_err());
+        assert!(started.elapsed() < Duration::from_millis(300), "took {:?}", started.elapsed());
+        // 预算已耗尽时不再发起请求
+        let error = validate_wrapper_chain(&slow, &config, Instant::now()).await.unwrap_err();
+        assert!(error.contains("budget exhausted"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn internal_hosts_are_refused_by_default() {
+        let base = vast_server().await;
+        let config = VastWrapperConfig { enabled: true, ..VastWrapperConfig::default() };
+        for uri in [format!("{}/inline", base), "http://localhost/inline".to_string(), "http://169.254.169.254/latest".to_string(), "http://[::1]/inline".to_string(), "file:///etc/passwd".to_string()] {
+            assert!(validate_wrapper_chain(&wrapper(&uri), &config, deadline()).await.is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn internal_addresses_are_recognized() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1"] {
+            assert!(is_internal_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "203.0.114.1", "2001:4860:4860::8888"] {
+            assert!(!is_internal_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
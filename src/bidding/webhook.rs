@@ -0,0 +1,82 @@
+// src/bidding/webhook.rs
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use bytes::Bytes;
+use tokio::sync::Semaphore;
+
+use crate::bidding::outcome::AuctionOutcome;
+
+/// 单次 webhook 请求超时
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 同时发送中的 webhook 请求上限，超出时直接丢弃，避免外部系统变慢时积压
+const MAX_IN_FLIGHT: usize = 1_000;
+
+/// 竞价结果 webhook：每次竞价结束后将 AuctionOutcome 以 JSON POST 到配置的 URL，
+/// 后台发送不阻塞竞价，失败时最多重试 max_retries 次
+#[derive(Debug)]
+pub struct AuctionWebhook {
+    client: reqwest::Client,
+    url: String,
+    max_retries: u32,
+    slots: Arc<Semaphore>,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl AuctionWebhook {
+    pub fn new(url: &str, max_retries: u32) -> Arc<Self> {
+        Arc::new(Self {
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .expect("Failed to build webhook http client"),
+            url: url.to_string(),
+            max_retries,
+            slots: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        })
+    }
+
+    /// 在后台发送竞价结果（fire-and-forget），发送中的请求已达上限时丢弃并计为失败
+    pub fn send(self: &Arc<Self>, outcome: &AuctionOutcome) {
+        let Ok(body) = serde_json::to_vec(outcome).map(Bytes::from) else {
+            return;
+        };
+        let Ok(slot) = self.slots.clone().try_acquire_owned() else {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            webhook.deliver(body).await;
+            drop(slot);
+        });
+    }
+
+    async fn deliver(&self, body: Bytes) {
+        for _ in 0..=self.max_retries {
+            let result = self.client.post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(body.clone())
+                .send()
+                .await;
+            if matches!(result, Ok(ref resp) if resp.status().is_success()) {
+                self.delivered.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
@@ -1,34 +1,544 @@
 // src/config/config_manager.rs
 
-use crate::model::dsp::{Demand, DemandManager};
-use crate::model::placements::{SspPlacement, DspPlacement};
+use crate::api::extract::DEFAULT_CONTENT_TYPES;
+use crate::bidding::adapter::DspAdapter;
+use crate::bidding::capture::BodyCapture;
+use crate::bidding::currency::FxTable;
+use crate::bidding::host_limit::HostLimiter;
+use crate::bidding::retry::TimeoutJitter;
+use crate::bidding::dsp_client::{build_client, DEFAULT_CONNECT_TIMEOUT_MS, DEFAULT_TMAX_MS, DEFAULT_USER_AGENT};
+use crate::bidding::notice::NoticeQueue;
+use crate::bidding::ranking::{BidComparator, BidComparatorKind, WeightedScore};
+use crate::bidding::recent_auctions::RecentAuctions;
+use crate::bidding::vast::VastWrapperConfig;
+use crate::bidding::webhook::AuctionWebhook;
+use crate::bidding::stats::{DspStats, HealthThresholds};
+use crate::bidding::pricing::{BidShader, LinearShade, NoShading, PricingStrategy, PricingStrategyKind, DEFAULT_PRICE_GRANULARITY, DEFAULT_PROFIT_RATE};
+use crate::model::dsp::{Demand, DemandManager, PriceUnit};
+use crate::model::placements::{AdType, SspPlacement, DspPlacement};
+use crate::openrtb::request::ImpDetail;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// tracking URL 模板中允许出现的宏
+pub const TRACKING_MACROS: [&str; 5] = [
+    "{AUCTION_PRICE}",
+    "{AUCTION_ID}",
+    "{AUCTION_IMP_ID}",
+    "{AUCTION_BID_ID}",
+    "{AUCTION_CURRENCY}",
+];
+
+/// ADX 自身曝光追踪配置，URL 为模板，可携带 TRACKING_MACROS 中的宏（由 SSP 渲染时替换）
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrackingConfig {
+    /// HTML 创意中注入的 img 像素 URL
+    pub html_impression_url: String,
+    /// VAST 创意中注入的 Impression 节点 URL
+    pub vast_impression_url: String,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        Self {
+            html_impression_url: "http://tk.rust-adx.com/impression?price={AUCTION_PRICE}".to_string(),
+            vast_impression_url: "http://tk.rust-adx.com/impression?price={AUCTION_PRICE}".to_string(),
+        }
+    }
+}
+
+impl TrackingConfig {
+    /// 校验 URL 模板：必须是 http(s) 地址，且只能包含已知宏
+    pub fn validate(&self) -> Result<(), String> {
+        for url in [&self.html_impression_url, &self.vast_impression_url] {
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| format!("invalid tracking url {}: {}", url, e))?;
+            if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+                return Err(format!("tracking url {} must be an absolute http(s) url", url));
+            }
+            if url.contains(['"', '<', '>', ' ']) {
+                return Err(format!("tracking url {} contains characters unsafe for creative injection", url));
+            }
+            let mut rest = url.as_str();
+            while let Some(start) = rest.find('{') {
+                let end = rest[start..].find('}')
+                    .ok_or_else(|| format!("tracking url {} has an unterminated macro", url))?;
+                let tracking_macro = &rest[start..start + end + 1];
+                if !TRACKING_MACROS.contains(&tracking_macro) {
+                    return Err(format!("tracking url {} uses unknown macro {}", url, tracking_macro));
+                }
+                rest = &rest[start + end + 1..];
+            }
+        }
+        Ok(())
+    }
+}
+
+/// tmax 低于最小值时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MinTmaxAction {
+    /// 返回 400 tmax_too_low
+    #[default]
+    Reject,
+    /// 将 tmax 提升到最小值后继续竞价
+    Clamp,
+}
+
+impl std::str::FromStr for MinTmaxAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(MinTmaxAction::Reject),
+            "clamp" => Ok(MinTmaxAction::Clamp),
+            other => Err(format!("unknown min tmax action: {}", other)),
+        }
+    }
+}
+
+/// DSP 响应 id 与转发的请求 id 不一致时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseIdPolicy {
+    /// 记录日志后照常使用该响应
+    #[default]
+    Lenient,
+    /// 丢弃该 DSP 的整个响应
+    Strict,
+}
+
+impl std::str::FromStr for ResponseIdPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lenient" => Ok(ResponseIdPolicy::Lenient),
+            "strict" => Ok(ResponseIdPolicy::Strict),
+            other => Err(format!("unknown response id policy: {}", other)),
+        }
+    }
+}
+
+/// 同一 DSP 对同一展示位返回多个出价时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IntraDspDuplicates {
+    /// 每个 (DSP, impid) 只保留最高出价
+    #[default]
+    KeepHighest,
+    /// 全部保留参与竞价
+    KeepAll,
+}
+
+impl std::str::FromStr for IntraDspDuplicates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep_highest" => Ok(IntraDspDuplicates::KeepHighest),
+            "keep_all" => Ok(IntraDspDuplicates::KeepAll),
+            other => Err(format!("unknown intra dsp duplicates strategy: {}", other)),
+        }
+    }
+}
+
+/// 聚合日志（调用链日志）的详细程度
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogVerbosity {
+    /// 只记录竞价结果、胜出出价（不含 adm）与未填充原因，不记录各 DSP 询价明细与拒绝明细
+    Minimal,
+    /// 记录各 DSP 询价明细与拒绝明细，胜出出价不含 adm
+    Standard,
+    /// 记录全部内容
+    #[default]
+    Full,
+}
+
+impl std::str::FromStr for LogVerbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(LogVerbosity::Minimal),
+            "standard" => Ok(LogVerbosity::Standard),
+            "full" => Ok(LogVerbosity::Full),
+            other => Err(format!("unknown log verbosity: {}", other)),
+        }
+    }
+}
+
+/// HTML 创意的净化方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CreativeSanitization {
+    /// 不处理
+    #[default]
+    Off,
+    /// 移除白名单外的标签与属性后返回
+    Sanitize,
+    /// 拒绝含有白名单外标签或属性的创意
+    Reject,
+}
+
+impl std::str::FromStr for CreativeSanitization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(CreativeSanitization::Off),
+            "sanitize" => Ok(CreativeSanitization::Sanitize),
+            "reject" => Ok(CreativeSanitization::Reject),
+            other => Err(format!("unknown creative sanitization mode: {}", other)),
+        }
+    }
+}
+
+/// 影子 DSP：按比例抽样转发请求，响应只记录日志、永不参与竞价，用于评估新接入 DSP 的出价质量
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ShadowDspConfig {
+    pub dsp_id: u64,
+    /// 抽样比例（0 ~ 1）
+    pub sample_rate: f64,
+}
+
+impl ShadowDspConfig {
+    /// 本次请求是否转发给影子 DSP
+    pub fn should_sample(&self) -> bool {
+        rand::random::<f64>() < self.sample_rate
+    }
+}
+
+/// 命中敏感词时的处理方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveAction {
+    /// 直接拒绝该出价
+    #[default]
+    Reject,
+    /// 记录日志（并在允许时标注到 ext）后照常参与竞价，用于新词表的灰度上线
+    Flag,
+}
+
+impl std::str::FromStr for SensitiveAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(SensitiveAction::Reject),
+            "flag" => Ok(SensitiveAction::Flag),
+            _ => Err(format!("Invalid sensitive action: {}", s)),
+        }
+    }
+}
+
+/// 敏感内容过滤配置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SensitiveFilterConfig {
+    pub keywords: Vec<String>,
+    pub action: SensitiveAction,
+}
+
+impl Default for SensitiveFilterConfig {
+    fn default() -> Self {
+        Self {
+            keywords: vec!["forbidden".to_string(), "banned".to_string(), "restricted".to_string()],
+            action: SensitiveAction::Reject,
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ConfigManager {
-    pub demand_manager: DemandManager,
+    /// DSP 列表，可通过管理接口从配置适配器重新加载
+    #[serde(skip)]
+    pub demand_manager: Arc<RwLock<DemandManager>>,
     #[serde(skip)]
     pub ssp_placements: Arc<RwLock<Vec<SspPlacement>>>,
     #[serde(skip)]
     pub dsp_placements: Arc<RwLock<Vec<DspPlacement>>>,
+    /// 是否对不同 DSP 返回的相同创意去重（同一展示位仅保留最高价）
+    #[serde(default)]
+    pub dedup_creatives: bool,
+    /// 同一 DSP 对同一展示位返回多个出价时的处理方式
+    #[serde(default)]
+    pub intra_dsp_duplicates: IntraDspDuplicates,
+    /// 聚合日志的详细程度
+    #[serde(default)]
+    pub log_verbosity: LogVerbosity,
+    /// 汇率表，用于不同货币之间的底价、出价比较
+    #[serde(skip)]
+    pub fx_table: Arc<RwLock<FxTable>>,
+    /// ADX 注入创意的曝光追踪配置
+    #[serde(default)]
+    pub tracking: TrackingConfig,
+    /// 成交定价策略
+    #[serde(default)]
+    pub pricing_strategy: PricingStrategyKind,
+    /// 候选出价的排序规则（决定胜出出价）
+    #[serde(default)]
+    pub bid_comparator: BidComparatorKind,
+    /// weighted_score 排序规则的权重与 DSP 优先级
+    #[serde(default)]
+    pub weighted_score: WeightedScore,
+    /// 利润率（FlatMargin / SecondPrice 的固定利润率，PerDspRate 的默认利润率）
+    #[serde(default = "default_profit_rate")]
+    pub profit_rate: f64,
+    /// 二价成交价向上取整的出价粒度（出价货币），0 表示不取整
+    #[serde(default = "default_price_granularity")]
+    pub price_granularity: f64,
+    /// 是否拒绝 TTL 内重复出现的请求 id（默认仅记录日志）
+    #[serde(default)]
+    pub reject_duplicate_requests: bool,
+    /// 胜出出价的 adm / nurl / burl 中仍有未替换的 ADX 宏时，是否不返回该出价并回退到下一个出价（默认仅记录日志）
+    #[serde(default)]
+    pub suppress_unsubstituted_macros: bool,
+    /// 经过路由过滤（测试流量、影子抽样、媒体类型、插屏）后没有可询价的 DSP 时返回给 SSP 的 nbr，
+    /// None 时按默认无竞价处理
+    #[serde(default)]
+    pub no_eligible_dsp_nbr: Option<i32>,
+    /// 测试流量分流：开启后测试流量（test=1 或测试 SSP）只询价测试 DSP，并写入独立的测试日志
+    #[serde(default)]
+    pub route_test_traffic: bool,
+    /// 敏感内容过滤配置
+    #[serde(default)]
+    pub sensitive_filter: SensitiveFilterConfig,
+    /// 一价成交的线性 shading 比例，None 表示不做 shading
+    #[serde(default)]
+    pub bid_shade_factor: Option<f64>,
+    /// 宏替换后 adm 的最大字节数，超出的创意会被拒绝
+    #[serde(default = "default_max_adm_bytes")]
+    pub max_adm_bytes: usize,
+    /// 单个请求内所有 DSP 共享的重试次数预算，0 表示不重试
+    #[serde(default)]
+    pub retry_budget: u32,
+    /// 运行时屏蔽的创意 ID（crid），通过管理接口维护，下一次竞价即生效
+    #[serde(skip)]
+    pub blocked_crids: Arc<RwLock<HashSet<String>>>,
+    /// 运行时启用的广告类型，通过管理接口切换；声明的类型全部被关闭的展示位以 ad_type_disabled 无竞价
+    #[serde(skip)]
+    pub enabled_ad_types: Arc<RwLock<HashSet<AdType>>>,
+    /// 全局可信来源 IP 段（CIDR），为空时不限制；SSP 配置了自身白名单时以 SSP 为准
+    #[serde(default)]
+    pub trusted_ips: Vec<IpNet>,
+    /// 允许访问 /admin 管理接口的来源 IP 段（CIDR），为空时只允许本机回环地址
+    #[serde(default)]
+    pub admin_trusted_ips: Vec<IpNet>,
+    /// /openrtb 允许的请求 Content-Type，不在其中的请求返回 415
+    #[serde(default = "default_allowed_content_types")]
+    pub allowed_content_types: Vec<String>,
+    /// DSP 请求/响应报文采样抓取，None 表示关闭
+    #[serde(skip)]
+    pub body_capture: Option<Arc<BodyCapture>>,
+    /// 最近竞价结果的环形缓冲区，None 表示关闭
+    #[serde(skip)]
+    pub recent_auctions: Option<Arc<RecentAuctions>>,
+    /// 竞价结果 webhook，None 表示关闭
+    #[serde(skip)]
+    pub auction_webhook: Option<Arc<AuctionWebhook>>,
+    /// 各 DSP（按 DSP ID）使用的请求 / 响应格式适配器，未配置的 DSP 使用标准 OpenRTB JSON
+    #[serde(skip)]
+    pub dsp_adapters: HashMap<u64, Arc<dyn DspAdapter>>,
+    /// 胜出出价未通过成交后校验时，最多回退到后续出价的次数，0 表示不回退
+    #[serde(default = "default_fallback_depth")]
+    pub fallback_depth: usize,
+    /// ADX 自身处理预留的时间（毫秒），转发给 DSP 的 tmax 会扣除该值与已耗时
+    #[serde(default = "default_tmax_reserve_ms")]
+    pub tmax_reserve_ms: u64,
+    /// 请求未携带 tmax 时使用的默认 tmax（毫秒），用于 DSP 超时与 tmax 超时判断
+    #[serde(default = "default_tmax_ms")]
+    pub default_tmax_ms: u64,
+    /// 与 DSP 建立连接的超时（毫秒），超出时记为 connect_timeout，其余超时记为 read_timeout
+    #[serde(default = "default_dsp_connect_timeout_ms")]
+    pub dsp_connect_timeout_ms: u64,
+    /// 向 DSP 询价时携带的 User-Agent
+    #[serde(default = "default_dsp_user_agent")]
+    pub dsp_user_agent: String,
+    /// 每个 DSP 主机保留的最大空闲连接数，None 使用 reqwest 默认值
+    #[serde(default)]
+    pub dsp_pool_max_idle_per_host: Option<usize>,
+    /// 按 dsp_connect_timeout_ms 与 dsp_pool_max_idle_per_host 构建的 DSP HTTP Client，在所有竞价请求间共享
+    #[serde(skip)]
+    pub dsp_http_client: reqwest::Client,
+    /// 每个 DSP 主机同时进行中的询价数上限，None 表示不限制
+    #[serde(skip)]
+    pub host_limiter: Option<Arc<HostLimiter>>,
+    /// DSP 超时与重试等待的随机抖动，None 表示不加抖动
+    #[serde(skip)]
+    pub timeout_jitter: Option<Arc<TimeoutJitter>>,
+    /// 内容类目（site.cat / app.cat）底价，单位 USD，与展示位底价取较大值
+    #[serde(default)]
+    pub category_floors: HashMap<String, f64>,
+    /// DSP 调用成功率的滚动统计
+    #[serde(skip)]
+    pub dsp_stats: Arc<DspStats>,
+    /// /readyz 健康状态阈值
+    #[serde(skip)]
+    pub health_thresholds: HealthThresholds,
+    /// VAST wrapper 链解析校验，默认关闭
+    #[serde(skip)]
+    pub vast_wrapper: VastWrapperConfig,
+    /// 允许的最小 tmax（毫秒），None 表示不限制
+    #[serde(default)]
+    pub min_tmax_ms: Option<u64>,
+    /// tmax 低于最小值时的处理方式
+    #[serde(default)]
+    pub min_tmax_action: MinTmaxAction,
+    /// 展示位声明了 video.companionad 时，是否拒绝不含伴随广告的 VAST 创意（默认仅透传字段）
+    #[serde(default)]
+    pub require_companions: bool,
+    /// DSP 响应 id 与请求 id 不一致时的处理方式
+    #[serde(default)]
+    pub response_id_policy: ResponseIdPolicy,
+    /// 影子 DSP 配置，None 表示关闭
+    #[serde(default)]
+    pub shadow_dsp: Option<ShadowDspConfig>,
+    /// 全局广告主域名白名单（小写），为空时不限制；SSP 配置了自身白名单时以 SSP 为准
+    #[serde(default)]
+    pub allowed_adomains: HashSet<String>,
+    /// 关闭的竞价流水线阶段（仅限可选阶段，见 pipeline::OPTIONAL_STAGES）
+    #[serde(default)]
+    pub disabled_auction_stages: HashSet<String>,
+    /// HTML 创意的 XSS 净化方式
+    #[serde(default)]
+    pub creative_sanitization: CreativeSanitization,
+    /// 胜出 / 败出通知重试队列，开启后由 ADX 发送 nurl / lurl；None 表示不发送
+    #[serde(skip)]
+    pub notice_queue: Option<Arc<NoticeQueue>>,
+}
+
+fn default_tmax_reserve_ms() -> u64 {
+    20
+}
+
+fn default_tmax_ms() -> u64 {
+    DEFAULT_TMAX_MS
+}
+
+fn default_allowed_content_types() -> Vec<String> {
+    DEFAULT_CONTENT_TYPES.map(String::from).to_vec()
+}
+
+fn default_price_granularity() -> f64 {
+    DEFAULT_PRICE_GRANULARITY
+}
+
+fn default_dsp_connect_timeout_ms() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_MS
+}
+
+fn default_dsp_user_agent() -> String {
+    DEFAULT_USER_AGENT.to_string()
+}
+
+fn default_fallback_depth() -> usize {
+    1
+}
+
+fn default_max_adm_bytes() -> usize {
+    512 * 1024
+}
+
+fn default_profit_rate() -> f64 {
+    DEFAULT_PROFIT_RATE
 }
 
 impl ConfigManager {
     pub fn new(demand_manager: DemandManager) -> Self {
         Self {
-            demand_manager,
+            demand_manager: Arc::new(RwLock::new(demand_manager)),
             ssp_placements: Arc::new(RwLock::new(Vec::new())),
             dsp_placements: Arc::new(RwLock::new(Vec::new())),
+            dedup_creatives: false,
+            intra_dsp_duplicates: IntraDspDuplicates::KeepHighest,
+            log_verbosity: LogVerbosity::Full,
+            fx_table: Arc::new(RwLock::new(FxTable::default())),
+            tracking: TrackingConfig::default(),
+            pricing_strategy: PricingStrategyKind::default(),
+            bid_comparator: BidComparatorKind::default(),
+            weighted_score: WeightedScore::default(),
+            profit_rate: DEFAULT_PROFIT_RATE,
+            price_granularity: default_price_granularity(),
+            reject_duplicate_requests: false,
+            suppress_unsubstituted_macros: false,
+            route_test_traffic: false,
+            sensitive_filter: SensitiveFilterConfig::default(),
+            bid_shade_factor: None,
+            max_adm_bytes: default_max_adm_bytes(),
+            retry_budget: 0,
+            blocked_crids: Arc::new(RwLock::new(HashSet::new())),
+            enabled_ad_types: Arc::new(RwLock::new(AdType::ALL.into_iter().collect())),
+            trusted_ips: Vec::new(),
+            admin_trusted_ips: Vec::new(),
+            allowed_content_types: default_allowed_content_types(),
+            body_capture: None,
+            recent_auctions: None,
+            auction_webhook: None,
+            dsp_adapters: HashMap::new(),
+            fallback_depth: default_fallback_depth(),
+            tmax_reserve_ms: default_tmax_reserve_ms(),
+            default_tmax_ms: default_tmax_ms(),
+            dsp_connect_timeout_ms: default_dsp_connect_timeout_ms(),
+            dsp_user_agent: default_dsp_user_agent(),
+            dsp_pool_max_idle_per_host: None,
+            dsp_http_client: build_client(Duration::from_millis(default_dsp_connect_timeout_ms()), None),
+            host_limiter: None,
+            timeout_jitter: None,
+            no_eligible_dsp_nbr: None,
+            category_floors: HashMap::new(),
+            dsp_stats: Arc::new(DspStats::default()),
+            health_thresholds: HealthThresholds::default(),
+            vast_wrapper: VastWrapperConfig::default(),
+            min_tmax_ms: None,
+            min_tmax_action: MinTmaxAction::default(),
+            require_companions: false,
+            response_id_policy: ResponseIdPolicy::default(),
+            shadow_dsp: None,
+            allowed_adomains: HashSet::new(),
+            disabled_auction_stages: HashSet::new(),
+            creative_sanitization: CreativeSanitization::Off,
+            notice_queue: None,
         }
     }
 
     pub fn active_demands(&self) -> Vec<Demand> {
-        self.demand_manager.active_demands()
+        self.demand_manager.read().unwrap().active_demands()
+    }
+
+    /// DSP 名称，DSP 不存在时返回 None
+    pub fn demand_name(&self, dsp_id: u64) -> Option<String> {
+        self.demand_manager.read().unwrap().demands.get(&dsp_id).map(|demand| demand.name.clone())
+    }
+
+    /// DSP 出价的价格单位，DSP 不存在时按 CPM 处理
+    pub fn price_unit(&self, dsp_id: u64) -> PriceUnit {
+        self.demand_manager.read().unwrap().demands.get(&dsp_id).map(|demand| demand.price_unit).unwrap_or_default()
+    }
+
+    /// 判断 DSP 是否为影子 DSP
+    pub fn is_shadow_dsp(&self, dsp_id: u64) -> bool {
+        self.shadow_dsp.is_some_and(|shadow| shadow.dsp_id == dsp_id)
     }
 
     pub fn active_dsp_urls(&self) -> Vec<String> {
-        self.demand_manager.active_demands().iter().map(|d| d.url.clone()).collect()
+        self.active_demands().iter().map(|d| d.url.clone()).collect()
+    }
+
+    /// 用新的 DSP 列表整体替换当前列表，返回替换前后的 DSP 数量
+    pub fn replace_demands(&self, demands: Vec<Demand>) -> (usize, usize) {
+        let mut manager = DemandManager::new();
+        for demand in demands {
+            manager.add_demand(demand);
+        }
+        let after = manager.demands.len();
+        let mut lock = self.demand_manager.write().unwrap();
+        let before = lock.demands.len();
+        *lock = manager;
+        (before, after)
     }
 
     pub fn get_ssp_placements(&self) -> Vec<SspPlacement> {
@@ -39,6 +549,70 @@ impl ConfigManager {
         self.dsp_placements.read().unwrap().clone()
     }
 
+    pub fn pricing_strategy(&self) -> Box<dyn PricingStrategy> {
+        self.pricing_strategy.build(self.profit_rate)
+    }
+
+    pub fn bid_comparator(&self) -> Box<dyn BidComparator> {
+        self.bid_comparator.build(&self.weighted_score)
+    }
+
+    pub fn bid_shader(&self) -> Box<dyn BidShader> {
+        match self.bid_shade_factor {
+            Some(factor) => Box::new(LinearShade { factor }),
+            None => Box::new(NoShading),
+        }
+    }
+
+    pub fn get_fx_table(&self) -> FxTable {
+        self.fx_table.read().unwrap().clone()
+    }
+
+    /// 屏蔽创意，返回该 crid 之前是否未被屏蔽
+    pub fn block_crid(&self, crid: &str) -> bool {
+        self.blocked_crids.write().unwrap().insert(crid.to_string())
+    }
+
+    /// 解除创意屏蔽，返回该 crid 之前是否被屏蔽
+    pub fn unblock_crid(&self, crid: &str) -> bool {
+        self.blocked_crids.write().unwrap().remove(crid)
+    }
+
+    pub fn is_crid_blocked(&self, crid: &str) -> bool {
+        self.blocked_crids.read().unwrap().contains(crid)
+    }
+
+    /// 当前屏蔽的 crid 列表（排序后返回）
+    pub fn get_blocked_crids(&self) -> Vec<String> {
+        let mut crids: Vec<String> = self.blocked_crids.read().unwrap().iter().cloned().collect();
+        crids.sort();
+        crids
+    }
+
+    /// 启用或关闭广告类型，返回状态是否发生变化
+    pub fn set_ad_type_enabled(&self, ad_type: AdType, enabled: bool) -> bool {
+        let mut enabled_ad_types = self.enabled_ad_types.write().unwrap();
+        if enabled {
+            enabled_ad_types.insert(ad_type)
+        } else {
+            enabled_ad_types.remove(&ad_type)
+        }
+    }
+
+    /// 当前启用的广告类型（排序后返回）
+    pub fn get_enabled_ad_types(&self) -> Vec<AdType> {
+        let mut ad_types: Vec<AdType> = self.enabled_ad_types.read().unwrap().iter().copied().collect();
+        ad_types.sort();
+        ad_types
+    }
+
+    /// 展示位声明的广告类型是否全部被关闭（未声明任何类型的展示位不受影响）
+    pub fn is_imp_ad_type_disabled(&self, imp: &ImpDetail) -> bool {
+        let ad_types = imp.ad_types();
+        let enabled_ad_types = self.enabled_ad_types.read().unwrap();
+        !ad_types.is_empty() && ad_types.iter().all(|ad_type| !enabled_ad_types.contains(ad_type))
+    }
+
     pub fn update_placements(&self, ssp: Vec<SspPlacement>, dsp: Vec<DspPlacement>) {
         {
             let mut lock = self.ssp_placements.write().unwrap();
@@ -48,6 +622,37 @@ impl ConfigManager {
             let mut lock = self.dsp_placements.write().unwrap();
             *lock = dsp;
         }
-        println!("Placements configuration updated");
+        tracing::info!("placements configuration updated");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracking(url: &str) -> TrackingConfig {
+        TrackingConfig { html_impression_url: url.to_string(), vast_impression_url: url.to_string() }
+    }
+
+    #[test]
+    fn tracking_urls_accept_known_macros() {
+        assert!(TrackingConfig::default().validate().is_ok());
+        assert!(tracking("https://tk.example.com/imp?id={AUCTION_ID}&p={AUCTION_PRICE}").validate().is_ok());
+    }
+
+    #[test]
+    fn tracking_urls_reject_unknown_macros_and_unsafe_urls() {
+        assert!(tracking("https://tk.example.com/imp?p={PRICE}").validate().is_err());
+        assert!(tracking("https://tk.example.com/imp?p={AUCTION_PRICE").validate().is_err());
+        assert!(tracking("ftp://tk.example.com/imp").validate().is_err());
+        assert!(tracking("https://tk.example.com/imp\"><script>").validate().is_err());
+    }
+
+    #[test]
+    fn shadow_dsp_sampling_follows_the_sample_rate() {
+        let never = ShadowDspConfig { dsp_id: 1, sample_rate: 0.0 };
+        let always = ShadowDspConfig { dsp_id: 1, sample_rate: 1.0 };
+        assert!((0..100).all(|_| !never.should_sample()));
+        assert!((0..100).all(|_| always.should_sample()));
     }
 }
@@ -0,0 +1,48 @@
+// src/lib.rs
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+pub mod api;
+pub mod bidding;
+pub mod config;
+pub mod logging;
+pub mod model;
+pub mod openrtb;
+pub mod mock_dsp;
+
+#[cfg(test)]
+mod tests;
+
+use api::idempotency::IdempotencyCache;
+use bidding::events::EventBus;
+use bidding::rate_limit::SspRateLimiter;
+use bidding::request_ids::RecentRequestIds;
+use config::config_manager::ConfigManager;
+use logging::runtime_logger::RuntimeLogger;
+use model::adapters::ConfigAdapter;
+use model::ssp::Ssp;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub runtime_logger: Arc<RuntimeLogger>,
+    /// 测试流量的运行日志（开启测试流量分流时），与生产日志分开写入
+    pub test_logger: Option<Arc<RuntimeLogger>>,
+    pub event_bus: Arc<EventBus>,
+    pub recent_request_ids: Arc<RecentRequestIds>,
+    pub config: Arc<ConfigManager>,
+    pub ssp_info: Vec<Ssp>,
+    /// 关键配置缺失时以降级模式启动，所有竞价请求返回 503
+    pub degraded: bool,
+    /// 维护（摘流）模式：开启后 /readyz 返回 503、竞价请求一律返回无竞价，进行中的竞价照常完成
+    pub maintenance: Arc<AtomicBool>,
+    /// 并发竞价数上限，超出的请求直接返回 503（负载保护）
+    pub auction_slots: Arc<Semaphore>,
+    /// 按 SSP 的 qps（含突发额度）限速，None 表示不限速
+    pub rate_limiter: Option<Arc<SspRateLimiter>>,
+    /// 配置数据源，管理接口从中重新加载 DSP 列表
+    pub config_adapter: Arc<dyn ConfigAdapter>,
+    /// 按请求 id 缓存竞价结果的幂等缓存，None 表示关闭
+    pub idempotency_cache: Option<Arc<IdempotencyCache>>,
+}
@@ -27,7 +27,7 @@ pub struct DspBidLog {
 
 impl AdxLog {
     pub fn new(ssp_uuid: &str, request_id: &str) -> Self {
-        let tz = FixedOffset::east(8 * 3600);
+        let tz = FixedOffset::east_opt(8 * 3600).unwrap();
         Self {
             timestamp: tz.from_utc_datetime(&Utc::now().naive_utc()).to_rfc3339(),
             log_type: "adx_bid_request".to_string(),
@@ -73,7 +73,7 @@ pub fn write_adx_log(log_content: &str, log_file: &str) {
 
 /// 生成调用链日志，并写入 adx_log 文件
 pub fn log_adx_call_chain(aggregated_log: &serde_json::Value) {
-    let tz = FixedOffset::east(8 * 3600);
+    let tz = FixedOffset::east_opt(8 * 3600).unwrap();
     let timestamp = tz.from_utc_datetime(&Utc::now().naive_utc()).to_string();
     let log_entry = serde_json::json!({
         "timestamp": timestamp,
@@ -13,14 +13,13 @@ use chrono::Utc;
 
 pub struct LogManager {
     sender: Sender<String>,
-    log_file: Arc<RollingFileAppender>,
 }
 
 impl LogManager {
     pub fn new(log_dir: &str, buffer_size: usize, batch_size: usize, flush_interval: u64) -> Arc<Self> {
         let (sender, receiver) = mpsc::channel(buffer_size);
         let log_file = Arc::new(rolling::hourly(log_dir, "adx_log.json"));
-        let manager = Arc::new(Self { sender, log_file: log_file.clone() });
+        let manager = Arc::new(Self { sender });
         let manager_clone = manager.clone();
         tokio::spawn(async move {
             Self::background_log_writer(log_file, receiver, batch_size, flush_interval).await;
@@ -28,7 +27,7 @@ impl LogManager {
         manager_clone
     }
 
-    pub async fn log(&self, level: impl Into<String>, message: impl Into<String>) {
+    pub async fn log(&self, _level: impl Into<String>, message: impl Into<String>) {
         let log_entry = json!({
             "timestamp": Utc::now().to_rfc3339(),
             "message": message.into()
@@ -36,7 +35,7 @@ impl LogManager {
             .to_string();
 
         if let Err(e) = self.sender.send(log_entry).await {
-            eprintln!("Failed to send log message: {}", e);
+            tracing::error!(error = %e, "failed to send log message");
         }
     }
 
@@ -67,13 +66,13 @@ impl LogManager {
         }
     }
 
-    async fn write_logs_to_disk(file: Arc<RollingFileAppender>, buffer: &Vec<String>) {
+    async fn write_logs_to_disk(file: Arc<RollingFileAppender>, buffer: &[String]) {
         let content = buffer.join("\n") + "\n";
         let file_clone = Arc::clone(&file);
         task::spawn_blocking(move || {
             let mut writer = file_clone.make_writer();
             if let Err(e) = writer.write_all(content.as_bytes()) {
-                eprintln!("Failed to write logs to file: {}", e);
+                tracing::error!(error = %e, "failed to write logs to file");
             }
         })
             .await
@@ -81,7 +80,7 @@ impl LogManager {
     }
 
     pub async fn shutdown(&self) {
-        drop(&self.sender);
+        // 发送端随自身一同释放，这里仅等待后台任务完成最后一次刷盘
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
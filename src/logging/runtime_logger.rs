@@ -2,17 +2,28 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::io::Write;
-use std::time::{Duration as StdDuration};
 use tokio::sync::mpsc::{self, Sender, Receiver};
 use tokio::time::{self, Duration};
 use tokio::task;
 use tracing_appender::rolling;
-use tracing_appender::rolling::RollingFileAppender;
 use serde_json::json;
-use chrono::{FixedOffset, TimeZone, Utc};
-use tokio::fs;
+use chrono::{Duration as ChronoDuration, FixedOffset, NaiveDateTime, TimeZone, Utc};
 use tracing_subscriber::fmt::MakeWriter;
+use serde::Serialize;
+
+/// 写盘失败时每个日志级别最多保留等待重写的日志条数，超出时丢弃最旧的日志
+const MAX_RETAINED_LINES: usize = 10_000;
+
+/// 日志文件保留时长（小时）
+const RETENTION_HOURS: u64 = 72;
+
+/// 默认的日志保留宽限时间（秒），容忍写入时间与文件系统之间的时钟偏差
+pub const DEFAULT_RETENTION_GRACE_SECS: u64 = 3600;
+
+/// 按小时滚动的日志文件名后缀格式（UTC），如 runtime_info.json.2024-01-01-08
+const HOURLY_SUFFIX_FORMAT: &str = "%Y-%m-%d-%H";
 
 /// 单条日志消息
 pub struct LogEntry {
@@ -20,12 +31,32 @@ pub struct LogEntry {
     pub content: String,
 }
 
+/// 日志通道状态，用于监控写盘是否跟得上
+#[derive(Serialize, Debug, Clone)]
+pub struct LogChannelStats {
+    /// 通道中积压的日志条数
+    pub depth: usize,
+    pub capacity: usize,
+    pub high_watermark: usize,
+    /// 是否处于丢弃模式
+    pub drop_mode: bool,
+    /// 丢弃模式下累计丢弃的日志条数
+    pub dropped: u64,
+}
+
 /// 运行日志管理器（RuntimeLogger）
 /// 将运行时日志按日志级别分流到不同的日志文件中。
+///
+/// 写盘变慢导致通道积压达到高水位时切换为丢弃模式：日志以非阻塞方式发送，通道满则丢弃，
+/// 保证 log().await 不会阻塞竞价链路；积压回落到高水位的一半以下后恢复正常模式。
 pub struct RuntimeLogger {
     sender: Sender<LogEntry>,
-    // 存储每个日志级别对应的 RollingFileAppender
-    log_files: HashMap<String, Arc<RollingFileAppender>>,
+    capacity: usize,
+    high_watermark: AtomicUsize,
+    drop_mode: AtomicBool,
+    dropped: AtomicU64,
+    /// 清理过期日志时在保留时长之外额外容忍的秒数，与后台清理任务共享
+    retention_grace_secs: Arc<AtomicU64>,
 }
 
 impl RuntimeLogger {
@@ -44,7 +75,7 @@ impl RuntimeLogger {
         flush_interval: u64,
     ) -> Arc<Self> {
         let (sender, receiver) = mpsc::channel(buffer_size);
-        // 定义需要分文件存储的日志级别
+        // 定义需要分文件存储的日志级别，各级别的 RollingFileAppender 由后台写入任务持有
         let levels = vec!["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
         let mut log_files = HashMap::new();
         for level in &levels {
@@ -52,16 +83,24 @@ impl RuntimeLogger {
             let appender = rolling::hourly(log_dir, &file_name);
             log_files.insert(level.to_string(), Arc::new(appender));
         }
-        let logger = Arc::new(Self { sender, log_files: log_files.clone() });
+        let logger = Arc::new(Self {
+            sender,
+            capacity: buffer_size,
+            high_watermark: AtomicUsize::new(buffer_size * 8 / 10),
+            drop_mode: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+            retention_grace_secs: Arc::new(AtomicU64::new(DEFAULT_RETENTION_GRACE_SECS)),
+        });
         tokio::spawn(Self::background_log_writer(log_files, receiver, batch_size, flush_interval));
         // 启动后台任务定期清理日志文件
         {
             let log_dir = log_dir.to_string();
+            let retention_grace_secs = Arc::clone(&logger.retention_grace_secs);
             tokio::spawn(async move {
-                let retention_hours = 72;
                 let cleanup_interval = Duration::from_secs(3600); // 每小时扫描一次
                 loop {
-                    Self::cleanup_old_logs(&log_dir, retention_hours).await;
+                    let grace = Duration::from_secs(retention_grace_secs.load(Ordering::Relaxed));
+                    Self::cleanup_old_logs(&log_dir, Duration::from_secs(RETENTION_HOURS * 3600) + grace).await;
                     tokio::time::sleep(cleanup_interval).await;
                 }
             });
@@ -71,7 +110,7 @@ impl RuntimeLogger {
 
     /// 记录运行日志，接受两个参数：level 和 message
     pub async fn log(&self, level: &str, message: &str) {
-        let tz = FixedOffset::east(8 * 3600);
+        let tz = FixedOffset::east_opt(8 * 3600).unwrap();
         let timestamp = tz.from_utc_datetime(&Utc::now().naive_utc()).to_rfc3339();
         let log_entry = json!({
         "timestamp": timestamp,
@@ -85,44 +124,99 @@ impl RuntimeLogger {
             content: log_entry,
         };
 
+        if self.update_drop_mode() {
+            if self.sender.try_send(entry).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            return;
+        }
         if let Err(e) = self.sender.send(entry).await {
-            eprintln!("Failed to send runtime log message: {}", e);
+            tracing::error!(error = %e, "failed to send runtime log message");
         }
     }
 
-    /// 后台日志写入任务
-    async fn background_log_writer(
-        log_files: HashMap<String, Arc<RollingFileAppender>>,
+    /// 设置切换到丢弃模式的通道积压高水位（条数），超过通道容量时按容量处理
+    pub fn set_high_watermark(&self, high_watermark: usize) {
+        self.high_watermark.store(high_watermark.clamp(1, self.capacity), Ordering::Relaxed);
+    }
+
+    /// 设置清理过期日志时的宽限时间：文件年龄超过保留时长加宽限时间才会被删除
+    pub fn set_retention_grace(&self, grace: Duration) {
+        self.retention_grace_secs.store(grace.as_secs(), Ordering::Relaxed);
+    }
+
+    /// 当前通道状态
+    pub fn channel_stats(&self) -> LogChannelStats {
+        LogChannelStats {
+            depth: self.depth(),
+            capacity: self.capacity,
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            drop_mode: self.drop_mode.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// 根据通道积压更新并返回是否处于丢弃模式
+    fn update_drop_mode(&self) -> bool {
+        let depth = self.depth();
+        let high_watermark = self.high_watermark.load(Ordering::Relaxed);
+        let drop_mode = self.drop_mode.load(Ordering::Relaxed);
+        if !drop_mode && depth >= high_watermark {
+            if !self.drop_mode.swap(true, Ordering::Relaxed) {
+                tracing::warn!(depth, high_watermark, "runtime log channel above high watermark, switching to drop mode");
+            }
+            return true;
+        }
+        if drop_mode && depth < high_watermark / 2 {
+            if self.drop_mode.swap(false, Ordering::Relaxed) {
+                tracing::warn!(depth, dropped = self.dropped.load(Ordering::Relaxed), "runtime log channel drained, leaving drop mode");
+            }
+            return false;
+        }
+        drop_mode
+    }
+
+    /// 后台日志写入任务，log_files 为各日志级别的写入目标（生产环境为 RollingFileAppender）
+    async fn background_log_writer<W>(
+        log_files: HashMap<String, Arc<W>>,
         mut receiver: Receiver<LogEntry>,
         batch_size: usize,
         flush_interval: u64,
-    ) {
+    ) where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
         // 每个日志级别独立的缓冲区
         let mut buffers: HashMap<String, Vec<String>> = HashMap::new();
         for level in log_files.keys() {
             buffers.insert(level.clone(), Vec::new());
         }
         let mut interval = time::interval(Duration::from_millis(flush_interval));
+        // 写盘失败后不再按批量大小立即重写，等到下一次定时刷新再重试，避免每条日志都触发一次写盘
+        let mut write_failed = false;
         loop {
             tokio::select! {
                 Some(entry) = receiver.recv() => {
-                    buffers.entry(entry.level.clone()).or_default().push(entry.content);
-                    if let Some(buffer) = buffers.get(&entry.level) {
-                        if buffer.len() >= batch_size {
-                            if let Some(appender) = log_files.get(&entry.level) {
-                                Self::write_logs_to_disk(appender.clone(), buffer).await;
-                            }
-                            buffers.insert(entry.level.clone(), Vec::new());
+                    let buffer = buffers.entry(entry.level.clone()).or_default();
+                    buffer.push(entry.content);
+                    if buffer.len() >= batch_size && !write_failed {
+                        match log_files.get(&entry.level) {
+                            Some(appender) => write_failed = !Self::flush_buffer(appender.clone(), buffer).await,
+                            None => buffer.clear(),
                         }
                     }
                 },
                 _ = interval.tick() => {
+                    write_failed = false;
                     for (level, buffer) in buffers.iter_mut() {
                         if !buffer.is_empty() {
-                            if let Some(appender) = log_files.get(level) {
-                                Self::write_logs_to_disk(appender.clone(), buffer).await;
+                            match log_files.get(level) {
+                                Some(appender) => write_failed |= !Self::flush_buffer(appender.clone(), buffer).await,
+                                None => buffer.clear(),
                             }
-                            buffer.clear();
                         }
                     }
                 }
@@ -130,34 +224,69 @@ impl RuntimeLogger {
         }
     }
 
-    async fn write_logs_to_disk(file: Arc<RollingFileAppender>, buffer: &Vec<String>) {
+    /// 将缓冲区写盘，成功后清空并返回 true；失败时保留这批日志等待下次重写，
+    /// 积压超过 MAX_RETAINED_LINES 时丢弃最旧的日志
+    async fn flush_buffer<W>(file: Arc<W>, buffer: &mut Vec<String>) -> bool
+    where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
+        if Self::write_logs_to_disk(file, buffer).await {
+            buffer.clear();
+            return true;
+        }
+        if buffer.len() > MAX_RETAINED_LINES {
+            let excess = buffer.len() - MAX_RETAINED_LINES;
+            buffer.drain(..excess);
+            tracing::error!(dropped = excess, "dropped runtime log lines after repeated write failures");
+        }
+        false
+    }
+
+    /// 在阻塞线程中写盘；写入出错或写盘线程 panic（如磁盘写满）时记录 tracing 错误日志并返回 false，
+    /// 后台写入任务继续运行
+    async fn write_logs_to_disk<W>(file: Arc<W>, buffer: &[String]) -> bool
+    where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
         let content = buffer.join("\n") + "\n";
         let file_clone = Arc::clone(&file);
-        task::spawn_blocking(move || {
+        let result = task::spawn_blocking(move || {
             let mut writer = file_clone.make_writer();
-            if let Err(e) = writer.write_all(content.as_bytes()) {
-                eprintln!("Failed to write runtime logs: {}", e);
+            writer.write_all(content.as_bytes())
+        }).await;
+        match result {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                tracing::error!(error = %e, "failed to write runtime logs");
+                false
             }
-        })
-            .await
-            .unwrap();
+            Err(e) => {
+                tracing::error!(error = %e, "runtime log write task failed");
+                false
+            }
+        }
     }
 
-    async fn cleanup_old_logs(log_dir: &str, retention_hours: u64) {
-        use std::time::{SystemTime, Duration as StdDuration};
-        let retention_duration = StdDuration::from_secs(retention_hours * 3600);
+    /// 删除年龄超过 max_age（保留时长 + 宽限时间）的日志文件，当前小时正在写入的文件无论 mtime 如何都不删除
+    async fn cleanup_old_logs(log_dir: &str, max_age: Duration) {
+        use std::time::SystemTime;
         let now = SystemTime::now();
+        let current_suffix = format!(".{}", Utc::now().format(HOURLY_SUFFIX_FORMAT));
         match tokio::fs::read_dir(log_dir).await {
             Ok(mut dir) => {
                 while let Ok(Some(entry)) = dir.next_entry().await {
                     let path = entry.path();
+                    let file_name = entry.file_name().to_string_lossy().into_owned();
+                    if file_name.ends_with(&current_suffix) {
+                        continue;
+                    }
                     if let Ok(metadata) = entry.metadata().await {
                         if let Ok(modified) = metadata.modified() {
-                            if now.duration_since(modified).unwrap_or_default() > retention_duration {
+                            if Self::log_file_age(&file_name, modified, now) > max_age {
                                 if let Err(e) = tokio::fs::remove_file(&path).await {
-                                    eprintln!("Failed to delete old log file {:?}: {}", path, e);
+                                    tracing::warn!(path = %path.display(), error = %e, "failed to delete old log file");
                                 } else {
-                                    println!("Deleted old log file: {:?}", path);
+                                    tracing::info!(path = %path.display(), "deleted old log file");
                                 }
                             }
                         }
@@ -165,13 +294,171 @@ impl RuntimeLogger {
                 }
             },
             Err(e) => {
-                eprintln!("Failed to read log directory {}: {}", log_dir, e);
+                tracing::warn!(log_dir, error = %e, "failed to read log directory");
             }
         }
     }
 
+    /// 日志文件年龄：取 mtime 与文件名中的滚动小时（按该小时结束时刻计）两者中较小的年龄，
+    /// mtime 在未来时按 0 计，避免时钟偏差或时区混淆导致误删较新的文件
+    fn log_file_age(file_name: &str, modified: std::time::SystemTime, now: std::time::SystemTime) -> Duration {
+        let mtime_age = now.duration_since(modified).unwrap_or_default();
+        let name_age = file_name.rsplit_once('.')
+            .and_then(|(_, suffix)| NaiveDateTime::parse_from_str(&format!("{}:00", suffix), "%Y-%m-%d-%H:%M").ok())
+            .map(|hour_start| {
+                let hour_end = (hour_start + ChronoDuration::hours(1)).and_utc();
+                (chrono::DateTime::<Utc>::from(now) - hour_end).to_std().unwrap_or_default()
+            });
+        match name_age {
+            Some(name_age) => mtime_age.min(name_age),
+            None => mtime_age,
+        }
+    }
+
     pub async fn shutdown(&self) {
-        drop(&self.sender);
+        // 发送端随自身一同释放，这里仅等待后台任务完成最后一次刷盘
         tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 后台写入停滞（不消费通道）的日志管理器，模拟磁盘变慢
+    fn stalled_logger(capacity: usize) -> (RuntimeLogger, Receiver<LogEntry>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let logger = RuntimeLogger {
+            sender,
+            capacity,
+            high_watermark: AtomicUsize::new(capacity * 8 / 10),
+            drop_mode: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+            retention_grace_secs: Arc::new(AtomicU64::new(DEFAULT_RETENTION_GRACE_SECS)),
+        };
+        (logger, receiver)
+    }
+
+    #[tokio::test]
+    async fn slow_sink_switches_to_drop_mode_instead_of_blocking() {
+        let (logger, mut receiver) = stalled_logger(10);
+        let logging = async {
+            for i in 0..100 {
+                logger.log("INFO", &format!("line {}", i)).await;
+            }
+        };
+        time::timeout(Duration::from_millis(200), logging).await.expect("log() blocked on a full channel");
+        let stats = logger.channel_stats();
+        assert!(stats.drop_mode);
+        assert_eq!(stats.depth, 10);
+        assert_eq!(stats.dropped, 100 - 10);
+
+        // 积压回落到高水位的一半以下后恢复正常模式
+        while receiver.try_recv().is_ok() {}
+        logger.log("INFO", "drained").await;
+        let stats = logger.channel_stats();
+        assert!(!stats.drop_mode);
+        assert_eq!(stats.depth, 1);
+    }
+
+    #[tokio::test]
+    async fn retention_grace_protects_files_just_past_retention() {
+        let log_dir = std::env::temp_dir().join(format!("rust-adx-retention-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&log_dir);
+        std::fs::create_dir_all(&log_dir).unwrap();
+        let retention = Duration::from_secs(RETENTION_HOURS * 3600);
+        let just_past = std::time::SystemTime::now() - retention - Duration::from_secs(600);
+        let ancient = std::time::SystemTime::now() - retention * 2;
+        let current_hour = format!("runtime_info.json.{}", Utc::now().format(HOURLY_SUFFIX_FORMAT));
+        for (name, modified) in [("runtime_warn.json", just_past), (current_hour.as_str(), ancient)] {
+            std::fs::File::create(log_dir.join(name)).unwrap().set_modified(modified).unwrap();
+        }
+        let exists = |name: &str| log_dir.join(name).exists();
+
+        // 宽限时间内的文件保留；当前小时的文件无论 mtime 如何都保留
+        let dir = log_dir.to_str().unwrap();
+        RuntimeLogger::cleanup_old_logs(dir, retention + Duration::from_secs(DEFAULT_RETENTION_GRACE_SECS)).await;
+        assert!(exists("runtime_warn.json"));
+        assert!(exists(&current_hour));
+        // 没有宽限时间时超出保留时长的文件被删除
+        RuntimeLogger::cleanup_old_logs(dir, retention).await;
+        assert!(!exists("runtime_warn.json"));
+        assert!(exists(&current_hour));
+        let _ = std::fs::remove_dir_all(&log_dir);
+    }
+
+    #[test]
+    fn file_age_uses_the_younger_of_mtime_and_the_hour_in_its_name() {
+        let now = std::time::SystemTime::now();
+        let two_hours_ago = (Utc::now() - ChronoDuration::hours(2)).format(HOURLY_SUFFIX_FORMAT);
+        let stale_mtime = now - Duration::from_secs(500 * 3600);
+        let age = RuntimeLogger::log_file_age(&format!("runtime_info.json.{}", two_hours_ago), stale_mtime, now);
+        assert!(age <= Duration::from_secs(2 * 3600), "age {:?}", age);
+        // mtime 在未来时按 0 计
+        assert_eq!(RuntimeLogger::log_file_age("runtime_info.json", now + Duration::from_secs(3600), now), Duration::ZERO);
+    }
+
+    /// 前若干次写盘失败的写入目标：第一次写盘线程 panic，之后返回 IO 错误，失败次数用完后正常写入
+    struct FlakySink {
+        failures: AtomicUsize,
+        written: Arc<std::sync::Mutex<String>>,
+    }
+
+    struct FlakyWriter {
+        fail: bool,
+        written: Arc<std::sync::Mutex<String>>,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.fail {
+                return Err(std::io::Error::other("no space left on device"));
+            }
+            self.written.lock().unwrap().push_str(&String::from_utf8_lossy(buf));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for FlakySink {
+        type Writer = FlakyWriter;
+
+        fn make_writer(&'a self) -> FlakyWriter {
+            let remaining = self.failures.load(Ordering::Relaxed);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::Relaxed);
+            }
+            if remaining == 2 {
+                panic!("disk full");
+            }
+            FlakyWriter { fail: remaining > 0, written: Arc::clone(&self.written) }
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_writes_keep_the_writer_running_and_are_retried() {
+        let written = Arc::new(std::sync::Mutex::new(String::new()));
+        let sink = Arc::new(FlakySink { failures: AtomicUsize::new(2), written: Arc::clone(&written) });
+        let (sender, receiver) = mpsc::channel(16);
+        let writer = tokio::spawn(RuntimeLogger::background_log_writer(
+            HashMap::from([("INFO".to_string(), sink)]), receiver, 1, 20,
+        ));
+        for line in ["first", "second"] {
+            sender.send(LogEntry { level: "INFO".to_string(), content: line.to_string() }).await.unwrap();
+        }
+
+        let deadline = time::Instant::now() + Duration::from_secs(2);
+        while !written.lock().unwrap().contains("second") && time::Instant::now() < deadline {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        // 失败的批次在下一次定时刷新时重写，未丢失任何日志
+        assert_eq!(*written.lock().unwrap(), "first\nsecond\n");
+        sender.send(LogEntry { level: "INFO".to_string(), content: "third".to_string() }).await.unwrap();
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(written.lock().unwrap().ends_with("third\n"));
+        assert!(!writer.is_finished());
+    }
+}
@@ -1,38 +1,45 @@
 // src/main.rs
 
-use axum::{Router, routing::post, serve};
+use axum::{Router, routing::{get, post}, serve};
 use clap::Parser;
+use ipnet::IpNet;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
-use tracing::{info};
+use tokio::sync::Semaphore;
+use tracing::{error, info};
 use tracing_subscriber::{fmt, EnvFilter, Registry};
 use tracing_appender::rolling;
 use tokio::net::TcpListener;
-use std::fs;
 use tracing_subscriber::layer::SubscriberExt;
 
-mod api;
-mod bidding;
-mod config;
-mod logging;
-mod model;
-mod openrtb;
-mod mock_dsp;
-
-use api::handlers::handle_openrtb_request;
-use config::config_manager::ConfigManager;
-use logging::runtime_logger::RuntimeLogger;
-use model::adapters::FileConfigAdapter;
-use model::dsp::init as dsp_init;
-use model::ssp::Ssp;
-use crate::model::adapters::ConfigAdapter;
-
-#[derive(Clone)]
-pub struct AppState {
-    pub runtime_logger: Arc<RuntimeLogger>,
-    pub config: Arc<ConfigManager>,
-    pub ssp_info: Vec<Ssp>,
-}
+use rust_adx::bidding::capture::{BodyCapture, DEFAULT_REDACT_FIELDS};
+use rust_adx::bidding::ranking::WeightedScore;
+use rust_adx::bidding::recent_auctions::RecentAuctions;
+use rust_adx::bidding::webhook::AuctionWebhook;
+use rust_adx::api::extract::{validate_content_types, DEFAULT_CONTENT_TYPES};
+use rust_adx::bidding::dsp_client::{build_client, validate_default_tmax};
+use rust_adx::bidding::currency::{spawn_fx_refresher, FxSource};
+use rust_adx::bidding::events::{spawn_log_subscriber, EventBus};
+use rust_adx::bidding::notice::{NoticeQueue, NoticeQueueConfig};
+use rust_adx::bidding::host_limit::HostLimiter;
+use rust_adx::bidding::retry::TimeoutJitter;
+use rust_adx::bidding::rate_limit::SspRateLimiter;
+use rust_adx::bidding::request_ids::RecentRequestIds;
+use rust_adx::bidding::stats::HealthThresholds;
+use rust_adx::bidding::vast::VastWrapperConfig;
+use rust_adx::config::config_manager::{ConfigManager, ShadowDspConfig, TrackingConfig};
+use rust_adx::logging::runtime_logger::{RuntimeLogger, DEFAULT_RETENTION_GRACE_SECS};
+use rust_adx::model::adapters::FileConfigAdapter;
+use rust_adx::model::dsp::{init as dsp_init, DemandManager};
+use rust_adx::model::adapters::ConfigAdapter;
+use rust_adx::api::idempotency::IdempotencyCache;
+use rust_adx::bidding::pipeline::validate_disabled_stages;
+use rust_adx::mock_dsp::{LatencyDistribution, MockCreativeType, MockTemplates};
+use rust_adx::{api, mock_dsp, AppState};
 
 #[derive(Parser, Debug)]
 #[command(author = "whiteCcinn", version = "1.0", about = "An OpenRTB-based ADX Server")]
@@ -41,6 +48,250 @@ struct CliArgs {
     port: u16,
     #[arg(long, default_value = "logs")]
     log_dir: String,
+    /// 对不同 DSP 返回的相同创意去重
+    #[arg(long)]
+    dedup_creatives: bool,
+    /// 同一 DSP 对同一展示位返回多个出价时的处理方式：keep_highest（只保留最高出价）/ keep_all
+    #[arg(long, default_value = "keep_highest")]
+    intra_dsp_duplicates: String,
+    /// 聚合日志的详细程度：minimal / standard / full
+    #[arg(long, default_value = "full")]
+    log_verbosity: String,
+    /// HTML 创意注入的曝光像素 URL 模板
+    #[arg(long, default_value = "http://tk.rust-adx.com/impression?price={AUCTION_PRICE}")]
+    tracker_html_url: String,
+    /// VAST 创意注入的 Impression URL 模板
+    #[arg(long, default_value = "http://tk.rust-adx.com/impression?price={AUCTION_PRICE}")]
+    tracker_vast_url: String,
+    /// 成交定价策略：flat_margin / per_dsp_rate / second_price
+    #[arg(long, default_value = "flat_margin")]
+    pricing_strategy: String,
+    /// 候选出价的排序规则：price（按出价）/ weighted_score（按出价、预估点击率与 DSP 优先级加权）
+    #[arg(long, default_value = "price")]
+    bid_comparator: String,
+    /// weighted_score 的权重，格式 PRICE,CTR,PRIORITY
+    #[arg(long, value_delimiter = ',', default_values_t = [1.0, 0.0, 0.0])]
+    score_weights: Vec<f64>,
+    /// weighted_score 的 DSP 优先级，格式 DSP_ID=PRIORITY，逗号分隔
+    #[arg(long, value_delimiter = ',', value_parser = parse_dsp_priority)]
+    dsp_priorities: Vec<(u64, f64)>,
+    /// 利润率（0 ~ 1）
+    #[arg(long, default_value_t = 0.2)]
+    profit_rate: f64,
+    /// 二价成交价向上取整的出价粒度，保证汇率换算后成交价不低于次高价，0 表示不取整
+    #[arg(long, default_value_t = 0.01)]
+    price_granularity: f64,
+    /// 重复请求 id 的检测窗口（毫秒）
+    #[arg(long, default_value_t = 60000)]
+    duplicate_request_ttl_ms: u64,
+    /// 拒绝检测窗口内重复的请求 id
+    #[arg(long)]
+    reject_duplicate_requests: bool,
+    /// 胜出出价中仍有未替换的 ADX 宏（unsubstituted_macro）时不返回该出价并回退到下一个出价，默认仅记录日志
+    #[arg(long)]
+    suppress_unsubstituted_macros: bool,
+    /// 请求经路由过滤后没有可询价的 DSP（no_eligible_dsp）时返回给 SSP 的 nbr，缺省按默认无竞价处理
+    #[arg(long)]
+    no_eligible_dsp_nbr: Option<i32>,
+    /// 测试流量（test=1 或测试 SSP）只询价测试 DSP，并写入独立的 test 日志
+    #[arg(long)]
+    route_test_traffic: bool,
+    /// 命中敏感词时的处理方式：reject / flag
+    #[arg(long, default_value = "reject")]
+    sensitive_action: String,
+    /// 一价成交的线性 bid shading 比例（0 ~ 1），不设置则不做 shading
+    #[arg(long)]
+    bid_shade_factor: Option<f64>,
+    /// 汇率数据源（本地 JSON 文件路径或 http(s) 地址），不设置则只支持 USD
+    #[arg(long)]
+    fx_source: Option<String>,
+    /// 汇率刷新间隔（秒）
+    #[arg(long, default_value_t = 300)]
+    fx_refresh_interval_secs: u64,
+    /// 宏替换后 adm 的最大字节数
+    #[arg(long, default_value_t = 512 * 1024)]
+    max_adm_bytes: usize,
+    /// 单个请求内所有 DSP 共享的重试次数预算，0 表示不重试
+    #[arg(long, default_value_t = 0)]
+    retry_budget: u32,
+    /// 关键配置（SSP 信息 / SSP 广告位）缺失时仍以降级模式启动（所有竞价请求返回 503），默认拒绝启动
+    #[arg(long)]
+    allow_degraded_start: bool,
+    /// 运行日志通道积压达到该条数时切换为丢弃模式，默认为通道容量的 80%
+    #[arg(long)]
+    log_high_watermark: Option<usize>,
+    /// 清理过期日志（保留 72 小时）时额外容忍的宽限秒数，防止时钟偏差导致误删较新的日志文件
+    #[arg(long, default_value_t = DEFAULT_RETENTION_GRACE_SECS)]
+    log_retention_grace_secs: u64,
+    /// 全局可信来源 IP 段（CIDR，逗号分隔），不设置则不限制来源
+    #[arg(long, value_delimiter = ',')]
+    trusted_ips: Vec<IpNet>,
+    /// 允许访问 /admin 管理接口的来源 IP 段（CIDR，逗号分隔），不设置时只允许本机访问
+    #[arg(long, value_delimiter = ',')]
+    admin_trusted_ips: Vec<IpNet>,
+    /// /openrtb 允许的请求 Content-Type（逗号分隔），请求体均按 JSON 解析，其余类型返回 415
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_CONTENT_TYPES.map(String::from))]
+    allowed_content_types: Vec<String>,
+    /// DSP 请求/响应报文抓取的采样率（每 N 次 DSP 调用抓取一次），0 表示关闭
+    #[arg(long, default_value_t = 0)]
+    dsp_capture_sample_rate: u64,
+    /// 报文抓取时需要脱敏的字段（点分路径，逗号分隔）
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_REDACT_FIELDS.map(String::from))]
+    dsp_capture_redact_fields: Vec<String>,
+    /// /admin/recent-auctions 保留的最近竞价数，0 表示关闭
+    #[arg(long, default_value_t = 0)]
+    recent_auctions: usize,
+    /// 竞价结果 webhook 地址，每次竞价结束后 POST AuctionOutcome（JSON），缺省不开启
+    #[arg(long)]
+    auction_webhook_url: Option<String>,
+    /// 竞价结果 webhook 发送失败时的最大重试次数
+    #[arg(long, default_value_t = 2)]
+    auction_webhook_retries: u32,
+    /// 胜出出价未通过成交后校验时，最多回退到后续出价的次数，0 表示不回退
+    #[arg(long, default_value_t = 1)]
+    fallback_depth: usize,
+    /// ADX 自身处理预留的时间（毫秒），转发给 DSP 的 tmax 会扣除该值与已耗时
+    #[arg(long, default_value_t = 20)]
+    tmax_reserve_ms: u64,
+    /// 请求未携带 tmax 时使用的默认 tmax（毫秒），范围 10 ~ 10000
+    #[arg(long, default_value_t = 250)]
+    default_tmax_ms: u64,
+    /// 与 DSP 建立连接的超时（毫秒），用于区分连接超时与读取超时
+    #[arg(long, default_value_t = 100)]
+    dsp_connect_timeout_ms: u64,
+    /// 向 DSP 询价时携带的 User-Agent，缺省为 rust-adx/<版本号>
+    #[arg(long)]
+    dsp_user_agent: Option<String>,
+    /// 每个 DSP 主机保留的最大空闲连接数，缺省使用 reqwest 默认值
+    #[arg(long)]
+    dsp_pool_max_idle_per_host: Option<usize>,
+    /// 每个 DSP 主机同时进行中的询价数上限，超出时该 DSP 记为 host_busy，0 表示不限制
+    #[arg(long, default_value_t = 0)]
+    dsp_max_inflight_per_host: usize,
+    /// DSP 超时与重试等待的随机抖动上限（毫秒），避免多个 DSP 同时超时、同时重试，0 表示不加抖动
+    #[arg(long, default_value_t = 0)]
+    dsp_timeout_jitter_ms: u64,
+    /// 抖动的随机种子，指定后抖动序列可复现（用于测试），缺省随机
+    #[arg(long)]
+    dsp_timeout_jitter_seed: Option<u64>,
+    /// 内容类目底价（USD），格式 IAB7=1.5,IAB25=3.0，与展示位底价取较大值
+    #[arg(long, value_delimiter = ',', value_parser = parse_category_floor)]
+    category_floors: Vec<(String, f64)>,
+    /// 同时进行的竞价数上限，超出的请求立即返回 503 而不是排队
+    #[arg(long, default_value_t = 1024)]
+    max_concurrent_auctions: usize,
+    /// DSP 整体成功率低于该值时 /readyz 为 degraded
+    #[arg(long, default_value_t = 0.9)]
+    health_degraded_below: f64,
+    /// DSP 整体成功率低于该值时 /readyz 为 unhealthy（返回 503）
+    #[arg(long, default_value_t = 0.5)]
+    health_unhealthy_below: f64,
+    /// 计算健康状态所需的最少 DSP 调用数（滚动 60 秒窗口内）
+    #[arg(long, default_value_t = 20)]
+    health_min_calls: usize,
+    /// 解析并校验 DSP 返回的 VAST wrapper 链（每层一次额外请求，默认关闭）
+    #[arg(long)]
+    resolve_vast_wrappers: bool,
+    /// VAST wrapper 最多解析的层数
+    #[arg(long, default_value_t = 1)]
+    vast_wrapper_max_depth: usize,
+    /// 每层 VAST wrapper 拉取的超时（毫秒），同时不超过竞价剩余的时间预算
+    #[arg(long, default_value_t = 100)]
+    vast_wrapper_timeout_ms: u64,
+    /// 每层 VAST wrapper 响应体的大小上限（字节）
+    #[arg(long, default_value_t = 256 * 1024)]
+    vast_wrapper_max_body_bytes: usize,
+    /// 允许 VAST wrapper 指向回环与内网地址（仅用于本地联调，默认拒绝）
+    #[arg(long)]
+    vast_wrapper_allow_private_hosts: bool,
+    /// 允许的最小 tmax（毫秒），不设置则不限制
+    #[arg(long)]
+    min_tmax_ms: Option<u64>,
+    /// tmax 低于最小值时的处理方式：reject（400 tmax_too_low）/ clamp（提升到最小值）
+    #[arg(long, default_value = "reject")]
+    min_tmax_action: String,
+    /// 展示位声明了伴随广告位时，拒绝不含 Companion 的 VAST 创意（missing_companion）
+    #[arg(long)]
+    require_companions: bool,
+    /// Mock DSP 的响应延迟分布：fixed:200 / uniform:100-300 / lognormal:150,400（p50,p99）
+    #[arg(long, default_value = "uniform:100-300")]
+    mock_dsp_latency: String,
+    /// Mock DSP 的创意模板文件，格式 banner=path,video=path（类型：banner / video / native / other），
+    /// 模板中 {BID_ID} 替换为出价 id，未配置或文件不可读的类型使用内置模板
+    #[arg(long, value_delimiter = ',', value_parser = parse_mock_template)]
+    mock_dsp_templates: Vec<(MockCreativeType, String)>,
+    /// HTML 创意的 XSS 净化方式：off / sanitize（移除 script、事件处理属性等）/ reject（拒绝，unsafe_creative）
+    #[arg(long, default_value = "off")]
+    creative_sanitization: String,
+    /// DSP 响应 id 与请求 id 不一致时的处理方式：lenient（记录日志）/ strict（丢弃该响应）
+    #[arg(long, default_value = "lenient")]
+    response_id_policy: String,
+    /// 按 SSP 配置的 qps 限速，超出的请求返回 429
+    #[arg(long)]
+    enforce_ssp_qps: bool,
+    /// qps 限速允许的突发时长（秒），令牌桶容量为 qps × burst_seconds
+    #[arg(long, default_value_t = 1.0)]
+    qps_burst_seconds: f64,
+    /// DSP 列表文件（JSON 数组），启动时与 /admin/reload-demands 从中读取；不设置时使用随机生成的 DSP
+    #[arg(long)]
+    demands_file: Option<String>,
+    /// 影子 DSP 的 ID：按抽样比例接收请求，响应只记录日志、永不胜出
+    #[arg(long)]
+    shadow_dsp_id: Option<u64>,
+    /// 影子 DSP 的请求抽样比例（0 ~ 1）
+    #[arg(long, default_value_t = 0.1)]
+    shadow_sample_rate: f64,
+    /// 全局广告主域名白名单（逗号分隔），设置后只有 adomain 全部在白名单内的出价可以参与竞价
+    #[arg(long, value_delimiter = ',')]
+    allowed_adomains: Vec<String>,
+    /// 关闭的竞价流水线阶段（逗号分隔）：filter_blocklists / filter_sensitive / sanitize_creatives / filter_eligibility / inject_tracking / limit_response_bids / notify
+    #[arg(long, value_delimiter = ',')]
+    disable_auction_stages: Vec<String>,
+    /// 幂等缓存 TTL（毫秒）：TTL 内同一 SSP 重复的请求 id 直接返回之前的竞价结果，不设置则关闭
+    #[arg(long)]
+    idempotency_ttl_ms: Option<u64>,
+    /// 由 ADX 发送 nurl / lurl，失败的通知进入重试队列（响应中不再返回 nurl / lurl）
+    #[arg(long)]
+    durable_notices: bool,
+    /// 通知重试窗口（秒），超出后放弃
+    #[arg(long, default_value_t = 300)]
+    notice_retry_window_secs: u64,
+    /// 同时处于发送 / 重试中的通知上限
+    #[arg(long, default_value_t = 10_000)]
+    notice_queue_capacity: usize,
+    /// 未能送达的通知落盘文件（JSON 行），启动时重新入队；不设置则不落盘
+    #[arg(long)]
+    notice_spool_path: Option<PathBuf>,
+}
+
+/// 解析 CATEGORY=FLOOR 形式的类目底价
+fn parse_category_floor(s: &str) -> Result<(String, f64), String> {
+    let (category, floor) = s.split_once('=')
+        .ok_or_else(|| format!("expected CATEGORY=FLOOR, got {}", s))?;
+    let floor: f64 = floor.parse().map_err(|e| format!("invalid floor for {}: {}", category, e))?;
+    if !floor.is_finite() || floor < 0.0 {
+        return Err(format!("invalid floor for {}: {}", category, floor));
+    }
+    Ok((category.to_string(), floor))
+}
+
+/// 解析 TYPE=PATH 形式的 Mock DSP 创意模板文件
+fn parse_mock_template(s: &str) -> Result<(MockCreativeType, String), String> {
+    let (creative_type, path) = s.split_once('=')
+        .ok_or_else(|| format!("expected TYPE=PATH, got {}", s))?;
+    Ok((creative_type.parse()?, path.to_string()))
+}
+
+/// 解析 DSP_ID=PRIORITY 形式的 DSP 优先级
+fn parse_dsp_priority(s: &str) -> Result<(u64, f64), String> {
+    let (dsp_id, priority) = s.split_once('=')
+        .ok_or_else(|| format!("expected DSP_ID=PRIORITY, got {}", s))?;
+    let dsp_id: u64 = dsp_id.parse().map_err(|e| format!("invalid dsp id {}: {}", dsp_id, e))?;
+    let priority: f64 = priority.parse().map_err(|e| format!("invalid priority for dsp {}: {}", dsp_id, e))?;
+    if !priority.is_finite() {
+        return Err(format!("invalid priority for dsp {}: {}", dsp_id, priority));
+    }
+    Ok((dsp_id, priority))
 }
 
 #[tokio::main]
@@ -50,12 +301,11 @@ async fn main() {
 
     let args = CliArgs::parse();
 
-    // 初始化 DSP 基础信息
-    let demand_manager = dsp_init();
-
     // 启动 Mock DSP 服务器（监听 9001 端口）
-    let dsp_mock_server = tokio::spawn(async {
-        mock_dsp::start_mock_dsp_server(9001).await;
+    let mock_dsp_latency: LatencyDistribution = args.mock_dsp_latency.parse().expect("Invalid mock dsp latency");
+    let mock_dsp_templates = MockTemplates::load(&args.mock_dsp_templates);
+    let dsp_mock_server = tokio::spawn(async move {
+        mock_dsp::start_mock_dsp_server(9001, mock_dsp_latency, mock_dsp_templates).await;
     });
 
     // 初始化全局 tracing 日志
@@ -70,22 +320,195 @@ async fn main() {
 
     // 初始化运行日志记录器
     let runtime_logger = RuntimeLogger::new(&args.log_dir, "runtime", 1000, 100, 1000);
+    if let Some(high_watermark) = args.log_high_watermark {
+        runtime_logger.set_high_watermark(high_watermark);
+    }
+    runtime_logger.set_retention_grace(Duration::from_secs(args.log_retention_grace_secs));
     runtime_logger.log("INFO", "ADX server is starting...").await;
 
     // 初始化 ConfigManager，并使用 FileConfigAdapter 从 /static 目录读取 SSP 广告位和 DSP 广告位配置
-    let adapter = FileConfigAdapter::new("static/ssp_placements.json", "static/dsp_placements.json", "static/ssp_info.json");
-    let config = Arc::new(ConfigManager::new(demand_manager));
+    let mut adapter = FileConfigAdapter::new("static/ssp_placements.json", "static/dsp_placements.json", "static/ssp_info.json");
+    if let Some(demands_file) = args.demands_file.as_deref() {
+        adapter = adapter.with_demands_file(demands_file);
+    }
+    let missing_config = adapter.missing_critical_config();
+    let degraded = !missing_config.is_empty();
+    if degraded {
+        let message = format!(
+            "Critical config missing or empty: {} (ssp_info: {}, ssp_placements: {})",
+            missing_config.join(", "),
+            adapter.ssp_info_file,
+            adapter.ssp_placements_file
+        );
+        error!("{}", message);
+        if !args.allow_degraded_start {
+            runtime_logger.log("ERROR", &format!("{}, refusing to start", message)).await;
+            runtime_logger.shutdown().await;
+            // 运行日志已关闭，拒绝启动时仅向 stderr 输出这一行退出提示
+            eprintln!("Refusing to start; pass --allow-degraded-start to serve 503 for all bid requests instead");
+            std::process::exit(1);
+        }
+        runtime_logger.log("ERROR", &format!("{}, starting in degraded mode: all bid requests will be rejected with 503", message)).await;
+    }
+    // 初始化竞价事件总线，并挂载运行日志订阅方
+    let event_bus = EventBus::new(1024);
+    spawn_log_subscriber(&event_bus, runtime_logger.clone());
+
+    // 初始化 DSP 基础信息：优先从配置适配器读取，否则随机生成
+    let demand_manager = match adapter.get_demands() {
+        Some(demands) => {
+            let mut manager = DemandManager::new();
+            for demand in demands {
+                manager.add_demand(demand);
+            }
+            manager
+        }
+        None => dsp_init(),
+    };
+    let mut config = ConfigManager::new(demand_manager);
+    config.dedup_creatives = args.dedup_creatives;
+    config.intra_dsp_duplicates = args.intra_dsp_duplicates.parse().expect("Invalid intra dsp duplicates strategy");
+    config.log_verbosity = args.log_verbosity.parse().expect("Invalid log verbosity");
+    config.tracking = TrackingConfig {
+        html_impression_url: args.tracker_html_url.clone(),
+        vast_impression_url: args.tracker_vast_url.clone(),
+    };
+    config.tracking.validate().expect("Invalid tracking configuration");
+    config.pricing_strategy = args.pricing_strategy.parse().expect("Invalid pricing strategy");
+    config.bid_comparator = args.bid_comparator.parse().expect("Invalid bid comparator");
+    assert_eq!(args.score_weights.len(), 3, "Invalid score weights: expected PRICE,CTR,PRIORITY");
+    config.weighted_score = WeightedScore {
+        price_weight: args.score_weights[0],
+        ctr_weight: args.score_weights[1],
+        priority_weight: args.score_weights[2],
+        dsp_priorities: args.dsp_priorities.iter().copied().collect(),
+    };
+    config.profit_rate = args.profit_rate;
+    config.price_granularity = args.price_granularity;
+    config.reject_duplicate_requests = args.reject_duplicate_requests;
+    config.suppress_unsubstituted_macros = args.suppress_unsubstituted_macros;
+    config.no_eligible_dsp_nbr = args.no_eligible_dsp_nbr;
+    config.route_test_traffic = args.route_test_traffic;
+    config.bid_shade_factor = args.bid_shade_factor;
+    config.max_adm_bytes = args.max_adm_bytes;
+    config.retry_budget = args.retry_budget;
+    config.trusted_ips = args.trusted_ips.clone();
+    config.admin_trusted_ips = args.admin_trusted_ips.clone();
+    validate_content_types(&args.allowed_content_types).expect("Invalid allowed content types");
+    config.allowed_content_types = args.allowed_content_types.clone();
+    config.fallback_depth = args.fallback_depth;
+    config.tmax_reserve_ms = args.tmax_reserve_ms;
+    validate_default_tmax(args.default_tmax_ms).expect("Invalid default tmax");
+    config.default_tmax_ms = args.default_tmax_ms;
+    config.dsp_connect_timeout_ms = args.dsp_connect_timeout_ms;
+    config.dsp_pool_max_idle_per_host = args.dsp_pool_max_idle_per_host;
+    config.dsp_http_client = build_client(Duration::from_millis(config.dsp_connect_timeout_ms), config.dsp_pool_max_idle_per_host);
+    config.host_limiter = HostLimiter::new(args.dsp_max_inflight_per_host).map(Arc::new);
+    config.timeout_jitter = TimeoutJitter::new(args.dsp_timeout_jitter_ms, args.dsp_timeout_jitter_seed).map(Arc::new);
+    if let Some(user_agent) = args.dsp_user_agent {
+        config.dsp_user_agent = user_agent;
+    }
+    config.min_tmax_ms = args.min_tmax_ms;
+    config.min_tmax_action = args.min_tmax_action.parse().expect("Invalid min tmax action");
+    config.require_companions = args.require_companions;
+    config.allowed_adomains = args.allowed_adomains.iter().map(|domain| domain.trim().to_ascii_lowercase()).collect();
+    config.disabled_auction_stages = args.disable_auction_stages.iter().map(|stage| stage.trim().to_string()).collect();
+    validate_disabled_stages(&config.disabled_auction_stages).expect("Invalid disabled auction stages");
+    config.shadow_dsp = args.shadow_dsp_id.map(|dsp_id| ShadowDspConfig {
+        dsp_id,
+        sample_rate: args.shadow_sample_rate.clamp(0.0, 1.0),
+    });
+    config.response_id_policy = args.response_id_policy.parse().expect("Invalid response id policy");
+    config.creative_sanitization = args.creative_sanitization.parse().expect("Invalid creative sanitization mode");
+    config.category_floors = args.category_floors.iter().cloned().collect();
+    config.vast_wrapper = VastWrapperConfig {
+        enabled: args.resolve_vast_wrappers,
+        max_depth: args.vast_wrapper_max_depth,
+        timeout: Duration::from_millis(args.vast_wrapper_timeout_ms),
+        max_body_bytes: args.vast_wrapper_max_body_bytes,
+        allow_private_hosts: args.vast_wrapper_allow_private_hosts,
+    };
+    config.health_thresholds = HealthThresholds {
+        degraded_below: args.health_degraded_below,
+        unhealthy_below: args.health_unhealthy_below,
+        min_calls: args.health_min_calls,
+    };
+    if args.dsp_capture_sample_rate > 0 {
+        // 抓取的报文写入独立的 dsp_capture_debug.json 日志
+        let capture_logger = RuntimeLogger::new(&args.log_dir, "dsp_capture", 1000, 100, 1000);
+        capture_logger.set_retention_grace(Duration::from_secs(args.log_retention_grace_secs));
+        config.body_capture = BodyCapture::new(
+            args.dsp_capture_sample_rate,
+            args.dsp_capture_redact_fields.clone(),
+            capture_logger,
+        ).map(Arc::new);
+    }
+    config.recent_auctions = RecentAuctions::new(args.recent_auctions).map(Arc::new);
+    config.auction_webhook = args.auction_webhook_url.as_deref()
+        .map(|url| AuctionWebhook::new(url, args.auction_webhook_retries));
+    if args.durable_notices {
+        let notice_queue = NoticeQueue::new(NoticeQueueConfig {
+            capacity: args.notice_queue_capacity,
+            retry_window: Duration::from_secs(args.notice_retry_window_secs),
+            spool_path: args.notice_spool_path.clone(),
+            ..NoticeQueueConfig::default()
+        }, event_bus.clone(), runtime_logger.clone());
+        let replayed = notice_queue.replay_spool().await;
+        if replayed > 0 {
+            runtime_logger.log("INFO", &format!("Re-enqueued {} spooled notices", replayed)).await;
+        }
+        config.notice_queue = Some(notice_queue);
+    }
+    config.sensitive_filter.action = args.sensitive_action.parse().expect("Invalid sensitive action");
+    let config = Arc::new(config);
     config.update_placements(adapter.get_ssp_placements(), adapter.get_dsp_placements());
 
-    // 从 FileConfigAdapter 中读取 SSP 基础信息（多个 SSP）
+    // 周期性刷新汇率表
+    if let Some(fx_source) = args.fx_source.as_deref() {
+        spawn_fx_refresher(
+            FxSource::parse(fx_source),
+            Duration::from_secs(args.fx_refresh_interval_secs),
+            config.fx_table.clone(),
+            runtime_logger.clone(),
+        );
+    }
+
+    // 从 FileConfigAdapter 中读取 SSP 基础信息（多个 SSP），并校验 SSP 专属的 tracking 模板与利润率
     let ssp_info = adapter.get_ssp_info();
+    for ssp in &ssp_info {
+        if let Some(tracking) = &ssp.tracking {
+            if let Err(e) = tracking.validate() {
+                panic!("Invalid tracking configuration for ssp {}: {}", ssp.uuid, e);
+            }
+        }
+        if let Some(profit_rate) = ssp.profit_rate {
+            if !(0.0..1.0).contains(&profit_rate) {
+                panic!("Invalid profit_rate for ssp {}: {} (expected 0 <= rate < 1)", ssp.uuid, profit_rate);
+            }
+        }
+    }
 
     // 构造全局状态 AppState，其中不在 main.rs 中构造 Context，
     // 而在 API Handler 中根据请求中的参数构造具体的 Context。
     let state = Arc::new(AppState {
         runtime_logger: runtime_logger.clone(),
+        test_logger: args.route_test_traffic
+            .then(|| {
+                let test_logger = RuntimeLogger::new(&args.log_dir, "test", 1000, 100, 1000);
+                test_logger.set_retention_grace(Duration::from_secs(args.log_retention_grace_secs));
+                test_logger
+            }),
+        event_bus,
+        recent_request_ids: Arc::new(RecentRequestIds::new(Duration::from_millis(args.duplicate_request_ttl_ms))),
         config: config.clone(),
         ssp_info,
+        degraded,
+        maintenance: Arc::new(AtomicBool::new(false)),
+        auction_slots: Arc::new(Semaphore::new(args.max_concurrent_auctions)),
+        config_adapter: Arc::new(adapter),
+        idempotency_cache: args.idempotency_ttl_ms
+            .map(|ttl_ms| Arc::new(IdempotencyCache::new(Duration::from_millis(ttl_ms)))),
+        rate_limiter: args.enforce_ssp_qps.then(|| Arc::new(SspRateLimiter::new(args.qps_burst_seconds))),
     });
 
     let adx_server = tokio::spawn({
@@ -95,11 +518,15 @@ async fn main() {
         async move {
             let app = Router::new()
                 .route("/openrtb", post(api::handlers::handle_openrtb_request))
+                .route("/openrtb/batch", post(api::handlers::handle_openrtb_batch))
+                .merge(api::admin::router(state.clone()))
+                .route("/readyz", get(api::health::readyz))
+                .route("/stats", get(api::stats::stats))
                 .with_state(state);
             let addr = format!("0.0.0.0:{}", port);
             runtime_logger.log("INFO", &format!("ADX server running at http://{}", addr)).await;
             let listener = TcpListener::bind(&addr).await.unwrap();
-            serve(listener, app).await.unwrap();
+            serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
         }
     });
 
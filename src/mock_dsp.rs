@@ -1,21 +1,216 @@
-use axum::{Router, routing::post, Json};
+use axum::{extract::State, Router, routing::post, Json};
 use serde_json::json;
 use tokio::net::TcpListener;
 use axum::serve;
 use tokio::time::{sleep, Duration};
-use tracing::info;
+use tracing::{info, warn};
 use rand::Rng;
+use std::sync::Arc;
 
 // 引入 OpenRTB 数据结构，假设这些结构体已在 openrtb 模块中定义
-use crate::openrtb::request::BidRequest;
+use crate::openrtb::request::{BidRequest, ImpDetail};
 use crate::openrtb::response::{Bid, BidResponse, SeatBid};
 
-/// 以下为辅助函数，用于生成扩展字段
+/// 标准正态分布的 99 分位数，用于由 p50 / p99 推算对数正态分布的 sigma
+const NORMAL_Z_99: f64 = 2.326_347_874;
+
+/// Mock DSP 的响应延迟分布（毫秒），启动参数格式：
+/// fixed:200 / uniform:100-300 / lognormal:150,400（p50,p99）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatencyDistribution {
+    Fixed { ms: u64 },
+    /// [min_ms, max_ms) 内均匀分布
+    Uniform { min_ms: u64, max_ms: u64 },
+    /// 对数正态分布，由中位数与 99 分位数确定
+    LogNormal { p50_ms: f64, p99_ms: f64 },
+}
+
+impl Default for LatencyDistribution {
+    fn default() -> Self {
+        LatencyDistribution::Uniform { min_ms: 100, max_ms: 300 }
+    }
+}
+
+impl LatencyDistribution {
+    /// 采样一次延迟（毫秒）
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u64 {
+        match *self {
+            LatencyDistribution::Fixed { ms } => ms,
+            LatencyDistribution::Uniform { min_ms, max_ms } => rng.gen_range(min_ms..max_ms),
+            LatencyDistribution::LogNormal { p50_ms, p99_ms } => {
+                let mu = p50_ms.ln();
+                let sigma = (p99_ms.ln() - mu) / NORMAL_Z_99;
+                // Box-Muller 生成标准正态分布样本
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                (mu + sigma * z).exp().round() as u64
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for LatencyDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, params) = s.split_once(':').ok_or_else(|| format!("latency distribution must be kind:params, got {}", s))?;
+        let parse_u64 = |v: &str| v.trim().parse::<u64>().map_err(|e| format!("invalid latency {:?}: {}", v, e));
+        let parse_f64 = |v: &str| v.trim().parse::<f64>().map_err(|e| format!("invalid latency {:?}: {}", v, e));
+        match kind {
+            "fixed" => Ok(LatencyDistribution::Fixed { ms: parse_u64(params)? }),
+            "uniform" => {
+                let (min, max) = params.split_once('-').ok_or_else(|| format!("uniform latency must be min-max, got {}", params))?;
+                let (min_ms, max_ms) = (parse_u64(min)?, parse_u64(max)?);
+                if min_ms >= max_ms {
+                    return Err(format!("uniform latency min {} must be below max {}", min_ms, max_ms));
+                }
+                Ok(LatencyDistribution::Uniform { min_ms, max_ms })
+            }
+            "lognormal" => {
+                let (p50, p99) = params.split_once(',').ok_or_else(|| format!("lognormal latency must be p50,p99, got {}", params))?;
+                let (p50_ms, p99_ms) = (parse_f64(p50)?, parse_f64(p99)?);
+                if !(p50_ms > 0.0 && p99_ms >= p50_ms && p99_ms.is_finite()) {
+                    return Err(format!("lognormal latency requires 0 < p50 <= p99, got {},{}", p50_ms, p99_ms));
+                }
+                Ok(LatencyDistribution::LogNormal { p50_ms, p99_ms })
+            }
+            other => Err(format!("unknown latency distribution: {}", other)),
+        }
+    }
+}
+
+/// 创意模板中出价 id 的占位符，{AUCTION_PRICE} 等 ADX 宏原样保留，由 ADX 替换
+pub const BID_ID_PLACEHOLDER: &str = "{BID_ID}";
+
+// 内置创意模板，未配置模板文件或文件不可读时使用
+
+const DEFAULT_BANNER_TEMPLATE: &str = "<html><body>Mock DSP Banner Ad<br/>Auction Price: {AUCTION_PRICE}<br/><a href=\"http://dsp-tracker.local/click?bid={BID_ID}\" target=\"_blank\">Click Here</a><img src=\"http://dsp-tracker.local/impression?bid={BID_ID}\" style=\"display:none;\" /></body></html>";
+
+const DEFAULT_VIDEO_TEMPLATE: &str = r#"<VAST version="3.0">
+  <Ad id="{BID_ID}">
+    <InLine>
+      <AdSystem>Mock DSP</AdSystem>
+      <AdTitle>Mock Video Ad</AdTitle>
+      <Impression><![CDATA[http://dsp-tracker.local/impression?bid={BID_ID}&price={AUCTION_PRICE}]]></Impression>
+      <Creatives>
+        <Creative>
+          <Linear>
+            <Duration>00:00:30</Duration>
+            <MediaFiles>
+              <MediaFile delivery="progressive" type="video/mp4" width="640" height="360" bitrate="500">
+                http://example.com/video.mp4
+              </MediaFile>
+            </MediaFiles>
+            <VideoClicks>
+              <ClickTracking><![CDATA[http://dsp-tracker.local/click?bid={BID_ID}&price={AUCTION_PRICE}]]></ClickTracking>
+            </VideoClicks>
+          </Linear>
+        </Creative>
+      </Creatives>
+    </InLine>
+  </Ad>
+</VAST>"#;
+
+const DEFAULT_NATIVE_TEMPLATE: &str = r#"{"native":{"assets":[{"title":{"text":"Mock Native Ad"}},{"img":{"url":"http://example.com/native.jpg"}}],"impression_tracking":"http://dsp-tracker.local/impression?bid={BID_ID}&price={AUCTION_PRICE}","click_tracking":"http://dsp-tracker.local/click?bid={BID_ID}&price={AUCTION_PRICE}"}}"#;
+
+const DEFAULT_OTHER_TEMPLATE: &str = "<html><body>Mock DSP Ad<br/>Auction Price: {AUCTION_PRICE}<br/><img src=\"http://dsp-tracker.local/impression?bid={BID_ID}\" style=\"display:none;\" /></body></html>";
+
+/// Mock DSP 按展示位类型选择的创意类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockCreativeType {
+    Banner,
+    Video,
+    Native,
+    /// 不含 banner / video / native 的展示位
+    Other,
+}
+
+impl std::str::FromStr for MockCreativeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "banner" => Ok(MockCreativeType::Banner),
+            "video" => Ok(MockCreativeType::Video),
+            "native" => Ok(MockCreativeType::Native),
+            "other" => Ok(MockCreativeType::Other),
+            other => Err(format!("unknown mock creative type: {}", other)),
+        }
+    }
+}
+
+/// Mock DSP 各创意类型的 adm 模板，{BID_ID} 替换为出价 id
+#[derive(Debug, Clone)]
+pub struct MockTemplates {
+    pub banner: String,
+    pub video: String,
+    pub native: String,
+    pub other: String,
+}
+
+impl Default for MockTemplates {
+    fn default() -> Self {
+        Self {
+            banner: DEFAULT_BANNER_TEMPLATE.to_string(),
+            video: DEFAULT_VIDEO_TEMPLATE.to_string(),
+            native: DEFAULT_NATIVE_TEMPLATE.to_string(),
+            other: DEFAULT_OTHER_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl MockTemplates {
+    /// 按 创意类型 → 模板文件 的映射加载模板，未配置或读取失败的类型使用内置模板
+    pub fn load(files: &[(MockCreativeType, String)]) -> Self {
+        let mut templates = Self::default();
+        for (creative_type, path) in files {
+            match std::fs::read_to_string(path) {
+                Ok(template) => *templates.get_mut(*creative_type) = template,
+                Err(e) => warn!("Mock DSP template {} unreadable, using built-in template: {}", path, e),
+            }
+        }
+        templates
+    }
+
+    /// 生成指定创意类型的 adm
+    pub fn render(&self, creative_type: MockCreativeType, bid_id: &str) -> String {
+        let template = match creative_type {
+            MockCreativeType::Banner => &self.banner,
+            MockCreativeType::Video => &self.video,
+            MockCreativeType::Native => &self.native,
+            MockCreativeType::Other => &self.other,
+        };
+        template.replace(BID_ID_PLACEHOLDER, bid_id)
+    }
+
+    fn get_mut(&mut self, creative_type: MockCreativeType) -> &mut String {
+        match creative_type {
+            MockCreativeType::Banner => &mut self.banner,
+            MockCreativeType::Video => &mut self.video,
+            MockCreativeType::Native => &mut self.native,
+            MockCreativeType::Other => &mut self.other,
+        }
+    }
+}
+
+/// Mock DSP 服务的共享状态
+#[derive(Debug, Clone)]
+struct MockDspState {
+    latency: LatencyDistribution,
+    templates: Arc<MockTemplates>,
+}
+
+// 以下为辅助函数，用于生成扩展字段
 
 fn generate_nurl() -> Option<String> {
     Some("http://example.com/nurl".to_string())
 }
 
+fn generate_burl() -> Option<String> {
+    Some("http://example.com/burl?price={AUCTION_PRICE}".to_string())
+}
+
 fn generate_adid() -> Option<String> {
     Some("ad-12345".to_string())
 }
@@ -40,16 +235,23 @@ fn generate_attr() -> Option<Vec<i32>> {
     Some(vec![1, 2])
 }
 
-fn generate_dealid() -> Option<String> {
-    Some("deal-123".to_string())
+/// 展示位提供了 deal 时以第一个 deal 出价，否则不带 dealid（ADX 会拒绝未提供的 dealid）
+fn generate_dealid(imp: &ImpDetail) -> Option<String> {
+    imp.get_pmp_detail()
+        .and_then(|pmp| pmp.deals.as_ref())
+        .and_then(|deals| deals.first())
+        .map(|deal| deal.id.clone())
 }
 
-fn generate_h() -> Option<i32> {
-    Some(rand::thread_rng().gen_range(50..600))
+/// banner 声明了尺寸时按声明的尺寸出价，否则随机生成
+fn generate_h(imp: &ImpDetail) -> Option<i32> {
+    imp.get_banner_detail().and_then(|banner| banner.h)
+        .or_else(|| Some(rand::thread_rng().gen_range(50..600)))
 }
 
-fn generate_w() -> Option<i32> {
-    Some(rand::thread_rng().gen_range(50..800))
+fn generate_w(imp: &ImpDetail) -> Option<i32> {
+    imp.get_banner_detail().and_then(|banner| banner.w)
+        .or_else(|| Some(rand::thread_rng().gen_range(50..800)))
 }
 
 fn generate_ext() -> Option<serde_json::Value> {
@@ -58,9 +260,10 @@ fn generate_ext() -> Option<serde_json::Value> {
 
 /// 模拟 DSP 竞价响应
 ///
-/// 根据每个 impression 的类型随机生成出价，并生成相应的 adm 内容，
+/// 根据每个 impression 的类型随机生成出价，并按创意类型的模板生成相应的 adm 内容，
 /// 同时在 adm 中注入 DSP 自己的 tracking URL 和 {AUCTION_PRICE} 占位符。
-async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
+async fn handle_dsp_bid(State(state): State<MockDspState>, Json(request): Json<BidRequest>) -> Json<BidResponse> {
+    let MockDspState { latency, templates } = state;
     // 使用 get_imp_details() 获取解析后的 imp 列表
     let imp_details = request.get_imp_details();
     info!(
@@ -69,8 +272,8 @@ async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
         imp_details.len()
     );
 
-    // 模拟 DSP 处理延迟：100 ~ 300 毫秒
-    let delay_ms = rand::thread_rng().gen_range(100..300);
+    // 按配置的延迟分布模拟 DSP 处理延迟
+    let delay_ms = latency.sample(&mut rand::thread_rng());
     sleep(Duration::from_millis(delay_ms)).await;
 
     let mut bids = Vec::new();
@@ -82,9 +285,10 @@ async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
 
         // 根据 impression 类型确定 multiplier
         let multiplier = if let Some(banner_detail) = imp.get_banner_detail() {
-            if banner_detail.w == 300 && banner_detail.h == 250 {
+            let size = banner_detail.w.zip(banner_detail.h);
+            if size == Some((300, 250)) {
                 rand::thread_rng().gen_range(1.0..3.0)
-            } else if banner_detail.w == 728 && banner_detail.h == 90 {
+            } else if size == Some((728, 90)) {
                 rand::thread_rng().gen_range(0.8..1.2)
             } else {
                 rand::thread_rng().gen_range(1.0..2.0)
@@ -99,51 +303,17 @@ async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
 
         let price = bidfloor * multiplier;
 
-        // 根据 impression 类型生成 adm 内容，并注入 DSP tracking URL 和 {AUCTION_PRICE} 占位符
-        let adm_value = if imp.get_banner_detail().is_some() {
-            Some(format!(
-                "<html><body>Mock DSP Banner Ad<br/>Auction Price: {{AUCTION_PRICE}}<br/><a href=\"http://dsp-tracker.local/click?bid={bid_id}\" target=\"_blank\">Click Here</a><img src=\"http://dsp-tracker.local/impression?bid={bid_id}\" style=\"display:none;\" /></body></html>",
-                bid_id = bid_id
-            ))
+        // 根据 impression 类型选择创意模板生成 adm 内容，模板中含 DSP tracking URL 和 {AUCTION_PRICE} 占位符
+        let creative_type = if imp.get_banner_detail().is_some() {
+            MockCreativeType::Banner
         } else if imp.video.is_some() {
-            Some(format!(
-                r#"<VAST version="3.0">
-  <Ad id="{bid_id}">
-    <InLine>
-      <AdSystem>Mock DSP</AdSystem>
-      <AdTitle>Mock Video Ad</AdTitle>
-      <Impression><![CDATA[http://dsp-tracker.local/impression?bid={bid_id}&price={{AUCTION_PRICE}}]]></Impression>
-      <Creatives>
-        <Creative>
-          <Linear>
-            <Duration>00:00:30</Duration>
-            <MediaFiles>
-              <MediaFile delivery="progressive" type="video/mp4" width="640" height="360" bitrate="500">
-                http://example.com/video.mp4
-              </MediaFile>
-            </MediaFiles>
-            <VideoClicks>
-              <ClickTracking><![CDATA[http://dsp-tracker.local/click?bid={bid_id}&price={{AUCTION_PRICE}}]]></ClickTracking>
-            </VideoClicks>
-          </Linear>
-        </Creative>
-      </Creatives>
-    </InLine>
-  </Ad>
-</VAST>"#,
-                bid_id = bid_id
-            ))
+            MockCreativeType::Video
         } else if imp.native.is_some() {
-            Some(format!(
-                r#"{{"native":{{"assets":[{{"title":{{"text":"Mock Native Ad"}}}},{{"img":{{"url":"http://example.com/native.jpg"}}}}],"impression_tracking":"http://dsp-tracker.local/impression?bid={bid_id}&price={{AUCTION_PRICE}}","click_tracking":"http://dsp-tracker.local/click?bid={bid_id}&price={{AUCTION_PRICE}}"}}}}"#,
-                bid_id = bid_id
-            ))
+            MockCreativeType::Native
         } else {
-            Some(format!(
-                "<html><body>Mock DSP Ad<br/>Auction Price: {{AUCTION_PRICE}}<br/><img src=\"http://dsp-tracker.local/impression?bid={bid_id}\" style=\"display:none;\" /></body></html>",
-                bid_id = bid_id
-            ))
+            MockCreativeType::Other
         };
+        let adm_value = Some(templates.render(creative_type, &bid_id));
 
         bids.push(Bid {
             id: bid_id,
@@ -151,15 +321,19 @@ async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
             price,
             adm: adm_value,
             nurl: generate_nurl(),
+            lurl: None,
+            burl: generate_burl(),
             adid: generate_adid(),
             adomain: generate_adomain(),
             cid: generate_cid(),
             crid: generate_crid(),
             cat: generate_cat(),
+            cattax: None,
             attr: generate_attr(),
-            dealid: generate_dealid(),
-            h: generate_h(),
-            w: generate_w(),
+            dealid: generate_dealid(imp),
+            h: generate_h(imp),
+            w: generate_w(imp),
+            exp: None,
             ext: generate_ext(),
         });
     }
@@ -177,14 +351,90 @@ async fn handle_dsp_bid(Json(request): Json<BidRequest>) -> Json<BidResponse> {
         cur: Some("USD".to_string()),
         customdata: None,
         nbr: None,
+        ext: None,
     })
 }
 
+/// Mock DSP 的路由（POST /bid）
+pub fn mock_dsp_router(latency: LatencyDistribution, templates: MockTemplates) -> Router {
+    let state = MockDspState { latency, templates: Arc::new(templates) };
+    Router::new().route("/bid", post(handle_dsp_bid)).with_state(state)
+}
+
 /// 启动 Mock DSP 服务
-pub async fn start_mock_dsp_server(port: u16) {
-    let app = Router::new().route("/bid", post(handle_dsp_bid));
+pub async fn start_mock_dsp_server(port: u16, latency: LatencyDistribution, templates: MockTemplates) {
+    let app = mock_dsp_router(latency, templates);
     let addr = format!("0.0.0.0:{}", port);
     info!("Mock DSP running at http://{}", addr);
     let listener = TcpListener::bind(&addr).await.unwrap();
     serve(listener, app).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// 按分布采样 n 次并排序
+    fn sorted_samples(distribution: LatencyDistribution, n: usize) -> Vec<u64> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut samples: Vec<u64> = (0..n).map(|_| distribution.sample(&mut rng)).collect();
+        samples.sort_unstable();
+        samples
+    }
+
+    fn percentile(sorted: &[u64], p: f64) -> f64 {
+        sorted[((sorted.len() as f64 * p) as usize).min(sorted.len() - 1)] as f64
+    }
+
+    #[test]
+    fn latency_distributions_are_parsed() {
+        assert_eq!("fixed:200".parse(), Ok(LatencyDistribution::Fixed { ms: 200 }));
+        assert_eq!("uniform:100-300".parse(), Ok(LatencyDistribution::Uniform { min_ms: 100, max_ms: 300 }));
+        assert_eq!("lognormal:150,400".parse(), Ok(LatencyDistribution::LogNormal { p50_ms: 150.0, p99_ms: 400.0 }));
+        assert!("uniform:300-100".parse::<LatencyDistribution>().is_err());
+        assert!("lognormal:400,150".parse::<LatencyDistribution>().is_err());
+        assert!("gaussian:100".parse::<LatencyDistribution>().is_err());
+    }
+
+    #[test]
+    fn fixed_and_uniform_samples_stay_in_range() {
+        assert!(sorted_samples(LatencyDistribution::Fixed { ms: 200 }, 1000).iter().all(|&ms| ms == 200));
+        let uniform = sorted_samples(LatencyDistribution::Uniform { min_ms: 100, max_ms: 300 }, 10_000);
+        assert!(uniform[0] >= 100 && uniform[uniform.len() - 1] < 300);
+        let median = percentile(&uniform, 0.5);
+        assert!((190.0..=210.0).contains(&median), "median {}", median);
+    }
+
+    #[test]
+    fn lognormal_samples_honor_the_configured_percentiles() {
+        let samples = sorted_samples(LatencyDistribution::LogNormal { p50_ms: 150.0, p99_ms: 400.0 }, 20_000);
+        let (p50, p99) = (percentile(&samples, 0.5), percentile(&samples, 0.99));
+        assert!((p50 - 150.0).abs() <= 150.0 * 0.05, "p50 {}", p50);
+        assert!((p99 - 400.0).abs() <= 400.0 * 0.1, "p99 {}", p99);
+    }
+
+    #[test]
+    fn templates_load_from_files_and_fall_back_to_built_ins() {
+        let path = std::env::temp_dir().join(format!("rust-adx-mock-banner-{}.html", std::process::id()));
+        std::fs::write(&path, "<div>custom {BID_ID} {AUCTION_PRICE}</div>").unwrap();
+        let templates = MockTemplates::load(&[
+            (MockCreativeType::Banner, path.to_str().unwrap().to_string()),
+            (MockCreativeType::Video, "/nonexistent/video.xml".to_string()),
+        ]);
+        let _ = std::fs::remove_file(&path);
+        // ADX 宏原样保留，只替换出价 id
+        assert_eq!(templates.render(MockCreativeType::Banner, "bid-1"), "<div>custom bid-1 {AUCTION_PRICE}</div>");
+        let defaults = MockTemplates::default();
+        assert_eq!(templates.video, defaults.video);
+        assert_eq!(templates.native, defaults.native);
+    }
+
+    #[test]
+    fn creative_types_are_parsed() {
+        assert_eq!("banner".parse(), Ok(MockCreativeType::Banner));
+        assert_eq!("other".parse(), Ok(MockCreativeType::Other));
+        assert!("audio".parse::<MockCreativeType>().is_err());
+    }
+}
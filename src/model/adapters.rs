@@ -1,5 +1,6 @@
 // src/model/adapters.rs
 
+use crate::model::dsp::Demand;
 use crate::model::placements::{SspPlacement, DspPlacement};
 use crate::model::ssp::Ssp;
 use serde::{Serialize, Deserialize};
@@ -20,6 +21,23 @@ pub trait ConfigAdapter: Send + Sync {
     fn get_ssp_placements(&self) -> Vec<SspPlacement>;
     fn get_dsp_placements(&self) -> Vec<DspPlacement>;
     fn get_ssp_info(&self) -> Vec<Ssp>;
+
+    /// DSP 列表，数据源未配置或读取失败时返回 None（调用方保留现有 DSP）
+    fn get_demands(&self) -> Option<Vec<Demand>> {
+        None
+    }
+
+    /// 返回缺失（读取失败或为空）的关键配置项名称，SSP 基础信息与 SSP 广告位缺一不可
+    fn missing_critical_config(&self) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.get_ssp_info().is_empty() {
+            missing.push("ssp_info");
+        }
+        if self.get_ssp_placements().is_empty() {
+            missing.push("ssp_placements");
+        }
+        missing
+    }
 }
 
 /// 文件配置适配器，从静态 JSON 文件读取数据
@@ -27,6 +45,8 @@ pub struct FileConfigAdapter {
     pub ssp_placements_file: String,
     pub dsp_placements_file: String,
     pub ssp_info_file: String,
+    /// DSP 列表文件，None 表示该适配器不提供 DSP 列表
+    pub demands_file: Option<String>,
 }
 
 impl FileConfigAdapter {
@@ -35,8 +55,15 @@ impl FileConfigAdapter {
             ssp_placements_file: ssp_placements_file.to_string(),
             dsp_placements_file: dsp_placements_file.to_string(),
             ssp_info_file: ssp_info_file.to_string(),
+            demands_file: None,
         }
     }
+
+    /// 从指定文件读取 DSP 列表
+    pub fn with_demands_file(mut self, demands_file: &str) -> Self {
+        self.demands_file = Some(demands_file.to_string());
+        self
+    }
 }
 
 impl ConfigAdapter for FileConfigAdapter {
@@ -60,4 +87,29 @@ impl ConfigAdapter for FileConfigAdapter {
         let config: JsonResult<Vec<Ssp>> = serde_json::from_str(&content);
         config.unwrap_or_default()
     }
+
+    fn get_demands(&self) -> Option<Vec<Demand>> {
+        let content = fs::read_to_string(self.demands_file.as_ref()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_config_files_are_reported_as_missing_critical_config() {
+        let adapter = FileConfigAdapter::new("missing/ssp_placements.json", "missing/dsp_placements.json", "missing/ssp_info.json")
+            .with_demands_file("missing/demands.json");
+        assert_eq!(adapter.missing_critical_config(), vec!["ssp_info", "ssp_placements"]);
+        assert!(adapter.get_dsp_placements().is_empty());
+        assert!(adapter.get_demands().is_none());
+    }
+
+    #[test]
+    fn bundled_config_files_are_complete() {
+        let adapter = FileConfigAdapter::new("static/ssp_placements.json", "static/dsp_placements.json", "static/ssp_info.json");
+        assert!(adapter.missing_critical_config().is_empty());
+    }
 }
@@ -4,7 +4,7 @@ use crate::openrtb::request::BidRequest;
 use crate::model::ssp::Ssp;
 use crate::model::placements::{SspPlacement, DspPlacement};
 use crate::model::dsp::Demand;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
 fn default_instant() -> Instant {
@@ -24,3 +24,21 @@ pub struct Context {
     #[serde(skip, default = "default_instant")]
     pub start_time: Instant,
 }
+
+impl Context {
+    /// 是否为测试流量：请求携带 test=1，或来自测试 SSP
+    pub fn is_test_traffic(&self) -> bool {
+        self.bid_request.test == Some(1) || self.ssp.test
+    }
+
+    /// 本次竞价的时间预算（毫秒）：请求 tmax（未携带时为默认 tmax）与 SSP 的 max_auction_ms 取较小者
+    pub fn auction_tmax(&self, default_tmax_ms: u64) -> u64 {
+        let tmax = self.bid_request.tmax.unwrap_or(default_tmax_ms);
+        self.ssp.max_auction_ms.map_or(tmax, |max_auction_ms| tmax.min(max_auction_ms))
+    }
+
+    /// 本次竞价需完成的时间点：开始时间加上时间预算，并扣除 ADX 自身的预留时间（毫秒）
+    pub fn auction_deadline(&self, default_tmax_ms: u64, reserve_ms: u64) -> Instant {
+        self.start_time + Duration::from_millis(self.auction_tmax(default_tmax_ms).saturating_sub(reserve_ms))
+    }
+}
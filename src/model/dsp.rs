@@ -13,6 +13,53 @@ pub struct Demand {
     pub url: String,          // DSP 竞价 API 地址
     pub status: bool,         // 是否启用
     pub timeout: Option<u64>, // 每个 DSP 的超时（毫秒），至少 100
+    #[serde(default)]
+    pub min_bid_price: Option<f64>, // DSP 最低出价，低于该价格的出价一律拒绝（与展示位底价无关）
+    #[serde(default)]
+    pub max_retries: Option<u32>,   // 请求失败（超时 / 网络错误）时的最大重试次数，受请求级重试预算约束
+    #[serde(default)]
+    pub strip_fields: Vec<String>,  // 转发前从请求中移除的字段（点分路径，如 user、device.geo），默认全部转发
+    #[serde(default)]
+    pub test: bool,                 // 测试 DSP：只接收测试流量，永不接收生产流量
+    #[serde(default = "default_accepts_interstitial")]
+    pub accepts_interstitial: bool, // 是否接收插屏展示位（imp.instl = 1），不接收时转发前移除插屏 imp
+    #[serde(default)]
+    pub price_unit: PriceUnit,      // 出价价格单位，内部统一换算为 CPM 比较
+    #[serde(default)]
+    pub max_request_bytes: Option<usize>, // 转发请求体的大小上限（字节），超出时不向该 DSP 询价
+}
+
+/// DSP 出价的价格单位
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceUnit {
+    /// 千次展示价格（OpenRTB 默认）
+    #[default]
+    Cpm,
+    /// 单次展示价格
+    Raw,
+}
+
+impl PriceUnit {
+    /// 换算为 CPM
+    pub fn to_cpm(self, price: f64) -> f64 {
+        match self {
+            PriceUnit::Cpm => price,
+            PriceUnit::Raw => price * 1000.0,
+        }
+    }
+
+    /// 将 CPM 换算回该单位，用于返回给 DSP 的价格（如 {AUCTION_PRICE}）
+    pub fn cpm_to_unit(self, cpm: f64) -> f64 {
+        match self {
+            PriceUnit::Cpm => cpm,
+            PriceUnit::Raw => cpm / 1000.0,
+        }
+    }
+}
+
+fn default_accepts_interstitial() -> bool {
+    true
 }
 
 impl Demand {
@@ -23,12 +70,19 @@ impl Demand {
             url: url.to_string(),
             status,
             timeout,
+            min_bid_price: None,
+            max_retries: None,
+            strip_fields: Vec::new(),
+            test: false,
+            accepts_interstitial: true,
+            price_unit: PriceUnit::Cpm,
+            max_request_bytes: None,
         }
     }
 }
 
 /// DSP 管理器，管理多个 DSP 的 Demand 信息
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct DemandManager {
     pub demands: HashMap<u64, Demand>,
 }
@@ -73,6 +127,13 @@ fn generate_demand() -> impl Strategy<Value = Demand> {
                 url,
                 status,
                 timeout: Some(timeout),
+                min_bid_price: None,
+                max_retries: None,
+                strip_fields: Vec::new(),
+                test: false,
+                accepts_interstitial: true,
+                price_unit: PriceUnit::Cpm,
+                max_request_bytes: None,
             }
         })
 }
@@ -96,7 +157,7 @@ fn generate_demand_manager() -> impl Strategy<Value = DemandManager> {
     })
 }
 
-/// 初始化并生成一个随机的 DemandManager，并打印生成的信息
+/// 初始化并生成一个随机的 DemandManager，生成的 DSP 信息写入 tracing 日志
 pub fn init() -> DemandManager {
     let mut runner = proptest::test_runner::TestRunner::default();
     let demand_manager = generate_demand_manager()
@@ -104,19 +165,19 @@ pub fn init() -> DemandManager {
         .unwrap()
         .current();
 
-    println!("Generated DemandManager with {} Demands", demand_manager.demands.len());
+    tracing::info!(
+        demands = demand_manager.demands.len(),
+        active = demand_manager.active_demands().len(),
+        "generated demand manager"
+    );
     for demand in demand_manager.demands.values() {
-        println!(
-            "ID: {}, Name: {}, URL: {}, Status: {}, Timeout: {:?}",
-            demand.id, demand.name, demand.url, demand.status, demand.timeout
-        );
-    }
-
-    println!("\nActive DSPs:");
-    for demand in demand_manager.active_demands() {
-        println!(
-            "ID: {}, Name: {}, URL: {}, Timeout: {:?}",
-            demand.id, demand.name, demand.url, demand.timeout
+        tracing::info!(
+            dsp_id = demand.id,
+            name = %demand.name,
+            url = %demand.url,
+            status = demand.status,
+            timeout = ?demand.timeout,
+            "generated demand"
         );
     }
 
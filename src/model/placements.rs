@@ -3,7 +3,7 @@
 use serde::{Serialize, Deserialize};
 use std::convert::TryFrom;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[serde(try_from = "u8", into = "u8")]
 pub enum AdType {
     Native = 1,
@@ -24,6 +24,10 @@ impl TryFrom<u8> for AdType {
     }
 }
 
+impl AdType {
+    pub const ALL: [AdType; 3] = [AdType::Native, AdType::Banner, AdType::Video];
+}
+
 impl From<AdType> for u8 {
     fn from(ad: AdType) -> Self {
         ad as u8
@@ -40,6 +44,8 @@ pub struct SspPlacement {
     pub ad_type: AdType,      // 广告位类型
     pub update_time: u64,     // 更新时间（Unix 时间戳）
     pub status: u8,           // 状态：1 = 开启, 2 = 禁用
+    #[serde(default)]
+    pub default_bidfloor: Option<f64>, // 默认底价（imp 未携带 bidfloor 时使用）
 }
 
 /// DSP 广告位信息集合
@@ -54,3 +60,16 @@ pub struct DspPlacement {
     pub update_time: u64,       // 更新时间
     pub status: u8,             // 状态：1 = 开启, 2 = 禁用
 }
+
+/// OpenRTB imp 中的媒体对象
+pub const MEDIA_TYPES: [&str; 4] = ["banner", "video", "audio", "native"];
+
+impl DspPlacement {
+    /// 该 DSP 广告位支持的媒体类型（解析 custom_ad_type，如 "banner+video"），忽略未知类型
+    pub fn supported_media(&self) -> Vec<&'static str> {
+        self.custom_ad_type
+            .split('+')
+            .filter_map(|t| MEDIA_TYPES.iter().find(|m| m.eq_ignore_ascii_case(t.trim())).copied())
+            .collect()
+    }
+}
@@ -1,6 +1,10 @@
 // src/model/ssp.rs
 
+use crate::config::config_manager::TrackingConfig;
+use crate::openrtb::response::BidResponse;
+use ipnet::IpNet;
 use serde::{Serialize, Deserialize};
+use std::net::IpAddr;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Ssp {
@@ -8,4 +12,131 @@ pub struct Ssp {
     pub uuid: String,
     pub name: String,
     pub qps: u32,
+    /// 是否在胜出创意中注入 ADX 的曝光追踪（SSP 自行注入时可关闭，仅做 {AUCTION_PRICE} 替换）
+    #[serde(default = "default_inject_tracking")]
+    pub inject_tracking: bool,
+    /// 是否在胜出出价的 ext.adx 中附带 ADX 内部信息（来源 DSP、扣利润前价格、利润率），需合同允许才开启
+    #[serde(default)]
+    pub expose_adx_ext: bool,
+    /// 是否在 BidResponse.ext.adx.winning_dsp 中返回胜出 DSP 的 id 与名称（多展示位时取成交价最高的出价）
+    #[serde(default)]
+    pub expose_winning_dsp: bool,
+    /// 是否在胜出出价的 ext.adx.prices 中返回成交价按请求 cur 中每种货币换算的结果，用于多币种对账
+    #[serde(default)]
+    pub expose_currency_prices: bool,
+    /// 是否在胜出出价的 ext.adx 中返回原始出价与成交价（二价时即次高价加价后的价格，均为结算货币），供 SSP 审计加价
+    #[serde(default)]
+    pub expose_clearing_price: bool,
+    /// 固定的响应结算货币，设置后胜出价格会换算为该货币返回；None 时取请求 cur 中第一个可换算的货币，均无法换算时沿用 DSP 出价货币
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// 允许的来源 IP 段（CIDR），非空时覆盖全局白名单
+    #[serde(default)]
+    pub trusted_ips: Vec<IpNet>,
+    /// 无竞价时的响应方式
+    #[serde(default)]
+    pub nobid_status: NoBidStatus,
+    /// SSP 专属的曝光追踪 URL 模板（按 HTML / VAST 创意类型），未配置时使用全局 tracking 配置
+    #[serde(default)]
+    pub tracking: Option<TrackingConfig>,
+    /// SSP 专属利润率（0 ~ 1），设置后优先于全局利润率与 DSP 广告位利润率，对所有定价策略生效
+    #[serde(default)]
+    pub profit_rate: Option<f64>,
+    /// 广告主域名白名单，非空时覆盖全局白名单，只有 adomain 全部在白名单内的出价可以参与竞价
+    #[serde(default)]
+    pub allowed_adomains: Vec<String>,
+    /// 出价必须携带的字段（如 adomain、crid、cat），缺少任一字段的出价在定价前丢弃
+    #[serde(default)]
+    pub required_bid_fields: Vec<String>,
+    /// 响应中最多返回的出价数，超出时按成交价保留最高的出价；None 表示不限制
+    #[serde(default)]
+    pub max_response_bids: Option<usize>,
+    /// 每个席位（同一 DSP 的同一 seat）在响应中最多返回的出价数，超出时按成交价保留最高的出价，
+    /// 先于 max_response_bids 生效；None 表示不限制
+    #[serde(default)]
+    pub max_bids_per_seat: Option<usize>,
+    /// 响应中 SeatBid 的组织方式
+    #[serde(default)]
+    pub seatbid_grouping: SeatBidGrouping,
+    /// 返回给该 SSP 前对响应可选字段的调整，默认不调整
+    #[serde(default)]
+    pub response_transform: ResponseTransform,
+    /// 测试 SSP：开启测试流量分流时，其全部请求按测试流量处理（与请求中 test=1 等效）
+    #[serde(default)]
+    pub test: bool,
+    /// 该 SSP 的整场竞价时长上限（毫秒），与请求 tmax 取较小者，即使 SSP 下发的 tmax 更宽松也按此 SLA 完成竞价
+    #[serde(default)]
+    pub max_auction_ms: Option<u64>,
+    /// 为响应序列化预留的时间（毫秒），从 DSP 的时间预算中扣除；该 SSP 的创意较大（VAST / native）时调大
+    #[serde(default)]
+    pub serialization_reserve_ms: Option<u64>,
+}
+
+/// 响应中 SeatBid 的组织方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatBidGrouping {
+    /// 每个胜出出价单独一个 SeatBid
+    #[default]
+    PerBid,
+    /// 同一 DSP 同一席位的出价合并为一个 SeatBid，保留席位
+    PerDsp,
+    /// 全部出价放在一个不带席位的 SeatBid 中
+    Single,
+}
+
+/// 按 SSP 要求调整 BidResponse 的可选字段，在序列化返回前执行
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ResponseTransform {
+    /// 不返回 cur
+    #[serde(default)]
+    pub omit_cur: bool,
+    /// bidid 为空时以请求 id 填充
+    #[serde(default)]
+    pub bidid_from_request_id: bool,
+    /// 不返回响应级 ext
+    #[serde(default)]
+    pub omit_ext: bool,
+    /// 无竞价时不返回 nbr
+    #[serde(default)]
+    pub omit_nbr: bool,
+}
+
+impl ResponseTransform {
+    pub fn apply(&self, response: &mut BidResponse) {
+        if self.omit_cur {
+            response.cur = None;
+        }
+        if self.bidid_from_request_id && response.bidid.is_none() {
+            response.bidid = Some(response.id.clone());
+        }
+        if self.omit_ext {
+            response.ext = None;
+        }
+        if self.omit_nbr {
+            response.nbr = None;
+        }
+    }
+}
+
+/// 无竞价响应方式：no_content 返回 204 且无 body；ok 返回 200 与带 nbr 的 BidResponse
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NoBidStatus {
+    #[default]
+    NoContent,
+    Ok,
+}
+
+impl Ssp {
+    /// 判断来源 IP 是否可信：SSP 自身白名单优先，否则使用全局白名单，白名单为空时不做限制
+    pub fn is_trusted_source(&self, ip: IpAddr, global_trusted_ips: &[IpNet]) -> bool {
+        let trusted_ips = if self.trusted_ips.is_empty() { global_trusted_ips } else { &self.trusted_ips };
+        let ip = ip.to_canonical();
+        trusted_ips.is_empty() || trusted_ips.iter().any(|net| net.contains(&ip))
+    }
+}
+
+fn default_inject_tracking() -> bool {
+    true
 }
\ No newline at end of file
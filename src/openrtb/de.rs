@@ -0,0 +1,87 @@
+// src/openrtb/de.rs
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use std::fmt;
+
+/// 宽松的数值反序列化：接受浮点数、整数与数字字符串（如 "1.25"），统一转换为 f64；
+/// 整数按数值本身处理（不做单位换算），非有限值与非数字字符串报错
+pub fn flexible_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(FlexibleF64Visitor)
+}
+
+/// 可选字段的宽松数值反序列化：null 为 None，其余同 flexible_f64；
+/// 字段需同时标注 #[serde(default)]，以便缺失时为 None
+pub fn flexible_opt_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Flexible(#[serde(deserialize_with = "flexible_f64")] f64);
+
+    Ok(Option::<Flexible>::deserialize(deserializer)?.map(|Flexible(value)| value))
+}
+
+struct FlexibleF64Visitor;
+
+impl<'de> Visitor<'de> for FlexibleF64Visitor {
+    type Value = f64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number or a numeric string")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(E::custom(format!("non-finite number {}", value)))
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+        Ok(value as f64)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+        let parsed: f64 = value.trim().parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))?;
+        self.visit_f64(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::openrtb::response::Bid;
+    use serde_json::json;
+
+    fn price(price: serde_json::Value) -> Result<f64, serde_json::Error> {
+        serde_json::from_value::<Bid>(json!({"id": "b1", "impid": "1", "price": price})).map(|bid| bid.price)
+    }
+
+    #[test]
+    fn bid_price_accepts_float_integer_and_numeric_string() {
+        assert_eq!(price(json!(1.25)).unwrap(), 1.25);
+        assert_eq!(price(json!(2)).unwrap(), 2.0);
+        assert_eq!(price(json!(-1)).unwrap(), -1.0);
+        assert_eq!(price(json!("1.25")).unwrap(), 1.25);
+        assert_eq!(price(json!(" 3 ")).unwrap(), 3.0);
+    }
+
+    #[test]
+    fn bid_price_rejects_non_numeric_values() {
+        assert!(price(json!("abc")).is_err());
+        assert!(price(json!("NaN")).is_err());
+        assert!(price(json!("inf")).is_err());
+        assert!(price(json!(null)).is_err());
+        assert!(price(json!(true)).is_err());
+        assert!(serde_json::from_value::<Bid>(json!({"id": "b1", "impid": "1"})).is_err());
+    }
+}
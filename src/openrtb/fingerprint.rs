@@ -0,0 +1,146 @@
+// src/openrtb/fingerprint.rs
+
+use simd_json::{OwnedValue, StaticNode};
+
+use crate::openrtb::request::BidRequest;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 请求指纹：对 imp、site、app、device、user 做与 JSON 键顺序无关的哈希（不含 id），
+/// 用于缓存与去重；使用 FNV-1a，跨进程、跨版本结果一致
+pub fn request_fingerprint(bid_request: &BidRequest) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hash_request(&mut hasher, bid_request);
+    hasher.finish()
+}
+
+/// 在 request_fingerprint 的基础上同时哈希请求 id
+pub fn request_fingerprint_with_id(bid_request: &BidRequest) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write_str(&bid_request.id);
+    hash_request(&mut hasher, bid_request);
+    hasher.finish()
+}
+
+fn hash_request(hasher: &mut Fnv1a, bid_request: &BidRequest) {
+    hash_value(hasher, &bid_request.imp);
+    for field in [&bid_request.site, &bid_request.app, &bid_request.device, &bid_request.user] {
+        match field {
+            Some(value) => hash_value(hasher, value),
+            None => hasher.write(&[0]),
+        }
+    }
+}
+
+/// 按类型标记递归哈希：对象按键排序，数值 1 与 1.0 视为相同
+fn hash_value(hasher: &mut Fnv1a, value: &OwnedValue) {
+    match value {
+        OwnedValue::Static(StaticNode::Null) => hasher.write(&[1]),
+        OwnedValue::Static(StaticNode::Bool(b)) => hasher.write(&[2, *b as u8]),
+        OwnedValue::Static(StaticNode::I64(n)) => hash_number(hasher, *n as f64, Some(*n as i128)),
+        OwnedValue::Static(StaticNode::U64(n)) => hash_number(hasher, *n as f64, Some(*n as i128)),
+        OwnedValue::Static(StaticNode::F64(n)) => {
+            let integral = (n.fract() == 0.0 && n.abs() < 9.0e15).then_some(*n as i128);
+            hash_number(hasher, *n, integral)
+        }
+        OwnedValue::String(s) => {
+            hasher.write(&[4]);
+            hasher.write_str(s);
+        }
+        OwnedValue::Array(items) => {
+            hasher.write(&[5]);
+            hasher.write(&(items.len() as u64).to_le_bytes());
+            for item in items.iter() {
+                hash_value(hasher, item);
+            }
+        }
+        OwnedValue::Object(object) => {
+            let mut entries: Vec<_> = object.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            hasher.write(&[6]);
+            hasher.write(&(entries.len() as u64).to_le_bytes());
+            for (key, value) in entries {
+                hasher.write_str(key);
+                hash_value(hasher, value);
+            }
+        }
+    }
+}
+
+fn hash_number(hasher: &mut Fnv1a, value: f64, integral: Option<i128>) {
+    hasher.write(&[3]);
+    match integral {
+        Some(n) => hasher.write(&n.to_le_bytes()),
+        None => hasher.write(&value.to_bits().to_le_bytes()),
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// 带长度前缀写入字符串，避免相邻字符串拼接产生碰撞
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> BidRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn reordered_keys_hash_equally() {
+        let a = parse(r#"{"id": "r1", "imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 1}],
+            "device": {"ua": "Mozilla", "geo": {"lat": 31.2, "country": "CHN"}}, "user": {"id": "u1"}}"#);
+        let b = parse(r#"{"user": {"id": "u1"}, "device": {"geo": {"country": "CHN", "lat": 31.2}, "ua": "Mozilla"},
+            "imp": [{"bidfloor": 1.0, "banner": {"h": 250, "w": 300}, "id": "1"}], "id": "r1"}"#);
+        assert_eq!(request_fingerprint(&a), request_fingerprint(&b));
+        assert_eq!(request_fingerprint_with_id(&a), request_fingerprint_with_id(&b));
+        // simd-json 解析的请求得到相同的指纹
+        let mut bytes = br#"{"id": "r1", "imp": [{"id": "1", "bidfloor": 1, "banner": {"h": 250, "w": 300}}], "user": {"id": "u1"}, "device": {"ua": "Mozilla", "geo": {"country": "CHN", "lat": 31.2}}}"#.to_vec();
+        let simd: BidRequest = simd_json::serde::from_slice(&mut bytes).unwrap();
+        assert_eq!(request_fingerprint(&simd), request_fingerprint(&a));
+    }
+
+    #[test]
+    fn id_is_only_hashed_on_request() {
+        let a = parse(r#"{"id": "r1", "imp": [{"id": "1"}]}"#);
+        let b = parse(r#"{"id": "r2", "imp": [{"id": "1"}]}"#);
+        assert_eq!(request_fingerprint(&a), request_fingerprint(&b));
+        assert_ne!(request_fingerprint_with_id(&a), request_fingerprint_with_id(&b));
+    }
+
+    #[test]
+    fn semantic_differences_change_the_fingerprint() {
+        let base = request_fingerprint(&parse(r#"{"id": "r1", "imp": [{"id": "1"}, {"id": "2"}], "site": {"id": "s1"}}"#));
+        for other in [
+            r#"{"id": "r1", "imp": [{"id": "2"}, {"id": "1"}], "site": {"id": "s1"}}"#,
+            r#"{"id": "r1", "imp": [{"id": "1"}, {"id": "2"}], "app": {"id": "s1"}}"#,
+            r#"{"id": "r1", "imp": [{"id": "1"}, {"id": "2"}], "site": {"id": "s2"}}"#,
+            r#"{"id": "r1", "imp": [{"id": "1"}, {"id": "2"}], "site": {"id": "s1", "page": "https://a.example.com"}}"#,
+        ] {
+            assert_ne!(request_fingerprint(&parse(other)), base, "{}", other);
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod de;
+pub mod fingerprint;
 pub mod request;
-pub mod response;
\ No newline at end of file
+pub mod response;
+#[cfg(feature = "typed-request")]
+pub mod typed;
\ No newline at end of file
@@ -1,49 +1,56 @@
 use serde::{Serialize, Deserialize};
 use once_cell::sync::OnceCell;
+use std::sync::Arc;
 use simd_json::base::ValueAsArray;
+use simd_json::prelude::ValueAsMutObject;
 use simd_json::OwnedValue;
 
+use crate::model::placements::AdType;
+use crate::openrtb::de::flexible_opt_f64;
+
 /// OpenRTB BidRequest 结构体，
 /// 对于每个对象或数组字段采用延迟解析方式存储为 OwnedValue（owned, 'static），
 /// 并为每个大字段提供一个 lazy 缓存字段和 getter 方法。
+/// 解析结果以 Arc 缓存，克隆请求时共享已解析的内容，不会重复解析或深拷贝。
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BidRequest {
     pub id: String,
 
-    /// 广告展示请求列表（imp）存储为 OwnedValue
+    /// 广告展示请求列表（imp）存储为 OwnedValue，缺失时为 null，由请求校验拒绝
+    #[serde(default)]
     pub imp: Box<OwnedValue>,
     #[serde(skip)]
-    pub imp_details: OnceCell<Vec<ImpDetail>>,
+    pub imp_details: OnceCell<Arc<Vec<ImpDetail>>>,
 
     /// 网站信息
     pub site: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub site_detail: OnceCell<SiteDetail>,
+    pub site_detail: OnceCell<Arc<SiteDetail>>,
 
     /// 应用信息
     pub app: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub app_detail: OnceCell<AppDetail>,
+    pub app_detail: OnceCell<Arc<AppDetail>>,
 
     /// 设备信息
     pub device: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub device_detail: OnceCell<DeviceDetail>,
+    pub device_detail: OnceCell<Arc<DeviceDetail>>,
 
     /// 用户信息
     pub user: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub user_detail: OnceCell<UserDetail>,
+    pub user_detail: OnceCell<Arc<UserDetail>>,
 
     /// 请求来源信息
     pub source: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub source_detail: OnceCell<SourceDetail>,
+    pub source_detail: OnceCell<Arc<SourceDetail>>,
 
     /// 隐私法规信息
     pub regs: Option<Box<OwnedValue>>,
     #[serde(skip)]
-    pub regs_detail: OnceCell<RegsDetail>,
+    pub regs_detail: OnceCell<Arc<RegsDetail>>,
 
     // 其它简单字段
     pub test: Option<i32>,
@@ -55,6 +62,8 @@ pub struct BidRequest {
     pub cur: Option<Vec<String>>,
     pub wlang: Option<Vec<String>>,
     pub bcat: Option<Vec<String>>,
+    /// bcat 等类目所用的分类体系版本，缺省为 1（IAB Content Category Taxonomy 1.0）
+    pub cattax: Option<i32>,
     pub badv: Option<Vec<String>>,
 }
 
@@ -62,7 +71,27 @@ pub struct BidRequest {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ImpDetail {
     pub id: String,
+    /// 部分 SSP 以字符串下发底价（如 "1.25"），按数值解析
+    #[serde(default, deserialize_with = "flexible_opt_f64")]
     pub bidfloor: Option<f64>,
+    /// 以 CPM micros（整数，micros = CPM × 1e6）表示的底价，与 bidfloor 同时存在时优先
+    pub bidfloor_micros: Option<i64>,
+    /// 底价货币，缺省时按请求 cur 的第一个、再缺省为 USD
+    pub bidfloorcur: Option<String>,
+    /// 渲染广告的 SDK / 播放器名称（SDK 流量），随原始 imp 原样透传给 DSP
+    pub displaymanager: Option<String>,
+    /// 渲染广告的 SDK / 播放器版本
+    pub displaymanagerver: Option<String>,
+    /// 点击打开方式：0 = 内嵌浏览器（webview），1 = 系统浏览器
+    pub clickbrowser: Option<i32>,
+    /// 是否要求 HTTPS 创意及追踪：0 = 不要求，1 = 要求；缺省时由请求上下文推断
+    pub secure: Option<i32>,
+    /// 展示位的质量/可见性等指标（如预测可见率），随原始 imp 原样透传给 DSP
+    pub metric: Option<Vec<Metric>>,
+    /// 竞价到实际展示之间可能间隔的秒数（如缓存广告、预加载场景）
+    pub exp: Option<i32>,
+    /// 是否为插屏（全屏）广告位：0 = 否，1 = 是
+    pub instl: Option<i32>,
 
     /// banner 信息延迟解析：原始 JSON 存为 OwnedValue
     pub banner: Option<Box<OwnedValue>>,
@@ -90,12 +119,65 @@ pub struct ImpDetail {
     pub pmp_detail: OnceCell<PmpDetail>,
 }
 
+/// Metric 表示 imp.metric 中的单个指标
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metric {
+    /// 指标类型，如 "viewability"
+    #[serde(rename = "type")]
+    pub metric_type: String,
+    /// 指标值，概率类指标取值 0.0 ~ 1.0
+    pub value: f64,
+    /// 指标来源，如 "EXCHANGE" 或第三方厂商名
+    pub vendor: Option<String>,
+}
+
 /// BannerDetail 表示 banner 解析后的数据结构
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BannerDetail {
+    pub w: Option<i32>,
+    pub h: Option<i32>,
+    /// 可接受的尺寸列表，与 w / h 同时存在时均可接受
+    pub format: Option<Vec<Format>>,
+    /// 弹性广告位的宽高范围（含边界），未声明的边界不限制
+    pub wmin: Option<i32>,
+    pub wmax: Option<i32>,
+    pub hmin: Option<i32>,
+    pub hmax: Option<i32>,
+    /// 支持的 API 框架（1 = VPAID 1.0, 2 = VPAID 2.0, 3 = MRAID-1, 5 = MRAID-2, 6 = MRAID-3, 7 = OMID-1 等）
+    pub api: Option<Vec<i32>>,
+    // 可扩展其它字段
+}
+
+/// Format 表示 banner.format 中的单个尺寸
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
     pub w: i32,
     pub h: i32,
-    // 可扩展其它字段
+}
+
+impl BannerDetail {
+    /// 是否声明了 w / h、format 或尺寸范围
+    pub fn declares_size(&self) -> bool {
+        self.w.zip(self.h).is_some() || self.format.as_ref().is_some_and(|f| !f.is_empty())
+            || self.wmin.is_some() || self.wmax.is_some() || self.hmin.is_some() || self.hmax.is_some()
+    }
+
+    /// 判断创意尺寸是否可以投放：与 w / h 或 format 中任一尺寸完全一致，或落在 wmin ~ wmax、hmin ~ hmax 范围内；
+    /// 广告位未声明任何尺寸时不做限制
+    pub fn accepts_size(&self, w: i32, h: i32) -> bool {
+        let exact = self.w.zip(self.h);
+        let formats = self.format.as_deref().unwrap_or_default();
+        let ranged = self.wmin.is_some() || self.wmax.is_some() || self.hmin.is_some() || self.hmax.is_some();
+        if exact.is_none() && formats.is_empty() && !ranged {
+            return true;
+        }
+        let in_range = |value: i32, min: Option<i32>, max: Option<i32>| {
+            min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+        };
+        exact == Some((w, h))
+            || formats.iter().any(|format| format.w == w && format.h == h)
+            || (ranged && in_range(w, self.wmin, self.wmax) && in_range(h, self.hmin, self.hmax))
+    }
 }
 
 /// VideoDetail 表示 video 解析后的数据结构
@@ -107,6 +189,20 @@ pub struct VideoDetail {
     pub protocols: Option<Vec<i32>>,
     pub w: Option<i32>,
     pub h: Option<i32>,
+    /// 支持的 API 框架，取值同 BannerDetail::api
+    pub api: Option<Vec<i32>>,
+    /// 可与视频同时展示的伴随广告位（Banner 对象）
+    pub companionad: Option<Vec<CompanionAd>>,
+    /// 支持的伴随广告类型：1 = Static Resource，2 = HTML Resource，3 = iframe Resource
+    pub companiontype: Option<Vec<i32>>,
+}
+
+/// CompanionAd 表示 video.companionad 中的单个伴随广告位
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompanionAd {
+    pub id: Option<String>,
+    pub w: Option<i32>,
+    pub h: Option<i32>,
 }
 
 /// AudioDetail 表示 audio 解析后的数据结构
@@ -135,7 +231,39 @@ pub struct PmpDetail {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Deal {
     pub id: String,
+    /// 同 imp.bidfloor，接受数字字符串
+    #[serde(default, deserialize_with = "flexible_opt_f64")]
     pub bidfloor: Option<f64>,
+    /// bidfloor 的货币，缺省为 USD
+    pub bidfloorcur: Option<String>,
+    /// 该 deal 的竞价类型，覆盖请求级的 at（1 = 一价，2 = 二价，3 = 按 bidfloor 固定价）
+    pub at: Option<i32>,
+    /// 允许在该 deal 上交易的席位，缺省或为空时不限制
+    pub wseat: Option<Vec<String>>,
+    /// 允许在该 deal 上投放的广告主域名，缺省或为空时不限制
+    pub wadomain: Option<Vec<String>>,
+}
+
+impl Deal {
+    /// 判断席位是否允许在该 deal 上交易
+    pub fn allows_seat(&self, seat: Option<&str>) -> bool {
+        match self.wseat.as_deref() {
+            Some(wseat) if !wseat.is_empty() => seat.is_some_and(|seat| wseat.iter().any(|s| s == seat)),
+            _ => true,
+        }
+    }
+
+    /// 判断出价的广告主域名是否允许在该 deal 上投放：限制了 wadomain 时，
+    /// adomain 必须非空且全部在 wadomain 内（不区分大小写）
+    pub fn allows_adomains(&self, adomains: &[String]) -> bool {
+        match self.wadomain.as_deref() {
+            Some(wadomain) if !wadomain.is_empty() => {
+                !adomains.is_empty()
+                    && adomains.iter().all(|domain| wadomain.iter().any(|allowed| allowed.eq_ignore_ascii_case(domain)))
+            }
+            _ => true,
+        }
+    }
 }
 
 /// SiteDetail 表示网站信息解析后的数据结构
@@ -144,6 +272,10 @@ pub struct SiteDetail {
     pub id: String,
     pub name: Option<String>,
     pub domain: Option<String>,
+    /// 展示广告的页面 URL
+    pub page: Option<String>,
+    /// 网站内容类目（IAB）
+    pub cat: Option<Vec<String>>,
 }
 
 /// AppDetail 表示应用信息解析后的数据结构
@@ -151,6 +283,10 @@ pub struct SiteDetail {
 pub struct AppDetail {
     pub id: String,
     pub name: Option<String>,
+    /// 应用商店 URL
+    pub storeurl: Option<String>,
+    /// 应用内容类目（IAB）
+    pub cat: Option<Vec<String>>,
 }
 
 /// DeviceDetail 表示设备信息解析后的数据结构
@@ -158,6 +294,29 @@ pub struct AppDetail {
 pub struct DeviceDetail {
     pub ua: Option<String>,
     pub ip: Option<String>,
+
+    /// 地理位置信息延迟解析
+    pub geo: Option<Box<OwnedValue>>,
+    #[serde(skip)]
+    pub geo_detail: OnceCell<GeoDetail>,
+}
+
+/// GeoDetail 表示 device.geo 解析后的数据结构
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GeoDetail {
+    /// 纬度（-90.0 ~ 90.0），属于精确位置，隐私规则要求时会被移除
+    pub lat: Option<f64>,
+    /// 经度（-180.0 ~ 180.0），属于精确位置，隐私规则要求时会被移除
+    pub lon: Option<f64>,
+    /// 国家代码（ISO-3166-1 alpha-3）
+    pub country: Option<String>,
+    /// 地区代码（ISO-3166-2）
+    pub region: Option<String>,
+    /// 位置来源：1 = GPS/定位服务，2 = IP 地址，3 = 用户提供
+    #[serde(rename = "type")]
+    pub geo_type: Option<i32>,
+    /// 位置精度（米）
+    pub accuracy: Option<i32>,
 }
 
 /// UserDetail 表示用户信息解析后的数据结构
@@ -178,145 +337,343 @@ pub struct SourceDetail {
 pub struct RegsDetail {
     pub coppa: Option<i32>,
     pub gdpr: Option<i32>,
+    /// IAB Global Privacy Platform（GPP）字符串，随原始 regs 原样透传给 DSP
+    pub gpp: Option<String>,
+    /// gpp 中适用于本次请求的 section id
+    pub gpp_sid: Option<Vec<i32>>,
 }
 
-// Getter 方法实现
+/// 将精确地理位置视为敏感信息的 GPP section：7 = US National，8 ~ 12 = 美国各州（CA、VA、CO、UT、CT）
+pub const GPP_SIDS_SENSITIVE_GEO: [i32; 6] = [7, 8, 9, 10, 11, 12];
+
+impl RegsDetail {
+    /// 是否需要移除精确地理位置：COPPA 流量，或适用的 GPP section 将精确位置视为敏感信息
+    pub fn requires_coarse_geo(&self) -> bool {
+        self.coppa == Some(1)
+            || self.gpp_sid.iter().flatten().any(|sid| GPP_SIDS_SENSITIVE_GEO.contains(sid))
+    }
+}
+
+/// 将延迟解析的原始 JSON 转换为具体结构，格式不符时返回错误而不是 panic（失败结果不缓存）。
+/// 直接从 OwnedValue 反序列化，不经过中间 JSON 文本
+fn parse_lazy<T: serde::de::DeserializeOwned>(raw: &OwnedValue) -> Result<T, simd_json::Error> {
+    T::deserialize(raw)
+}
+
+// Getter 方法实现，嵌套对象格式不符时返回 None
 impl BidRequest {
     pub fn get_imp_details(&self) -> &Vec<ImpDetail> {
         self.imp_details.get_or_init(|| {
-            if let Some(arr) = self.imp.as_array() {
-                arr.iter().map(|item| {
-                    let s = serde_json::to_string(&*item)
-                        .expect("Failed to convert imp item to JSON string");
-                    serde_json::from_str(&s)
-                        .expect("Failed to parse imp item into ImpDetail")
-                }).collect()
-            } else {
-                Vec::new()
-            }
+            // 无法解析的元素被跳过，请求校验阶段已拒绝这类请求
+            Arc::new(match self.imp.as_array() {
+                Some(arr) => arr.iter().filter_map(|item| parse_lazy(item).ok()).collect(),
+                None => Vec::new(),
+            })
         })
     }
 
     pub fn get_site_detail(&self) -> Option<&SiteDetail> {
-        self.site.as_ref().map(|raw| {
-            self.site_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert site to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse site into SiteDetail")
-            })
-        })
+        self.site.as_ref().and_then(|raw| self.site_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
     }
 
     pub fn get_app_detail(&self) -> Option<&AppDetail> {
-        self.app.as_ref().map(|raw| {
-            self.app_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert app to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse app into AppDetail")
-            })
-        })
+        self.app.as_ref().and_then(|raw| self.app_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
     }
 
     pub fn get_device_detail(&self) -> Option<&DeviceDetail> {
-        self.device.as_ref().map(|raw| {
-            self.device_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert device to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse device into DeviceDetail")
-            })
-        })
+        self.device.as_ref().and_then(|raw| self.device_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
+    }
+
+    /// 席位是否被请求的 bseat 屏蔽
+    pub fn is_seat_blocked(&self, seat: Option<&str>) -> bool {
+        seat.is_some_and(|seat| self.bseat.iter().flatten().any(|blocked| blocked == seat))
+    }
+
+    /// 设备地理位置（device.geo）
+    pub fn get_geo(&self) -> Option<&GeoDetail> {
+        self.get_device_detail()?.get_geo()
+    }
+
+    /// 移除 device.geo 中的精确经纬度，只保留国家、地区等粗粒度位置，
+    /// 转发给 DSP 的请求同样不再携带经纬度
+    pub fn strip_precise_geo(&mut self) {
+        let Some(geo) = self.device.as_mut()
+            .and_then(|device| device.as_object_mut())
+            .and_then(|device| device.get_mut("geo"))
+            .and_then(|geo| geo.as_object_mut()) else {
+            return;
+        };
+        let lat = geo.remove("lat");
+        let lon = geo.remove("lon");
+        if lat.is_some() || lon.is_some() {
+            self.device_detail = OnceCell::new();
+        }
     }
 
     pub fn get_user_detail(&self) -> Option<&UserDetail> {
-        self.user.as_ref().map(|raw| {
-            self.user_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert user to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse user into UserDetail")
-            })
-        })
+        self.user.as_ref().and_then(|raw| self.user_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
     }
 
     pub fn get_source_detail(&self) -> Option<&SourceDetail> {
-        self.source.as_ref().map(|raw| {
-            self.source_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert source to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse source into SourceDetail")
-            })
-        })
+        self.source.as_ref().and_then(|raw| self.source_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
     }
 
     pub fn get_regs_detail(&self) -> Option<&RegsDetail> {
-        self.regs.as_ref().map(|raw| {
-            self.regs_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert regs to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse regs into RegsDetail")
-            })
-        })
+        self.regs.as_ref().and_then(|raw| self.regs_detail.get_or_try_init(|| parse_lazy(raw).map(Arc::new)).ok()).map(|detail| &**detail)
+    }
+}
+
+impl DeviceDetail {
+    pub fn get_geo(&self) -> Option<&GeoDetail> {
+        self.geo.as_ref().and_then(|raw| self.geo_detail.get_or_try_init(|| parse_lazy(raw)).ok())
     }
 }
 
 impl ImpDetail {
+    /// 是否为插屏广告位（instl = 1）
+    pub fn is_interstitial(&self) -> bool {
+        self.instl == Some(1)
+    }
+
+    /// 获取指定类型的指标值，供 bid shading、DSP 路由等使用
+    pub fn metric_value(&self, metric_type: &str) -> Option<f64> {
+        self.metric.as_ref()?
+            .iter()
+            .find(|m| m.metric_type == metric_type)
+            .map(|m| m.value)
+    }
+
+    /// 展示位声明的广告类型（banner / video / native）
+    pub fn ad_types(&self) -> Vec<AdType> {
+        [(self.banner.is_some(), AdType::Banner), (self.video.is_some(), AdType::Video), (self.native.is_some(), AdType::Native)]
+            .into_iter()
+            .filter_map(|(present, ad_type)| present.then_some(ad_type))
+            .collect()
+    }
+
+    /// 展示位支持的 API 框架（banner 与 video 声明的并集），均未声明时返回 None
+    pub fn supported_apis(&self) -> Option<Vec<i32>> {
+        let banner_api = self.get_banner_detail().and_then(|b| b.api.as_ref());
+        let video_api = self.get_video_detail().and_then(|v| v.api.as_ref());
+        if banner_api.is_none() && video_api.is_none() {
+            return None;
+        }
+        let mut apis: Vec<i32> = banner_api.into_iter().chain(video_api).flatten().copied().collect();
+        apis.sort_unstable();
+        apis.dedup();
+        Some(apis)
+    }
+
     pub fn get_banner_detail(&self) -> Option<&BannerDetail> {
-        self.banner.as_ref().map(|raw| {
-            self.banner_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert banner to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse banner into BannerDetail")
-            })
-        })
+        self.banner.as_ref().and_then(|raw| self.banner_detail.get_or_try_init(|| parse_lazy(raw)).ok())
     }
 
     pub fn get_video_detail(&self) -> Option<&VideoDetail> {
-        self.video.as_ref().map(|raw| {
-            self.video_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert video to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse video into VideoDetail")
-            })
-        })
+        self.video.as_ref().and_then(|raw| self.video_detail.get_or_try_init(|| parse_lazy(raw)).ok())
     }
 
     pub fn get_audio_detail(&self) -> Option<&AudioDetail> {
-        self.audio.as_ref().map(|raw| {
-            self.audio_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert audio to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse audio into AudioDetail")
-            })
-        })
+        self.audio.as_ref().and_then(|raw| self.audio_detail.get_or_try_init(|| parse_lazy(raw)).ok())
     }
 
     pub fn get_native_detail(&self) -> Option<&NativeDetail> {
-        self.native.as_ref().map(|raw| {
-            self.native_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert native to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse native into NativeDetail")
-            })
-        })
+        self.native.as_ref().and_then(|raw| self.native_detail.get_or_try_init(|| parse_lazy(raw)).ok())
     }
 
     pub fn get_pmp_detail(&self) -> Option<&PmpDetail> {
-        self.pmp.as_ref().map(|raw| {
-            self.pmp_detail.get_or_init(|| {
-                let s = serde_json::to_string(&*raw)
-                    .expect("Failed to convert pmp to JSON string");
-                serde_json::from_str(&s)
-                    .expect("Failed to parse pmp into PmpDetail")
-            })
-        })
+        self.pmp.as_ref().and_then(|raw| self.pmp_detail.get_or_try_init(|| parse_lazy(raw)).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// fuzz/corpus/bid_request 下的种子请求
+    const SEED_CORPUS: [&str; 5] = [
+        include_str!("../../fuzz/corpus/bid_request/seed_banner.json"),
+        include_str!("../../fuzz/corpus/bid_request/seed_malformed_nested.json"),
+        include_str!("../../fuzz/corpus/bid_request/seed_native_audio.json"),
+        include_str!("../../fuzz/corpus/bid_request/seed_partial.json"),
+        include_str!("../../fuzz/corpus/bid_request/seed_video_pmp.json"),
+    ];
+
+    fn parse(json: &str) -> BidRequest {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn getters_do_not_panic_on_the_seed_corpus() {
+        for seed in SEED_CORPUS {
+            let mut bid_request = parse(seed);
+            for imp in bid_request.get_imp_details() {
+                let _ = (imp.get_banner_detail(), imp.get_video_detail(), imp.get_audio_detail());
+                let _ = (imp.get_native_detail(), imp.get_pmp_detail(), imp.supported_apis());
+            }
+            let _ = (bid_request.get_site_detail(), bid_request.get_app_detail(), bid_request.get_device_detail());
+            let _ = (bid_request.get_geo(), bid_request.get_user_detail(), bid_request.get_source_detail(), bid_request.get_regs_detail());
+            bid_request.strip_precise_geo();
+            let _ = bid_request.get_geo();
+        }
+    }
+
+    #[test]
+    fn malformed_nested_objects_read_as_absent() {
+        let bid_request = parse(SEED_CORPUS[1]);
+        let imp = &bid_request.get_imp_details()[0];
+        assert!(imp.get_banner_detail().is_none());
+        assert!(imp.get_video_detail().is_none());
+        assert!(imp.get_pmp_detail().is_none());
+        assert!(bid_request.get_site_detail().is_none());
+        assert!(bid_request.get_geo().is_none());
+        assert!(bid_request.get_regs_detail().is_none());
+    }
+
+    #[test]
+    fn clone_shares_already_parsed_details() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1", "banner": {"w": 300, "h": 250}}], "device": {"ua": "Mozilla"}, "user": {"id": "u1"}}"#);
+        bid_request.get_imp_details();
+        bid_request.get_device_detail();
+
+        let cloned = bid_request.clone();
+        assert!(Arc::ptr_eq(cloned.imp_details.get().unwrap(), bid_request.imp_details.get().unwrap()));
+        assert!(Arc::ptr_eq(cloned.device_detail.get().unwrap(), bid_request.device_detail.get().unwrap()));
+        // 克隆前未解析的字段在克隆上仍按需解析
+        assert!(cloned.user_detail.get().is_none());
+        assert_eq!(cloned.get_user_detail().unwrap().id.as_deref(), Some("u1"));
+    }
+
+    #[test]
+    fn device_geo_is_parsed_with_country_and_coordinates() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1"}], "device": {"ua": "Mozilla", "geo": {
+            "lat": 31.2304, "lon": 121.4737, "country": "CHN", "region": "CN-SH", "type": 2, "accuracy": 500
+        }}}"#);
+        let geo = bid_request.get_geo().unwrap();
+        assert_eq!((geo.lat, geo.lon), (Some(31.2304), Some(121.4737)));
+        assert_eq!(geo.country.as_deref(), Some("CHN"));
+        assert_eq!(geo.region.as_deref(), Some("CN-SH"));
+        assert_eq!((geo.geo_type, geo.accuracy), (Some(2), Some(500)));
+        assert!(bid_request.get_device_detail().unwrap().get_geo().is_some());
+    }
+
+    #[test]
+    fn stripping_precise_geo_keeps_the_coarse_location() {
+        let mut bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1"}], "device": {"geo": {"lat": 31.2304, "lon": 121.4737, "country": "CHN"}}}"#);
+        assert_eq!(bid_request.get_geo().unwrap().lat, Some(31.2304));
+        bid_request.strip_precise_geo();
+        let geo = bid_request.get_geo().unwrap();
+        assert_eq!((geo.lat, geo.lon), (None, None));
+        assert_eq!(geo.country.as_deref(), Some("CHN"));
+        let forwarded = serde_json::to_value(&bid_request).unwrap();
+        assert_eq!(forwarded["device"]["geo"], serde_json::json!({"country": "CHN"}));
+    }
+
+    #[test]
+    fn gpp_string_and_section_ids_are_parsed() {
+        let regs = |regs: &str| parse(&format!(r#"{{"id": "r1", "imp": [{{"id": "1"}}], "regs": {}}}"#, regs)).get_regs_detail().cloned().unwrap();
+        let us = regs(r#"{"gpp": "DBABMA~CPXxRfAPXxRfAAfKABENB-CgAAAAAAAAAAYgAAAAAAAA", "gpp_sid": [7, 8]}"#);
+        assert_eq!(us.gpp.as_deref(), Some("DBABMA~CPXxRfAPXxRfAAfKABENB-CgAAAAAAAAAAYgAAAAAAAA"));
+        assert_eq!(us.gpp_sid, Some(vec![7, 8]));
+        assert!(us.requires_coarse_geo());
+        assert!(!regs(r#"{"gpp": "DBABMA", "gpp_sid": [2]}"#).requires_coarse_geo());
+        assert!(regs(r#"{"coppa": 1}"#).requires_coarse_geo());
+        assert!(!regs(r#"{}"#).requires_coarse_geo());
+    }
+
+    #[test]
+    fn displaymanager_fields_are_parsed() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1", "displaymanager": "SDK-X", "displaymanagerver": "4.2.1"}, {"id": "2"}]}"#);
+        let imps = bid_request.get_imp_details();
+        assert_eq!(imps[0].displaymanager.as_deref(), Some("SDK-X"));
+        assert_eq!(imps[0].displaymanagerver.as_deref(), Some("4.2.1"));
+        assert_eq!(imps[1].displaymanager, None);
+    }
+
+    #[test]
+    fn clickbrowser_is_parsed() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1", "clickbrowser": 0}, {"id": "2"}]}"#);
+        let imps = bid_request.get_imp_details();
+        assert_eq!(imps[0].clickbrowser, Some(0));
+        assert_eq!(imps[1].clickbrowser, None);
+    }
+
+    #[test]
+    fn video_companion_requirements_are_parsed() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1", "video": {
+            "mimes": ["video/mp4"], "companiontype": [1, 3],
+            "companionad": [{"id": "c1", "w": 300, "h": 250}, {"w": 728, "h": 90}]
+        }}]}"#);
+        let video = bid_request.get_imp_details()[0].get_video_detail().unwrap();
+        assert_eq!(video.companiontype, Some(vec![1, 3]));
+        let companions = video.companionad.as_ref().unwrap();
+        assert_eq!(companions.len(), 2);
+        assert_eq!((companions[0].id.as_deref(), companions[0].w, companions[0].h), (Some("c1"), Some(300), Some(250)));
+        assert_eq!((companions[1].id.as_deref(), companions[1].w), (None, Some(728)));
+    }
+
+    #[test]
+    fn banner_and_video_api_arrays_are_parsed() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [
+            {"id": "1", "banner": {"w": 300, "h": 250, "api": [5, 7]}, "video": {"mimes": ["video/mp4"], "api": [2, 7]}},
+            {"id": "2", "banner": {"w": 300, "h": 250}}
+        ]}"#);
+        let imps = bid_request.get_imp_details();
+        assert_eq!(imps[0].get_banner_detail().unwrap().api, Some(vec![5, 7]));
+        assert_eq!(imps[0].get_video_detail().unwrap().api, Some(vec![2, 7]));
+        assert_eq!(imps[0].supported_apis(), Some(vec![2, 5, 7]));
+        assert_eq!(imps[1].supported_apis(), None);
+    }
+
+    #[test]
+    fn metric_array_is_parsed_with_vendor_and_value() {
+        let bid_request = parse(r#"{"id": "r1", "imp": [{"id": "1", "metric": [
+            {"type": "viewability", "value": 0.85, "vendor": "EXCHANGE"},
+            {"type": "click_through_rate", "value": 0.02}
+        ]}]}"#);
+        let imp = &bid_request.get_imp_details()[0];
+        let metrics = imp.metric.as_ref().unwrap();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].vendor.as_deref(), Some("EXCHANGE"));
+        assert_eq!(metrics[1].vendor, None);
+        assert_eq!(imp.metric_value("viewability"), Some(0.85));
+        assert_eq!(imp.metric_value("session_depth"), None);
+    }
+
+    /// 原先 parse_lazy 的实现：先序列化为 JSON 文本再解析
+    fn parse_via_text<T: serde::de::DeserializeOwned>(raw: &OwnedValue) -> Option<T> {
+        serde_json::from_str(&serde_json::to_string(raw).ok()?).ok()
+    }
+
+    /// 直接反序列化与经 JSON 文本解析的结果（含是否解析成功）一致
+    fn assert_same_parse<T: serde::de::DeserializeOwned + Serialize>(raw: &OwnedValue) {
+        let direct = parse_lazy::<T>(raw).ok().map(|detail| serde_json::to_value(detail).unwrap());
+        let via_text = parse_via_text::<T>(raw).map(|detail| serde_json::to_value(detail).unwrap());
+        assert_eq!(direct, via_text, "{}", raw);
+    }
+
+    #[test]
+    fn direct_parse_matches_the_json_text_round_trip() {
+        for seed in SEED_CORPUS {
+            let bid_request = parse(seed);
+            for imp in bid_request.imp.as_array().into_iter().flatten() {
+                assert_same_parse::<ImpDetail>(imp);
+            }
+            for imp in bid_request.get_imp_details() {
+                imp.banner.iter().for_each(|raw| assert_same_parse::<BannerDetail>(raw));
+                imp.video.iter().for_each(|raw| assert_same_parse::<VideoDetail>(raw));
+                imp.audio.iter().for_each(|raw| assert_same_parse::<AudioDetail>(raw));
+                imp.native.iter().for_each(|raw| assert_same_parse::<NativeDetail>(raw));
+                imp.pmp.iter().for_each(|raw| assert_same_parse::<PmpDetail>(raw));
+            }
+            bid_request.site.iter().for_each(|raw| assert_same_parse::<SiteDetail>(raw));
+            bid_request.app.iter().for_each(|raw| assert_same_parse::<AppDetail>(raw));
+            bid_request.device.iter().for_each(|raw| assert_same_parse::<DeviceDetail>(raw));
+            bid_request.user.iter().for_each(|raw| assert_same_parse::<UserDetail>(raw));
+            bid_request.source.iter().for_each(|raw| assert_same_parse::<SourceDetail>(raw));
+            bid_request.regs.iter().for_each(|raw| assert_same_parse::<RegsDetail>(raw));
+            if let Some(device) = bid_request.get_device_detail() {
+                device.geo.iter().for_each(|raw| assert_same_parse::<GeoDetail>(raw));
+            }
+        }
     }
 }
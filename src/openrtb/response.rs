@@ -2,6 +2,8 @@
 
 use serde::{Serialize, Deserialize};
 
+use crate::openrtb::de::flexible_f64;
+
 /// **Top-level OpenRTB Bid Response（竞价响应）**
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BidResponse {
@@ -11,6 +13,89 @@ pub struct BidResponse {
     pub cur: Option<String>,      // 竞价的货币类型（如 USD, CNY）
     pub customdata: Option<String>, // DSP 返回的自定义数据
     pub nbr: Option<i32>,         // 竞价失败原因代码（仅在未填充广告时返回）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ext: Option<serde_json::Value>, // 扩展字段（如多展示位请求中各未填充展示位的原因 imp_nbr）
+}
+
+impl BidResponse {
+    /// 移除不含任何出价的 SeatBid，返回是否仍有出价
+    pub fn drop_empty_seatbids(&mut self) -> bool {
+        self.seatbid.retain(|seatbid| !seatbid.bid.is_empty());
+        !self.seatbid.is_empty()
+    }
+}
+
+/// OpenRTB 无竞价原因（BidResponse.nbr），DSP 返回的 nbr 与 ADX 返回给 SSP 的 nbr 共用
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum NoBidReason {
+    UnknownError,
+    TechnicalError,
+    InvalidRequest,
+    KnownWebSpider,
+    SuspectedNonHumanTraffic,
+    CloudDataCenterOrProxyIp,
+    UnsupportedDevice,
+    BlockedPublisherOrSite,
+    UnmatchedUser,
+    DailyReaderCapMet,
+    DailyDomainCapMet,
+    /// 规范之外的取值（如交易平台自定义的 500 以上的代码）
+    Other,
+}
+
+impl NoBidReason {
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            0 => NoBidReason::UnknownError,
+            1 => NoBidReason::TechnicalError,
+            2 => NoBidReason::InvalidRequest,
+            3 => NoBidReason::KnownWebSpider,
+            4 => NoBidReason::SuspectedNonHumanTraffic,
+            5 => NoBidReason::CloudDataCenterOrProxyIp,
+            6 => NoBidReason::UnsupportedDevice,
+            7 => NoBidReason::BlockedPublisherOrSite,
+            8 => NoBidReason::UnmatchedUser,
+            9 => NoBidReason::DailyReaderCapMet,
+            10 => NoBidReason::DailyDomainCapMet,
+            _ => NoBidReason::Other,
+        }
+    }
+
+    /// OpenRTB 代码，规范之外的取值映射为 0（未知错误）
+    pub fn code(&self) -> i32 {
+        match self {
+            NoBidReason::UnknownError | NoBidReason::Other => 0,
+            NoBidReason::TechnicalError => 1,
+            NoBidReason::InvalidRequest => 2,
+            NoBidReason::KnownWebSpider => 3,
+            NoBidReason::SuspectedNonHumanTraffic => 4,
+            NoBidReason::CloudDataCenterOrProxyIp => 5,
+            NoBidReason::UnsupportedDevice => 6,
+            NoBidReason::BlockedPublisherOrSite => 7,
+            NoBidReason::UnmatchedUser => 8,
+            NoBidReason::DailyReaderCapMet => 9,
+            NoBidReason::DailyDomainCapMet => 10,
+        }
+    }
+
+    /// 日志与统计中使用的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoBidReason::UnknownError => "unknown_error",
+            NoBidReason::TechnicalError => "technical_error",
+            NoBidReason::InvalidRequest => "invalid_request",
+            NoBidReason::KnownWebSpider => "known_web_spider",
+            NoBidReason::SuspectedNonHumanTraffic => "suspected_non_human_traffic",
+            NoBidReason::CloudDataCenterOrProxyIp => "cloud_data_center_or_proxy_ip",
+            NoBidReason::UnsupportedDevice => "unsupported_device",
+            NoBidReason::BlockedPublisherOrSite => "blocked_publisher_or_site",
+            NoBidReason::UnmatchedUser => "unmatched_user",
+            NoBidReason::DailyReaderCapMet => "daily_reader_cap_met",
+            NoBidReason::DailyDomainCapMet => "daily_domain_cap_met",
+            NoBidReason::Other => "other",
+        }
+    }
 }
 
 /// **SeatBid（DSP 返回的竞价广告列表）**
@@ -26,17 +111,24 @@ pub struct SeatBid {
 pub struct Bid {
     pub id: String,               // 竞价 ID（DSP 生成）
     pub impid: String,            // 对应的 Impression ID
-    pub price: f64,               // 竞价价格（货币单位同 `BidResponse.cur`）
+    #[serde(deserialize_with = "flexible_f64")]
+    pub price: f64,               // 竞价价格（货币单位同 `BidResponse.cur`），兼容整数与数字字符串
     pub nurl: Option<String>,     // 点击时通知 DSP 的 URL
+    #[serde(default)]
+    pub lurl: Option<String>,     // 败出时通知 DSP 的 URL
+    #[serde(default)]
+    pub burl: Option<String>,     // 计费通知 URL（渲染 / 可计费事件时由 SSP 触发）
     pub adm: Option<String>,      // 广告物料（HTML、VAST XML、原生 JSON）
     pub adid: Option<String>,     // DSP 生成的广告 ID
     pub adomain: Option<Vec<String>>, // 广告主域名（如 ["example.com"]）
     pub cid: Option<String>,      // DSP 生成的 Campaign ID（广告系列 ID）
     pub crid: Option<String>,     // DSP 生成的 Creative ID（创意 ID）
     pub cat: Option<Vec<String>>, // 广告类别（IAB 分类）
+    pub cattax: Option<i32>,      // cat 所用的分类体系版本，缺省为 1（IAB 1.0）
     pub attr: Option<Vec<i32>>,   // 广告属性（如自动播放、可跳过等）
     pub dealid: Option<String>,   // 与 PMP 交易对应的 Deal ID
     pub h: Option<i32>,           // 广告高度（像素）
     pub w: Option<i32>,           // 广告宽度（像素）
+    pub exp: Option<i32>,         // 创意在竞价后保持有效的秒数
     pub ext: Option<serde_json::Value>, // 额外的扩展字段
 }
@@ -0,0 +1,177 @@
+// src/openrtb/typed.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::openrtb::de::flexible_opt_f64;
+use crate::openrtb::request::{
+    AppDetail, AudioDetail, BannerDetail, BidRequest, DeviceDetail, GeoDetail, ImpDetail, Metric, NativeDetail,
+    PmpDetail, RegsDetail, SiteDetail, SourceDetail, UserDetail, VideoDetail,
+};
+
+/// 完全类型化的 BidRequest（typed-request feature）：所有嵌套对象都是普通的 Rust 结构体，
+/// 不经过 getter 即可访问，适合作为库嵌入、不需要延迟解析的场景。
+/// 只保留已建模的字段，由延迟解析的 BidRequest 转换而来时，未建模的字段与格式不符的嵌套对象会被丢弃
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TypedBidRequest {
+    pub id: String,
+    pub imp: Vec<TypedImp>,
+    pub site: Option<SiteDetail>,
+    pub app: Option<AppDetail>,
+    pub device: Option<TypedDevice>,
+    pub user: Option<UserDetail>,
+    pub source: Option<SourceDetail>,
+    pub regs: Option<RegsDetail>,
+    pub test: Option<i32>,
+    pub at: Option<i32>,
+    pub tmax: Option<u64>,
+    pub wseat: Option<Vec<String>>,
+    pub bseat: Option<Vec<String>>,
+    pub allimps: Option<i32>,
+    pub cur: Option<Vec<String>>,
+    pub wlang: Option<Vec<String>>,
+    pub bcat: Option<Vec<String>>,
+    pub cattax: Option<i32>,
+    pub badv: Option<Vec<String>>,
+}
+
+/// 完全类型化的 imp，字段含义同 ImpDetail
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TypedImp {
+    pub id: String,
+    #[serde(default, deserialize_with = "flexible_opt_f64")]
+    pub bidfloor: Option<f64>,
+    pub bidfloor_micros: Option<i64>,
+    pub bidfloorcur: Option<String>,
+    pub displaymanager: Option<String>,
+    pub displaymanagerver: Option<String>,
+    pub clickbrowser: Option<i32>,
+    pub secure: Option<i32>,
+    pub metric: Option<Vec<Metric>>,
+    pub exp: Option<i32>,
+    pub instl: Option<i32>,
+    pub banner: Option<BannerDetail>,
+    pub video: Option<VideoDetail>,
+    pub audio: Option<AudioDetail>,
+    pub native: Option<NativeDetail>,
+    pub pmp: Option<PmpDetail>,
+}
+
+/// 完全类型化的 device，字段含义同 DeviceDetail
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TypedDevice {
+    pub ua: Option<String>,
+    pub ip: Option<String>,
+    pub geo: Option<GeoDetail>,
+}
+
+impl From<&ImpDetail> for TypedImp {
+    fn from(imp: &ImpDetail) -> Self {
+        Self {
+            id: imp.id.clone(),
+            bidfloor: imp.bidfloor,
+            bidfloor_micros: imp.bidfloor_micros,
+            bidfloorcur: imp.bidfloorcur.clone(),
+            displaymanager: imp.displaymanager.clone(),
+            displaymanagerver: imp.displaymanagerver.clone(),
+            clickbrowser: imp.clickbrowser,
+            secure: imp.secure,
+            metric: imp.metric.clone(),
+            exp: imp.exp,
+            instl: imp.instl,
+            banner: imp.get_banner_detail().cloned(),
+            video: imp.get_video_detail().cloned(),
+            audio: imp.get_audio_detail().cloned(),
+            native: imp.get_native_detail().cloned(),
+            pmp: imp.get_pmp_detail().cloned(),
+        }
+    }
+}
+
+impl From<&DeviceDetail> for TypedDevice {
+    fn from(device: &DeviceDetail) -> Self {
+        Self {
+            ua: device.ua.clone(),
+            ip: device.ip.clone(),
+            geo: device.get_geo().cloned(),
+        }
+    }
+}
+
+impl From<&BidRequest> for TypedBidRequest {
+    fn from(request: &BidRequest) -> Self {
+        Self {
+            id: request.id.clone(),
+            imp: request.get_imp_details().iter().map(TypedImp::from).collect(),
+            site: request.get_site_detail().cloned(),
+            app: request.get_app_detail().cloned(),
+            device: request.get_device_detail().map(TypedDevice::from),
+            user: request.get_user_detail().cloned(),
+            source: request.get_source_detail().cloned(),
+            regs: request.get_regs_detail().cloned(),
+            test: request.test,
+            at: request.at,
+            tmax: request.tmax,
+            wseat: request.wseat.clone(),
+            bseat: request.bseat.clone(),
+            allimps: request.allimps,
+            cur: request.cur.clone(),
+            wlang: request.wlang.clone(),
+            bcat: request.bcat.clone(),
+            cattax: request.cattax,
+            badv: request.badv.clone(),
+        }
+    }
+}
+
+impl TryFrom<&TypedBidRequest> for BidRequest {
+    type Error = serde_json::Error;
+
+    /// 转换回延迟解析的 BidRequest（经由 JSON 值，嵌套对象重新存为原始 JSON）
+    fn try_from(request: &TypedBidRequest) -> Result<Self, Self::Error> {
+        serde_json::to_value(request).and_then(serde_json::from_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lazy(value: serde_json::Value) -> BidRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn lazy_request_converts_to_typed_and_back() {
+        let request = lazy(json!({
+            "id": "r1",
+            "imp": [{"id": "1", "bidfloor": "0.5", "instl": 1, "banner": {"w": 320, "h": 480}, "pmp": {"deals": [{"id": "d1", "bidfloor": 2.0}]}}],
+            "site": {"id": "s1", "domain": "news.example.com"},
+            "device": {"ua": "Mozilla", "geo": {"country": "CHN"}},
+            "tmax": 300,
+            "cur": ["USD"],
+        }));
+        let typed = TypedBidRequest::from(&request);
+        assert_eq!(typed.imp[0].bidfloor, Some(0.5));
+        assert_eq!(typed.imp[0].banner.as_ref().and_then(|banner| banner.w), Some(320));
+        assert_eq!(typed.imp[0].pmp.as_ref().unwrap().deals.as_ref().unwrap()[0].id, "d1");
+        assert_eq!(typed.site.as_ref().unwrap().domain.as_deref(), Some("news.example.com"));
+        assert_eq!(typed.device.as_ref().unwrap().geo.as_ref().unwrap().country.as_deref(), Some("CHN"));
+
+        let round_tripped = BidRequest::try_from(&typed).unwrap();
+        assert_eq!(round_tripped.id, "r1");
+        assert_eq!((round_tripped.tmax, round_tripped.cur.clone()), (Some(300), Some(vec!["USD".to_string()])));
+        let imp = &round_tripped.get_imp_details()[0];
+        assert_eq!((imp.bidfloor, imp.instl), (Some(0.5), Some(1)));
+        assert_eq!(imp.get_banner_detail().and_then(|banner| banner.h), Some(480));
+        assert_eq!(round_tripped.get_geo().unwrap().country.as_deref(), Some("CHN"));
+    }
+
+    #[test]
+    fn malformed_nested_objects_are_dropped_in_the_typed_form() {
+        let request = lazy(json!({"id": "r1", "imp": [{"id": "1", "banner": "not-an-object"}], "site": 42}));
+        let typed = TypedBidRequest::from(&request);
+        assert!(typed.imp[0].banner.is_none());
+        assert!(typed.site.is_none());
+    }
+}
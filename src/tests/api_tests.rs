@@ -0,0 +1,386 @@
+// src/tests/api_tests.rs
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::api;
+use crate::api::extract::validate_content_types;
+use crate::AppState;
+use crate::bidding::rate_limit::SspRateLimiter;
+use crate::bidding::recent_auctions::RecentAuctions;
+use crate::bidding::stats::HealthThresholds;
+use crate::model::dsp::Demand;
+use crate::tests::dsp_mock::{app_state, bid, bid_request, config, config_with, context, dsp_result, idle_dsp, run_auction, ssp, ssp_placement, was_contacted};
+
+/// 以 peer 为来源地址发送请求，返回状态码与 JSON 响应体（非 JSON 时为 null）
+async fn send_from(router: Router, peer: &str, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if body.is_some() {
+        request = request.header("content-type", "application/json");
+    }
+    let mut request = request
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// 只包含竞价接口的路由
+fn openrtb_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/openrtb", post(api::handlers::handle_openrtb_request))
+        .route("/openrtb/batch", post(api::handlers::handle_openrtb_batch))
+}
+
+/// 从本机回环地址发送请求
+async fn send(router: Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    send_from(router, "127.0.0.1:50000", method, uri, body).await
+}
+
+#[tokio::test]
+async fn blocked_crid_is_dropped_from_subsequent_auctions() {
+    let state = app_state(config(&[1]), vec![]);
+    let router = api::admin::router(state.clone()).with_state(state.clone());
+    let (status, _) = send(router, "POST", "/admin/blocked_crids", Some(json!({"crid": "crid-b1"}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(state.config.is_crid_blocked("crid-b1"));
+
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.0), bid("b2", "1", 1.0)]))];
+    let response = run_auction(&context, &state.config, results).await.expect("b2 still bids");
+    let bid_ids: Vec<&str> = response.seatbid.iter().flat_map(|seatbid| &seatbid.bid).map(|bid| bid.id.as_str()).collect();
+    assert_eq!(bid_ids, vec!["b2"]);
+}
+
+#[tokio::test]
+async fn disabled_video_no_bids_video_imps_while_banner_serves() {
+    let state = app_state(config(&[1]), vec![]);
+    let router = api::admin::router(state.clone()).with_state(state.clone());
+    let (status, body) = send(router.clone(), "POST", "/admin/ad-types", Some(json!({"ad_type": 3, "enabled": false}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["enabled_ad_types"], json!([1, 2]));
+
+    let context = context(bid_request(json!({"imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}},
+        {"id": "2", "video": {"mimes": ["video/mp4"]}},
+    ]})), ssp(json!({})));
+    let results = || vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.0), bid("v1", "2", 5.0)]))];
+    let response = run_auction(&context, &state.config, results()).await.expect("banner still serves");
+    let bid_ids: Vec<&str> = response.seatbid.iter().flat_map(|seatbid| &seatbid.bid).map(|bid| bid.id.as_str()).collect();
+    assert_eq!(bid_ids, vec!["b1"]);
+    assert_eq!(response.ext.unwrap()["imp_nbr"], json!([{"impid": "2", "reason": "ad_type_disabled"}]));
+
+    // 重新启用后视频展示位恢复竞价
+    let (_, body) = send(router, "POST", "/admin/ad-types", Some(json!({"ad_type": 3, "enabled": true}))).await;
+    assert_eq!(body["enabled_ad_types"], json!([1, 2, 3]));
+    let response = run_auction(&context, &state.config, results()).await.unwrap();
+    assert_eq!(response.seatbid.iter().map(|seatbid| seatbid.bid.len()).sum::<usize>(), 2);
+}
+
+#[tokio::test]
+async fn recent_auctions_keep_the_newest_and_evict_the_oldest() {
+    let mut config = config(&[1]);
+    config.recent_auctions = RecentAuctions::new(2).map(Arc::new);
+    let state = app_state(config, vec![]);
+    for request_id in ["req-1", "req-2", "req-3"] {
+        let context = context(bid_request(json!({"id": request_id})), ssp(json!({})));
+        run_auction(&context, &state.config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.0)]))]).await;
+    }
+    let router = api::admin::router(state.clone()).with_state(state);
+    let (status, body) = send(router, "GET", "/admin/recent-auctions", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((body["enabled"].clone(), body["capacity"].clone()), (json!(true), json!(2)));
+    let request_ids: Vec<&str> = body["auctions"].as_array().unwrap().iter().map(|auction| auction["request_id"].as_str().unwrap()).collect();
+    assert_eq!(request_ids, vec!["req-3", "req-2"]);
+    assert_eq!(body["auctions"][0]["winning_bids"][0]["id"], "b1");
+}
+
+#[tokio::test]
+async fn recent_auctions_report_disabled_without_a_buffer() {
+    let state = app_state(config(&[1]), vec![]);
+    let router = api::admin::router(state.clone()).with_state(state);
+    let (_, body) = send(router, "GET", "/admin/recent-auctions", None).await;
+    assert_eq!(body, json!({"enabled": false, "capacity": 0, "auctions": []}));
+}
+
+#[tokio::test]
+async fn admin_endpoints_reject_untrusted_sources() {
+    let state = app_state(config(&[1]), vec![]);
+    let router = api::admin::router(state.clone()).with_state(state.clone());
+    for (method, uri) in [
+        ("GET", "/admin/blocked_crids"),
+        ("POST", "/admin/reload-demands"),
+        ("GET", "/admin/ad-types"),
+        ("GET", "/admin/recent-auctions"),
+        ("GET", "/admin/maintenance"),
+    ] {
+        assert_eq!(send_from(router.clone(), "203.0.113.7:50000", method, uri, None).await.0, StatusCode::FORBIDDEN, "{}", uri);
+    }
+    let (status, _) = send_from(router, "203.0.113.7:50000", "POST", "/admin/blocked_crids", Some(json!({"crid": "crid-b1"}))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(!state.config.is_crid_blocked("crid-b1"));
+}
+
+#[tokio::test]
+async fn admin_trusted_ips_replace_the_loopback_default() {
+    let mut config = config(&[1]);
+    config.admin_trusted_ips = vec!["10.0.0.0/8".parse().unwrap()];
+    let state = app_state(config, vec![]);
+    let router = api::admin::router(state.clone()).with_state(state.clone());
+    assert_eq!(send_from(router.clone(), "10.1.2.3:50000", "GET", "/admin/blocked_crids", None).await.0, StatusCode::OK);
+    assert_eq!(send(router, "GET", "/admin/blocked_crids", None).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn maintenance_mode_fails_readiness_and_returns_no_bid() {
+    let (dsp, dsp_url) = idle_dsp();
+    let state = app_state(config_with(vec![Demand::new(1, "dsp1", &dsp_url, true, Some(50))]), vec![ssp(json!({}))]);
+    let router = openrtb_router()
+        .route("/readyz", get(api::health::readyz))
+        .merge(api::admin::router(state.clone()))
+        .with_state(state.clone());
+    let toggle = |enabled: bool| send(router.clone(), "POST", "/admin/maintenance", Some(json!({"enabled": enabled})));
+
+    assert_eq!(send_from(router.clone(), "203.0.113.7:50000", "POST", "/admin/maintenance", Some(json!({"enabled": true}))).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(send(router.clone(), "GET", "/readyz", None).await.0, StatusCode::OK);
+
+    assert_eq!(toggle(true).await.0, StatusCode::OK);
+    assert_eq!(send(router.clone(), "GET", "/readyz", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _) = send(router.clone(), "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(!was_contacted(&dsp), "no auction runs in maintenance mode");
+
+    assert_eq!(toggle(false).await.0, StatusCode::OK);
+    assert_eq!(send(router, "GET", "/readyz", None).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn non_array_imp_is_rejected_with_a_json_error() {
+    let router = openrtb_router().with_state(app_state(config(&[1]), vec![ssp(json!({}))]));
+    let (status, body) = send(router, "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({"imp": {}})))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_imp");
+}
+
+#[tokio::test]
+async fn tmax_below_the_minimum_is_rejected_before_the_auction() {
+    let (dsp, dsp_url) = idle_dsp();
+    let mut min_tmax_config = config_with(vec![Demand::new(1, "dsp1", &dsp_url, true, Some(50))]);
+    min_tmax_config.min_tmax_ms = Some(50);
+    min_tmax_config.update_placements(vec![ssp_placement()], vec![]);
+    let router = openrtb_router().with_state(app_state(min_tmax_config, vec![ssp(json!({}))]));
+
+    let (status, body) = send(router.clone(), "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({"tmax": 5})))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "tmax_too_low");
+    assert!(!was_contacted(&dsp), "a rejected request never reaches the DSPs");
+
+    let (status, _) = send(router, "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({"tmax": 300})))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn requests_beyond_the_ssp_burst_allowance_get_429() {
+    let mut state = Arc::into_inner(app_state(config(&[1]), vec![ssp(json!({"qps": 2}))])).unwrap();
+    state.rate_limiter = Some(Arc::new(SspRateLimiter::new(1.0)));
+    let router = openrtb_router().with_state(Arc::new(state));
+    for _ in 0..2 {
+        let (status, _) = send(router.clone(), "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+        assert_ne!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+    let (status, body) = send(router, "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"], "qps_exceeded");
+}
+
+#[tokio::test]
+async fn dsp_no_bid_reasons_are_counted_per_dsp_on_stats() {
+    let state = app_state(config(&[1, 2, 3]), vec![ssp(json!({}))]);
+    let no_bid = |dsp_id: u64, nbr: i32| {
+        let mut result = dsp_result(dsp_id, "USD", json!([]));
+        result.bid_response.seatbid.clear();
+        result.bid_response.nbr = Some(nbr);
+        result
+    };
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    run_auction(&context, &state.config, vec![no_bid(1, 8), no_bid(2, 2), no_bid(3, 600)]).await;
+    run_auction(&context, &state.config, vec![no_bid(1, 8), no_bid(2, 3)]).await;
+
+    let router = Router::new().route("/stats", get(api::stats::stats)).with_state(state);
+    let (status, body) = send(router, "GET", "/stats", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["no_bid_reasons"], json!({
+        "1": {"unmatched_user": 2},
+        "2": {"invalid_request": 1, "known_web_spider": 1},
+        "3": {"other": 1},
+    }));
+}
+
+#[tokio::test]
+async fn degraded_start_rejects_every_bid_request_with_503() {
+    let mut state = Arc::into_inner(app_state(config(&[1]), vec![])).unwrap();
+    state.degraded = true;
+    let router = openrtb_router()
+        .route("/readyz", get(api::health::readyz))
+        .with_state(Arc::new(state));
+    let (status, body) = send(router.clone(), "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "config_unavailable");
+    let batch = json!([{"ssp_uuid": "ssp-1", "bid_request": bid_request(json!({}))}]);
+    let (status, body) = send(router.clone(), "POST", "/openrtb/batch", Some(batch)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["status"], 503);
+    assert_eq!(send(router, "GET", "/readyz", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn ssp_requests_outside_the_trusted_ranges_are_rejected() {
+    let mut trusted_config = config(&[1]);
+    trusted_config.trusted_ips = vec!["192.168.0.0/16".parse().unwrap()];
+    let ssps = vec![ssp(json!({"trusted_ips": ["10.0.0.0/8"]})), ssp(json!({"uuid": "ssp-global"}))];
+    let router = openrtb_router().with_state(app_state(trusted_config, ssps));
+    let error_from = |peer: &'static str, ssp_uuid: &'static str| {
+        let router = router.clone();
+        async move {
+            let uri = format!("/openrtb?ssp_uuid={}", ssp_uuid);
+            let (status, body) = send_from(router, peer, "POST", &uri, Some(bid_request(json!({})))).await;
+            (status, body["error"].clone())
+        }
+    };
+    let untrusted = (StatusCode::FORBIDDEN, json!("untrusted_source"));
+
+    // SSP 自身的白名单优先于全局白名单
+    assert_eq!(error_from("203.0.113.7:50000", "ssp-1").await, untrusted);
+    assert_eq!(error_from("192.168.1.1:50000", "ssp-1").await, untrusted);
+    assert_ne!(error_from("10.1.2.3:50000", "ssp-1").await, untrusted);
+    // 未配置 SSP 白名单时使用全局白名单
+    assert_eq!(error_from("203.0.113.7:50000", "ssp-global").await, untrusted);
+    assert_ne!(error_from("192.168.1.1:50000", "ssp-global").await, untrusted);
+
+    let open_router = openrtb_router().with_state(app_state(config(&[1]), vec![ssp(json!({}))]));
+    let (status, body) = send_from(open_router, "203.0.113.7:50000", "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_ne!((status, body["error"].clone()), untrusted, "an empty allowlist allows every source");
+}
+
+#[tokio::test]
+async fn requests_beyond_the_concurrent_auction_limit_are_shed() {
+    let state = app_state(config(&[1]), vec![ssp(json!({}))]);
+    let router = openrtb_router().with_state(state.clone());
+    let limit = state.auction_slots.available_permits() as u32;
+    let in_flight = state.auction_slots.clone().try_acquire_many_owned(limit).unwrap();
+    let (status, body) = send(router.clone(), "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "overloaded");
+
+    drop(in_flight);
+    let (status, _) = send(router, "POST", "/openrtb?ssp_uuid=ssp-1", Some(bid_request(json!({})))).await;
+    assert_ne!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn readiness_follows_the_rolling_dsp_success_rate() {
+    let state = app_state(config(&[1]), vec![]);
+    let router = Router::new().route("/readyz", get(api::health::readyz)).with_state(state.clone());
+    let record = |successes: usize, failures: usize| {
+        for _ in 0..successes {
+            state.config.dsp_stats.record(1, true, false, 10);
+        }
+        for _ in 0..failures {
+            state.config.dsp_stats.record(1, false, true, 10);
+        }
+    };
+
+    record(20, 0);
+    let (status, body) = send(router.clone(), "GET", "/readyz", None).await;
+    assert_eq!((status, body["status"].clone()), (StatusCode::OK, json!("healthy")));
+    record(0, 10);
+    let (status, body) = send(router.clone(), "GET", "/readyz", None).await;
+    assert_eq!((status, body["status"].clone()), (StatusCode::OK, json!("degraded")));
+    record(0, 20);
+    let (status, body) = send(router.clone(), "GET", "/readyz", None).await;
+    assert_eq!((status, body["status"].clone()), (StatusCode::SERVICE_UNAVAILABLE, json!("unhealthy")));
+    assert_eq!(body["success_rate"], 0.4);
+    record(30, 0);
+    let (status, body) = send(router, "GET", "/readyz", None).await;
+    assert_eq!((status, body["status"].clone()), (StatusCode::OK, json!("degraded")));
+}
+
+#[tokio::test]
+async fn readiness_thresholds_are_configurable() {
+    let mut config = config(&[1]);
+    config.health_thresholds = HealthThresholds { degraded_below: 0.5, unhealthy_below: 0.2, min_calls: 5 };
+    let state = app_state(config, vec![]);
+    for success in [true, true, false, false, false] {
+        state.config.dsp_stats.record(1, success, !success, 10);
+    }
+    let router = Router::new().route("/readyz", get(api::health::readyz)).with_state(state);
+    let (status, body) = send(router, "GET", "/readyz", None).await;
+    assert_eq!((status, body["status"].clone()), (StatusCode::OK, json!("degraded")));
+}
+
+/// 以指定 Content-Type 从本机回环地址 POST 原始请求体
+async fn post_raw(router: Router, uri: &str, content_type: &str, body: &str) -> (StatusCode, Value) {
+    let mut request = Request::post(uri)
+        .header("content-type", content_type)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+    let response = router.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn malformed_requests_get_structured_json_errors() {
+    let router = openrtb_router().with_state(app_state(config(&[1]), vec![ssp(json!({}))]));
+    let valid = bid_request(json!({})).to_string();
+    let cases = [
+        ("/openrtb?ssp_uuid=ssp-1", "application/json", "{\"id\": ", StatusCode::BAD_REQUEST, "invalid_json"),
+        ("/openrtb?ssp_uuid=ssp-1", "application/json", "{\"imp\": []}", StatusCode::UNPROCESSABLE_ENTITY, "invalid_body"),
+        ("/openrtb", "application/json", valid.as_str(), StatusCode::BAD_REQUEST, "invalid_query"),
+        ("/openrtb?ssp_uuid=ssp-1", "text/plain", valid.as_str(), StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_content_type"),
+    ];
+    for (uri, content_type, body, expected_status, expected_error) in cases {
+        let (status, error) = post_raw(router.clone(), uri, content_type, body).await;
+        assert_eq!(status, expected_status, "{} {}", uri, body);
+        assert_eq!(error["error"], expected_error);
+        assert!(error["detail"].as_str().is_some_and(|detail| !detail.is_empty()), "{}", error);
+    }
+}
+
+#[tokio::test]
+async fn content_types_outside_the_allowlist_get_415() {
+    let mut config = config(&[1]);
+    config.allowed_content_types = vec!["application/json".to_string(), "application/x-openrtb+json".to_string()];
+    let router = openrtb_router().with_state(app_state(config, vec![ssp(json!({}))]));
+    let valid = bid_request(json!({})).to_string();
+    for content_type in ["text/plain", "application/x-protobuf", ""] {
+        let (status, error) = post_raw(router.clone(), "/openrtb?ssp_uuid=ssp-1", content_type, &valid).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{:?}", content_type);
+        assert_eq!(error["error"], "unsupported_content_type");
+    }
+    // 白名单内的类型（忽略大小写与参数）进入后续处理
+    for content_type in ["application/json; charset=utf-8", "Application/X-OpenRTB+JSON"] {
+        let (status, _) = post_raw(router.clone(), "/openrtb?ssp_uuid=ssp-1", content_type, &valid).await;
+        assert_ne!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{:?}", content_type);
+    }
+}
+
+#[test]
+fn protobuf_or_empty_content_type_allowlists_are_rejected() {
+    assert!(validate_content_types(&["application/json".to_string()]).is_ok());
+    assert!(validate_content_types(&[]).is_err());
+    assert!(validate_content_types(&["application/json".to_string(), "application/x-protobuf".to_string()]).is_err());
+}
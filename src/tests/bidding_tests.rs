@@ -0,0 +1,1177 @@
+// src/tests/bidding_tests.rs
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+use crate::bidding::currency::FxTable;
+use crate::bidding::dsp_client::DspCallOutcome;
+use crate::bidding::engine::{process_bid_request, process_bid_request_with};
+use crate::bidding::events::{AuctionEvent, EventBus};
+use crate::bidding::pipeline::{validate_disabled_stages, AuctionContext, AuctionPipeline, AuctionStage, StageFlow};
+use crate::bidding::recent_auctions::RecentAuctions;
+use crate::bidding::vast::VastWrapperConfig;
+use crate::config::config_manager::{ConfigManager, CreativeSanitization, IntraDspDuplicates, LogVerbosity, ResponseIdPolicy, SensitiveAction, ShadowDspConfig, TrackingConfig};
+use crate::logging::runtime_logger::RuntimeLogger;
+use crate::model::dsp::{Demand, DemandManager};
+use crate::openrtb::response::{BidResponse, NoBidReason};
+use crate::tests::dsp_mock::{CannedFetcher, bid, bid_request, config, config_with, demand, context, dsp_result, failed_result, idle_dsp, merged, run_auction, runtime_logger, ssp, was_contacted};
+
+fn cny_fx_table() -> FxTable {
+    FxTable::from_rates(HashMap::from([("CNY".to_string(), 7.0)])).unwrap()
+}
+
+#[tokio::test]
+async fn ssp_pinned_to_cny_receives_usd_bids_in_cny() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    let context = context(bid_request(json!({"cur": ["USD"]})), ssp(json!({"currency": "CNY"})));
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.0)]))]).await.unwrap();
+    assert_eq!(response.cur.as_deref(), Some("CNY"));
+    assert_eq!(response.seatbid[0].bid[0].price, 14.0);
+}
+
+#[tokio::test]
+async fn unpinned_ssp_settles_in_the_first_convertible_request_cur() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    let context = context(bid_request(json!({"cur": ["CNY", "USD"]})), ssp(json!({})));
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.0)]))]).await.unwrap();
+    assert_eq!(response.cur.as_deref(), Some("CNY"));
+    assert_eq!(response.seatbid[0].bid[0].price, 14.0);
+}
+
+#[tokio::test]
+async fn currency_prices_cover_each_convertible_request_cur() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    let request = bid_request(json!({"cur": ["USD", "CNY", "JPY"]}));
+    let results = || vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.0)]))];
+    let exposing = context(request.clone(), ssp(json!({"expose_currency_prices": true})));
+    let response = run_auction(&exposing, &config, results()).await.unwrap();
+    // 汇率表中没有 JPY，无法换算的货币不返回
+    assert_eq!(response.seatbid[0].bid[0].ext.as_ref().unwrap()["adx"]["prices"], json!({"USD": 2.0, "CNY": 14.0}));
+
+    let default = context(request, ssp(json!({})));
+    let response = run_auction(&default, &config, results()).await.unwrap();
+    assert!(response.seatbid[0].bid[0].ext.as_ref().is_none_or(|ext| ext["adx"].get("prices").is_none()));
+}
+
+#[tokio::test]
+async fn currency_prices_convert_the_cleared_price_not_the_bid() {
+    let config = config(&[1, 2]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    let request = bid_request(json!({"at": 2, "cur": ["USD", "CNY"]}));
+    let results = vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 2.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let exposing = context(request, ssp(json!({"expose_currency_prices": true})));
+    let response = run_auction(&exposing, &config, results).await.unwrap();
+    // 二价成交价为 1.01 USD，而非出价 2.0
+    let prices = &response.seatbid[0].bid[0].ext.as_ref().unwrap()["adx"]["prices"];
+    assert_eq!(prices["USD"], json!(1.01));
+    assert!((prices["CNY"].as_f64().unwrap() - 7.07).abs() < 1e-9, "{}", prices);
+}
+
+#[tokio::test]
+async fn clearing_price_is_exposed_only_for_ssps_that_enable_it() {
+    let config = config(&[1, 2]);
+    let request = bid_request(json!({"at": 2}));
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 2.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let exposing = context(request.clone(), ssp(json!({"expose_clearing_price": true})));
+    let response = run_auction(&exposing, &config, results()).await.unwrap();
+    // 二价成交：次高价 1.0 加价 0.01，扣利润前为 1.01
+    let adx = &response.seatbid[0].bid[0].ext.as_ref().unwrap()["adx"];
+    assert_eq!(adx["original_price"], json!(2.0));
+    assert_eq!(adx["clearing_price"], json!(1.01));
+
+    let default = context(request, ssp(json!({})));
+    let response = run_auction(&default, &config, results()).await.unwrap();
+    assert!(response.seatbid[0].bid[0].ext.as_ref().is_none_or(|ext| {
+        ext["adx"].get("original_price").is_none() && ext["adx"].get("clearing_price").is_none()
+    }));
+}
+
+fn winning_bid_ids(response: &BidResponse) -> Vec<String> {
+    response.seatbid.iter().flat_map(|seatbid| seatbid.bid.iter()).map(|bid| bid.id.clone()).collect()
+}
+
+#[tokio::test]
+async fn winning_nurl_has_the_auction_price_substituted() {
+    let config = config(&[1]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let mut winner = bid("b1", "1", 2.0);
+    winner["nurl"] = json!("http://dsp-1.local/win?price={AUCTION_PRICE}");
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([winner]))]).await.unwrap();
+    let nurl = response.seatbid[0].bid[0].nurl.clone().unwrap();
+    assert!(nurl.starts_with("http://dsp-1.local/win?price="));
+    assert!(!nurl.contains("{AUCTION_PRICE}"));
+}
+
+#[tokio::test]
+async fn lurl_macros_do_not_suppress_the_winner() {
+    let mut config = config(&[1]);
+    config.suppress_unsubstituted_macros = true;
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let mut winner = bid("b1", "1", 2.0);
+    winner["lurl"] = json!("http://dsp-1.local/loss?reason={AUCTION_LOSS}");
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([winner]))]).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn suppressed_winner_falls_back_to_the_runner_up() {
+    let mut config = config(&[1, 2]);
+    config.suppress_unsubstituted_macros = true;
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let mut top = bid("b1", "1", 3.0);
+    top["adm"] = json!("<div>{AUCTION_LOSS}</div>");
+    let results = vec![dsp_result(1, "USD", json!([top])), dsp_result(2, "USD", json!([bid("b2", "1", 2.0)]))];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn unsubstituted_macros_are_only_logged_by_default() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let mut top = bid("b1", "1", 3.0);
+    top["adm"] = json!("<div>{AUCTION_LOSS}</div>");
+    let results = vec![dsp_result(1, "USD", json!([top])), dsp_result(2, "USD", json!([bid("b2", "1", 2.0)]))];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn no_active_dsp_returns_a_technical_error_nbr() {
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let response = process_bid_request(&context, &config(&[]), &runtime_logger(), &EventBus::new(16)).await;
+    assert_eq!(response.and_then(|response| response.nbr), Some(NoBidReason::TechnicalError.code()));
+}
+
+#[tokio::test]
+async fn sole_dsp_nbr_is_propagated_only_when_unambiguous() {
+    let no_bid = |dsp_id: u64, nbr: i32| {
+        let mut result = dsp_result(dsp_id, "USD", json!([]));
+        result.bid_response.seatbid.clear();
+        result.bid_response.nbr = Some(nbr);
+        result
+    };
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let response = run_auction(&context, &config(&[1]), vec![no_bid(1, 8)]).await.unwrap();
+    assert_eq!(response.nbr, Some(NoBidReason::UnmatchedUser.code()));
+    assert!(response.seatbid.is_empty());
+
+    // 两个 DSP 时原因不唯一，交由调用方使用默认原因
+    let response = run_auction(&context, &config(&[1, 2]), vec![no_bid(1, 8), no_bid(2, 8)]).await;
+    assert!(response.is_none());
+}
+
+#[tokio::test]
+async fn disabled_dsps_are_not_contacted() {
+    let (dsp, dsp_url) = idle_dsp();
+    let mut demand_manager = DemandManager::new();
+    demand_manager.add_demand(Demand::new(1, "dsp1", &dsp_url, false, Some(50)));
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let response = process_bid_request(&context, &ConfigManager::new(demand_manager), &runtime_logger(), &EventBus::new(16)).await;
+    assert_eq!(response.and_then(|response| response.nbr), Some(NoBidReason::TechnicalError.code()));
+    assert!(!was_contacted(&dsp));
+}
+
+#[tokio::test]
+async fn routing_filters_that_leave_no_dsp_yield_no_eligible_dsp() {
+    let (dsp, dsp_url) = idle_dsp();
+    let mut config = config_with(vec![Demand::new(1, "live", &dsp_url, true, Some(50))]);
+    // 测试流量只发给测试 DSP，唯一的 DSP 不是测试 DSP
+    config.route_test_traffic = true;
+    let log_dir = std::env::temp_dir().join(format!("rust-adx-no-eligible-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&log_dir);
+    let logger = RuntimeLogger::new(log_dir.to_str().unwrap(), "test", 1024, 1, 10);
+    let context = context(bid_request(json!({"test": 1})), ssp(json!({})));
+    assert!(process_bid_request(&context, &config, &logger, &EventBus::new(16)).await.is_none());
+    assert!(!was_contacted(&dsp));
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while !read_logs(&log_dir).contains("no_eligible_dsp") && std::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(read_logs(&log_dir).contains("no_eligible_dsp"));
+    let _ = std::fs::remove_dir_all(&log_dir);
+
+    // 配置了 nbr 时按该原因返回
+    config.no_eligible_dsp_nbr = Some(NoBidReason::TechnicalError.code());
+    let response = process_bid_request(&context, &config, &runtime_logger(), &EventBus::new(16)).await;
+    assert_eq!(response.and_then(|response| response.nbr), Some(NoBidReason::TechnicalError.code()));
+    assert!(!was_contacted(&dsp));
+}
+
+#[tokio::test]
+async fn bids_below_the_placement_floor_are_rejected() {
+    let config = config(&[1]);
+    let mut context = context(bid_request(json!({})), ssp(json!({})));
+    context.ssp_placement.default_bidfloor = Some(1.0);
+    assert!(run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 0.5)]))]).await.is_none());
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.5)]))]).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn failed_dsp_calls_are_recorded_and_skipped() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![
+        failed_result(1, DspCallOutcome::ReadTimeout),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    let timeouts = config.dsp_stats.timeouts();
+    assert_eq!(timeouts[&1]["read_timeout"], 1);
+    assert!(!timeouts.contains_key(&2));
+}
+
+#[tokio::test]
+async fn all_failed_dsp_calls_yield_no_bid() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![failed_result(1, DspCallOutcome::ConnectTimeout), failed_result(2, DspCallOutcome::JsonParseError)];
+    assert!(run_auction(&context, &config, results).await.is_none());
+    let snapshot = config.dsp_stats.snapshot();
+    assert!(snapshot.iter().all(|dsp| dsp.calls == 1 && dsp.successes == 0));
+}
+
+#[tokio::test]
+async fn imp_floor_is_compared_in_the_request_currency() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    // bidfloorcur 缺省时沿用请求 cur（CNY）：7 CNY = 1 USD
+    let context = context(bid_request(json!({"cur": ["CNY"], "imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 7.0}]})), ssp(json!({})));
+    assert!(run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 0.9)]))]).await.is_none());
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.1)]))]).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn each_imp_floor_is_enforced_in_its_own_bidfloorcur() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    // imp 1 底价 1 USD，imp 2 底价 14 CNY = 2 USD
+    let request = bid_request(json!({"cur": ["USD"], "imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 1.0, "bidfloorcur": "USD"},
+        {"id": "2", "banner": {"w": 300, "h": 250}, "bidfloor": 14.0, "bidfloorcur": "CNY"},
+    ]}));
+    let context = context(request, ssp(json!({})));
+    let results = |imp2_price: f64| vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.5), bid("b2", "2", imp2_price)]))];
+    let response = run_auction(&context, &config, results(1.5)).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+    let response = run_auction(&context, &config, results(2.5)).await.unwrap();
+    let mut winners = winning_bid_ids(&response);
+    winners.sort();
+    assert_eq!(winners, vec!["b1", "b2"]);
+}
+
+#[tokio::test]
+async fn configured_impression_tracker_is_injected_into_the_creative() {
+    let mut config = config(&[1]);
+    config.tracking = TrackingConfig {
+        html_impression_url: "https://tk.example.com/imp?p={AUCTION_PRICE}".to_string(),
+        vast_impression_url: "https://tk.example.com/vast?p={AUCTION_PRICE}".to_string(),
+    };
+    let html_bid = merged(bid("b1", "1", 1.0), json!({"adm": "<html><body>b1</body></html>"}));
+    let vast_bid = merged(bid("b2", "2", 1.0), json!({"adm": "<VAST version=\"3.0\"></VAST>"}));
+    let context = context(bid_request(json!({"imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}},
+        {"id": "2", "banner": {"w": 300, "h": 250}},
+    ]})), ssp(json!({})));
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([html_bid, vast_bid]))]).await.unwrap();
+    let adms: Vec<&str> = response.seatbid.iter().flat_map(|seatbid| &seatbid.bid).filter_map(|bid| bid.adm.as_deref()).collect();
+    assert_eq!(adms.len(), 2);
+    assert!(adms.iter().any(|adm| adm.starts_with("<html><body>b1</body></html>") && adm.contains("<img src=\"https://tk.example.com/imp?p=")));
+    assert!(adms.iter().any(|adm| adm.contains("<Impression><![CDATA[https://tk.example.com/vast?p=")));
+    assert!(adms.iter().all(|adm| !adm.contains("tk.rust-adx.com")));
+}
+
+#[tokio::test]
+async fn each_ssp_gets_its_own_injected_pixel() {
+    let mut config = config(&[1]);
+    config.tracking = TrackingConfig {
+        html_impression_url: "https://tk.global.com/imp?p={AUCTION_PRICE}".to_string(),
+        vast_impression_url: "https://tk.global.com/vast?p={AUCTION_PRICE}".to_string(),
+    };
+    let html_bid = || merged(bid("b1", "1", 1.0), json!({"adm": "<html><body>b1</body></html>"}));
+    let ssp_tracking = |host: &str| json!({"tracking": {
+        "html_impression_url": format!("https://{host}/imp?p={{AUCTION_PRICE}}"),
+        "vast_impression_url": format!("https://{host}/vast?p={{AUCTION_PRICE}}"),
+    }});
+    let ssp_a = context(bid_request(json!({})), ssp(ssp_tracking("tk.ssp-a.com")));
+    let ssp_b = context(bid_request(json!({})), ssp(merged(json!({"id": 2, "uuid": "ssp-2"}), ssp_tracking("tk.ssp-b.com"))));
+    let untemplated = context(bid_request(json!({})), ssp(json!({})));
+
+    let injected_adm = |response: &BidResponse| response.seatbid[0].bid[0].adm.clone().unwrap();
+    let adm_a = injected_adm(&run_auction(&ssp_a, &config, vec![dsp_result(1, "USD", json!([html_bid()]))]).await.unwrap());
+    let adm_b = injected_adm(&run_auction(&ssp_b, &config, vec![dsp_result(1, "USD", json!([html_bid()]))]).await.unwrap());
+    let adm_global = injected_adm(&run_auction(&untemplated, &config, vec![dsp_result(1, "USD", json!([html_bid()]))]).await.unwrap());
+
+    assert!(adm_a.contains("<img src=\"https://tk.ssp-a.com/imp?p="));
+    assert!(!adm_a.contains("tk.ssp-b.com") && !adm_a.contains("tk.global.com"));
+    assert!(adm_b.contains("<img src=\"https://tk.ssp-b.com/imp?p="));
+    assert!(!adm_b.contains("tk.ssp-a.com") && !adm_b.contains("tk.global.com"));
+    assert!(adm_global.contains("<img src=\"https://tk.global.com/imp?p="));
+}
+
+#[tokio::test]
+async fn event_subscriber_sees_received_filtered_and_won_in_order() {
+    let config = config(&[1, 2]);
+    let mut context = context(bid_request(json!({})), ssp(json!({})));
+    context.ssp_placement.default_bidfloor = Some(0.5);
+    let events = EventBus::new(64);
+    let mut subscriber = events.subscribe();
+    let results = vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 2.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 0.1)])),
+    ];
+    process_bid_request_with(&context, &config, &runtime_logger(), &events, &CannedFetcher { results }).await.unwrap();
+    let mut received = Vec::new();
+    while let Ok(event) = subscriber.try_recv() {
+        received.push(event);
+    }
+    let request_id = "req-1".to_string();
+    assert_eq!(received, vec![
+        AuctionEvent::BidReceived { request_id: request_id.clone(), dsp_id: 1, bid_id: "b1".to_string(), impid: "1".to_string(), price: 2.0 },
+        AuctionEvent::BidReceived { request_id: request_id.clone(), dsp_id: 2, bid_id: "b2".to_string(), impid: "1".to_string(), price: 0.1 },
+        AuctionEvent::BidFiltered { request_id: request_id.clone(), bid_id: "b2".to_string(), reason: "below_floor".to_string() },
+        AuctionEvent::BidWon { request_id, bid_id: "b1".to_string(), impid: "1".to_string(), original_price: 2.0, final_price: 1.6 },
+    ]);
+}
+
+#[tokio::test]
+async fn at_selects_first_or_second_price_clearing() {
+    let config = config(&[1, 2]);
+    let results = || vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"nurl": "http://dsp-1.local/win?p={AUCTION_PRICE}"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let win_notice = |response: BidResponse| response.seatbid[0].bid[0].nurl.clone().unwrap();
+    // {AUCTION_PRICE} 为扣除默认 20% 利润后的成交价：一价 2.0 → 1.6，二价 1.01 → 0.808
+    let first = run_auction(&context(bid_request(json!({"at": 1})), ssp(json!({}))), &config, results()).await.unwrap();
+    assert_eq!(win_notice(first), "http://dsp-1.local/win?p=1.6");
+    let second = run_auction(&context(bid_request(json!({"at": 2})), ssp(json!({}))), &config, results()).await.unwrap();
+    assert_eq!(win_notice(second), "http://dsp-1.local/win?p=0.808");
+}
+
+#[tokio::test]
+async fn burl_is_returned_with_the_auction_price_substituted() {
+    let config = config(&[1]);
+    let context = context(bid_request(json!({"at": 1})), ssp(json!({})));
+    let winner = merged(bid("b1", "1", 2.0), json!({"burl": "http://dsp-1.local/bill?p={AUCTION_PRICE}&id=b1"}));
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([winner]))]).await.unwrap();
+    // 与 nurl 相同，替换为扣除默认 20% 利润后的成交价
+    assert_eq!(response.seatbid[0].bid[0].burl.as_deref(), Some("http://dsp-1.local/bill?p=1.6&id=b1"));
+
+    // 未返回 burl 的出价不补充该字段
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.0)]))]).await.unwrap();
+    assert_eq!(response.seatbid[0].bid[0].burl, None);
+}
+
+#[tokio::test]
+async fn ssps_with_different_profit_rates_clear_the_same_bids_differently() {
+    let config = config(&[1]);
+    let results = || vec![dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"nurl": "http://dsp-1.local/win?p={AUCTION_PRICE}"}))]))];
+    let win_notice = |response: BidResponse| response.seatbid[0].bid[0].nurl.clone().unwrap();
+    let premium = ssp(json!({"id": 2, "uuid": "ssp-2", "profit_rate": 0.05}));
+    let standard = ssp(json!({"id": 3, "uuid": "ssp-3", "profit_rate": 0.3}));
+    // 未配置专属利润率的 SSP 沿用默认 20%
+    let default = ssp(json!({}));
+    let cleared = |ssp| async { win_notice(run_auction(&context(bid_request(json!({})), ssp), &config, results()).await.unwrap()) };
+    assert_eq!(cleared(premium).await, "http://dsp-1.local/win?p=1.9");
+    assert_eq!(cleared(standard).await, "http://dsp-1.local/win?p=1.4");
+    assert_eq!(cleared(default).await, "http://dsp-1.local/win?p=1.6");
+}
+
+#[tokio::test]
+async fn mismatched_response_id_is_kept_when_lenient_and_dropped_when_strict() {
+    let mut config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = || {
+        let mut spoofed = dsp_result(1, "USD", json!([bid("b1", "1", 2.0)]));
+        spoofed.bid_response.id = "not-req-1".to_string();
+        vec![spoofed, dsp_result(2, "USD", json!([bid("b2", "1", 1.0)]))]
+    };
+    let lenient = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&lenient), vec!["b1"]);
+
+    config.response_id_policy = ResponseIdPolicy::Strict;
+    let strict = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&strict), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn adomain_allowlist_permits_and_rejects_specific_domains() {
+    let mut config = config(&[1, 2, 3]);
+    config.allowed_adomains = ["brand.com".to_string()].into();
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let results = || vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 3.0), json!({"adomain": ["other.com"]}))])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 2.0), json!({"adomain": ["brand.com"]}))])),
+        dsp_result(3, "USD", json!([merged(bid("b3", "1", 1.0), json!({"adomain": ["partner.com"]}))])),
+    ];
+    let global = context(bid_request(json!({})), ssp(json!({})));
+    assert_eq!(winning_bid_ids(&run_auction(&global, &config, results()).await.unwrap()), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    let mut rejected: Vec<(String, String)> = outcome["rejections"].as_array().unwrap().iter()
+        .map(|rejection| (rejection["bid_id"].as_str().unwrap().to_string(), rejection["reason"].as_str().unwrap().to_string()))
+        .collect();
+    rejected.sort();
+    assert_eq!(rejected, vec![
+        ("b1".to_string(), "adomain_not_allowed".to_string()),
+        ("b3".to_string(), "adomain_not_allowed".to_string()),
+    ]);
+
+    // SSP 自身的白名单覆盖全局白名单
+    let partner_only = context(bid_request(json!({})), ssp(json!({"allowed_adomains": ["Partner.com"]})));
+    assert_eq!(winning_bid_ids(&run_auction(&partner_only, &config, results()).await.unwrap()), vec!["b3"]);
+}
+
+#[tokio::test]
+async fn bid_without_a_required_adomain_is_dropped() {
+    let mut config = config(&[1, 2]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 3.0)])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 1.0), json!({"adomain": ["brand.com"]}))])),
+    ];
+    let strict = context(bid_request(json!({})), ssp(json!({"required_bid_fields": ["adomain"]})));
+    assert_eq!(winning_bid_ids(&run_auction(&strict, &config, results()).await.unwrap()), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    assert_eq!(outcome["rejections"][0]["bid_id"], "b1");
+    assert_eq!(outcome["rejections"][0]["reason"], "missing_required_field");
+
+    // 未配置必填字段的 SSP 不受影响
+    let lenient = context(bid_request(json!({})), ssp(json!({})));
+    assert_eq!(winning_bid_ids(&run_auction(&lenient, &config, results()).await.unwrap()), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn bids_beyond_the_ssp_cap_are_truncated_by_price() {
+    let config = config(&[1, 2]);
+    let imps: Vec<_> = (1..=4).map(|i| json!({"id": i.to_string(), "banner": {"w": 300, "h": 250}})).collect();
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 1.0), bid("b2", "2", 4.0)])),
+        dsp_result(2, "USD", json!([bid("b3", "3", 2.0), bid("b4", "4", 3.0)])),
+    ];
+    let capped = context(bid_request(json!({"imp": imps})), ssp(json!({"max_response_bids": 2})));
+    let response = run_auction(&capped, &config, results()).await.unwrap();
+    let mut kept = winning_bid_ids(&response);
+    kept.sort();
+    assert_eq!(kept, vec!["b2", "b4"]);
+    let mut unfilled: Vec<Value> = response.ext.unwrap()["imp_nbr"].as_array().unwrap().clone();
+    unfilled.sort_by_key(|imp_nbr| imp_nbr["impid"].to_string());
+    assert_eq!(unfilled, vec![
+        json!({"impid": "1", "reason": "max_response_bids"}),
+        json!({"impid": "3", "reason": "max_response_bids"}),
+    ]);
+
+    let uncapped = context(bid_request(json!({"imp": imps})), ssp(json!({})));
+    assert_eq!(winning_bid_ids(&run_auction(&uncapped, &config, results()).await.unwrap()).len(), 4);
+}
+
+#[tokio::test]
+async fn bids_beyond_the_per_seat_cap_are_truncated_by_price() {
+    let config = config(&[1, 2]);
+    let imps: Vec<_> = (1..=4).map(|i| json!({"id": i.to_string(), "banner": {"w": 300, "h": 250}})).collect();
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 1.0), bid("b2", "2", 3.0), bid("b3", "3", 2.0)])),
+        dsp_result(2, "USD", json!([bid("b4", "4", 0.5)])),
+    ];
+    // DSP 1 的席位超出上限，只保留价格最高的两个；DSP 2 的席位不受影响
+    let capped = context(bid_request(json!({"imp": imps})), ssp(json!({"max_bids_per_seat": 2})));
+    let response = run_auction(&capped, &config, results()).await.unwrap();
+    let mut kept = winning_bid_ids(&response);
+    kept.sort();
+    assert_eq!(kept, vec!["b2", "b3", "b4"]);
+    assert_eq!(response.ext.unwrap()["imp_nbr"], json!([{"impid": "1", "reason": "max_bids_per_seat"}]));
+
+    // 席位上限先生效，再按整个响应的上限截断
+    let both = context(bid_request(json!({"imp": imps})), ssp(json!({"max_bids_per_seat": 2, "max_response_bids": 2})));
+    let mut kept = winning_bid_ids(&run_auction(&both, &config, results()).await.unwrap());
+    kept.sort();
+    assert_eq!(kept, vec!["b2", "b3"]);
+}
+
+#[tokio::test]
+async fn inline_script_creative_under_each_sanitization_mode() {
+    let mut config = config(&[1]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let adm = r#"<html><body><img src="https://cdn.example.com/a.png"><script>alert(1)</script></body></html>"#;
+    let results = || vec![dsp_result(1, "USD", json!([merged(bid("b1", "1", 1.0), json!({"adm": adm}))]))];
+    let returned_adm = |response: BidResponse| response.seatbid[0].bid[0].adm.clone().unwrap();
+
+    config.creative_sanitization = CreativeSanitization::Off;
+    assert!(returned_adm(run_auction(&context, &config, results()).await.unwrap()).contains("<script>alert(1)</script>"));
+
+    config.creative_sanitization = CreativeSanitization::Sanitize;
+    let sanitized = returned_adm(run_auction(&context, &config, results()).await.unwrap());
+    assert!(!sanitized.contains("script") && sanitized.contains("https://cdn.example.com/a.png"), "{}", sanitized);
+
+    config.creative_sanitization = CreativeSanitization::Reject;
+    assert!(run_auction(&context, &config, results()).await.is_none());
+}
+
+#[tokio::test]
+async fn winning_dsp_is_exposed_only_when_the_ssp_opts_in() {
+    let config = config(&[1, 2]);
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 1.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 2.0)])),
+    ];
+    let opted_in = context(bid_request(json!({})), ssp(json!({"expose_winning_dsp": true})));
+    let response = run_auction(&opted_in, &config, results()).await.unwrap();
+    assert_eq!(response.ext.unwrap()["adx"]["winning_dsp"], json!({"id": 2, "name": "dsp2"}));
+
+    let opted_out = context(bid_request(json!({})), ssp(json!({})));
+    let response = run_auction(&opted_out, &config, results()).await.unwrap();
+    assert!(response.ext.is_none());
+    let serialized = serde_json::to_string(&response).unwrap();
+    assert!(!serialized.contains("winning_dsp") && !serialized.contains("dsp2"), "{}", serialized);
+}
+
+#[tokio::test]
+async fn two_bids_from_one_dsp_for_one_imp_keep_the_highest() {
+    let mut config = config(&[1]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = || vec![dsp_result(1, "USD", json!([
+        merged(bid("low", "1", 1.0), json!({"crid": "cr-low"})),
+        merged(bid("high", "1", 2.0), json!({"crid": "cr-high"})),
+    ]))];
+    let rejection_reasons = || -> Vec<Value> {
+        let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+        outcome["rejections"].as_array().unwrap().iter().map(|rejection| rejection["reason"].clone()).collect()
+    };
+
+    let response = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["high"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    assert_eq!(outcome["rejections"], json!([{"bid_id": "low", "dsp_id": 1, "reason": "intra_dsp_dedup"}]));
+
+    // keep_all 时两个出价都参与竞价，不做折叠
+    config.intra_dsp_duplicates = IntraDspDuplicates::KeepAll;
+    let response = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["high"]);
+    assert!(!rejection_reasons().contains(&json!("intra_dsp_dedup")));
+}
+
+#[tokio::test]
+async fn bid_for_an_impid_not_in_the_request_is_rejected() {
+    let mut config = config(&[1]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![dsp_result(1, "USD", json!([bid("bogus", "99", 5.0), bid("b1", "1", 1.0)]))];
+    assert_eq!(winning_bid_ids(&run_auction(&context, &config, results).await.unwrap()), vec!["b1"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    assert_eq!(outcome["rejections"], json!([{"bid_id": "bogus", "dsp_id": 1, "reason": "unknown_impid"}]));
+}
+
+#[tokio::test]
+async fn dsp_min_bid_price_only_guards_that_dsp() {
+    let config = config_with(vec![Demand { min_bid_price: Some(1.0), ..demand(1) }, demand(2)]);
+    let context = context(bid_request(json!({"imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}},
+        {"id": "2", "banner": {"w": 300, "h": 250}},
+    ]})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 0.5)])),
+        dsp_result(2, "USD", json!([bid("b2", "2", 0.5)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn ssp_with_injection_disabled_receives_the_original_adm() {
+    let config = config(&[1]);
+    let html_bid = merged(bid("b1", "1", 1.0), json!({"adm": "<html><body>b1</body></html>"}));
+    let context = context(bid_request(json!({})), ssp(json!({"inject_tracking": false})));
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([html_bid]))]).await.unwrap();
+    assert_eq!(response.seatbid[0].bid[0].adm.as_deref(), Some("<html><body>b1</body></html>"));
+}
+
+#[tokio::test]
+async fn response_seat_matches_the_winning_dsp_seat() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 1.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 2.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(response.seatbid.len(), 1);
+    assert_eq!(response.seatbid[0].seat.as_deref(), Some("seat-2"));
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn adx_annotation_is_present_only_when_enabled() {
+    let config = config(&[1]);
+    let results = || vec![dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"ext": {"dsp": "kept"}}))]))];
+    let annotated = run_auction(&context(bid_request(json!({})), ssp(json!({"expose_adx_ext": true}))), &config, results()).await.unwrap();
+    let ext = annotated.seatbid[0].bid[0].ext.clone().unwrap();
+    assert_eq!(ext["dsp"], "kept");
+    assert_eq!(ext["adx"]["dsp_id"], 1);
+    assert_eq!(ext["adx"]["pre_markdown_price"], 2.0);
+    assert_eq!(ext["adx"]["profit_rate"], 0.2);
+
+    let plain = run_auction(&context(bid_request(json!({})), ssp(json!({}))), &config, results()).await.unwrap();
+    assert_eq!(plain.seatbid[0].bid[0].ext, Some(json!({"dsp": "kept"})));
+}
+
+#[tokio::test]
+async fn creative_forcing_an_external_browser_loses_to_a_compatible_one() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "clickbrowser": 0}]})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": "<a href=\"intent://shop#Intent;end\">buy</a>"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn sensitive_bid_is_rejected_by_default() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": "<div>forbidden offer</div>"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+}
+
+#[tokio::test]
+async fn sensitive_bid_is_flagged_and_served_under_the_flag_action() {
+    let mut config = config(&[1, 2]);
+    config.sensitive_filter.action = SensitiveAction::Flag;
+    let context = context(bid_request(json!({})), ssp(json!({"expose_adx_ext": true})));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": "<div>forbidden offer</div>"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+    let ext = response.seatbid[0].bid[0].ext.clone().unwrap();
+    assert_eq!(ext["adx"]["sensitive_keywords"], json!(["forbidden"]));
+}
+
+#[tokio::test]
+async fn aggregated_log_lists_each_rejected_bid_with_its_reason() {
+    let mut config = config(&[1, 2, 3]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let mut context = context(bid_request(json!({})), ssp(json!({})));
+    context.ssp_placement.default_bidfloor = Some(0.5);
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": "<div>banned</div>"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 0.1)])),
+        dsp_result(3, "USD", json!([bid("b3", "1", 1.0)])),
+    ];
+    run_auction(&context, &config, results).await.unwrap();
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    let mut rejections = outcome["rejections"].as_array().unwrap().clone();
+    rejections.sort_by_key(|rejection| rejection["bid_id"].to_string());
+    assert_eq!(rejections, vec![
+        json!({"bid_id": "b1", "dsp_id": 1, "reason": "contains_sensitive_content"}),
+        json!({"bid_id": "b2", "dsp_id": 2, "reason": "below_floor"}),
+    ]);
+}
+
+#[tokio::test]
+async fn incompatible_creative_is_rejected_when_the_exchange_decides_and_flagged_otherwise() {
+    let mut config = config(&[1, 2]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250, "api": [5]}});
+    let results = || vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"ext": {"api": [7]}}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let mut winners = Vec::new();
+    for fd in [0, 1] {
+        let context = context(bid_request(json!({"imp": [imp], "source": {"fd": fd}})), ssp(json!({})));
+        let response = run_auction(&context, &config, results()).await.unwrap();
+        let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+        winners.push((winning_bid_ids(&response), outcome["decision_mode"].clone()));
+    }
+    assert_eq!(winners, vec![
+        (vec!["b2".to_string()], json!("exchange")),
+        (vec!["b1".to_string()], json!("upstream")),
+    ]);
+}
+
+#[tokio::test]
+async fn seatbids_follow_the_ssp_grouping() {
+    let config = config(&[1, 2]);
+    let imps: Vec<Value> = (1..=3).map(|i| json!({"id": i.to_string(), "banner": {"w": 300, "h": 250}})).collect();
+    let results = || vec![
+        dsp_result(1, "USD", json!([bid("b1", "1", 2.0), bid("b2", "2", 2.0)])),
+        dsp_result(2, "USD", json!([bid("b3", "3", 1.0)])),
+    ];
+    let mut groupings = Vec::new();
+    for grouping in ["per_bid", "per_dsp", "single"] {
+        let context = context(bid_request(json!({"imp": imps})), ssp(json!({"seatbid_grouping": grouping})));
+        let response = run_auction(&context, &config, results()).await.unwrap();
+        groupings.push(response.seatbid.iter()
+            .map(|seatbid| (seatbid.seat.clone(), seatbid.bid.iter().map(|bid| bid.id.as_str()).collect::<Vec<_>>().join(",")))
+            .collect::<Vec<_>>());
+    }
+    let seat = |id: &str| Some(id.to_string());
+    assert_eq!(groupings[0], vec![(seat("seat-1"), "b1".to_string()), (seat("seat-1"), "b2".to_string()), (seat("seat-2"), "b3".to_string())]);
+    // 按 DSP 分组时保留各 DSP 的席位
+    assert_eq!(groupings[1], vec![(seat("seat-1"), "b1,b2".to_string()), (seat("seat-2"), "b3".to_string())]);
+    assert_eq!(groupings[2], vec![(None, "b1,b2,b3".to_string())]);
+}
+
+#[tokio::test]
+async fn unfilled_imp_reports_its_own_no_bid_reason() {
+    let config = config(&[1]);
+    let context = context(bid_request(json!({"imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}},
+        {"id": "2", "banner": {"w": 300, "h": 250}},
+    ]})), ssp(json!({})));
+    let results = vec![dsp_result(1, "USD", json!([
+        bid("b1", "1", 1.0),
+        merged(bid("b2", "2", 2.0), json!({"adm": "<div>forbidden</div>"})),
+    ]))];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+    assert_eq!(response.nbr, None);
+    assert_eq!(response.ext.unwrap()["imp_nbr"], json!([{"impid": "2", "reason": "all_bids_filtered"}]));
+
+    let results = vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.0)]))];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(response.ext.unwrap()["imp_nbr"], json!([{"impid": "2", "reason": "no_bids"}]));
+}
+
+#[tokio::test]
+async fn bid_expiring_before_the_impression_is_rejected() {
+    let mut config = config(&[1, 2]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let context = context(bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "exp": 600}]})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"exp": 300}))])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 1.0), json!({"exp": 900}))])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    assert_eq!(outcome["rejections"], json!([{"bid_id": "b1", "dsp_id": 1, "reason": "creative_expiry_mismatch"}]));
+}
+
+#[tokio::test]
+async fn vast_without_a_required_companion_is_rejected() {
+    let mut config = config(&[1, 2]);
+    config.require_companions = true;
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let imp = json!({"id": "1", "video": {"mimes": ["video/mp4"], "companiontype": [1], "companionad": [{"w": 300, "h": 250}]}});
+    let context = context(bid_request(json!({"imp": [imp]})), ssp(json!({})));
+    let linear_only = "<VAST version=\"3.0\"><Ad><InLine><Creatives><Creative><Linear></Linear></Creative></Creatives></InLine></Ad></VAST>";
+    let with_companion = "<VAST version=\"3.0\"><Ad><InLine><Creatives><Creative><CompanionAds><Companion width=\"300\" height=\"250\"></Companion></CompanionAds></Creative></Creatives></InLine></Ad></VAST>";
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": linear_only}))])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 1.0), json!({"adm": with_companion}))])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    assert_eq!(outcome["rejections"][0]["bid_id"], "b1");
+    assert_eq!(outcome["rejections"][0]["reason"], "missing_companion");
+}
+
+#[tokio::test]
+async fn seat_whose_bids_are_all_filtered_is_not_returned() {
+    let config = config(&[1, 2]);
+    let context = context(bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 1.0}]})), ssp(json!({})));
+    let mut multi_seat = dsp_result(1, "USD", json!([bid("b1", "1", 0.5)]));
+    let valid_seat = dsp_result(1, "USD", json!([bid("b2", "1", 1.5)])).bid_response.seatbid;
+    multi_seat.bid_response.seatbid.extend(valid_seat);
+    multi_seat.bid_response.seatbid.push(serde_json::from_value(json!({"seat": "seat-empty", "bid": []})).unwrap());
+
+    let response = run_auction(&context, &config, vec![multi_seat]).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    assert!(response.seatbid.iter().all(|seatbid| !seatbid.bid.is_empty()));
+
+    // 所有席位的出价都被过滤或为空时按无竞价处理
+    let mut all_filtered = dsp_result(1, "USD", json!([bid("b1", "1", 0.5)]));
+    all_filtered.bid_response.seatbid.push(serde_json::from_value(json!({"seat": "seat-empty", "bid": []})).unwrap());
+    let only_empty = dsp_result(2, "USD", json!([]));
+    assert!(run_auction(&context, &config, vec![all_filtered, only_empty]).await.is_none());
+}
+
+#[tokio::test]
+async fn runner_up_wins_when_the_top_bid_fails_a_post_auction_check() {
+    let mut config = config(&[1, 2, 3]);
+    config.suppress_unsubstituted_macros = true;
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = || vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 3.0), json!({"nurl": "http://dsp-1.local/win?l={AUCTION_LOSS}"}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 2.0)])),
+        dsp_result(3, "USD", json!([bid("b3", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+
+    // 回退深度为 0 时不再回退
+    config.fallback_depth = 0;
+    assert!(run_auction(&context, &config, results()).await.is_none());
+}
+
+#[tokio::test]
+async fn category_floor_raises_the_effective_floor() {
+    let mut config = config(&[1]);
+    config.category_floors = HashMap::from([("IAB7".to_string(), 2.0)]);
+    let results = || vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.5)]))];
+    let mut health = context(bid_request(json!({"site": {"id": "s1", "cat": ["IAB7-1"]}})), ssp(json!({})));
+    assert!(run_auction(&health, &config, results()).await.is_none());
+    let news = context(bid_request(json!({"site": {"id": "s1", "cat": ["IAB12"]}})), ssp(json!({})));
+    assert_eq!(winning_bid_ids(&run_auction(&news, &config, results()).await.unwrap()), vec!["b1"]);
+
+    // 展示位底价更高时以展示位底价为准
+    health.ssp_placement.default_bidfloor = Some(3.0);
+    let results = vec![dsp_result(1, "USD", json!([bid("b1", "1", 2.5)]))];
+    assert!(run_auction(&health, &config, results).await.is_none());
+}
+
+#[tokio::test]
+async fn deal_restricted_to_a_seat_only_clears_for_that_seat() {
+    let config = config(&[1, 2]);
+    let deal = json!({"id": "d1", "bidfloor": 1.5, "at": 3, "wseat": ["seat-2"]});
+    let context = context(bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "pmp": {"deals": [deal]}}]})), ssp(json!({})));
+    let deal_bid = |id: &str, price: f64| merged(bid(id, "1", price), json!({"dealid": "d1", "nurl": format!("http://{}.local/win?p={{AUCTION_PRICE}}", id)}));
+    let results = vec![
+        dsp_result(1, "USD", json!([deal_bid("b1", 3.0)])),
+        dsp_result(2, "USD", json!([deal_bid("b2", 2.0)])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    // at = 3 的 deal 按约定价格成交
+    assert_eq!(response.seatbid[0].bid[0].nurl.as_deref(), Some("http://b2.local/win?p=1.5"));
+}
+
+#[tokio::test]
+async fn deal_restricted_to_an_advertiser_domain_rejects_other_advertisers() {
+    let mut config = config(&[1, 2, 3]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let deal = json!({"id": "d1", "bidfloor": 1.0, "wadomain": ["brand.com"]});
+    let context = context(bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "pmp": {"deals": [deal]}}]})), ssp(json!({})));
+    let deal_bid = |id: &str, price: f64, adomain: Value| merged(bid(id, "1", price), json!({"dealid": "d1", "adomain": adomain}));
+    let results = vec![
+        dsp_result(1, "USD", json!([deal_bid("b1", 3.0, json!(["other.com"]))])),
+        dsp_result(2, "USD", json!([deal_bid("b2", 2.5, json!(["brand.com", "other.com"]))])),
+        dsp_result(3, "USD", json!([deal_bid("b3", 2.0, json!(["Brand.com"]))])),
+    ];
+    let response = run_auction(&context, &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b3"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    let mut rejected: Vec<(String, String)> = outcome["rejections"].as_array().unwrap().iter()
+        .map(|rejection| (rejection["bid_id"].as_str().unwrap().to_string(), rejection["reason"].as_str().unwrap().to_string()))
+        .collect();
+    rejected.sort();
+    assert_eq!(rejected, vec![
+        ("b1".to_string(), "deal_adomain_violation".to_string()),
+        ("b2".to_string(), "deal_adomain_violation".to_string()),
+    ]);
+}
+
+#[tokio::test]
+async fn bseat_blocks_a_seat_even_when_the_deal_allows_it() {
+    let mut config = config(&[1, 2, 3]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let deal = json!({"id": "d1", "bidfloor": 1.0, "wseat": ["seat-1", "seat-2"]});
+    let request = bid_request(json!({
+        "bseat": ["seat-1", "seat-3"],
+        "imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "pmp": {"deals": [deal]}}],
+    }));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 3.0), json!({"dealid": "d1"}))])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 2.0), json!({"dealid": "d1"}))])),
+        dsp_result(3, "USD", json!([bid("b3", "1", 4.0)])),
+    ];
+    let response = run_auction(&context(request, ssp(json!({}))), &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    let mut rejected: Vec<(String, String)> = outcome["rejections"].as_array().unwrap().iter()
+        .map(|rejection| (rejection["bid_id"].as_str().unwrap().to_string(), rejection["reason"].as_str().unwrap().to_string()))
+        .collect();
+    rejected.sort();
+    // deal 出价记为 seat_blocked_on_deal，非 deal 出价记为 seat_blocked
+    assert_eq!(rejected, vec![
+        ("b1".to_string(), "seat_blocked_on_deal".to_string()),
+        ("b3".to_string(), "seat_blocked".to_string()),
+    ]);
+}
+
+#[tokio::test]
+async fn dealids_not_offered_on_the_impression_are_rejected() {
+    let mut config = config(&[1, 2, 3]);
+    let recent_auctions = Arc::new(RecentAuctions::new(1).unwrap());
+    config.recent_auctions = Some(recent_auctions.clone());
+    let request = bid_request(json!({"imp": [
+        {"id": "1", "banner": {"w": 300, "h": 250}, "pmp": {"deals": [{"id": "d1", "bidfloor": 1.0}]}},
+        {"id": "2", "banner": {"w": 300, "h": 250}},
+    ]}));
+    let results = vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 3.0), json!({"dealid": "fabricated"}))])),
+        dsp_result(2, "USD", json!([merged(bid("b2", "1", 2.0), json!({"dealid": "d1"}))])),
+        // d1 只在 imp 1 上提供
+        dsp_result(3, "USD", json!([merged(bid("b3", "2", 2.0), json!({"dealid": "d1"}))])),
+    ];
+    let response = run_auction(&context(request, ssp(json!({}))), &config, results).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+    let outcome = serde_json::to_value(&recent_auctions.snapshot()[0]).unwrap();
+    let mut rejected: Vec<(String, String)> = outcome["rejections"].as_array().unwrap().iter()
+        .map(|rejection| (rejection["bid_id"].as_str().unwrap().to_string(), rejection["reason"].as_str().unwrap().to_string()))
+        .collect();
+    rejected.sort();
+    assert_eq!(rejected, vec![
+        ("b1".to_string(), "unknown_dealid".to_string()),
+        ("b3".to_string(), "unknown_dealid".to_string()),
+    ]);
+}
+
+#[tokio::test]
+async fn fixed_price_deal_clears_at_the_converted_deal_price_without_markdown() {
+    let config = config(&[1]);
+    *config.fx_table.write().unwrap() = cny_fx_table();
+    let deal = json!({"id": "d1", "bidfloor": 10.5, "bidfloorcur": "CNY", "at": 3});
+    let context = context(
+        bid_request(json!({"imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "pmp": {"deals": [deal]}}]})),
+        ssp(json!({"profit_rate": 0.3})),
+    );
+    let nurl = json!({"nurl": "http://dsp-1.local/win?p={AUCTION_PRICE}"});
+    let win_notice = |response: BidResponse| response.seatbid[0].bid[0].nurl.clone().unwrap();
+
+    // 10.5 CNY = 1.5 USD，不扣除 SSP 的 30% 利润
+    let deal_bid = merged(merged(bid("b1", "1", 3.0), json!({"dealid": "d1"})), nurl.clone());
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([deal_bid]))]).await.unwrap();
+    assert_eq!(win_notice(response), "http://dsp-1.local/win?p=1.5");
+
+    // 未匹配 deal 的出价照常一价成交并扣除利润
+    let open_bid = merged(bid("b1", "1", 2.0), nurl);
+    let response = run_auction(&context, &config, vec![dsp_result(1, "USD", json!([open_bid]))]).await.unwrap();
+    assert_eq!(win_notice(response), "http://dsp-1.local/win?p=1.4");
+}
+
+/// 读取日志目录下所有日志文件的内容
+fn read_logs(log_dir: &std::path::Path) -> String {
+    std::fs::read_dir(log_dir).into_iter().flatten().flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .collect()
+}
+
+#[tokio::test]
+async fn shadow_dsp_bids_are_logged_but_never_win() {
+    let mut config = config(&[1, 2]);
+    config.shadow_dsp = Some(ShadowDspConfig { dsp_id: 1, sample_rate: 1.0 });
+    let log_dir = std::env::temp_dir().join(format!("rust-adx-shadow-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&log_dir);
+    let logger = RuntimeLogger::new(log_dir.to_str().unwrap(), "test", 1024, 1, 10);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = vec![
+        dsp_result(1, "USD", json!([bid("shadow-bid", "1", 5.0)])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let fetcher = CannedFetcher { results };
+    let response = process_bid_request_with(&context, &config, &logger, &EventBus::new(16), &fetcher).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(2);
+    while !read_logs(&log_dir).contains("shadow_dsp_response") && std::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let logs = read_logs(&log_dir);
+    assert!(logs.contains("shadow_dsp_response") && logs.contains("shadow-bid"), "{}", logs);
+    let _ = std::fs::remove_dir_all(&log_dir);
+
+    // 只有影子 DSP 出价时按无竞价处理
+    let results = vec![dsp_result(1, "USD", json!([bid("shadow-bid", "1", 5.0)]))];
+    assert!(run_auction(&context, &config, results).await.is_none());
+}
+
+#[tokio::test]
+async fn minimal_log_verbosity_omits_per_dsp_details() {
+    let mut aggregated_logs = Vec::new();
+    for verbosity in [LogVerbosity::Minimal, LogVerbosity::Full] {
+        let mut config = config(&[1, 2]);
+        config.log_verbosity = verbosity;
+        let log_dir = std::env::temp_dir().join(format!("rust-adx-verbosity-{:?}-{}", verbosity, std::process::id()));
+        let _ = std::fs::remove_dir_all(&log_dir);
+        let logger = RuntimeLogger::new(log_dir.to_str().unwrap(), "test", 1024, 1, 10);
+        let context = context(bid_request(json!({})), ssp(json!({})));
+        let fetcher = CannedFetcher { results: vec![
+            dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": "<div>ad</div>"}))])),
+            dsp_result(2, "USD", json!([bid("bogus", "99", 1.0)])),
+        ] };
+        process_bid_request_with(&context, &config, &logger, &EventBus::new(16), &fetcher).await.unwrap();
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(2);
+        while !read_logs(&log_dir).contains("adx_inquiry_result") && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let logs = read_logs(&log_dir);
+        let line = logs.lines().find(|line| line.contains("adx_inquiry_result")).expect("aggregated log");
+        let entry: Value = serde_json::from_str(line).unwrap();
+        aggregated_logs.push(serde_json::from_str::<Value>(entry["message"].as_str().unwrap()).unwrap());
+        let _ = std::fs::remove_dir_all(&log_dir);
+    }
+    let (minimal, full) = (&aggregated_logs[0], &aggregated_logs[1]);
+    assert_eq!(minimal["dsp_call_details"], json!([]));
+    assert_eq!(minimal["rejections"], json!([]));
+    assert_eq!(minimal["winning_bids"][0]["id"], "b1");
+    assert_eq!(minimal["winning_bids"][0]["adm"], Value::Null);
+    let details = full["dsp_call_details"].as_array().unwrap();
+    assert!(details.iter().any(|detail| detail["dsp_id"] == 1) && details.iter().any(|detail| detail["dsp_id"] == 2), "{:?}", details);
+    assert_eq!(full["rejections"][0]["reason"], "unknown_impid");
+    assert_eq!(full["winning_bids"][0]["adm"], "<div>ad</div>");
+}
+
+#[tokio::test]
+async fn unsampled_requests_skip_the_shadow_dsp() {
+    let (shadow, shadow_url) = idle_dsp();
+    let (live, live_url) = idle_dsp();
+    let mut config = config_with(vec![Demand::new(1, "shadow", &shadow_url, true, Some(50)), Demand::new(2, "live", &live_url, true, Some(50))]);
+    config.shadow_dsp = Some(ShadowDspConfig { dsp_id: 1, sample_rate: 0.0 });
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    process_bid_request(&context, &config, &runtime_logger(), &EventBus::new(16)).await;
+    assert!(!was_contacted(&shadow));
+    assert!(was_contacted(&live));
+}
+
+#[tokio::test]
+async fn dead_vast_wrapper_is_rejected_when_resolution_is_enabled() {
+    let (_dead, dead_url) = idle_dsp();
+    let mut config = config(&[1, 2]);
+    config.vast_wrapper = VastWrapperConfig { enabled: true, timeout: Duration::from_millis(50), allow_private_hosts: true, ..VastWrapperConfig::default() };
+    let wrapper = format!(r#"<VAST version="3.0"><Ad><Wrapper><VASTAdTagURI><![CDATA[{}]]></VASTAdTagURI></Wrapper></Ad></VAST>"#, dead_url);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let results = || vec![
+        dsp_result(1, "USD", json!([merged(bid("b1", "1", 2.0), json!({"adm": wrapper}))])),
+        dsp_result(2, "USD", json!([bid("b2", "1", 1.0)])),
+    ];
+    let response = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b2"]);
+
+    // 默认关闭时不解析 wrapper
+    config.vast_wrapper = VastWrapperConfig::default();
+    let response = run_auction(&context, &config, results()).await.unwrap();
+    assert_eq!(winning_bid_ids(&response), vec!["b1"]);
+}
+
+#[tokio::test]
+async fn vast_wrappers_resolve_concurrently_within_the_auction_budget() {
+    let (_dead, dead_url) = idle_dsp();
+    let mut config = config(&[1, 2, 3, 4, 5]);
+    // 单层超时远大于 tmax，解析耗时应由竞价剩余的时间预算约束
+    config.vast_wrapper = VastWrapperConfig { enabled: true, timeout: Duration::from_secs(2), allow_private_hosts: true, ..VastWrapperConfig::default() };
+    let wrapper = format!(r#"<VAST version="3.0"><Ad><Wrapper><VASTAdTagURI><![CDATA[{}]]></VASTAdTagURI></Wrapper></Ad></VAST>"#, dead_url);
+    let context = context(bid_request(json!({"tmax": 200})), ssp(json!({})));
+    let results = (1..=5)
+        .map(|dsp_id| dsp_result(dsp_id, "USD", json!([merged(bid(&format!("b{}", dsp_id), "1", 1.0), json!({"adm": wrapper}))])))
+        .collect();
+    let started = std::time::Instant::now();
+    assert!(run_auction(&context, &config, results).await.is_none());
+    assert!(started.elapsed() < Duration::from_millis(400), "took {:?}", started.elapsed());
+}
+
+/// 记录执行顺序的测试阶段
+struct RecordingStage {
+    name: &'static str,
+    required: bool,
+    flow: StageFlow,
+    ran: Arc<std::sync::Mutex<Vec<&'static str>>>,
+}
+
+impl AuctionStage for RecordingStage {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn required(&self) -> bool {
+        self.required
+    }
+
+    fn run<'s>(&'s self, _auction: &'s mut AuctionContext<'_>) -> BoxFuture<'s, StageFlow> {
+        self.ran.lock().unwrap().push(self.name);
+        Box::pin(async move { self.flow })
+    }
+}
+
+#[tokio::test]
+async fn minimal_pipeline_runs_its_stages_in_order() {
+    let config = config(&[1]);
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    let logger = runtime_logger();
+    let events = EventBus::new(16);
+    let ran = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let stage = |name, required, flow| RecordingStage { name, required, flow, ran: ran.clone() };
+
+    let pipeline = AuctionPipeline::new()
+        .with_stage(stage("fan_out", true, StageFlow::Continue))
+        .with_stage(stage("filter_blocklists", false, StageFlow::Continue))
+        .with_stage(stage("price", true, StageFlow::Continue))
+        .with_stage(stage("notify", false, StageFlow::Continue));
+    pipeline.run(&mut AuctionContext::new(&context, &config, &logger, &events)).await;
+    assert_eq!(*ran.lock().unwrap(), vec!["fan_out", "filter_blocklists", "price", "notify"]);
+
+    // 关闭的可选阶段不执行，必需阶段不受影响；Halt 跳过后续阶段
+    ran.lock().unwrap().clear();
+    let disabled: HashSet<String> = ["filter_blocklists".to_string(), "price".to_string()].into();
+    let pipeline = AuctionPipeline::new()
+        .with_stage(stage("fan_out", true, StageFlow::Continue))
+        .with_stage(stage("filter_blocklists", false, StageFlow::Continue))
+        .with_stage(stage("price", true, StageFlow::Halt))
+        .with_stage(stage("notify", false, StageFlow::Continue))
+        .without(&disabled);
+    assert_eq!(pipeline.stage_names(), vec!["fan_out", "price", "notify"]);
+    pipeline.run(&mut AuctionContext::new(&context, &config, &logger, &events)).await;
+    assert_eq!(*ran.lock().unwrap(), vec!["fan_out", "price"]);
+}
+
+#[test]
+fn standard_pipeline_orders_its_stages_and_only_disables_optional_ones() {
+    let fetcher = CannedFetcher { results: Vec::new() };
+    assert_eq!(AuctionPipeline::standard(&fetcher).stage_names(), vec![
+        "fan_out", "validate", "filter_blocklists", "filter_sensitive", "sanitize_creatives",
+        "filter_eligibility", "price", "inject_tracking", "limit_response_bids", "notify",
+    ]);
+    let disabled: HashSet<String> = ["notify".to_string(), "fan_out".to_string()].into();
+    assert!(!AuctionPipeline::standard(&fetcher).without(&disabled).stage_names().contains(&"notify"));
+    assert!(AuctionPipeline::standard(&fetcher).without(&disabled).stage_names().contains(&"fan_out"));
+    assert!(validate_disabled_stages(&["notify".to_string()].into()).is_ok());
+    assert!(validate_disabled_stages(&["price".to_string()].into()).is_err());
+    assert!(validate_disabled_stages(&["unknown".to_string()].into()).is_err());
+}
+
+#[test]
+fn auction_tmax_is_capped_by_the_ssp_max_auction_ms() {
+    let capped = ssp(json!({"max_auction_ms": 80}));
+    assert_eq!(context(bid_request(json!({"tmax": 1000})), capped.clone()).auction_tmax(300), 80);
+    assert_eq!(context(bid_request(json!({"tmax": 50})), capped.clone()).auction_tmax(300), 50);
+    assert_eq!(context(bid_request(json!({})), capped).auction_tmax(300), 80);
+    assert_eq!(context(bid_request(json!({"tmax": 1000})), ssp(json!({}))).auction_tmax(300), 1000);
+}
@@ -0,0 +1,175 @@
+// src/tests/dsp_mock.rs
+
+//! 测试用的 DSP 与竞价上下文：CannedFetcher 返回预置出价，不发起网络请求
+
+use std::net::TcpListener;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::sync::Semaphore;
+
+use crate::bidding::dsp_client::{BidFetcher, DspCallOutcome, DspCallResult};
+use crate::bidding::engine::process_bid_request_with;
+use crate::bidding::events::EventBus;
+use crate::bidding::request_ids::RecentRequestIds;
+use crate::config::config_manager::ConfigManager;
+use crate::logging::runtime_logger::RuntimeLogger;
+use crate::model::adapters::FileConfigAdapter;
+use crate::model::context::Context;
+use crate::model::dsp::{Demand, DemandManager};
+use crate::model::placements::SspPlacement;
+use crate::model::ssp::Ssp;
+use crate::openrtb::request::BidRequest;
+use crate::openrtb::response::BidResponse;
+use crate::AppState;
+
+/// 返回预置出价的 BidFetcher
+pub struct CannedFetcher {
+    pub results: Vec<DspCallResult>,
+}
+
+impl BidFetcher for CannedFetcher {
+    async fn fetch_bids(&self, _request: &Arc<BidRequest>) -> Vec<DspCallResult> {
+        self.results.clone()
+    }
+}
+
+/// 将 overrides 中的字段覆盖到 base 上
+pub fn merged(mut base: Value, overrides: Value) -> Value {
+    if let (Some(base), Value::Object(overrides)) = (base.as_object_mut(), overrides) {
+        base.extend(overrides);
+    }
+    base
+}
+
+/// 单个 DSP 的成功询价结果，bids 为 Bid 的 JSON 数组
+pub fn dsp_result(dsp_id: u64, cur: &str, bids: Value) -> DspCallResult {
+    let bid_response: BidResponse = serde_json::from_value(json!({
+        "id": "req-1",
+        "seatbid": [{"seat": format!("seat-{}", dsp_id), "bid": bids}],
+        "cur": cur,
+    })).expect("valid canned bid response");
+    let price = bid_response.seatbid.iter()
+        .flat_map(|seatbid| seatbid.bid.iter())
+        .map(|bid| bid.price)
+        .fold(0.0, f64::max);
+    DspCallResult {
+        dsp_id,
+        dsp_url: format!("http://dsp-{}.local/bid", dsp_id),
+        price,
+        bid_response,
+        outcome: DspCallOutcome::Success,
+        elapsed_ms: 0,
+        retries: 0,
+    }
+}
+
+/// 单个 DSP 失败的询价结果（无出价）
+pub fn failed_result(dsp_id: u64, outcome: DspCallOutcome) -> DspCallResult {
+    DspCallResult {
+        outcome,
+        price: 0.0,
+        bid_response: serde_json::from_value(json!({"id": "req-1", "seatbid": []})).unwrap(),
+        ..dsp_result(dsp_id, "USD", json!([]))
+    }
+}
+
+/// 单个展示位的出价
+pub fn bid(id: &str, impid: &str, price: f64) -> Value {
+    json!({"id": id, "impid": impid, "price": price, "crid": format!("crid-{}", id), "adm": format!("<div>{}</div>", id)})
+}
+
+/// 单个 banner 展示位（id 为 "1"）的请求，overrides 覆盖请求级字段
+pub fn bid_request(overrides: Value) -> Value {
+    merged(json!({
+        "id": "req-1",
+        "imp": [{"id": "1", "banner": {"w": 300, "h": 250}}],
+        "tmax": 300,
+    }), overrides)
+}
+
+/// SSP 配置，overrides 覆盖默认字段
+pub fn ssp(overrides: Value) -> Ssp {
+    serde_json::from_value(merged(json!({"id": 1, "uuid": "ssp-1", "name": "Test SSP", "qps": 1000}), overrides)).unwrap()
+}
+
+/// ssp() 对应的 SSP 广告位
+pub fn ssp_placement() -> SspPlacement {
+    serde_json::from_value(json!({
+        "ssp_id": 1, "ssp_uuid": "ssp-1", "placement_id": "placement-1",
+        "ad_type": 2, "update_time": 0, "status": 1
+    })).unwrap()
+}
+
+pub fn context(bid_request: Value, ssp: Ssp) -> Context {
+    Context {
+        bid_request: serde_json::from_value(bid_request).unwrap(),
+        ssp,
+        ssp_placement: ssp_placement(),
+        dsp_requests: vec![],
+        start_time: Instant::now(),
+    }
+}
+
+/// 本地监听但从不应答的 DSP 地址，用于断言竞价没有发起询价
+pub fn idle_dsp() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let url = format!("http://{}/bid", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+/// 监听器是否收到过连接
+pub fn was_contacted(listener: &TcpListener) -> bool {
+    listener.accept().is_ok()
+}
+
+/// 启用的 DSP，询价地址为 http://dsp-{id}.local/bid
+pub fn demand(id: u64) -> Demand {
+    Demand::new(id, &format!("dsp{}", id), &format!("http://dsp-{}.local/bid", id), true, Some(200))
+}
+
+/// 包含给定 DSP 的配置
+pub fn config_with(demands: Vec<Demand>) -> ConfigManager {
+    let mut demand_manager = DemandManager::new();
+    for demand in demands {
+        demand_manager.add_demand(demand);
+    }
+    ConfigManager::new(demand_manager)
+}
+
+/// 包含给定 DSP（均启用）的配置
+pub fn config(dsp_ids: &[u64]) -> ConfigManager {
+    config_with(dsp_ids.iter().map(|&id| demand(id)).collect())
+}
+
+pub fn runtime_logger() -> Arc<RuntimeLogger> {
+    let log_dir = std::env::temp_dir().join("rust-adx-tests");
+    RuntimeLogger::new(log_dir.to_str().unwrap(), "test", 10_000, 1000, 1000)
+}
+
+/// 以给定配置与 SSP 构造的全局状态（不限速、不开启幂等缓存）
+pub fn app_state(config: ConfigManager, ssp_info: Vec<Ssp>) -> Arc<AppState> {
+    Arc::new(AppState {
+        runtime_logger: runtime_logger(),
+        test_logger: None,
+        event_bus: EventBus::new(1024),
+        recent_request_ids: Arc::new(RecentRequestIds::new(Duration::from_millis(0))),
+        config: Arc::new(config),
+        ssp_info,
+        degraded: false,
+        maintenance: Arc::new(AtomicBool::new(false)),
+        auction_slots: Arc::new(Semaphore::new(16)),
+        rate_limiter: None,
+        config_adapter: Arc::new(FileConfigAdapter::new("", "", "")),
+        idempotency_cache: None,
+    })
+}
+
+/// 以预置出价完成一次竞价
+pub async fn run_auction(context: &Context, config: &ConfigManager, results: Vec<DspCallResult>) -> Option<BidResponse> {
+    let events = EventBus::new(1024);
+    process_bid_request_with(context, config, &runtime_logger(), &events, &CannedFetcher { results }).await
+}
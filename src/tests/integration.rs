@@ -0,0 +1,839 @@
+// src/tests/integration.rs
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::body::{to_bytes, Body, Bytes};
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::response::Response;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+use crate::api;
+use crate::bidding::adapter::DspAdapter;
+use crate::bidding::dsp_client::{DspCallOutcome, DspCallResult, DspClient, DEFAULT_USER_AGENT};
+use crate::bidding::events::EventBus;
+use crate::bidding::host_limit::HostLimiter;
+use crate::bidding::notice::{Notice, NoticeKind, NoticeQueue, NoticeQueueConfig};
+use crate::bidding::retry::RetryBudget;
+use crate::bidding::webhook::AuctionWebhook;
+use crate::config::config_manager::ConfigManager;
+use crate::logging::runtime_logger::RuntimeLogger;
+use crate::model::adapters::FileConfigAdapter;
+use crate::mock_dsp::{mock_dsp_router, LatencyDistribution, MockCreativeType, MockTemplates};
+use crate::model::dsp::{Demand, DemandManager, PriceUnit};
+use crate::openrtb::request::BidRequest;
+use crate::openrtb::response::BidResponse;
+use crate::tests::dsp_mock::{app_state, bid, bid_request, config, config_with, context, dsp_result, run_auction, runtime_logger, ssp, ssp_placement};
+
+/// 本地启动的 DSP，记录收到的每个询价请求（请求头与 JSON 请求体）
+struct RecordingDsp {
+    url: String,
+    requests: Arc<Mutex<Vec<(HeaderMap, Value)>>>,
+}
+
+impl RecordingDsp {
+    /// 启动 DSP，对每个询价返回 seatbid 为 bids 的响应（id 与请求一致）
+    async fn start(bids: Value) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bid", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let dsp = Router::new().route("/bid", post(move |headers: HeaderMap, Json(request): Json<Value>| async move {
+            let id = request["id"].clone();
+            recorded.lock().unwrap().push((headers, request));
+            Json(json!({"id": id, "seatbid": [{"seat": "seat-1", "bid": bids}], "cur": "USD"}))
+        }));
+        tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+        Self { url, requests }
+    }
+
+    /// 收到的询价请求体
+    fn received(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().iter().map(|(_, body)| body.clone()).collect()
+    }
+
+    /// 收到的询价请求携带的 User-Agent
+    fn received_user_agents(&self) -> Vec<String> {
+        self.requests.lock().unwrap().iter()
+            .map(|(headers, _)| headers[header::USER_AGENT].to_str().unwrap().to_string())
+            .collect()
+    }
+}
+
+/// 以 DspClient 向 DSP 询价一次
+async fn fetch(demands: Vec<Demand>, bid_request: Value) -> Vec<DspCallResult> {
+    let bid_request: BidRequest = serde_json::from_value(bid_request).unwrap();
+    DspClient::new(demands).fetch_bids(&Arc::new(bid_request)).await
+}
+
+/// 经 /openrtb 完成一次询价真实 DSP 的竞价，ssp_overrides 覆盖 SSP 配置
+async fn openrtb_response(dsp_url: &str, ssp_overrides: Value) -> Response {
+    openrtb_response_for(dsp_url, ssp_overrides, bid_request(json!({}))).await
+}
+
+/// 同 openrtb_response，使用指定的竞价请求
+async fn openrtb_response_for(dsp_url: &str, ssp_overrides: Value, bid_request: Value) -> Response {
+    let mut demand_manager = DemandManager::new();
+    demand_manager.add_demand(Demand::new(1, "dsp1", dsp_url, true, Some(200)));
+    let config = ConfigManager::new(demand_manager);
+    config.update_placements(vec![ssp_placement()], vec![]);
+    let state = app_state(config, vec![ssp(ssp_overrides)]);
+    let router = Router::new()
+        .route("/openrtb", post(api::handlers::handle_openrtb_request))
+        .with_state(state);
+    let mut request = Request::post("/openrtb?ssp_uuid=ssp-1")
+        .header("content-type", "application/json")
+        .body(Body::from(bid_request.to_string()))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+    router.oneshot(request).await.unwrap()
+}
+
+/// 同 openrtb_response，返回状态码与 JSON 响应体（无响应体时为 null）
+async fn openrtb_auction(dsp_url: &str, ssp_overrides: Value) -> (StatusCode, Value) {
+    let response = openrtb_response(dsp_url, ssp_overrides).await;
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn openrtb_request_returns_the_dsp_bid() {
+    let dsp = RecordingDsp::start(json!([bid("b1", "1", 1.5)])).await;
+    let (status, response) = openrtb_auction(&dsp.url, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["seatbid"][0]["bid"][0]["id"], "b1");
+}
+
+#[tokio::test]
+async fn ssp_response_transform_populates_bidid_with_the_request_id() {
+    let dsp = RecordingDsp::start(json!([bid("b1", "1", 1.5)])).await;
+    let (status, response) = openrtb_auction(&dsp.url, json!({"response_transform": {"bidid_from_request_id": true, "omit_cur": true}})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(response["bidid"], "req-1");
+    assert!(response["cur"].is_null(), "{}", response);
+
+    // 默认不调整响应
+    let (_, response) = openrtb_auction(&dsp.url, json!({})).await;
+    assert!(response["bidid"].is_null(), "{}", response);
+    assert_eq!(response["cur"], "USD");
+}
+
+#[tokio::test]
+async fn integer_and_string_prices_from_dsps_are_parsed() {
+    for price in [json!(2), json!("1.5")] {
+        let dsp = RecordingDsp::start(json!([{"id": "b1", "impid": "1", "price": price}])).await;
+        let (status, response) = openrtb_auction(&dsp.url, json!({})).await;
+        assert_eq!(status, StatusCode::OK, "price {}", price);
+        assert_eq!(response["seatbid"][0]["bid"][0]["id"], "b1");
+    }
+}
+
+#[tokio::test]
+async fn gpp_is_forwarded_and_us_sections_strip_precise_geo() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let gpp = "DBABMA~CPXxRfAPXxRfAAfKABENB-CgAAAAAAAAAAYgAAAAAAAA";
+    let request = |gpp_sid: Value| bid_request(json!({
+        "regs": {"gpp": gpp, "gpp_sid": gpp_sid},
+        "device": {"ua": "Mozilla", "geo": {"lat": 37.77, "lon": -122.41, "country": "USA"}},
+    }));
+    // 7 = US National：精确经纬度被移除，GPP 原样透传
+    openrtb_response_for(&dsp.url, json!({}), request(json!([7]))).await;
+    // 2 = EU TCF v2：保留经纬度
+    openrtb_response_for(&dsp.url, json!({}), request(json!([2]))).await;
+
+    let received = dsp.received();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0]["regs"], json!({"gpp": gpp, "gpp_sid": [7]}));
+    assert_eq!(received[0]["device"]["geo"], json!({"country": "USA"}));
+    assert_eq!(received[1]["regs"]["gpp_sid"], json!([2]));
+    assert_eq!(received[1]["device"]["geo"]["lat"], 37.77);
+}
+
+#[tokio::test]
+async fn bid_for_a_bogus_impid_is_not_returned() {
+    let dsp = RecordingDsp::start(json!([bid("b1", "1", 1.0), bid("bogus", "99", 5.0)])).await;
+    let (status, response) = openrtb_auction(&dsp.url, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let bids = response["seatbid"][0]["bid"].as_array().unwrap();
+    assert_eq!(bids.len(), 1);
+    assert_eq!(bids[0]["id"], "b1");
+
+    let only_bogus = RecordingDsp::start(json!([bid("bogus", "99", 5.0)])).await;
+    let (status, _) = openrtb_auction(&only_bogus.url, json!({})).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn no_bid_status_follows_the_ssp_configuration() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let (status, body) = openrtb_auction(&dsp.url, json!({})).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(body, Value::Null);
+
+    let (status, body) = openrtb_auction(&dsp.url, json!({"nobid_status": "ok"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], "req-1");
+    assert!(body["seatbid"].as_array().is_none_or(Vec::is_empty), "{}", body);
+    assert!(body["nbr"].is_number(), "{}", body);
+}
+
+#[tokio::test]
+async fn sole_dsp_nbr_is_passed_through_to_the_ssp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dsp_url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let dsp = Router::new().route("/bid", post(|Json(request): Json<Value>| async move {
+        Json(json!({"id": request["id"], "seatbid": [], "nbr": 8}))
+    }));
+    tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+
+    let (status, body) = openrtb_auction(&dsp_url, json!({"nobid_status": "ok"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["nbr"], 8);
+}
+
+#[tokio::test]
+async fn latency_header_is_returned_on_bids_and_no_bids() {
+    let bidding = RecordingDsp::start(json!([bid("b1", "1", 1.5)])).await;
+    let silent = RecordingDsp::start(json!([])).await;
+    for (dsp_url, expected_status) in [(&bidding.url, StatusCode::OK), (&silent.url, StatusCode::NO_CONTENT)] {
+        let started = Instant::now();
+        let response = openrtb_response(dsp_url, json!({})).await;
+        let wall_ms = started.elapsed().as_millis();
+        assert_eq!(response.status(), expected_status);
+        let latency_ms: u128 = response.headers()[api::handlers::LATENCY_HEADER].to_str().unwrap().parse().unwrap();
+        assert!(latency_ms <= wall_ms, "latency {} ms exceeds wall time {} ms", latency_ms, wall_ms);
+    }
+}
+
+#[tokio::test]
+async fn reloaded_demands_are_queried_without_a_restart() {
+    let existing = RecordingDsp::start(json!([])).await;
+    let onboarded = RecordingDsp::start(json!([bid("b2", "1", 1.5)])).await;
+    let demands_file = std::env::temp_dir().join(format!("rust-adx-demands-{}.json", std::process::id()));
+    let existing_demand = Demand::new(1, "dsp1", &existing.url, true, Some(200));
+
+    let mut demand_manager = DemandManager::new();
+    demand_manager.add_demand(existing_demand.clone());
+    let config = ConfigManager::new(demand_manager);
+    config.update_placements(vec![ssp_placement()], vec![]);
+    let mut state = Arc::into_inner(app_state(config, vec![ssp(json!({}))])).unwrap();
+    state.config_adapter = Arc::new(FileConfigAdapter::new("", "", "").with_demands_file(demands_file.to_str().unwrap()));
+    let state = Arc::new(state);
+    let router = Router::new()
+        .route("/openrtb", post(api::handlers::handle_openrtb_request))
+        .merge(api::admin::router(state.clone()))
+        .with_state(state);
+    let send = |request: Request<Body>| {
+        let mut request = request;
+        request.extensions_mut().insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+        router.clone().oneshot(request)
+    };
+    let auction = || Request::post("/openrtb?ssp_uuid=ssp-1")
+        .header("content-type", "application/json")
+        .body(Body::from(bid_request(json!({})).to_string()))
+        .unwrap();
+
+    assert_eq!(send(auction()).await.unwrap().status(), StatusCode::NO_CONTENT);
+    assert!(onboarded.received().is_empty());
+
+    let updated = vec![existing_demand, Demand::new(2, "dsp2", &onboarded.url, true, Some(200))];
+    std::fs::write(&demands_file, serde_json::to_string(&updated).unwrap()).unwrap();
+    let response = send(Request::post("/admin/reload-demands").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let counts: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert_eq!(counts, json!({"before": 1, "after": 2, "active": 2}));
+
+    let response = send(auction()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(onboarded.received().len(), 1);
+    let _ = std::fs::remove_file(&demands_file);
+}
+
+/// 在子进程中以 --nocapture 执行一次正常竞价：除测试框架自身的输出外，不应有任何直接写到 stdout / stderr 的内容
+#[test]
+fn normal_request_writes_nothing_to_stdout_or_stderr() {
+    let test_name = "tests::integration::openrtb_request_returns_the_dsp_bid";
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", test_name, "--nocapture", "--test-threads=1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let test_line = format!("test {} ... ok", test_name);
+    let stray: Vec<&str> = stdout.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with("running ") && !line.starts_with("test result:") && *line != test_line)
+        .collect();
+    assert!(stray.is_empty(), "unexpected stdout: {:?}", stray);
+    assert!(output.stderr.is_empty(), "unexpected stderr: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[tokio::test]
+async fn displaymanager_fields_are_forwarded_to_dsps() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250}, "displaymanager": "SDK-X", "displaymanagerver": "4.2.1"});
+    fetch(vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))], bid_request(json!({"imp": [imp]}))).await;
+    let forwarded = &dsp.received()[0]["imp"][0];
+    assert_eq!(forwarded["displaymanager"], "SDK-X");
+    assert_eq!(forwarded["displaymanagerver"], "4.2.1");
+}
+
+#[tokio::test]
+async fn clickbrowser_is_forwarded_to_dsps() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250}, "clickbrowser": 0});
+    fetch(vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))], bid_request(json!({"imp": [imp]}))).await;
+    assert_eq!(dsp.received()[0]["imp"][0]["clickbrowser"], 0);
+}
+
+#[tokio::test]
+async fn banner_and_video_api_are_forwarded_to_dsps() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250, "api": [5, 7]}, "video": {"mimes": ["video/mp4"], "api": [2]}});
+    fetch(vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))], bid_request(json!({"imp": [imp]}))).await;
+    let forwarded = &dsp.received()[0]["imp"][0];
+    assert_eq!(forwarded["banner"]["api"], json!([5, 7]));
+    assert_eq!(forwarded["video"]["api"], json!([2]));
+}
+
+#[tokio::test]
+async fn metric_array_is_forwarded_to_dsps_intact() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let metric = json!([{"type": "viewability", "value": 0.85, "vendor": "EXCHANGE"}]);
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250}, "metric": metric});
+    fetch(vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))], bid_request(json!({"imp": [imp]}))).await;
+    assert_eq!(dsp.received()[0]["imp"][0]["metric"], metric);
+}
+
+#[tokio::test]
+async fn banner_only_dsp_receives_the_imp_without_video() {
+    let banner_only = RecordingDsp::start(json!([])).await;
+    let full = RecordingDsp::start(json!([])).await;
+    let video_only = RecordingDsp::start(json!([])).await;
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250}, "video": {"mimes": ["video/mp4"]}});
+    let request: BidRequest = serde_json::from_value(bid_request(json!({"imp": [imp, {"id": "2", "banner": {"w": 320, "h": 50}}]}))).unwrap();
+    let client = DspClient::new(vec![
+        Demand::new(1, "dsp1", &banner_only.url, true, Some(200)),
+        Demand::new(2, "dsp2", &full.url, true, Some(200)),
+        Demand::new(3, "dsp3", &video_only.url, true, Some(200)),
+    ]).with_media_types(HashMap::from([(1, vec!["banner"]), (3, vec!["video"])]));
+    client.fetch_bids(&Arc::new(request)).await;
+
+    let trimmed = &banner_only.received()[0]["imp"];
+    assert_eq!(trimmed.as_array().unwrap().len(), 2);
+    assert!(trimmed[0].get("video").is_none());
+    assert_eq!(trimmed[0]["banner"]["w"], 300);
+    let forwarded = &full.received()[0]["imp"][0];
+    assert_eq!(forwarded["video"]["mimes"], json!(["video/mp4"]));
+    // 纯 banner 的 imp 对只投视频的 DSP 没有可投放的媒体对象，被整个移除
+    let video_imps = video_only.received()[0]["imp"].clone();
+    assert_eq!(video_imps.as_array().unwrap().len(), 1);
+    assert!(video_imps[0].get("banner").is_none());
+}
+
+#[tokio::test]
+async fn configured_dsp_receives_the_request_without_stripped_fields() {
+    let stripping = RecordingDsp::start(json!([])).await;
+    let full = RecordingDsp::start(json!([])).await;
+    let mut stripping_demand = Demand::new(1, "dsp1", &stripping.url, true, Some(200));
+    stripping_demand.strip_fields = vec!["user".to_string(), "device.geo".to_string()];
+    let request = bid_request(json!({
+        "user": {"id": "u1"},
+        "device": {"ua": "Mozilla", "geo": {"lat": 31.2, "lon": 121.5}},
+    }));
+    fetch(vec![stripping_demand, Demand::new(2, "dsp2", &full.url, true, Some(200))], request).await;
+
+    let stripped = &stripping.received()[0];
+    assert!(stripped.get("user").is_none());
+    assert!(stripped["device"].get("geo").is_none());
+    assert_eq!(stripped["device"]["ua"], "Mozilla");
+    let forwarded = &full.received()[0];
+    assert_eq!(forwarded["user"]["id"], "u1");
+    assert_eq!(forwarded["device"]["geo"]["lat"], 31.2);
+}
+
+#[tokio::test]
+async fn forwarded_tmax_is_reduced_by_the_elapsed_time_and_reserve() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let start_time = Instant::now() - Duration::from_millis(100);
+    for request in [json!({"tmax": 1000}), json!({"tmax": null})] {
+        let bid_request: BidRequest = serde_json::from_value(bid_request(request)).unwrap();
+        DspClient::new(vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))])
+            .with_deadline(start_time, 20)
+            .fetch_bids(&Arc::new(bid_request))
+            .await;
+    }
+    let received = dsp.received();
+    let forwarded = received[0]["tmax"].as_u64().unwrap();
+    assert!(forwarded <= 1000 - 100 - 20, "forwarded tmax {}", forwarded);
+    assert!(forwarded > 800, "forwarded tmax {}", forwarded);
+    // 未携带 tmax 的请求原样转发
+    assert!(received[1]["tmax"].is_null());
+}
+
+#[tokio::test]
+async fn interstitial_imps_reach_only_interstitial_capable_dsps() {
+    let capable = RecordingDsp::start(json!([])).await;
+    let incapable = RecordingDsp::start(json!([])).await;
+    let mut no_interstitial = Demand::new(2, "dsp2", &incapable.url, true, Some(200));
+    no_interstitial.accepts_interstitial = false;
+    let demands = || vec![Demand::new(1, "dsp1", &capable.url, true, Some(200)), no_interstitial.clone()];
+    let interstitial = json!({"id": "1", "instl": 1, "banner": {"w": 320, "h": 480}});
+    let regular = json!({"id": "2", "banner": {"w": 300, "h": 250}});
+
+    fetch(demands(), bid_request(json!({"imp": [interstitial, regular]}))).await;
+    // 只有插屏 imp 时，不接收插屏的 DSP 不被询价
+    fetch(demands(), bid_request(json!({"imp": [interstitial]}))).await;
+
+    let capable_received = capable.received();
+    assert_eq!(capable_received.len(), 2);
+    assert_eq!(capable_received[0]["imp"][0]["instl"], 1);
+    assert_eq!(capable_received[0]["imp"].as_array().unwrap().len(), 2);
+    let incapable_received = incapable.received();
+    assert_eq!(incapable_received.len(), 1);
+    assert_eq!(incapable_received[0]["imp"], json!([regular]));
+}
+
+#[tokio::test]
+async fn raw_unit_bids_compare_against_cpm_bids_after_normalization() {
+    let cpm = RecordingDsp::start(json!([bid("cpm-bid", "1", 1.5)])).await;
+    // 单次展示价格 0.002 即 CPM 2.0，高于 CPM DSP 的 1.5
+    let raw = RecordingDsp::start(json!([{"id": "raw-bid", "impid": "1", "price": 0.002, "crid": "crid-raw",
+        "nurl": "http://raw.local/win?p={AUCTION_PRICE}"}])).await;
+    let mut raw_demand = Demand::new(2, "raw_dsp", &raw.url, true, Some(200));
+    raw_demand.price_unit = PriceUnit::Raw;
+    let demands = vec![Demand::new(1, "cpm_dsp", &cpm.url, true, Some(200)), raw_demand];
+
+    let results = fetch(demands.clone(), bid_request(json!({"at": 1}))).await;
+    let prices: Vec<(u64, f64)> = results.iter().map(|result| (result.dsp_id, result.price)).collect();
+    assert_eq!(prices, vec![(2, 2.0), (1, 1.5)]);
+
+    let context = context(bid_request(json!({"at": 1})), ssp(json!({})));
+    let response = run_auction(&context, &config_with(demands), results).await.unwrap();
+    let winner = &response.seatbid[0].bid[0];
+    assert_eq!(winner.id, "raw-bid");
+    // 返回给 DSP 的成交价换算回单次展示价格：2.0 扣除 20% 利润为 CPM 1.6，即 0.0016
+    assert_eq!(winner.nurl.as_deref(), Some("http://raw.local/win?p=0.0016"));
+}
+
+#[tokio::test]
+async fn dsp_receives_the_configured_user_agent() {
+    let dsp = RecordingDsp::start(json!([])).await;
+    let demands = || vec![Demand::new(1, "dsp1", &dsp.url, true, Some(200))];
+    let bid_request = Arc::new(serde_json::from_value::<BidRequest>(bid_request(json!({}))).unwrap());
+    DspClient::new(demands()).fetch_bids(&bid_request).await;
+    DspClient::new(demands()).with_user_agent("partner-adx/2.0").fetch_bids(&bid_request).await;
+    assert_eq!(dsp.received_user_agents(), vec![DEFAULT_USER_AGENT.to_string(), "partner-adx/2.0".to_string()]);
+    assert!(DEFAULT_USER_AGENT.starts_with("rust-adx/"));
+}
+
+#[tokio::test]
+async fn in_flight_calls_to_one_host_stay_bounded_under_load() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let in_flight = Arc::new(AtomicU32::new(0));
+    let peak = Arc::new(AtomicU32::new(0));
+    let (counter, observed) = (in_flight.clone(), peak.clone());
+    let dsp = Router::new().route("/bid", post(move |Json(request): Json<Value>| async move {
+        observed.fetch_max(counter.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        counter.fetch_sub(1, Ordering::SeqCst);
+        Json(json!({"id": request["id"], "seatbid": [], "cur": "USD"}))
+    }));
+    tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+
+    let host_limiter = HostLimiter::new(2).map(Arc::new);
+    let bid_request = Arc::new(serde_json::from_value::<BidRequest>(bid_request(json!({}))).unwrap());
+    let auctions = (0..10).map(|_| {
+        let demands = (1..=4).map(|id| Demand::new(id, &format!("dsp{}", id), &url, true, Some(500))).collect();
+        let client = DspClient::new(demands).with_host_limiter(host_limiter.clone());
+        let bid_request = bid_request.clone();
+        async move { client.fetch_bids(&bid_request).await }
+    });
+    let results: Vec<DspCallResult> = futures::future::join_all(auctions).await.into_iter().flatten().collect();
+
+    assert!(peak.load(Ordering::SeqCst) <= 2, "peak in-flight {}", peak.load(Ordering::SeqCst));
+    let busy = results.iter().filter(|result| result.outcome == DspCallOutcome::HostBusy).count();
+    assert!(busy > 0 && busy < results.len(), "{} of {} calls were host_busy", busy, results.len());
+    assert_eq!(host_limiter.unwrap().in_flight(&url), 0);
+}
+
+/// 私有格式的适配器：请求为 "id=<请求 id>"，响应为 "<出价 id>,<展示位 id>,<价格>" 每行一个出价
+#[derive(Debug)]
+struct LineAdapter;
+
+impl DspAdapter for LineAdapter {
+    fn name(&self) -> &'static str {
+        "line"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/plain"
+    }
+
+    fn to_request(&self, request: &BidRequest) -> Bytes {
+        Bytes::from(format!("id={}", request.id))
+    }
+
+    fn parse_response(&self, body: Bytes) -> Result<BidResponse, String> {
+        let body = String::from_utf8(body.to_vec()).map_err(|e| e.to_string())?;
+        let bids: Vec<Value> = body.lines()
+            .map(|line| match line.split(',').collect::<Vec<_>>()[..] {
+                [id, impid, price] => Ok(json!({"id": id, "impid": impid, "price": price})),
+                _ => Err(format!("malformed line: {}", line)),
+            })
+            .collect::<Result<_, _>>()?;
+        serde_json::from_value(json!({"id": "", "seatbid": [{"bid": bids}]})).map_err(|e| e.to_string())
+    }
+}
+
+#[tokio::test]
+async fn custom_adapter_encodes_requests_and_decodes_responses() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let recorded = received.clone();
+    let dsp = Router::new().route("/bid", post(move |headers: HeaderMap, body: String| async move {
+        recorded.lock().unwrap().push((headers[header::CONTENT_TYPE].to_str().unwrap().to_string(), body));
+        "b1,1,1.5\nb2,1,0.5"
+    }));
+    tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+
+    let bid_request = Arc::new(serde_json::from_value::<BidRequest>(bid_request(json!({}))).unwrap());
+    let adapters: HashMap<u64, Arc<dyn DspAdapter>> = HashMap::from([(1, Arc::new(LineAdapter) as Arc<dyn DspAdapter>)]);
+    let results = DspClient::new(vec![Demand::new(1, "line_dsp", &url, true, Some(200))])
+        .with_adapters(adapters)
+        .fetch_bids(&bid_request)
+        .await;
+    assert_eq!(*received.lock().unwrap(), vec![("text/plain".to_string(), "id=req-1".to_string())]);
+    assert_eq!(results[0].outcome, DspCallOutcome::Success);
+    assert_eq!(results[0].price, 1.5);
+    let bid_ids: Vec<&str> = results[0].bid_response.seatbid[0].bid.iter().map(|bid| bid.id.as_str()).collect();
+    assert_eq!(bid_ids, vec!["b1", "b2"]);
+}
+
+#[tokio::test]
+async fn gzipped_dsp_response_is_decoded_before_parsing() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let dsp = Router::new().route("/bid", post(|headers: HeaderMap, Json(request): Json<Value>| async move {
+        assert!(headers[header::ACCEPT_ENCODING].to_str().unwrap().contains("gzip"));
+        let body = json!({"id": request["id"], "seatbid": [{"seat": "seat-1", "bid": [bid("b1", "1", 1.5)]}], "cur": "USD"});
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.to_string().as_bytes()).unwrap();
+        ([(header::CONTENT_TYPE, "application/json"), (header::CONTENT_ENCODING, "gzip")], encoder.finish().unwrap())
+    }));
+    tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+
+    let results = fetch(vec![Demand::new(1, "dsp1", &url, true, Some(200))], bid_request(json!({}))).await;
+    assert_eq!(results[0].price, 1.5);
+    assert_eq!(results[0].bid_response.seatbid[0].bid[0].id, "b1");
+}
+
+#[tokio::test]
+async fn test_traffic_reaches_only_test_dsps_and_the_test_log() {
+    let production = RecordingDsp::start(json!([bid("b1", "1", 1.5)])).await;
+    let test = RecordingDsp::start(json!([bid("t1", "1", 1.5)])).await;
+    let mut test_demand = Demand::new(2, "test_dsp", &test.url, true, Some(200));
+    test_demand.test = true;
+    let mut demand_manager = DemandManager::new();
+    demand_manager.add_demand(Demand::new(1, "dsp1", &production.url, true, Some(200)));
+    demand_manager.add_demand(test_demand);
+    let mut config = ConfigManager::new(demand_manager);
+    config.route_test_traffic = true;
+    config.update_placements(vec![ssp_placement()], vec![]);
+
+    let test_log_dir = std::env::temp_dir().join(format!("rust-adx-test-traffic-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&test_log_dir);
+    let mut state = Arc::into_inner(app_state(config, vec![ssp(json!({}))])).unwrap();
+    state.test_logger = Some(RuntimeLogger::new(test_log_dir.to_str().unwrap(), "test", 1000, 1, 10));
+    let router = Router::new()
+        .route("/openrtb", post(api::handlers::handle_openrtb_request))
+        .with_state(Arc::new(state));
+    for request in [bid_request(json!({"id": "prod-req"})), bid_request(json!({"id": "test-req", "test": 1}))] {
+        let mut request = Request::post("/openrtb?ssp_uuid=ssp-1")
+            .header("content-type", "application/json")
+            .body(Body::from(request.to_string()))
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+        assert_eq!(router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    }
+
+    let ids = |dsp: &RecordingDsp| dsp.received().iter().map(|request| request["id"].clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&production), vec![json!("prod-req")]);
+    assert_eq!(ids(&test), vec![json!("test-req")]);
+
+    let read_test_log = || std::fs::read_dir(&test_log_dir).into_iter().flatten().flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .collect::<String>();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !read_test_log().contains("test-req") && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let test_log = read_test_log();
+    assert!(test_log.contains("test-req") && !test_log.contains("prod-req"), "{}", test_log);
+    let _ = std::fs::remove_dir_all(&test_log_dir);
+}
+
+#[tokio::test]
+async fn batch_isolates_invalid_requests_from_valid_ones() {
+    let dsp = RecordingDsp::start(json!([bid("b1", "1", 1.5)])).await;
+    let mut demand_manager = DemandManager::new();
+    demand_manager.add_demand(Demand::new(1, "dsp1", &dsp.url, true, Some(200)));
+    let config = ConfigManager::new(demand_manager);
+    config.update_placements(vec![ssp_placement()], vec![]);
+    let router = Router::new()
+        .route("/openrtb/batch", post(api::handlers::handle_openrtb_batch))
+        .with_state(app_state(config, vec![ssp(json!({}))]));
+    let batch = json!([
+        {"ssp_uuid": "ssp-1", "bid_request": bid_request(json!({"id": "req-ok"}))},
+        {"ssp_uuid": "ssp-unknown", "bid_request": bid_request(json!({"id": "req-unknown-ssp"}))},
+        {"ssp_uuid": "ssp-1"},
+        {"ssp_uuid": "ssp-1", "bid_request": bid_request(json!({"id": "req-ok-2"}))},
+    ]);
+    let mut request = Request::post("/openrtb/batch")
+        .header("content-type", "application/json")
+        .body(Body::from(batch.to_string()))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo("127.0.0.1:50000".parse::<SocketAddr>().unwrap()));
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let items: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+
+    let statuses: Vec<u64> = items.as_array().unwrap().iter().map(|item| item["status"].as_u64().unwrap()).collect();
+    assert_eq!(statuses, vec![200, 400, 400, 200]);
+    assert_eq!(items[1]["error"]["error"], "unknown_ssp");
+    assert_eq!(items[2]["error"]["error"], "invalid_batch_item");
+    assert_eq!(items[0]["response"]["id"], "req-ok");
+    assert_eq!(items[3]["response"]["id"], "req-ok-2");
+    assert_eq!(items[0]["response"]["seatbid"][0]["bid"][0]["id"], "b1");
+    assert_eq!(dsp.received().len(), 2);
+}
+
+/// 启动一个接受连接后立即断开的 DSP（可重试的失败），返回其地址与收到的连接数
+async fn flaky_dsp() -> (String, Arc<AtomicU32>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicU32::new(0));
+    let counted = hits.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            counted.fetch_add(1, Ordering::SeqCst);
+            drop(stream);
+        }
+    });
+    (url, hits)
+}
+
+#[tokio::test]
+async fn retries_across_flaky_dsps_stay_within_the_shared_budget() {
+    let mut demands = Vec::new();
+    let mut hits = Vec::new();
+    for id in 1..=3 {
+        let (url, dsp_hits) = flaky_dsp().await;
+        let mut demand = Demand::new(id, &format!("dsp{}", id), &url, true, Some(500));
+        demand.max_retries = Some(5);
+        demands.push(demand);
+        hits.push(dsp_hits);
+    }
+    let retry_budget = Arc::new(RetryBudget::new(4));
+    let bid_request: BidRequest = serde_json::from_value(bid_request(json!({}))).unwrap();
+    let results = DspClient::new(demands)
+        .with_retry_budget(retry_budget.clone())
+        .fetch_bids(&Arc::new(bid_request))
+        .await;
+
+    assert!(results.iter().all(|result| result.outcome == DspCallOutcome::InvalidResponse));
+    assert_eq!(results.iter().map(|result| result.retries).sum::<u32>(), 4);
+    let total_hits: u32 = hits.iter().map(|hits| hits.load(Ordering::SeqCst)).sum();
+    assert_eq!(total_hits, 3 + 4);
+    assert_eq!(retry_budget.used(), 4);
+    assert!(retry_budget.is_exhausted());
+}
+
+/// 启动一个前 failures 次返回 503、之后恢复为 204 的通知接收端，返回其地址与收到的请求数
+async fn recovering_notice_endpoint(failures: u32) -> (String, Arc<AtomicU32>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/win?price=1.2", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicU32::new(0));
+    let counted = hits.clone();
+    let endpoint = Router::new().route("/win", get(move || async move {
+        if counted.fetch_add(1, Ordering::SeqCst) < failures {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            StatusCode::NO_CONTENT
+        }
+    }));
+    tokio::spawn(async move { axum::serve(listener, endpoint).await.unwrap() });
+    (url, hits)
+}
+
+fn win_notice(url: &str) -> Notice {
+    Notice { kind: NoticeKind::Win, request_id: "req-1".to_string(), bid_id: "b1".to_string(), dsp_id: 1, url: url.to_string() }
+}
+
+fn notice_queue(config: NoticeQueueConfig) -> Arc<NoticeQueue> {
+    NoticeQueue::new(config, EventBus::new(16), runtime_logger())
+}
+
+/// 等待队列中的通知全部处理完毕
+async fn drain(queue: &NoticeQueue) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while queue.pending() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(queue.pending(), 0, "notice queue did not drain");
+}
+
+#[tokio::test]
+async fn failed_win_notice_is_retried_until_the_endpoint_recovers() {
+    let (url, hits) = recovering_notice_endpoint(2).await;
+    let queue = notice_queue(NoticeQueueConfig {
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(50),
+        ..NoticeQueueConfig::default()
+    });
+    queue.enqueue(win_notice(&url)).await;
+    drain(&queue).await;
+
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+    assert_eq!(queue.delivered(), 1);
+    assert_eq!(queue.expired(), 0);
+}
+
+#[tokio::test]
+async fn expired_notice_is_spooled_and_delivered_on_replay() {
+    let (url, hits) = recovering_notice_endpoint(1).await;
+    let spool_path = std::env::temp_dir().join(format!("rust-adx-notice-spool-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&spool_path);
+    let config = NoticeQueueConfig {
+        retry_window: Duration::ZERO,
+        spool_path: Some(spool_path.clone()),
+        ..NoticeQueueConfig::default()
+    };
+
+    let queue = notice_queue(config.clone());
+    queue.enqueue(win_notice(&url)).await;
+    drain(&queue).await;
+    assert_eq!(queue.expired(), 1);
+    assert_eq!(queue.delivered(), 0);
+    assert!(std::fs::read_to_string(&spool_path).unwrap().contains("/win?price=1.2"));
+
+    // 重启后重放落盘的通知，接收端已恢复
+    let restarted = notice_queue(config);
+    assert_eq!(restarted.replay_spool().await, 1);
+    drain(&restarted).await;
+    assert_eq!(restarted.delivered(), 1);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+    assert_eq!(std::fs::read_to_string(&spool_path).unwrap(), "");
+    let _ = std::fs::remove_file(&spool_path);
+}
+
+#[tokio::test]
+async fn auction_webhook_receives_the_outcome_json() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/outcomes", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let recorded = received.clone();
+    // 第一次返回 503，验证失败后重试
+    let endpoint = Router::new().route("/outcomes", post(move |Json(outcome): Json<Value>| async move {
+        let mut received = recorded.lock().unwrap();
+        received.push(outcome);
+        if received.len() == 1 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::NO_CONTENT }
+    }));
+    tokio::spawn(async move { axum::serve(listener, endpoint).await.unwrap() });
+
+    let mut config = config(&[1]);
+    let webhook = AuctionWebhook::new(&url, 2);
+    config.auction_webhook = Some(webhook.clone());
+    let context = context(bid_request(json!({})), ssp(json!({})));
+    run_auction(&context, &config, vec![dsp_result(1, "USD", json!([bid("b1", "1", 1.5)]))]).await.unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while webhook.delivered() == 0 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!((webhook.delivered(), webhook.failed()), (1, 0));
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[1]["request_id"], "req-1");
+    assert_eq!(received[1]["adx_inquiry_result"], "success");
+    assert_eq!(received[1]["winning_bids"][0]["id"], "b1");
+}
+
+#[tokio::test]
+async fn dsps_with_a_request_size_cap_below_the_payload_are_skipped() {
+    let capped_dsp = RecordingDsp::start(json!([])).await;
+    let roomy_dsp = RecordingDsp::start(json!([])).await;
+    let mut capped = Demand::new(1, "capped_dsp", &capped_dsp.url, true, Some(200));
+    capped.max_request_bytes = Some(16);
+    let mut roomy = Demand::new(2, "roomy_dsp", &roomy_dsp.url, true, Some(200));
+    roomy.max_request_bytes = Some(64 * 1024);
+    let results = fetch(vec![capped, roomy], bid_request(json!({}))).await;
+    let outcome = |dsp_id: u64| results.iter().find(|result| result.dsp_id == dsp_id).unwrap().outcome;
+    // 超出上限的 DSP 不发起询价
+    assert_eq!(outcome(1), DspCallOutcome::PayloadTooLarge);
+    assert!(capped_dsp.received().is_empty());
+    assert_eq!(outcome(2), DspCallOutcome::Success);
+    assert_eq!(roomy_dsp.received().len(), 1);
+}
+
+#[tokio::test]
+async fn mock_dsp_custom_template_flows_through_the_adx() {
+    let template_path = std::env::temp_dir().join(format!("rust-adx-mock-template-{}.html", std::process::id()));
+    std::fs::write(&template_path, "<div>custom creative {BID_ID} price={AUCTION_PRICE}</div>").unwrap();
+    let templates = MockTemplates::load(&[(MockCreativeType::Banner, template_path.to_str().unwrap().to_string())]);
+    let _ = std::fs::remove_file(&template_path);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dsp_url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let mock_dsp = mock_dsp_router(LatencyDistribution::Fixed { ms: 0 }, templates);
+    tokio::spawn(async move { axum::serve(listener, mock_dsp).await.unwrap() });
+
+    let imp = json!({"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 1.0});
+    let response = openrtb_response_for(&dsp_url, json!({}), bid_request(json!({"imp": [imp]}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    // 模板中的出价 id 由 Mock DSP 替换，{AUCTION_PRICE} 由 ADX 替换为成交价
+    let adm = body["seatbid"][0]["bid"][0]["adm"].as_str().unwrap();
+    assert!(adm.contains("custom creative bid-1 price="), "{}", adm);
+    assert!(!adm.contains("{AUCTION_PRICE}"), "{}", adm);
+}
+
+#[tokio::test]
+async fn serialization_reserve_keeps_large_creatives_under_tmax() {
+    // DSP 120ms 后返回约 300KB 的创意
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let dsp_url = format!("http://{}/bid", listener.local_addr().unwrap());
+    let large_adm = format!("<div>{}</div>", "x".repeat(300 * 1024));
+    let dsp = Router::new().route("/bid", post(move |Json(request): Json<Value>| async move {
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let large_bid = json!({"id": "b1", "impid": "1", "price": 1.5, "adm": large_adm, "crid": "crid-b1"});
+        Json(json!({"id": request["id"], "seatbid": [{"seat": "seat-1", "bid": [large_bid]}], "cur": "USD"}))
+    }));
+    tokio::spawn(async move { axum::serve(listener, dsp).await.unwrap() });
+    let request = || bid_request(json!({"tmax": 400}));
+
+    // 预留 100ms：DSP 仍有足够时间返回，序列化大创意后总耗时不超过 tmax
+    let started = Instant::now();
+    let response = openrtb_response_for(&dsp_url, json!({"serialization_reserve_ms": 100}), request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_slice(&to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+    assert!(body["seatbid"][0]["bid"][0]["adm"].as_str().unwrap().len() > 300 * 1024);
+    assert!(started.elapsed() < Duration::from_millis(400), "took {:?}", started.elapsed());
+
+    // 预留 300ms：DSP 的时间预算只剩约 80ms，不再等待慢 DSP
+    let started = Instant::now();
+    let response = openrtb_response_for(&dsp_url, json!({"serialization_reserve_ms": 300}), request()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(started.elapsed() < Duration::from_millis(400), "took {:?}", started.elapsed());
+}
@@ -0,0 +1,9 @@
+// src/tests/mod.rs
+
+//! 跨模块的测试：dsp_mock 提供不发起网络请求的 DSP 与竞价上下文构造，
+//! bidding_tests 覆盖竞价流水线，api_tests 覆盖 HTTP 接口，integration 覆盖 HTTP 询价的端到端流程
+
+mod api_tests;
+mod bidding_tests;
+mod dsp_mock;
+mod integration;